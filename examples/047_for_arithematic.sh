@@ -1,4 +1,5 @@
-for i in {1..5}
+j=1
+for ((i = 1; i <= 5; i++))
 do
 	j=$(($j*$i))
 done
@@ -1,134 +1,328 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Where a node came from in the source text: a byte range plus the 1-based
+/// line and column of its first character. Nodes built by the generators
+/// rather than the parser carry `Span::default()`.
+///
+/// Spans never take part in AST equality, so two parses of differently laid
+/// out text compare equal when their structure matches.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Self { start, end, line, col }
+    }
+
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span { end: other.end.max(self.start), ..*self }
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
+// Serialized through the impls by `commands_to_json`, which let the span be
+// left out of the JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", content = "value")]
 pub enum Command {
-    Simple(SimpleCommand),
-    BuiltinCommand(BuiltinCommand),
-    ShoptCommand(ShoptCommand),
-    TestExpression(TestExpression),
-    Pipeline(Pipeline),
-    If(IfStatement),
-    Case(CaseStatement),
-    While(WhileLoop),
-    For(ForLoop),
-    Function(Function),
-    Subshell(Box<Command>),
-    Background(Box<Command>),
-    Block(Block),
-    Redirect(RedirectCommand),
-    Break(Option<String>),      // Optional loop level
-    Continue(Option<String>),   // Optional loop level
-    Return(Option<Word>),       // Optional return value
-    BlankLine,
-}
-
-#[derive(Debug, Clone, PartialEq)]
+    Simple(SimpleCommand, Span),
+    BuiltinCommand(BuiltinCommand, Span),
+    ShoptCommand(ShoptCommand, Span),
+    TestExpression(TestExpression, Span),
+    Pipeline(Pipeline, Span),
+    If(IfStatement, Span),
+    Case(CaseStatement, Span),
+    While(WhileLoop, Span),
+    For(ForLoop, Span),
+    Select(SelectLoop, Span),
+    ArithmeticFor(ArithmeticFor, Span),
+    Function(Function, Span),
+    Subshell(Box<Command>, Span),
+    Background(Box<Command>, Span),
+    Block(Block, Span),
+    Redirect(RedirectCommand, Span),
+    Break(Option<String>, Span),      // Optional loop level
+    Continue(Option<String>, Span),   // Optional loop level
+    Return(Option<Word>, Span),       // Optional return value
+    BlankLine(Span),
+    Error(String, Span),              // Source skipped after a parse error
+}
+
+impl Command {
+    pub fn span(&self) -> Span {
+        match self {
+            Command::Simple(_, span)
+            | Command::BuiltinCommand(_, span)
+            | Command::ShoptCommand(_, span)
+            | Command::TestExpression(_, span)
+            | Command::Pipeline(_, span)
+            | Command::If(_, span)
+            | Command::Case(_, span)
+            | Command::While(_, span)
+            | Command::For(_, span)
+            | Command::Select(_, span)
+            | Command::ArithmeticFor(_, span)
+            | Command::Function(_, span)
+            | Command::Subshell(_, span)
+            | Command::Background(_, span)
+            | Command::Block(_, span)
+            | Command::Redirect(_, span)
+            | Command::Break(_, span)
+            | Command::Continue(_, span)
+            | Command::Return(_, span)
+            | Command::Error(_, span)
+            | Command::BlankLine(span) => *span,
+        }
+    }
+
+    /// The same command, located at `span`.
+    pub fn with_span(mut self, span: Span) -> Self {
+        match &mut self {
+            Command::Simple(_, s)
+            | Command::BuiltinCommand(_, s)
+            | Command::ShoptCommand(_, s)
+            | Command::TestExpression(_, s)
+            | Command::Pipeline(_, s)
+            | Command::If(_, s)
+            | Command::Case(_, s)
+            | Command::While(_, s)
+            | Command::For(_, s)
+            | Command::Select(_, s)
+            | Command::ArithmeticFor(_, s)
+            | Command::Function(_, s)
+            | Command::Subshell(_, s)
+            | Command::Background(_, s)
+            | Command::Block(_, s)
+            | Command::Redirect(_, s)
+            | Command::Break(_, s)
+            | Command::Continue(_, s)
+            | Command::Return(_, s)
+            | Command::Error(_, s)
+            | Command::BlankLine(s) => *s = span,
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SimpleCommand {
     pub name: Word,
     pub args: Vec<Word>,
     pub redirects: Vec<Redirect>,
-    pub env_vars: HashMap<String, Word>,
+    pub env_vars: Vec<Assignment>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BuiltinCommand {
     pub name: String,
     pub args: Vec<Word>,
     pub redirects: Vec<Redirect>,
-    pub env_vars: HashMap<String, Word>,
+    pub env_vars: Vec<Assignment>,
+}
+
+/// A `name=value` or `name[index]=value` assignment, either standalone or
+/// as a prefix to a command. Commands keep these in source order, since a
+/// later value may refer to an earlier one as in `a=1 b=$a`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub name: String,
+    pub index: Option<String>,
+    pub value: Word,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Assignment {
+    /// Build an assignment from its target text, splitting off any
+    /// `[index]` subscript.
+    pub fn new(target: &str, value: Word) -> Self {
+        let (name, index) = match target.find('[') {
+            Some(open) if open > 0 && target.ends_with(']') => {
+                (&target[..open], Some(target[open + 1..target.len() - 1].to_string()))
+            }
+            _ => (target, None),
+        };
+        Self { name: name.to_string(), index, value }
+    }
+
+    /// The assignment target as written, e.g. `map[key]`.
+    pub fn target(&self) -> String {
+        match &self.index {
+            Some(index) => format!("{}[{}]", self.name, index),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ShoptCommand {
     pub option: String,
     pub enable: bool, // true for -s (set), false for -u (unset)
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pipeline {
     pub commands: Vec<Command>,
     pub operators: Vec<PipeOperator>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PipeOperator {
     Pipe,      // |
     And,       // &&
     Or,        // ||
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IfStatement {
     pub condition: Box<Command>,
     pub then_branch: Box<Command>,
     pub else_branch: Option<Box<Command>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CaseStatement {
     pub word: Word,
     pub cases: Vec<CaseClause>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CaseClause {
     pub patterns: Vec<Word>,
     pub body: Vec<Command>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WhileLoop {
     pub condition: Box<Command>,
     pub body: Block,
+    #[serde(default)]
+    pub until: bool, // `until` loops run while the condition fails
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ForLoop {
     pub variable: String,
     pub items: Vec<Word>,
     pub body: Block,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// `for (( init; condition; step ))`; an empty condition is always true.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArithmeticFor {
+    pub init: ArithmeticExpression,
+    pub condition: ArithmeticExpression,
+    pub step: ArithmeticExpression,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectLoop {
+    pub variable: String,
+    pub items: Vec<Word>, // Empty means the positional parameters
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Function {
     pub name: String,
     pub parameters: Vec<String>,
     pub body: Block,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub commands: Vec<Command>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RedirectCommand {
     pub command: Box<Command>,
     pub redirects: Vec<Redirect>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Redirect {
     pub fd: Option<i32>,
+    /// `{name}>file`: the shell picks a free descriptor and stores it in `name`
+    #[serde(default)]
+    pub fd_var: Option<String>,
     pub operator: RedirectOperator,
     pub target: Word,
-    pub heredoc_body: Option<String>,
+    pub heredoc: Option<Heredoc>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+/// The lines a `<<` or `<<-` redirect feeds to its command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heredoc {
+    /// The lines as written, up to but not including the delimiter line
+    pub text: String,
+    /// `<<'EOF'` or `<<"EOF"`: the lines are taken as they are
+    pub quoted: bool,
+    /// What the command reads: the lines with `<<-` tabs stripped, as a
+    /// `Literal` when quoted and a `StringInterpolation` otherwise
+    pub body: Word,
+}
+
+impl Redirect {
+    /// The descriptor this redirect changes: the explicit one if given,
+    /// otherwise stdin or stdout depending on the operator's direction.
+    pub fn descriptor(&self) -> i32 {
+        self.fd.unwrap_or(match self.operator {
+            RedirectOperator::Input
+            | RedirectOperator::InputOutput
+            | RedirectOperator::Heredoc
+            | RedirectOperator::HeredocTabs
+            | RedirectOperator::HereString
+            | RedirectOperator::DuplicateInput => 0,
+            _ => 1,
+        })
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum RedirectOperator {
     Input,     // <
     Output,    // >
     Append,    // >>
     InputOutput, // <>
+    OutputClobber, // >|
+    OutputAll, // &>
+    AppendAll, // &>>
+    DuplicateInput,  // <&N, or <&N- to move the descriptor
+    DuplicateOutput, // >&N, or >&N- to move the descriptor
+    Close,     // >&- and <&-
     Heredoc,   // <<
     HeredocTabs, // <<-
     HereString, // <<<
-    ProcessSubstitutionInput(Box<Command>),  // <(command)
-    ProcessSubstitutionOutput(Box<Command>), // >(command)
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Which way data flows through a process substitution.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ProcessDirection {
+    Input,  // <(command): read what the command writes
+    Output, // >(command): write what the command reads
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParameterExpansion {
     pub variable: String,
     pub operator: ParameterExpansionOperator,
@@ -136,41 +330,26 @@ pub struct ParameterExpansion {
 
 impl std::fmt::Display for ParameterExpansion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.operator {
-            ParameterExpansionOperator::None => write!(f, "${{{}}}", self.variable),
-            ParameterExpansionOperator::UppercaseAll => write!(f, "${{{0}^^}}", self.variable),
-            ParameterExpansionOperator::LowercaseAll => write!(f, "${{{0},,}}", self.variable),
-            ParameterExpansionOperator::UppercaseFirst => write!(f, "${{{0}^}}", self.variable),
-            ParameterExpansionOperator::RemoveLongestPrefix(pattern) => write!(f, "${{{0}##{1}}}", self.variable, pattern),
-            ParameterExpansionOperator::RemoveShortestPrefix(pattern) => write!(f, "${{{0}#{1}}}", self.variable, pattern),
-            ParameterExpansionOperator::RemoveLongestSuffix(pattern) => write!(f, "${{{0}%%{1}}}", self.variable, pattern),
-            ParameterExpansionOperator::RemoveShortestSuffix(pattern) => write!(f, "${{{0}%{1}}}", self.variable, pattern),
-            ParameterExpansionOperator::SubstituteAll(pattern, replacement) => write!(f, "${{{0}//{1}/{2}}}", self.variable, pattern, replacement),
-            ParameterExpansionOperator::DefaultValue(default) => write!(f, "${{{0}:-{1}}}", self.variable, default),
-            ParameterExpansionOperator::AssignDefault(default) => write!(f, "${{{0}:={1}}}", self.variable, default),
-            ParameterExpansionOperator::ErrorIfUnset(error) => write!(f, "${{{0}:?{1}}}", self.variable, error),
-            ParameterExpansionOperator::Basename => write!(f, "${{{0}##*/}}", self.variable),
-            ParameterExpansionOperator::Dirname => write!(f, "${{{0}%/*}}", self.variable),
-            ParameterExpansionOperator::ArraySlice(offset, length) => {
-                if let Some(length_str) = length {
-                    write!(f, "${{{}}}:{}:{}", self.variable, offset, length_str)
-                } else {
-                    write!(f, "${{{}}}:{}", self.variable, offset)
-                }
-            }
-        }
+        f.write_str(&crate::printer::parameter_expansion(self))
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum ParameterExpansionOperator {
     // No operator (simple variable reference)
     None,
-    
+
+    // Length of the value
+    Length,                        // #var
+
     // Case modification
     UppercaseAll,      // ^^
     LowercaseAll,      // ,,
     UppercaseFirst,    // ^
+    LowercaseFirst,    // ,
+    ToggleCaseAll,     // ~~
+    ToggleCaseFirst,   // ~
     
     // Substring removal
     RemoveLongestPrefix(String),  // ##pattern
@@ -179,206 +358,122 @@ pub enum ParameterExpansionOperator {
     RemoveShortestSuffix(String), // %pattern
     
     // Pattern substitution
-    SubstituteAll(String, String), // //pattern/replacement
+    SubstituteAll(String, String),    // //pattern/replacement
+    SubstituteFirst(String, String),  // /pattern/replacement
+    SubstitutePrefix(String, String), // /#pattern/replacement
+    SubstituteSuffix(String, String), // /%pattern/replacement
     
-    // Default values
+    // Default values; the forms with a colon treat an empty value as unset
     DefaultValue(String),          // :-default
     AssignDefault(String),         // :=default
     ErrorIfUnset(String),         // :?error
+    AlternativeValue(String),      // :+alternative
+    DefaultIfUnset(String),        // -default
+    AssignIfUnset(String),         // =default
+    ErrorIfNotSet(String),         // ?error
+    AlternativeIfSet(String),      // +alternative
     
     // Path manipulation
     Basename,                      // ##*/
     Dirname,                       // %/*
     
-    // Array slice operations
+    // Substring, or a slice of the positional parameters for `@` and `*`
     ArraySlice(String, Option<String>), // :offset or :start:length
+
+    // Names of the variables starting with the prefix held in `variable`
+    PrefixNames(char),             // !prefix* or !prefix@
+
+    // Transformation
+    Transform(char),               // @Q, @U, @L, @u
 }
 
 // New AST nodes for expressions
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", content = "value")]
 pub enum Word {
-    Literal(String),
-    Variable(String),
-    ParameterExpansion(ParameterExpansion),
-    Array(String, Vec<String>), // array_name, elements
-    MapAccess(String, String), // map_name, key
-    MapKeys(String), // !map[@] -> get keys of associative array
-    MapLength(String), // #arr[@] -> get length of array
-    ArraySlice(String, String, Option<String>), // array_name, offset, optional_length
-    Arithmetic(ArithmeticExpression),
-    BraceExpansion(BraceExpansion),
-    CommandSubstitution(Box<Command>),
-    StringInterpolation(StringInterpolation),
+    Literal(String, Span),
+    Variable(String, Span),
+    ParameterExpansion(ParameterExpansion, Span),
+    Array(String, Vec<String>, Span), // array_name, elements
+    MapAccess(String, String, Span), // map_name, key
+    MapKeys(String, Span), // !map[@] -> get keys of associative array
+    MapLength(String, Span), // #arr[@] -> get length of array
+    ArraySlice(String, String, Option<String>, Span), // array_name, offset, optional_length
+    Arithmetic(ArithmeticExpression, Span),
+    BraceExpansion(BraceExpansion, Span),
+    CommandSubstitution(Box<Command>, Span),
+    ProcessSubstitution(Box<Command>, ProcessDirection, Span),
+    StringInterpolation(StringInterpolation, Span),
+    // Parts written next to each other, as in `x=$y` or `"$dir"/file`
+    Concatenation(Vec<Word>, Span),
 }
 
 impl std::fmt::Display for Word {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Word::Literal(s) => write!(f, "{}", s),
-            Word::Variable(var) => write!(f, "${}", var),
-            Word::ParameterExpansion(pe) => {
-                match &pe.operator {
-                    ParameterExpansionOperator::None => write!(f, "${{{}}}", pe.variable),
-                    ParameterExpansionOperator::UppercaseAll => write!(f, "${{{}}}", pe.variable),
-                    ParameterExpansionOperator::LowercaseAll => write!(f, "${{{}}}", pe.variable),
-                    ParameterExpansionOperator::UppercaseFirst => write!(f, "${{{}}}", pe.variable),
-                    ParameterExpansionOperator::RemoveLongestPrefix(pattern) => write!(f, "${{{}}}##{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveShortestPrefix(pattern) => write!(f, "${{{}}}#{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveLongestSuffix(pattern) => write!(f, "${{{}}}%%{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveShortestSuffix(pattern) => write!(f, "${{{}}}%{}", pe.variable, pattern),
-                    ParameterExpansionOperator::SubstituteAll(pattern, replacement) => write!(f, "${{{}}}//{}/{}", pe.variable, pattern, replacement),
-                    ParameterExpansionOperator::DefaultValue(default) => write!(f, "${{{}}}:-{}", pe.variable, default),
-                    ParameterExpansionOperator::AssignDefault(default) => write!(f, "${{{}}}:={}", pe.variable, default),
-                    ParameterExpansionOperator::ErrorIfUnset(error) => write!(f, "${{{}}}:?{}", pe.variable, error),
-                    ParameterExpansionOperator::Basename => write!(f, "${{{}}}##*/", pe.variable),
-                    ParameterExpansionOperator::Dirname => write!(f, "${{{}}}%/*", pe.variable),
-                    ParameterExpansionOperator::ArraySlice(offset, length) => {
-                        if let Some(length_str) = length {
-                            write!(f, "${{{}}}:{}:{}", pe.variable, offset, length_str)
-                        } else {
-                            write!(f, "${{{}}}:{}", pe.variable, offset)
-                        }
-                    }
-                }
-            },
-            Word::Array(name, elements) => write!(f, "{}=({})", name, elements.join(" ")),
-            Word::MapAccess(map_name, key) => write!(f, "{}[{}]", map_name, key),
-            Word::MapKeys(map_name) => write!(f, "!{}[@]", map_name),
-            Word::MapLength(map_name) => write!(f, "#{}[@]", map_name),
-            Word::ArraySlice(array_name, offset, length) => {
-                if let Some(length_str) = length {
-                    write!(f, "${{{}}}[@]:{}:{}", array_name, offset, length_str)
-                } else {
-                    write!(f, "${{{}}}[@]:{}", array_name, offset)
-                }
-            },
-            Word::Arithmetic(expr) => write!(f, "{}", expr.expression),
-            Word::BraceExpansion(expansion) => {
-                let mut result = String::new();
-                if let Some(ref prefix) = expansion.prefix {
-                    result.push_str(prefix);
-                }
-                for (i, item) in expansion.items.iter().enumerate() {
-                    if i > 0 {
-                        result.push(',');
-                    }
-                    match item {
-                        BraceItem::Literal(s) => result.push_str(s),
-                        BraceItem::Range(range) => {
-                            result.push_str(&range.start);
-                            result.push_str("..");
-                            result.push_str(&range.end);
-                            if let Some(ref step) = range.step {
-                                result.push_str("..");
-                                result.push_str(step);
-                            }
-                        }
-                        BraceItem::Sequence(seq) => {
-                            result.push_str(&seq.join(","));
-                        }
-                    }
-                }
-                if let Some(ref suffix) = expansion.suffix {
-                    result.push_str(suffix);
-                }
-                write!(f, "{{{}}}", result)
-            }
-            Word::CommandSubstitution(_) => write!(f, "$(...)"),
-            Word::StringInterpolation(interp) => {
-                let mut result = String::new();
-                for part in &interp.parts {
-                    match part {
-                        StringPart::Literal(s) => result.push_str(s),
-                        StringPart::Variable(var) => result.push_str(&format!("${}", var)),
-                        StringPart::ParameterExpansion(pe) => {
-                            match &pe.operator {
-                                ParameterExpansionOperator::None => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::UppercaseAll => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::LowercaseAll => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::UppercaseFirst => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::RemoveLongestPrefix(pattern) => result.push_str(&format!("${{{}}}##{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveShortestPrefix(pattern) => result.push_str(&format!("${{{}}}#{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveLongestSuffix(pattern) => result.push_str(&format!("${{{}}}%%{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveShortestSuffix(pattern) => result.push_str(&format!("${{{}}}%{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::SubstituteAll(pattern, replacement) => result.push_str(&format!("${{{}}}//{}/{}", pe.variable, pattern, replacement)),
-                                ParameterExpansionOperator::DefaultValue(default) => result.push_str(&format!("${{{}}}:-{}", pe.variable, default)),
-                                ParameterExpansionOperator::AssignDefault(default) => result.push_str(&format!("${{{}}}:={}", pe.variable, default)),
-                                ParameterExpansionOperator::ErrorIfUnset(error) => result.push_str(&format!("${{{}}}:?{}", pe.variable, error)),
-                                ParameterExpansionOperator::Basename => result.push_str(&format!("${{{}}}##*/", pe.variable)),
-                                ParameterExpansionOperator::Dirname => result.push_str(&format!("${{{}}}%/*", pe.variable)),
-                                ParameterExpansionOperator::ArraySlice(var, offset) => {
-                                    if let Some(offset_str) = offset {
-                                        result.push_str(&format!("${{{}}}:{1}:{2}", pe.variable, var, offset_str))
-                                    } else {
-                                        result.push_str(&format!("${{{}}}:{1}", pe.variable, var))
-                                    }
-                                }
-                            }
-                        }
-                        StringPart::MapAccess(map_name, key) => result.push_str(&format!("{}[{}]", map_name, key)),
-                        StringPart::MapKeys(map_name) => result.push_str(&format!("!{}[@]", map_name)),
-                        StringPart::MapLength(map_name) => result.push_str(&format!("#{}[@]", map_name)),
-                        StringPart::ArraySlice(array_name, offset, length) => {
-                            if let Some(length_str) = length {
-                                result.push_str(&format!("${{{}[@]}}:{}:{}", array_name, offset, length_str));
-                            } else {
-                                result.push_str(&format!("${{{}[@]}}:{}", array_name, offset));
-                            }
-                        },
-                        StringPart::Arithmetic(expr) => result.push_str(&expr.expression),
-                        StringPart::CommandSubstitution(_) => result.push_str("$(...)"),
-                    }
-                }
-                write!(f, "{}", result)
-            }
-        }
+        f.write_str(&crate::printer::ShellPrinter::new().print_word(self))
     }
 }
 
 impl Word {
+    pub fn span(&self) -> Span {
+        match self {
+            Word::Literal(_, span)
+            | Word::Variable(_, span)
+            | Word::ParameterExpansion(_, span)
+            | Word::Array(_, _, span)
+            | Word::MapAccess(_, _, span)
+            | Word::MapKeys(_, span)
+            | Word::MapLength(_, span)
+            | Word::ArraySlice(_, _, _, span)
+            | Word::Arithmetic(_, span)
+            | Word::BraceExpansion(_, span)
+            | Word::CommandSubstitution(_, span)
+            | Word::ProcessSubstitution(_, _, span)
+            | Word::StringInterpolation(_, span)
+            | Word::Concatenation(_, span) => *span,
+        }
+    }
+
+    /// The same word, located at `span`.
+    pub fn with_span(mut self, span: Span) -> Self {
+        match &mut self {
+            Word::Literal(_, s)
+            | Word::Variable(_, s)
+            | Word::ParameterExpansion(_, s)
+            | Word::Array(_, _, s)
+            | Word::MapAccess(_, _, s)
+            | Word::MapKeys(_, s)
+            | Word::MapLength(_, s)
+            | Word::ArraySlice(_, _, _, s)
+            | Word::Arithmetic(_, s)
+            | Word::BraceExpansion(_, s)
+            | Word::CommandSubstitution(_, s)
+            | Word::ProcessSubstitution(_, _, s)
+            | Word::StringInterpolation(_, s)
+            | Word::Concatenation(_, s) => *s = span,
+        }
+        self
+    }
+
     /// Get a string representation of the word, suitable for display
     pub fn to_string(&self) -> String {
         match self {
-            Word::Literal(s) => s.to_string(),
-            Word::Variable(var) => format!("${}", var),
-            Word::ParameterExpansion(pe) => {
-                match &pe.operator {
-                    ParameterExpansionOperator::None => format!("${{{}}}", pe.variable),
-                    ParameterExpansionOperator::UppercaseAll => format!("${{{}}}", pe.variable),
-                    ParameterExpansionOperator::LowercaseAll => format!("${{{}}}", pe.variable),
-                    ParameterExpansionOperator::UppercaseFirst => format!("${{{}}}", pe.variable),
-                    ParameterExpansionOperator::RemoveLongestPrefix(pattern) => format!("${{{}}}##{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveShortestPrefix(pattern) => format!("${{{}}}#{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveLongestSuffix(pattern) => format!("${{{}}}%%{}", pe.variable, pattern),
-                    ParameterExpansionOperator::RemoveShortestSuffix(pattern) => format!("${{{}}}%{}", pe.variable, pattern),
-                    ParameterExpansionOperator::SubstituteAll(pattern, replacement) => format!("${{{}}}//{}/{}", pe.variable, pattern, replacement),
-                    ParameterExpansionOperator::DefaultValue(default) => format!("${{{}}}:-{}", pe.variable, default),
-                    ParameterExpansionOperator::AssignDefault(default) => format!("${{{}}}:={}", pe.variable, default),
-                    ParameterExpansionOperator::ErrorIfUnset(error) => format!("${{{}}}:?{}", pe.variable, error),
-                    ParameterExpansionOperator::Basename => format!("${{{}}}##*/", pe.variable),
-                    ParameterExpansionOperator::Dirname => format!("${{{}}}%/*", pe.variable),
-                    ParameterExpansionOperator::ArraySlice(offset, length) => {
-                        if let Some(length_str) = length {
-                            format!("${{{}}}:{1}:{2}", pe.variable, offset, length_str)
-                        } else {
-                            format!("${{{}}}:{1}", pe.variable, offset)
-                        }
-                    }
-                }
-            },
-            Word::Array(name, elements) => format!("{}=({})", name, elements.join(" ")),
-            Word::MapAccess(map_name, key) => format!("{}[{}]", map_name, key),
-            Word::MapKeys(map_name) => format!("!{}[@]", map_name),
-            Word::MapLength(map_name) => format!("#{}[@]", map_name),
-            Word::ArraySlice(array_name, offset, length) => {
+            Word::Literal(s, _) => s.to_string(),
+            Word::Variable(var, _) => format!("${}", var),
+            Word::ParameterExpansion(pe, _) => crate::printer::parameter_expansion(pe),
+            Word::Array(name, elements, _) => format!("{}=({})", name, elements.join(" ")),
+            Word::MapAccess(map_name, key, _) => format!("{}[{}]", map_name, key),
+            Word::MapKeys(map_name, _) => format!("!{}[@]", map_name),
+            Word::MapLength(map_name, _) => format!("#{}[@]", map_name),
+            Word::ArraySlice(array_name, offset, length, _) => {
                 if let Some(length_str) = length {
                     format!("${{{}}}[@]:{}:{}", array_name, offset, length_str)
                 } else {
                     format!("${{{}}}[@]:{}", array_name, offset)
                 }
             },
-            Word::Arithmetic(expr) => expr.expression.to_string(),
-            Word::BraceExpansion(expansion) => {
+            Word::Arithmetic(expr, _) => expr.expression.to_string(),
+            Word::BraceExpansion(expansion, _) => {
                 let mut result = String::new();
                 if let Some(ref prefix) = expansion.prefix {
                     result.push_str(prefix);
@@ -408,38 +503,16 @@ impl Word {
                 }
                 format!("{{{}}}", result)
             }
-            Word::CommandSubstitution(_) => "$(...)".to_string(),
-            Word::StringInterpolation(interp) => {
+            Word::CommandSubstitution(..) => "$(...)".to_string(),
+            Word::ProcessSubstitution(_, ProcessDirection::Input, _) => "<(...)".to_string(),
+            Word::ProcessSubstitution(_, ProcessDirection::Output, _) => ">(...)".to_string(),
+            Word::StringInterpolation(interp, _) => {
                 let mut result = String::new();
                 for part in &interp.parts {
                     match part {
                         StringPart::Literal(s) => result.push_str(s),
                         StringPart::Variable(var) => result.push_str(&format!("${}", var)),
-                        StringPart::ParameterExpansion(pe) => {
-                            match &pe.operator {
-                                ParameterExpansionOperator::None => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::UppercaseAll => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::LowercaseAll => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::UppercaseFirst => result.push_str(&format!("${{{}}}", pe.variable)),
-                                ParameterExpansionOperator::RemoveLongestPrefix(pattern) => result.push_str(&format!("${{{}}}##{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveShortestPrefix(pattern) => result.push_str(&format!("${{{}}}#{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveLongestSuffix(pattern) => result.push_str(&format!("${{{}}}%%{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::RemoveShortestSuffix(pattern) => result.push_str(&format!("${{{}}}%{}", pe.variable, pattern)),
-                                ParameterExpansionOperator::SubstituteAll(pattern, replacement) => result.push_str(&format!("${{{}}}//{}/{}", pe.variable, pattern, replacement)),
-                                ParameterExpansionOperator::DefaultValue(default) => result.push_str(&format!("${{{}}}:-{}", pe.variable, default)),
-                                ParameterExpansionOperator::AssignDefault(default) => result.push_str(&format!("${{{}}}:={}", pe.variable, default)),
-                                ParameterExpansionOperator::ErrorIfUnset(error) => result.push_str(&format!("${{{}}}:?{}", pe.variable, error)),
-                                ParameterExpansionOperator::Basename => result.push_str(&format!("${{{}}}##*/", pe.variable)),
-                                ParameterExpansionOperator::Dirname => result.push_str(&format!("${{{}}}%/*", pe.variable)),
-                                ParameterExpansionOperator::ArraySlice(offset, length) => {
-                                    if let Some(length_str) = length {
-                                        result.push_str(&format!("${{{}}}:{1}:{2}", pe.variable, offset, length_str))
-                                    } else {
-                                        result.push_str(&format!("${{{}}}:{1}", pe.variable, offset))
-                                    }
-                                }
-                            }
-                        },
+                        StringPart::ParameterExpansion(pe) => result.push_str(&crate::printer::parameter_expansion(pe)),
                         StringPart::MapAccess(map_name, key) => result.push_str(&format!("${{{}}}[{}]", map_name, key)),
                         StringPart::MapKeys(map_name) => result.push_str(&format!("${{!{}}}[@]", map_name)),
                         StringPart::MapLength(map_name) => result.push_str(&format!("${{#{}}}[@]", map_name)),
@@ -456,13 +529,14 @@ impl Word {
                 }
                 format!("\"{}\"", result)
             }
+            Word::Concatenation(parts, _) => parts.iter().map(Word::to_string).collect(),
         }
     }
 
     /// Get the raw string value if this is a literal, or convert to string otherwise
     pub fn as_str(&self) -> &str {
         match self {
-            Word::Literal(s) => s,
+            Word::Literal(s, _) => s,
             _ => "",
         }
     }
@@ -470,7 +544,7 @@ impl Word {
     /// Get the literal value if this word is a literal, None otherwise
     pub fn as_literal(&self) -> Option<&str> {
         match self {
-            Word::Literal(s) => Some(s),
+            Word::Literal(s, _) => Some(s),
             _ => None,
         }
     }
@@ -493,7 +567,7 @@ impl std::ops::Deref for Word {
     
     fn deref(&self) -> &Self::Target {
         match self {
-            Word::Literal(s) => s,
+            Word::Literal(s, _) => s,
             _ => "",
         }
     }
@@ -502,12 +576,12 @@ impl std::ops::Deref for Word {
 impl PartialEq<str> for Word {
     fn eq(&self, other: &str) -> bool {
         match self {
-            Word::Literal(s) => s == other,
-            Word::Variable(var) => var == other,
-            Word::ParameterExpansion(pe) => pe.variable == other,
-            Word::MapKeys(map_name) => map_name == other,
-            Word::MapLength(map_name) => map_name == other,
-            Word::Arithmetic(expr) => expr.expression == other,
+            Word::Literal(s, _) => s == other,
+            Word::Variable(var, _) => var == other,
+            Word::ParameterExpansion(pe, _) => pe.variable == other,
+            Word::MapKeys(map_name, _) => map_name == other,
+            Word::MapLength(map_name, _) => map_name == other,
+            Word::Arithmetic(expr, _) => expr.expression == other,
             _ => false,
         }
     }
@@ -529,37 +603,104 @@ impl PartialEq<String> for Word {
 
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArithmeticExpression {
     pub expression: String,
-    pub tokens: Vec<ArithmeticToken>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ArithmeticToken {
-    Number(String),
-    Variable(String),
-    Operator(String),
-    ParenOpen,
-    ParenClose,
+    /// The parsed form of `expression`, or None if it is empty or could not
+    /// be parsed (e.g. it contains a command substitution).
+    #[serde(default)]
+    pub tree: Option<ArithmeticNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum ArithmeticNode {
+    Number(i64),
+    Variable(String),                              // `x`, `$x`, `${x}` or a positional like `$1`
+    Element(String, Box<ArithmeticNode>),          // `arr[i]`
+    Unary(ArithmeticOperator, Box<ArithmeticNode>),
+    Binary(Box<ArithmeticNode>, ArithmeticOperator, Box<ArithmeticNode>),
+    // `x = e`, or `x op= e` when an operator is given
+    Assign(Box<ArithmeticNode>, Option<ArithmeticOperator>, Box<ArithmeticNode>),
+    PreIncrement(Box<ArithmeticNode>),
+    PreDecrement(Box<ArithmeticNode>),
+    PostIncrement(Box<ArithmeticNode>),
+    PostDecrement(Box<ArithmeticNode>),
+    Conditional(Box<ArithmeticNode>, Box<ArithmeticNode>, Box<ArithmeticNode>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    BitAnd,
+    BitXor,
+    BitOr,
+    BitNot,
+    LogicalAnd,
+    LogicalOr,
+    LogicalNot,
+    Comma,
+}
+
+impl ArithmeticOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArithmeticOperator::Add => "+",
+            ArithmeticOperator::Subtract => "-",
+            ArithmeticOperator::Multiply => "*",
+            ArithmeticOperator::Divide => "/",
+            ArithmeticOperator::Modulo => "%",
+            ArithmeticOperator::Power => "**",
+            ArithmeticOperator::ShiftLeft => "<<",
+            ArithmeticOperator::ShiftRight => ">>",
+            ArithmeticOperator::Less => "<",
+            ArithmeticOperator::LessEqual => "<=",
+            ArithmeticOperator::Greater => ">",
+            ArithmeticOperator::GreaterEqual => ">=",
+            ArithmeticOperator::Equal => "==",
+            ArithmeticOperator::NotEqual => "!=",
+            ArithmeticOperator::BitAnd => "&",
+            ArithmeticOperator::BitXor => "^",
+            ArithmeticOperator::BitOr => "|",
+            ArithmeticOperator::BitNot => "~",
+            ArithmeticOperator::LogicalAnd => "&&",
+            ArithmeticOperator::LogicalOr => "||",
+            ArithmeticOperator::LogicalNot => "!",
+            ArithmeticOperator::Comma => ",",
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BraceExpansion {
     pub prefix: Option<String>,
     pub items: Vec<BraceItem>,
     pub suffix: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
+#[serde(tag = "type", content = "value")]
 pub enum BraceItem {
     Literal(String),
     Range(BraceRange),
     Sequence(Vec<String>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BraceRange {
     pub start: String,
     pub end: String,
@@ -567,13 +708,14 @@ pub struct BraceRange {
     pub format: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StringInterpolation {
     pub parts: Vec<StringPart>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
+#[serde(tag = "type", content = "value")]
 pub enum StringPart {
     Literal(String),
     Variable(String),
@@ -586,13 +728,29 @@ pub enum StringPart {
     CommandSubstitution(Box<Command>),
 } 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TestExpression {
+    /// The source text between the brackets; `[[ ... ]]` keeps its
+    /// spacing, `[ ... ]` is trimmed.
     pub expression: String,
+    pub condition: TestCondition,
     pub modifiers: TestModifiers,
+    /// Whether this is `[[ ... ]]` rather than `[ ... ]`.
+    pub double_bracket: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum TestCondition {
+    Unary(String, Word),                              // `-f file`, `-z string`
+    Binary(Word, String, Word),                       // `a = b`, `n -lt 3`, `s =~ re`
+    Word(Word),                                       // a lone operand, true if non-empty
+    Not(Box<TestCondition>),
+    And(Box<TestCondition>, Box<TestCondition>),      // `&&`, or `-a` in `[ ]`
+    Or(Box<TestCondition>, Box<TestCondition>),       // `||`, or `-o` in `[ ]`
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TestModifiers {
     pub extglob: bool,
     pub nocasematch: bool,
@@ -613,4 +771,191 @@ impl Default for TestModifiers {
             dotglob: false,
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// The parts of a concatenated word as one double-quoted string, which is
+/// how the generators expand it.
+pub fn concatenated_parts(words: &[Word]) -> StringInterpolation {
+    // Text as it would be written between double quotes
+    let quoted = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+            .replace('`', "\\`")
+    };
+    let mut parts = Vec::new();
+    for word in words {
+        match word {
+            Word::Literal(text, _) => {
+                let text = match text.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+                    Some(inner) => inner.to_string(),
+                    None => unescape_unquoted(text),
+                };
+                parts.push(StringPart::Literal(quoted(&text)));
+            }
+            Word::StringInterpolation(interp, _) => parts.extend(interp.parts.iter().cloned()),
+            Word::Concatenation(words, _) => parts.extend(concatenated_parts(words).parts),
+            Word::Variable(name, _) => parts.push(StringPart::Variable(name.clone())),
+            Word::ParameterExpansion(pe, _) => parts.push(StringPart::ParameterExpansion(pe.clone())),
+            Word::MapAccess(name, key, _) => parts.push(StringPart::MapAccess(name.clone(), key.clone())),
+            Word::MapKeys(name, _) => parts.push(StringPart::MapKeys(name.clone())),
+            Word::MapLength(name, _) => parts.push(StringPart::MapLength(name.clone())),
+            Word::ArraySlice(name, offset, length, _) => {
+                parts.push(StringPart::ArraySlice(name.clone(), offset.clone(), length.clone()))
+            }
+            Word::Arithmetic(expr, _) => parts.push(StringPart::Arithmetic(expr.clone())),
+            Word::CommandSubstitution(cmd, _) => parts.push(StringPart::CommandSubstitution(cmd.clone())),
+            other => parts.push(StringPart::Literal(quoted(&other.to_string()))),
+        }
+    }
+    StringInterpolation { parts }
+}
+
+/// Unquoted text with its backslash escapes removed, as in `a\ b`.
+fn unescape_unquoted(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Serialize a parsed script to the JSON form of the AST.
+///
+/// Every enum is adjacently tagged as `{"type": "Variant", "value": ...}` so
+/// that tools in other languages can walk the tree without knowing serde's
+/// conventions. `commands_from_json` reads the same form back.
+pub fn commands_to_json(commands: &[Command]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(commands)
+}
+
+/// A `Command` or `Word` in JSON may leave out its span, as in an AST
+/// written by hand or by another tool; it is then `Span::default()`.
+pub fn commands_from_json(json: &str) -> serde_json::Result<Vec<Command>> {
+    serde_json::from_str(json)
+}
+
+impl Serialize for Command {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Command::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Every variant has one field besides its span, but a blank line
+        let node = with_span(serde_json::Value::deserialize(deserializer)?, |variant| match variant {
+            "BlankLine" => 0,
+            _ => 1,
+        });
+        Command::deserialize(node).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Word {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Word::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Word {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let node = with_span(serde_json::Value::deserialize(deserializer)?, |variant| match variant {
+            "Array" | "MapAccess" | "ProcessSubstitution" => 2,
+            "ArraySlice" => 3,
+            _ => 1,
+        });
+        Word::deserialize(node).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A node of the JSON form with its span added if it was left out, given
+/// how many other fields each variant has.
+fn with_span(mut node: serde_json::Value, fields: impl Fn(&str) -> usize) -> serde_json::Value {
+    use serde_json::Value;
+    let Some(object) = node.as_object_mut() else { return node };
+    let Some(fields) = object.get("type").and_then(Value::as_str).map(fields) else { return node };
+    let is_span = |value: &Value| value.get("line").is_some();
+    let mut items = match (fields, object.remove("value").unwrap_or(Value::Null)) {
+        (0, Value::Null) => Vec::new(),
+        (1, Value::Array(items)) if items.len() == 2 && is_span(&items[1]) => items,
+        (2.., Value::Array(items)) => items,
+        (_, value) => vec![value],
+    };
+    if items.len() == fields {
+        items.push(serde_json::to_value(Span::default()).expect("a span is plain data"));
+    }
+    let value = if items.len() == 1 { items.remove(0) } else { Value::Array(items) };
+    object.insert("value".to_string(), value);
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::commands::Parser;
+
+    #[test]
+    fn test_json_round_trip() {
+        let script = "x=1 y=2 echo \"$x ${y}\" > out.txt\nfor i in a b; do echo $(date) | cat; done";
+        let commands = Parser::new(script).parse().unwrap();
+        let json = commands_to_json(&commands).unwrap();
+        assert!(json.contains("\"type\": \"For\""));
+        assert_eq!(commands_from_json(&json).unwrap(), commands);
+    }
+
+    #[test]
+    fn test_generate_from_json() {
+        let script = "until [ -f done ]; do touch done; done\ncase $x in a*) echo a;; *) echo other;; esac";
+        let commands = Parser::new(script).parse().unwrap();
+        let json = commands_to_json(&commands).unwrap();
+        let perl = crate::generator::Generator::new().generate(&commands_from_json(&json).unwrap());
+        assert_eq!(perl, crate::generator::Generator::new().generate(&commands));
+        assert!(perl.contains("until"));
+    }
+
+    #[test]
+    fn test_json_without_spans() {
+        let json = r#"[
+            {"type": "Simple", "value": {"name": {"type": "Literal", "value": "echo"},
+                "args": [{"type": "Variable", "value": "x"}, {"type": "ProcessSubstitution", "value": [
+                    {"type": "Simple", "value": {"name": {"type": "Literal", "value": "ls"},
+                        "args": [], "redirects": [], "env_vars": []}},
+                    "Input"]}],
+                "redirects": [], "env_vars": []}},
+            {"type": "BlankLine"}
+        ]"#;
+        let commands = commands_from_json(json).unwrap();
+        assert_eq!(commands[0], Parser::new("echo $x <(ls)").parse().unwrap()[0]);
+        assert!(matches!(commands[1], Command::BlankLine(_)));
+        assert_eq!(commands[0].span().line, 0);
+    }
+
+    #[test]
+    fn test_spans() {
+        let script = "echo hi\nls -l $(pwd) | wc";
+        let commands = Parser::new(script).parse().unwrap();
+        let span = commands[1].span();
+        assert_eq!((span.line, span.col), (2, 1));
+        assert_eq!(&script[span.start..span.end], "ls -l $(pwd) | wc");
+        let Command::Pipeline(pipeline, _) = &commands[1] else { panic!("expected a pipeline") };
+        let Command::Simple(ls, _) = &pipeline.commands[0] else { panic!("expected a simple command") };
+        let substitution = ls.args[1].span();
+        assert_eq!(&script[substitution.start..substitution.end], "$(pwd)");
+        let Word::CommandSubstitution(pwd, _) = &ls.args[1] else { panic!("expected a substitution") };
+        assert_eq!(pwd.span().col, 9);
+    }
+
+    #[test]
+    fn test_assignment_order() {
+        let commands = Parser::new("z=1 y=$z m[k]=v echo").parse().unwrap();
+        let Command::Simple(cmd, _) = &commands[0] else { panic!("expected a simple command") };
+        let targets: Vec<String> = cmd.env_vars.iter().map(Assignment::target).collect();
+        assert_eq!(targets, vec!["z", "y", "m[k]"]);
+        assert_eq!(cmd.env_vars[2].index.as_deref(), Some("k"));
+    }
+}
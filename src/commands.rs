@@ -3,7 +3,31 @@ use std::process::Command;
 use std::io::Write;
 use crate::shared_utils;
 use crate::utils::{extract_line_col, caret_snippet};
-use debashl::{Lexer, Parser, Generator};
+use debashl::{Lexer, Parser, Generator, PythonGenerator, RustGenerator, ShellPrinter, Backend,
+              commands_to_json, commands_from_json};
+use debashl::parser::errors::ParserError;
+
+/// The code generator for a target language as the command line names it.
+pub fn backend_for(lang: &str) -> Box<dyn Backend> {
+    match lang {
+        "perl" => Box::new(Generator::new()),
+        "python" => Box::new(PythonGenerator::new()),
+        "rust" => Box::new(RustGenerator::new()),
+        _ => {
+            eprintln!("Error: Only 'perl', 'python' and 'rust' languages are supported");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The name of a target language in headings.
+fn language_name(lang: &str) -> &'static str {
+    match lang {
+        "python" => "Python",
+        "rust" => "Rust",
+        _ => "Perl",
+    }
+}
 
 pub fn run_generated(lang: &str, input: &str) {
     let source = if input.ends_with(".sh") || std::path::Path::new(input).exists() {
@@ -13,17 +37,31 @@ pub fn run_generated(lang: &str, input: &str) {
         Ok(c) => c,
         Err(e) => { println!("Parse error: {}", e); return; }
     };
+    let code = backend_for(lang).generate(&commands);
     match lang {
-        "perl" => {
-            let mut gen = Generator::new();
-            let code = gen.generate(&commands);
-            let tmp = "__tmp_run.pl";
+        "perl" | "python" => {
+            let (tmp, interpreter) = if lang == "perl" { ("__tmp_run.pl", "perl") } else { ("__tmp_run.py", "python3") };
+            if shared_utils::SharedUtils::write_utf8_file(tmp, &code).is_ok() {
+                let _ = std::process::Command::new(interpreter).arg(tmp).status();
+                let _ = fs::remove_file(tmp);
+            }
+        }
+        _ => {
+            let tmp = "__tmp_run.rs";
+            let bin = "__tmp_run_bin";
             if shared_utils::SharedUtils::write_utf8_file(tmp, &code).is_ok() {
-                let _ = std::process::Command::new("perl").arg(tmp).status();
+                let compiled = std::process::Command::new("rustc")
+                    .args(["--edition", "2021", "-A", "warnings", "-o", bin, tmp])
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false);
+                if compiled {
+                    let _ = std::process::Command::new(format!("./{}", bin)).status();
+                    let _ = fs::remove_file(bin);
+                }
                 let _ = fs::remove_file(tmp);
             }
         }
-        _ => println!("Unsupported language for --run: {}", lang),
     }
 }
 
@@ -77,7 +115,18 @@ pub fn parse_file(filename: &str) {
     
     match fs::read_to_string(filename) {
         Ok(content) => {
-            parse_input(&content);
+            println!("AST:");
+            println!("{}", "=".repeat(50));
+            // Carry on past errors so that every problem in the file is reported
+            let (commands, errors) = Parser::new(&content).parse_with_recovery();
+            for (i, command) in commands.iter().enumerate() {
+                println!("Command {}: {:?}", i + 1, command);
+            }
+            report_parse_errors(&content, &errors);
+            println!("{}", "=".repeat(50));
+            if !errors.is_empty() {
+                std::process::exit(1);
+            }
         }
         Err(e) => {
             println!("Error reading file: {}", e);
@@ -85,7 +134,162 @@ pub fn parse_file(filename: &str) {
     }
 }
 
-pub fn parse_to_perl(input: &str) {
+/// Print each error to STDERR with its location and the offending source
+/// line, keeping it out of the generated code on STDOUT.
+fn report_parse_errors(content: &str, errors: &[ParserError]) {
+    for e in errors {
+        match e.location() {
+            Some((line, col)) => {
+                // The location is already in the header, so show the bare error
+                let message = match e {
+                    ParserError::Located { error, .. } => error.to_string(),
+                    _ => e.to_string(),
+                };
+                eprintln!("Parse error at {}:{}: {}", line, col, message);
+                if let Some(snippet) = caret_snippet(content, line, col) {
+                    eprintln!("{}", snippet);
+                }
+            }
+            None => eprintln!("Parse error: {}", e),
+        }
+    }
+    if errors.len() > 1 {
+        eprintln!("{} parse errors", errors.len());
+    }
+}
+
+/// Reformat a shell script with `ShellPrinter`, like `shfmt`.
+///
+/// With `write` the file is rewritten in place. With `check` nothing is
+/// printed on success; instead the formatted script is parsed again and the
+/// command fails unless it yields the same AST as the original.
+pub fn format_script(input: &str, write: bool, check: bool) {
+    let is_file = input.ends_with(".sh") || std::path::Path::new(input).exists();
+    let content = if is_file {
+        match fs::read_to_string(input) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error reading file {}: {}", input, e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        input.to_string()
+    };
+
+    let commands = match Parser::new(&content).parse() {
+        Ok(commands) => commands,
+        Err(e) => {
+            if let Some((line, col)) = extract_line_col(&e) {
+                eprintln!("Parse error at {}:{}: {}", line, col, e);
+                if let Some(snippet) = caret_snippet(&content, line, col) {
+                    eprintln!("{}", snippet);
+                }
+            } else {
+                eprintln!("Parse error: {}", e);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    let mut formatted = ShellPrinter::new().print(&commands);
+    // Comments are not part of the AST, but the interpreter line must survive
+    if let Some(shebang) = content.lines().next().filter(|line| line.starts_with("#!")) {
+        formatted = format!("{}\n{}", shebang, formatted);
+    }
+
+    if check {
+        match Parser::new(&formatted).parse() {
+            Ok(reparsed) if reparsed == commands => {}
+            Ok(reparsed) => {
+                eprintln!("Round-trip mismatch: the formatted script parses to a different AST");
+                for (i, (before, after)) in commands.iter().zip(reparsed.iter()).enumerate() {
+                    if before != after {
+                        eprintln!("First difference at command {}:", i + 1);
+                        eprintln!("  original:  {:?}", before);
+                        eprintln!("  formatted: {:?}", after);
+                        break;
+                    }
+                }
+                if commands.len() != reparsed.len() {
+                    eprintln!("Command count changed from {} to {}", commands.len(), reparsed.len());
+                }
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("The formatted script does not parse: {}", e);
+                eprint!("{}", formatted);
+                std::process::exit(1);
+            }
+        }
+    } else if write && is_file {
+        // A rewrite that changed what the script does would lose the original
+        if !matches!(Parser::new(&formatted).parse(), Ok(reparsed) if reparsed == commands) {
+            eprintln!("Refusing to rewrite {}: the formatted script does not parse back to the same AST", input);
+            std::process::exit(1);
+        }
+        if let Err(e) = shared_utils::SharedUtils::write_utf8_file(input, &formatted) {
+            eprintln!("Error writing file {}: {}", input, e);
+            std::process::exit(1);
+        }
+    } else {
+        print!("{}", formatted);
+    }
+}
+
+/// Print the AST of a script as JSON, with nothing else on stdout so the
+/// output can be piped straight into other tools.
+pub fn parse_to_json(input: &str) {
+    let content = if input.ends_with(".sh") || std::path::Path::new(input).exists() {
+        fs::read_to_string(input).unwrap_or_else(|_| input.to_string())
+    } else {
+        input.to_string()
+    };
+
+    let commands = match Parser::new(&content).parse() {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match commands_to_json(&commands) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Error serializing AST: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Generate code from an AST in the JSON form produced by `parse --json`.
+/// `input` is a file name, or `-` to read the JSON from stdin.
+pub fn generate_from_json(lang: &str, input: &str) {
+    let json = if input == "-" {
+        let mut json = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut json).map(|_| json)
+    } else {
+        fs::read_to_string(input)
+    };
+    let json = match json {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", input, e);
+            std::process::exit(1);
+        }
+    };
+    let commands = match commands_from_json(&json) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("Invalid AST JSON: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    print!("{}", backend_for(lang).generate(&commands));
+}
+
+pub fn parse_to_code(lang: &str, input: &str) {
     // Check if input looks like a filename and read it if so
     let content = if input.ends_with(".sh") || std::path::Path::new(input).exists() {
         match fs::read_to_string(input) {
@@ -95,16 +299,16 @@ pub fn parse_to_perl(input: &str) {
     } else {
         input.to_string()
     };
+    let name = language_name(lang);
     
-    println!("Converting to Perl: {}", content);
-    println!("Perl Code:");
+    println!("Converting to {}: {}", name, content);
+    println!("{} Code:", name);
     println!("{}", "=".repeat(50));
     
     match Parser::new(&content).parse() {
         Ok(commands) => {
-            let mut generator = Generator::new();
-            let perl_code = generator.generate(&commands);
-            println!("{}", perl_code);
+            let code = backend_for(lang).generate(&commands);
+            println!("{}", code);
         }
         Err(e) => {
             println!("Parse error: {}", e);
@@ -114,27 +318,26 @@ pub fn parse_to_perl(input: &str) {
     println!("{}", "=".repeat(50));
 }
 
-pub fn parse_file_to_perl(filename: &str) {
-    println!("Converting file to Perl: {}", filename);
+pub fn parse_file_to_code(lang: &str, filename: &str) {
+    let name = language_name(lang);
+    println!("Converting file to {}: {}", name, filename);
     
     match fs::read_to_string(filename) {
         Ok(content) => {
-            println!("Converting to Perl: {}", content);
-            println!("Perl Code:");
+            println!("Converting to {}: {}", name, content);
+            println!("{} Code:", name);
             println!("{}", "=".repeat(50));
             
-            match Parser::new(&content).parse() {
-                Ok(commands) => {
-                    let mut generator = Generator::new();
-                    let perl_code = generator.generate(&commands);
-                    println!("{}", perl_code);
-                }
-                Err(e) => {
-                    println!("Parse error: {}", e);
-                }
-            }
+            // Statements that fail to parse are reported and left out
+            let (commands, errors) = Parser::new(&content).parse_with_recovery();
+            report_parse_errors(&content, &errors);
+            let code = backend_for(lang).generate(&commands);
+            println!("{}", code);
             
             println!("{}", "=".repeat(50));
+            if !errors.is_empty() {
+                std::process::exit(1);
+            }
         }
         Err(e) => {
             println!("Error reading file: {}", e);
@@ -0,0 +1,324 @@
+use crate::ast::*;
+use super::Generator;
+
+/// How the value of an arithmetic expression is used. Perl's comparison and
+/// logical operators do not yield bash's 0 and 1, so their results are
+/// normalized unless only their truth matters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Context {
+    Value,     // printed or stored
+    Operand,   // used by another arithmetic operator
+    Condition, // only tested for truth, or discarded
+}
+
+// Perl operator precedence, loosest first
+const COMMA: u8 = 1;
+const ASSIGN: u8 = 2;
+const TERNARY: u8 = 3;
+const OR: u8 = 4;
+const AND: u8 = 5;
+const BIT_OR: u8 = 6; // `|` and `^`
+const BIT_AND: u8 = 7;
+const EQUALITY: u8 = 8;
+const RELATIONAL: u8 = 9;
+const SHIFT: u8 = 10;
+const ADDITIVE: u8 = 11;
+const MULTIPLICATIVE: u8 = 12;
+const UNARY: u8 = 13;
+const POWER: u8 = 14;
+const INCREMENT: u8 = 15;
+const TERM: u8 = 16;
+
+pub fn convert_arithmetic_to_perl_impl(_generator: &Generator, expr: &ArithmeticExpression) -> String {
+    let code = convert(expr, Context::Value);
+    // A leading parenthesis would make e.g. `print (a) * b` a function call
+    if code.starts_with('(') { format!("+{}", code) } else { code }
+}
+
+pub fn convert_arithmetic_condition_to_perl_impl(_generator: &Generator, expr: &ArithmeticExpression) -> String {
+    convert(expr, Context::Condition)
+}
+
+/// A standalone `(( expr ))` command, evaluated for its side effects and
+/// setting the status to whether the value is non-zero.
+pub fn generate_arithmetic_command_impl(generator: &mut Generator, expr: &ArithmeticExpression) -> String {
+    let mut output = declare_arithmetic_names(generator, expr);
+    output.push_str(&format!("{}$? = ({}) ? 0 : 256;\n", generator.indent(), convert(expr, Context::Condition)));
+    output
+}
+
+/// Declares the variables an expression assigns, as `use strict` requires.
+pub fn declare_arithmetic_names(generator: &mut Generator, expr: &ArithmeticExpression) -> String {
+    let mut output = String::new();
+    let mut names = Vec::new();
+    if let Some(tree) = &expr.tree {
+        assigned_names(tree, &mut names);
+    }
+    for name in names {
+        if !generator.declared_locals.contains(&name) {
+            output.push_str(&format!("{}my ${};\n", generator.indent(), name));
+            generator.declared_locals.insert(name);
+        }
+    }
+    output
+}
+
+fn assigned_names(node: &ArithmeticNode, names: &mut Vec<String>) {
+    match node {
+        ArithmeticNode::Number(_) | ArithmeticNode::Variable(_) => {}
+        ArithmeticNode::Element(_, index) => assigned_names(index, names),
+        ArithmeticNode::Unary(_, operand) => assigned_names(operand, names),
+        ArithmeticNode::Binary(left, _, right) => {
+            assigned_names(left, names);
+            assigned_names(right, names);
+        }
+        ArithmeticNode::Assign(target, _, value) => {
+            assigned_names(target, names);
+            assigned_names(value, names);
+            push_target(target, names);
+        }
+        ArithmeticNode::PreIncrement(target)
+        | ArithmeticNode::PreDecrement(target)
+        | ArithmeticNode::PostIncrement(target)
+        | ArithmeticNode::PostDecrement(target) => push_target(target, names),
+        ArithmeticNode::Conditional(condition, then_value, else_value) => {
+            assigned_names(condition, names);
+            assigned_names(then_value, names);
+            assigned_names(else_value, names);
+        }
+    }
+}
+
+fn push_target(target: &ArithmeticNode, names: &mut Vec<String>) {
+    if let ArithmeticNode::Variable(name) = target {
+        if !name.starts_with(|c: char| c.is_ascii_digit()) && !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+}
+
+fn convert(expr: &ArithmeticExpression, context: Context) -> String {
+    let Some(tree) = &expr.tree else {
+        return convert_arithmetic_text_to_perl(&expr.expression);
+    };
+    let (code, _) = node_to_perl(tree, context);
+    if needs_integer(tree) {
+        // Truncating division, C-style `%`, 64-bit wraparound and signed bit operations
+        format!("do {{ use integer; {} }}", code)
+    } else {
+        code
+    }
+}
+
+/// Whether Perl's floating-point arithmetic would give a different result
+/// than bash's 64-bit integers for this expression.
+fn needs_integer(node: &ArithmeticNode) -> bool {
+    use ArithmeticOperator::*;
+    let integer_operator = |op: &ArithmeticOperator| {
+        matches!(op, Multiply | Divide | Modulo | Power | ShiftLeft | ShiftRight | BitAnd | BitXor | BitOr | BitNot)
+    };
+    match node {
+        // Doubles only hold integers exactly up to 2**53
+        ArithmeticNode::Number(value) => value.unsigned_abs() > 1 << 53,
+        ArithmeticNode::Variable(_) => false,
+        ArithmeticNode::Element(_, index) => needs_integer(index),
+        ArithmeticNode::Unary(op, operand) => integer_operator(op) || needs_integer(operand),
+        ArithmeticNode::Binary(left, op, right) => integer_operator(op) || needs_integer(left) || needs_integer(right),
+        ArithmeticNode::Assign(_, op, value) => op.as_ref().is_some_and(integer_operator) || needs_integer(value),
+        ArithmeticNode::PreIncrement(_)
+        | ArithmeticNode::PreDecrement(_)
+        | ArithmeticNode::PostIncrement(_)
+        | ArithmeticNode::PostDecrement(_) => false,
+        ArithmeticNode::Conditional(condition, then_value, else_value) => {
+            needs_integer(condition) || needs_integer(then_value) || needs_integer(else_value)
+        }
+    }
+}
+
+/// Perl code for a node, with the precedence of its outermost operator.
+fn node_to_perl(node: &ArithmeticNode, context: Context) -> (String, u8) {
+    use ArithmeticOperator::*;
+    match node {
+        ArithmeticNode::Number(value) if *value < 0 => (value.to_string(), UNARY),
+        ArithmeticNode::Number(value) => (value.to_string(), TERM),
+        ArithmeticNode::Variable(_) | ArithmeticNode::Element(..) => (lvalue_to_perl(node), TERM),
+        ArithmeticNode::Unary(LogicalNot, operand) => {
+            let code = format!("!{}", operand_to_perl(operand, Context::Condition, UNARY));
+            boolean_result(code, UNARY, context == Context::Value)
+        }
+        ArithmeticNode::Unary(Add, operand) => node_to_perl(operand, context),
+        ArithmeticNode::Unary(op, operand) => {
+            let operand = operand_to_perl(operand, Context::Operand, UNARY);
+            // Keep `- -x` from reading as a decrement
+            let separator = if operand.starts_with(['-', '+']) { " " } else { "" };
+            (format!("{}{}{}", op.as_str(), separator, operand), UNARY)
+        }
+        ArithmeticNode::Binary(left, Comma, right) => {
+            let left = operand_to_perl(left, Context::Condition, ASSIGN);
+            let right = operand_to_perl(right, context, ASSIGN);
+            if context == Context::Value {
+                // A list would be flattened into the surrounding `print`
+                (format!("do {{ {}; {} }}", left, right), TERM)
+            } else {
+                (format!("{}, {}", left, right), COMMA)
+            }
+        }
+        ArithmeticNode::Binary(left, Power, right) => (power_to_perl(left, right), TERM),
+        ArithmeticNode::Binary(left, op @ (LogicalAnd | LogicalOr), right) => {
+            let precedence = if *op == LogicalAnd { AND } else { OR };
+            let code = format!("{} {} {}", operand_to_perl(left, Context::Condition, precedence), op.as_str(),
+                               operand_to_perl(right, Context::Condition, precedence + 1));
+            // Perl's `&&` and `||` yield an operand rather than 0 or 1
+            boolean_result(code, precedence, context != Context::Condition)
+        }
+        ArithmeticNode::Binary(left, op, right) => {
+            let precedence = binary_precedence(*op);
+            let code = format!("{} {} {}", operand_to_perl(left, Context::Operand, precedence), op.as_str(),
+                               operand_to_perl(right, Context::Operand, precedence + 1));
+            let boolean = matches!(op, Less | LessEqual | Greater | GreaterEqual | Equal | NotEqual);
+            boolean_result(code, precedence, boolean && context == Context::Value)
+        }
+        ArithmeticNode::Assign(target, None, value) => {
+            (format!("{} = {}", lvalue_to_perl(target), operand_to_perl(value, Context::Value, ASSIGN)), ASSIGN)
+        }
+        ArithmeticNode::Assign(target, Some(Power), value) => {
+            (format!("{} = {}", lvalue_to_perl(target), power_to_perl(target, value)), ASSIGN)
+        }
+        ArithmeticNode::Assign(target, Some(op), value) => {
+            (format!("{} {}= {}", lvalue_to_perl(target), op.as_str(), operand_to_perl(value, Context::Operand, ASSIGN)), ASSIGN)
+        }
+        ArithmeticNode::PreIncrement(target) | ArithmeticNode::PreDecrement(target) => {
+            let op = if matches!(node, ArithmeticNode::PreIncrement(_)) { "++" } else { "--" };
+            let code = format!("{}{}", op, lvalue_to_perl(target));
+            if context == Context::Value {
+                // Perl's prefix operators yield the variable itself, which a
+                // later argument of the same `print` could still change
+                (format!("{} + 0", code), ADDITIVE)
+            } else {
+                (code, INCREMENT)
+            }
+        }
+        ArithmeticNode::PostIncrement(target) => (format!("{}++", lvalue_to_perl(target)), INCREMENT),
+        ArithmeticNode::PostDecrement(target) => (format!("{}--", lvalue_to_perl(target)), INCREMENT),
+        ArithmeticNode::Conditional(condition, then_value, else_value) => {
+            let code = format!("{} ? {} : {}", operand_to_perl(condition, Context::Condition, TERNARY + 1),
+                               operand_to_perl(then_value, context, TERNARY),
+                               operand_to_perl(else_value, context, TERNARY));
+            (code, TERNARY)
+        }
+    }
+}
+
+/// Code for a subexpression, parenthesized if it binds looser than `min`.
+fn operand_to_perl(node: &ArithmeticNode, context: Context, min: u8) -> String {
+    let (code, precedence) = node_to_perl(node, context);
+    if precedence < min { format!("({})", code) } else { code }
+}
+
+fn boolean_result(code: String, precedence: u8, normalize: bool) -> (String, u8) {
+    if normalize {
+        let condition = if precedence > TERNARY { code } else { format!("({})", code) };
+        (format!("{} ? 1 : 0", condition), TERNARY)
+    } else {
+        (code, precedence)
+    }
+}
+
+fn binary_precedence(op: ArithmeticOperator) -> u8 {
+    use ArithmeticOperator::*;
+    match op {
+        Multiply | Divide | Modulo => MULTIPLICATIVE,
+        Add | Subtract => ADDITIVE,
+        ShiftLeft | ShiftRight => SHIFT,
+        Less | LessEqual | Greater | GreaterEqual => RELATIONAL,
+        Equal | NotEqual => EQUALITY,
+        BitAnd => BIT_AND,
+        BitOr | BitXor => BIT_OR,
+        LogicalAnd => AND,
+        LogicalOr => OR,
+        Comma => COMMA,
+        Power => POWER,
+        BitNot | LogicalNot => UNARY,
+    }
+}
+
+/// Perl's `**` works in floating point even under `use integer`, so integer
+/// powers are computed by repeated multiplication.
+fn power_to_perl(base: &ArithmeticNode, exponent: &ArithmeticNode) -> String {
+    if let (ArithmeticNode::Number(base), ArithmeticNode::Number(exponent)) = (base, exponent) {
+        if let Ok(exponent) = u32::try_from(*exponent) {
+            return base.wrapping_pow(exponent).to_string();
+        }
+    }
+    format!("do {{ my ($base, $exponent, $power) = ({}, {}, 1); $power *= $base while $exponent-- > 0; $power }}",
+            operand_to_perl(base, Context::Operand, ASSIGN), operand_to_perl(exponent, Context::Operand, ASSIGN))
+}
+
+fn lvalue_to_perl(node: &ArithmeticNode) -> String {
+    match node {
+        ArithmeticNode::Variable(name) => match name.parse::<usize>() {
+            // Positional parameters
+            Ok(0) => "$0".to_string(),
+            Ok(n) => format!("$ARGV[{}]", n - 1),
+            Err(_) => format!("${}", name),
+        },
+        ArithmeticNode::Element(name, index) => {
+            format!("${}[{}]", name, operand_to_perl(index, Context::Operand, COMMA + 1))
+        }
+        other => node_to_perl(other, Context::Operand).0,
+    }
+}
+
+/// Fallback for expressions the arithmetic parser does not understand:
+/// operators carry over unchanged, but bare variable names need their sigil.
+fn convert_arithmetic_text_to_perl(expr: &str) -> String {
+    let chars: Vec<char> = expr.trim().chars().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_digit() {
+            // Numbers, including forms like 0x1f, pass through whole
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                result.push(chars[i]);
+                i += 1;
+            }
+        } else if c == '$' || c.is_ascii_alphabetic() || c == '_' {
+            if c == '$' {
+                i += 1;
+            }
+            let braced = chars.get(i) == Some(&'{');
+            if braced {
+                i += 1;
+            }
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            if braced && chars.get(i) == Some(&'}') {
+                i += 1;
+            }
+            match name.parse::<usize>() {
+                // Positional parameters
+                Ok(n) if n > 0 => result.push_str(&format!("$ARGV[{}]", n - 1)),
+                _ => result.push_str(&format!("${}", name)),
+            }
+        } else {
+            result.push(c);
+            i += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_increment_and_decrement() {
+        let script = "c=5\necho $((--c)) $c\necho $((c--)) $c\necho $((++c)) $((c++)) $c\nx=$((c-- + 1))\necho \"$x $c\"\n(( c-- ))\necho \"n=$(( (c + 1) * 2 ))\"";
+        assert_eq!(run_perl(script, "").0, "4 4\n4 3\n4 4 5\n6 4\nn=8\n");
+    }
+}
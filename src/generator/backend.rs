@@ -0,0 +1,42 @@
+use crate::ast::*;
+
+/// Per-node entry points shared by every code generator.
+///
+/// Each target language implements this trait so that callers can pick a
+/// backend at runtime (see `backend_for` behind `debashc parse --perl`,
+/// `--python` and `--rust`) without knowing which concrete generator they
+/// are driving.
+pub trait Backend {
+    /// Translate a whole script, including any prologue the target needs.
+    fn generate(&mut self, ast: &[Command]) -> String;
+
+    fn generate_command(&mut self, command: &Command) -> String;
+    fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String;
+    fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String;
+    fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String;
+    fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String;
+    fn generate_subshell(&mut self, command: &Command) -> String;
+    fn generate_background(&mut self, command: &Command) -> String;
+    fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String;
+    fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String;
+    fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String;
+    fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String;
+    fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String;
+    fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String;
+    fn generate_function(&mut self, func: &Function) -> String;
+    fn generate_block(&mut self, block: &Block) -> String;
+    fn generate_break_statement(&mut self, level: &Option<String>) -> String;
+    fn generate_continue_statement(&mut self, level: &Option<String>) -> String;
+    fn generate_return_statement(&mut self, value: &Option<Word>) -> String;
+    fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String;
+    fn generate_redirect(&mut self, redirect: &Redirect) -> String;
+    fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String;
+
+    /// Render a word as an expression in the target language.
+    fn word_to_code(&mut self, word: &Word) -> String;
+
+    /// Render a word as it should appear inside a test/condition.
+    fn word_to_code_for_test(&mut self, word: &Word) -> String {
+        self.word_to_code(word)
+    }
+}
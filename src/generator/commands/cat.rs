@@ -1,19 +1,18 @@
 use crate::ast::*;
 use crate::generator::Generator;
+use crate::generator::redirects::heredoc_to_perl;
 
 pub fn generate_cat_command(generator: &mut Generator, cmd: &SimpleCommand, redirects: &[Redirect]) -> String {
     let mut output = String::new();
     
-    // Check if this cat command has heredoc redirects
-    let mut has_heredoc = false;
-    for redir in redirects {
-        if matches!(redir.operator, RedirectOperator::Heredoc | RedirectOperator::HeredocTabs) {
-            has_heredoc = true;
-            if let Some(body) = &redir.heredoc_body {
-                // Print the heredoc content directly
-                output.push_str(&format!("print {};\n", generator.perl_string_literal(&Word::Literal(body.clone()))));
-            }
-        }
+    // Check if this cat command has heredoc redirects; the last one is
+    // what stdin ends up reading
+    let heredoc = redirects.iter().rev()
+        .find(|redir| matches!(redir.operator, RedirectOperator::Heredoc | RedirectOperator::HeredocTabs));
+    let has_heredoc = heredoc.is_some();
+    if let Some(redir) = heredoc {
+        // Print the heredoc content directly
+        output.push_str(&format!("print {};\n", heredoc_to_perl(generator, redir)));
     }
     
     // If no heredocs, handle file reading as before
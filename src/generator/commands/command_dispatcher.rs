@@ -2,51 +2,87 @@ use crate::ast::*;
 use crate::generator::Generator;
 use super::cat::generate_cat_command;
 
+/// Generate a command, then wait for the process substitutions its words
+/// started, which must stay open while it runs.
 pub fn generate_command_impl(generator: &mut Generator, command: &Command) -> String {
+    let outer = std::mem::take(&mut generator.pending_process_subs);
+    let mut output = generate_command_kind(generator, command);
+    let started = std::mem::replace(&mut generator.pending_process_subs, outer);
+    if started > 0 {
+        output.push_str(&format!("{}{{ local $?; close($_) for splice(@process_subs, -{}); }}\n", generator.indent(), started));
+    }
+    output
+}
+
+fn generate_command_kind(generator: &mut Generator, command: &Command) -> String {
     match command {
-        Command::Simple(cmd) => generator.generate_simple_command(cmd),
-        Command::ShoptCommand(cmd) => generator.generate_shopt_command(cmd),
-        Command::TestExpression(test_expr) => {
-            generator.generate_test_expression(test_expr)
+        Command::Simple(cmd, _) => generator.generate_simple_command(cmd),
+        Command::ShoptCommand(cmd, _) => generator.generate_shopt_command(cmd),
+        Command::TestExpression(test_expr, span) => {
+            // On its own a test only sets the status, as `system` would
+            let condition = generator.generate_test_expression(test_expr);
+            let mut output = format!("{}$? = {} ? 0 : 256;\n", generator.indent(), condition);
+            if generator.checks_status {
+                output.push_str(&format!("{}check_status({});\n", generator.indent(), span.line));
+            }
+            output
         },
-        Command::Pipeline(pipeline) => generator.generate_pipeline(pipeline),
-        Command::If(if_stmt) => generator.generate_if_statement(if_stmt),
-        Command::Case(case_stmt) => generator.generate_case_statement(case_stmt),
-        Command::While(while_loop) => generator.generate_while_loop(while_loop),
-        Command::For(for_loop) => generator.generate_for_loop(for_loop),
-        Command::Function(func) => generator.generate_function(func),
-        Command::Subshell(cmd) => generator.generate_subshell(cmd),
-        Command::Background(cmd) => generator.generate_background(cmd),
-        Command::Block(block) => generator.generate_block(block),
-        Command::BuiltinCommand(cmd) => generator.generate_builtin_command(cmd),
-        Command::Break(level) => generator.generate_break_statement(level),
-        Command::Continue(level) => generator.generate_continue_statement(level),
-        Command::Return(value) => generator.generate_return_statement(value),
-        Command::BlankLine => "\n".to_string(),
-        Command::Redirect(redirect_cmd) => {
+        Command::Pipeline(pipeline, _) => generator.generate_pipeline(pipeline),
+        Command::If(if_stmt, _) => generator.generate_if_statement(if_stmt),
+        Command::Case(case_stmt, _) => generator.generate_case_statement(case_stmt),
+        Command::While(while_loop, _) => generator.generate_while_loop(while_loop),
+        Command::For(for_loop, _) => generator.generate_for_loop(for_loop),
+        Command::Select(select_loop, _) => generator.generate_select_loop(select_loop),
+        Command::ArithmeticFor(arith_for, _) => generator.generate_arithmetic_for(arith_for),
+        Command::Function(func, _) => generator.generate_function(func),
+        Command::Subshell(cmd, _) => generator.generate_subshell(cmd),
+        Command::Background(cmd, _) => generator.generate_background(cmd),
+        Command::Block(block, _) => generator.generate_block(block),
+        Command::BuiltinCommand(cmd, _) => generator.generate_builtin_command(cmd),
+        Command::Break(level, _) => generator.generate_break_statement(level),
+        Command::Continue(level, _) => generator.generate_continue_statement(level),
+        Command::Return(value, _) => generator.generate_return_statement(value),
+        Command::BlankLine(_) => "\n".to_string(),
+        Command::Error(text, _) => {
+            let indent = generator.indent();
+            format!("{}# Not translated (parse error): {}\n", indent, text.lines().collect::<Vec<_>>().join(&format!("\n{}# ", indent)))
+        }
+        Command::Redirect(redirect_cmd, _) => {
             // Check if this is a cat command with heredocs
-            if let Command::Simple(cat_cmd) = &*redirect_cmd.command {
-                if let Word::Literal(cmd_name) = &cat_cmd.name {
+            if let Command::Simple(cat_cmd, _) = &*redirect_cmd.command {
+                if let Word::Literal(cmd_name, _) = &cat_cmd.name {
                     if cmd_name == "cat" {
-                        // Check if any of the redirects are heredocs
-                        let has_heredoc = redirect_cmd.redirects.iter().any(|r| {
-                            matches!(r.operator, RedirectOperator::Heredoc | RedirectOperator::HeredocTabs)
+                        // Split stdin heredocs from any other redirects
+                        let (heredocs, others): (Vec<Redirect>, Vec<Redirect>) = redirect_cmd.redirects.iter().cloned().partition(|r| {
+                            matches!(r.operator, RedirectOperator::Heredoc | RedirectOperator::HeredocTabs) && r.descriptor() == 0
                         });
                         
-                        if has_heredoc {
+                        if !heredocs.is_empty() && others.is_empty() {
                             // Use the dedicated cat command generator for heredocs
-                            return generate_cat_command(generator, cat_cmd, &redirect_cmd.redirects);
+                            return generate_cat_command(generator, cat_cmd, &heredocs);
+                        }
+                        if !heredocs.is_empty() {
+                            // `cat <<EOF >file`: print the heredoc with the other redirects in place
+                            let printed = Command::Redirect(RedirectCommand {
+                                command: redirect_cmd.command.clone(),
+                                redirects: heredocs,
+                            }, Span::default());
+                            return generator.generate_redirect_command(&RedirectCommand {
+                                command: Box::new(printed),
+                                redirects: others,
+                            });
                         }
                     }
                 }
             }
-            
-            // Default redirect handling for other commands
-            let mut result = generate_command_impl(generator, &redirect_cmd.command);
-            for redirect in &redirect_cmd.redirects {
-                result.push_str(&generator.generate_redirect(redirect));
+            // `exec` keeps its redirects for the rest of the script
+            if let Command::BuiltinCommand(builtin, _) = &*redirect_cmd.command {
+                if builtin.name == "exec" {
+                    return generator.generate_exec_command(builtin, &redirect_cmd.redirects);
+                }
             }
-            result
+            
+            generator.generate_redirect_command(redirect_cmd)
         }
     }
 }
@@ -29,13 +29,13 @@ pub fn generate_find_command(generator: &mut Generator, cmd: &SimpleCommand, gen
     // Parse find arguments
     let mut i = 0;
     while i < cmd.args.len() {
-        if let Word::Literal(arg) = &cmd.args[i] {
+        if let Word::Literal(arg, _) = &cmd.args[i] {
             if arg == "." {
                 path = ".";
             } else if arg == "-name" && i + 1 < cmd.args.len() {
                 if let Some(next_arg) = cmd.args.get(i + 1) {
                     pattern = match next_arg {
-                        Word::StringInterpolation(interp) => {
+                        Word::StringInterpolation(interp, _) => {
                             interp.parts.iter()
                                 .map(|part| match part {
                                     crate::ast::StringPart::Literal(s) => s,
@@ -0,0 +1,65 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use super::utilities::text_to_perl;
+use crate::generator::words::word_list_to_perl;
+
+pub fn generate_getopts_command(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let (call, assign) = getopts_to_perl(generator, cmd);
+    format!("{}{{ my ($status, $option) = {}; {} }}\n", generator.indent(), call, assign)
+}
+
+/// `getopts` as a Perl expression that is true while options remain, for
+/// `while getopts ...; do case $opt in ...`.
+pub fn getopts_condition(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let (call, assign) = getopts_to_perl(generator, cmd);
+    format!("do {{ my ($status, $option) = {}; {} $status == 0 }}", call, assign)
+}
+
+/// The `getopts_next` call for a `getopts` command, and the statement
+/// assigning the option it returns to the variable named.
+fn getopts_to_perl(generator: &mut Generator, cmd: &SimpleCommand) -> (String, String) {
+    generator.uses_getopts = true;
+    let optstring = match cmd.args.first() {
+        Some(word) => text_to_perl(generator, word),
+        None => "''".to_string(),
+    };
+    let variable = cmd.args.get(1).map_or("OPTION".to_string(), |word| word.to_string());
+    for name in [variable.as_str(), "OPTIND", "OPTARG"] {
+        if !generator.declared_locals.contains(name) {
+            generator.declared_locals.insert(name.to_string());
+            generator.environment_variables.insert(name.to_string());
+        }
+    }
+    // Arguments after the name are parsed instead of the positional parameters
+    let args = if cmd.args.len() > 2 {
+        let words: Vec<String> = cmd.args[2..].iter()
+            .map(|word| word_list_to_perl(generator, word).unwrap_or_else(|| text_to_perl(generator, word)))
+            .collect();
+        format!("[{}]", words.join(", "))
+    } else {
+        format!("\\{}", generator.positional_parameters())
+    };
+    (format!("getopts_next({}, {}, \\$OPTIND, \\$OPTARG)", optstring, args), format!("${} = $option;", variable))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    const PARSE: &str = "parse() {\n  while getopts \":ab:\" opt \"$@\"; do\n    case $opt in\n      a) echo \"a at $OPTIND\";;\n      b) echo \"b=$OPTARG\";;\n      :) echo \"missing $OPTARG\";;\n      \\?) echo \"unknown $OPTARG\";;\n    esac\n  done\n  echo \"optind=$OPTIND\"\n}\n";
+
+    #[test]
+    fn test_getopts_explicit_arguments() {
+        let script = format!("{}parse -a -b val -x rest", PARSE);
+        let (output, status) = run_perl(&script, "");
+        assert_eq!(status, 0);
+        assert_eq!(output, "a at 2\nb=val\nunknown x\noptind=5\n");
+    }
+
+    #[test]
+    fn test_getopts_optind_and_missing_argument() {
+        let script = format!("{}parse -a -b val\nOPTIND=1\nparse -ab", PARSE);
+        let (output, _) = run_perl(&script, "");
+        assert_eq!(output, "a at 2\nb=val\noptind=4\na at 1\nmissing b\noptind=2\n");
+    }
+}
@@ -13,7 +13,7 @@ pub fn generate_grep_command(generator: &mut Generator, cmd: &SimpleCommand, inp
     let mut word_match = false;
     
     for arg in &cmd.args {
-        if let Word::Literal(s) = arg {
+        if let Word::Literal(s, _) = arg {
             if s.starts_with('-') {
                 if s.contains('c') { count_only = true; }
                 if s.contains('n') { line_numbers = true; }
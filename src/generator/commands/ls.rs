@@ -10,8 +10,7 @@ fn generate_ls_helper(generator: &mut Generator, dir: &str, array_name: &str, so
     
     if sort_files {
         // For sorting, we still need to collect files first
-        output.push_str(&generator.indent());
-        output.push_str(&format!("if (opendir(my $dh, '{}')) {{\n", dir));
+        output.push_str(&opened_directory(generator, dir, array_name));
         generator.indent_level += 1;
         output.push_str(&generator.indent());
         output.push_str("while (my $file = readdir($dh)) {\n");
@@ -28,13 +27,11 @@ fn generate_ls_helper(generator: &mut Generator, dir: &str, array_name: &str, so
         output.push_str(&generator.indent());
         output.push_str(&format!("@{} = sort {{ $a cmp $b }} @{};\n", array_name, array_name));
         generator.indent_level -= 1;
-        output.push_str(&generator.indent());
-        output.push_str("}\n");
+        output.push_str(&not_listed(generator, dir));
     } else {
         // For non-sorting, collect to array instead of printing directly
         // This is needed for pipeline context where we need the array
-        output.push_str(&generator.indent());
-        output.push_str(&format!("if (opendir(my $dh, '{}')) {{\n", dir));
+        output.push_str(&opened_directory(generator, dir, array_name));
         generator.indent_level += 1;
         output.push_str(&generator.indent());
         output.push_str("while (my $file = readdir($dh)) {\n");
@@ -49,13 +46,28 @@ fn generate_ls_helper(generator: &mut Generator, dir: &str, array_name: &str, so
         output.push_str(&generator.indent());
         output.push_str("closedir($dh);\n");
         generator.indent_level -= 1;
-        output.push_str(&generator.indent());
-        output.push_str("}\n");
+        output.push_str(&not_listed(generator, dir));
     }
     
     output
 }
 
+/// The start of the branches listing `dir`: a file is listed by its own
+/// name, and a directory by its entries.
+fn opened_directory(generator: &mut Generator, dir: &str, array_name: &str) -> String {
+    let indent = generator.indent();
+    format!("{}if (-e '{}' && !-d _) {{\n{}    push @{}, '{}';\n{}}} elsif (opendir(my $dh, '{}')) {{\n",
+        indent, dir, indent, array_name, dir, indent, dir)
+}
+
+/// The branch for a path that cannot be listed, which like `ls` fails
+/// with status 2.
+fn not_listed(generator: &mut Generator, dir: &str) -> String {
+    let indent = generator.indent();
+    format!("{0}}} else {{\n{0}    my $error = \"$!\";\n{0}    STDOUT->flush();\n{0}    warn \"ls: cannot access '{1}': $error\\n\";\n{0}    $? = 2 << 8;\n{0}}}\n",
+        indent, dir)
+}
+
 pub fn generate_ls_command(generator: &mut Generator, cmd: &SimpleCommand, pipeline_context: bool) -> String {
     let mut output = String::new();
     
@@ -64,7 +76,7 @@ pub fn generate_ls_command(generator: &mut Generator, cmd: &SimpleCommand, pipel
     let mut single_column = false;
     
     for arg in &cmd.args {
-        if let Word::Literal(s) = arg {
+        if let Word::Literal(s, _) = arg {
             if s.starts_with('-') {
                 // Parse flags
                 for flag in s.chars().skip(1) {
@@ -80,17 +92,24 @@ pub fn generate_ls_command(generator: &mut Generator, cmd: &SimpleCommand, pipel
         }
     }
     
-    // Only print files if not in pipeline context
+    // Only print files if not in pipeline context, in a block of their own
+    // so that each listing has its own array
     if !pipeline_context {
-        if single_column {
+        output.push_str(&format!("{}{{\n", generator.indent()));
+        generator.indent_level += 1;
+        let separator = if single_column {
             // -1 flag: one file per line, preserve directory order (no sorting)
             output.push_str(&generate_ls_helper(generator, dir, "ls_files", false));
+            "\\n"
         } else {
             // Default: space-separated on one line, with sorting
             output.push_str(&generate_ls_helper(generator, dir, "ls_files", true));
-            output.push_str(&generator.indent());
-            output.push_str("print join(\" \", @ls_files) . \"\\n\";\n");
-        }
+            " "
+        };
+        output.push_str(&generator.indent());
+        output.push_str(&format!("print join(\"{}\", @ls_files) . \"\\n\" if @ls_files;\n", separator));
+        generator.indent_level -= 1;
+        output.push_str(&format!("{}}}\n", generator.indent()));
     } else {
         // In pipeline context, always collect to array for output
         // For -1 flag, we want newline-separated output, so use single_column=true
@@ -107,7 +126,7 @@ pub fn generate_ls_for_substitution(generator: &mut Generator, cmd: &SimpleComma
     let mut single_column = false; // Default to multi-column (space-separated) like shell ls
     
     for arg in &cmd.args {
-        if let Word::Literal(s) = arg {
+        if let Word::Literal(s, _) = arg {
             if s.starts_with('-') {
                 // Parse flags
                 for flag in s.chars().skip(1) {
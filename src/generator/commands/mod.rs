@@ -20,6 +20,11 @@ pub mod sed;
 pub mod comm;
 pub mod tr;
 pub mod sleep;
+pub mod read;
+pub mod printf;
+pub mod getopts;
+pub mod trap;
+pub mod set;
 pub mod cut;
 pub mod basename;
 pub mod dirname;
@@ -53,7 +58,7 @@ pub use simple_commands::generate_simple_command_impl;
 pub use pipeline_commands::generate_pipeline_impl;
 pub use subshell_commands::generate_subshell_impl;
 pub use subshell_commands::generate_background_impl;
-pub use system_commands::generate_command_string_for_system_impl;
+pub use system_commands::{generate_command_string_for_system_impl, shell_output_impl};
 pub use utilities::escape_perl_string;
 
 // Re-export individual command functions
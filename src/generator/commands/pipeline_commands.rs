@@ -1,11 +1,19 @@
 use crate::generator::Generator;
 use crate::ast::*;
+use super::simple_commands::command_condition;
+use super::utilities::text_to_perl;
 
 pub fn generate_pipeline_impl(generator: &mut Generator, pipeline: &Pipeline) -> String {
     generate_pipeline_with_print_option(generator, pipeline, true)
 }
 
 pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline: &Pipeline, should_print: bool) -> String {
+    if should_print && pipeline.commands.len() > 1 {
+        let list = generate_and_or_list(generator, pipeline).or_else(|| generate_status_pipeline(generator, pipeline));
+        if let Some(output) = list {
+            return output;
+        }
+    }
     let mut output = String::new();
     
     if pipeline.commands.len() == 1 {
@@ -20,13 +28,13 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
         if should_print {
             // Check if this is a simple ls | grep pipeline that can use streaming
             if pipeline.commands.len() == 2 {
-                if let (Command::Simple(cmd1), Command::Simple(cmd2)) = (&pipeline.commands[0], &pipeline.commands[1]) {
+                if let (Command::Simple(cmd1, _), Command::Simple(cmd2, _)) = (&pipeline.commands[0], &pipeline.commands[1]) {
                     let cmd1_name = match &cmd1.name {
-                        Word::Literal(s) => s,
+                        Word::Literal(s, _) => s,
                         _ => "unknown_command"
                     };
                     let cmd2_name = match &cmd2.name {
-                        Word::Literal(s) => s,
+                        Word::Literal(s, _) => s,
                         _ => "unknown_command"
                     };
 
@@ -49,7 +57,7 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                         let mut invert_match = false;
 
                         for arg in &cmd2.args {
-                            if let Word::Literal(s) = arg {
+                            if let Word::Literal(s, _) = arg {
                                 if s.starts_with('-') {
                                     if s.contains('v') { invert_match = true; }
                                 } else {
@@ -122,9 +130,9 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                             
                             if i == 0 {
                                 // First command - generate output
-                                if let Command::Simple(cmd) = command {
+                                if let Command::Simple(cmd, _) = command {
                                     let cmd_name = match &cmd.name {
-                                        Word::Literal(s) => s,
+                                        Word::Literal(s, _) => s,
                                         _ => "unknown_command"
                                     };
                                     
@@ -144,22 +152,18 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                                     } else {
                                         // Generic first command
                                         output.push_str(&generator.indent());
-                                        output.push_str("$output = `");
-                                        output.push_str(&generator.generate_command_string_for_system(command));
-                                        output.push_str("`;\n");
+                                        output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                                     }
                                 } else {
                                     // Non-simple first command
                                     output.push_str(&generator.indent());
-                                    output.push_str("$output = `");
-                                    output.push_str(&generator.generate_command_string_for_system(command));
-                                    output.push_str("`;\n");
+                                    output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                                 }
                             } else {
                                 // Subsequent commands - process the output from previous command
-                                if let Command::Simple(cmd) = command {
+                                if let Command::Simple(cmd, _) = command {
                                     let cmd_name = match &cmd.name {
-                                        Word::Literal(s) => s,
+                                        Word::Literal(s, _) => s,
                                         _ => "unknown_command"
                                     };
                                     
@@ -281,21 +285,11 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                                         // xargs command modifies $output directly
                                     } else {
                                         // Generic command
-                                        output.push_str(&generator.indent());
-                                        output.push_str("my $new_output = `");
-                                        output.push_str(&generator.generate_command_string_for_system(command));
-                                        output.push_str("`;\n");
-                                        output.push_str(&generator.indent());
-                                        output.push_str("$output = $new_output;\n");
+                                        output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                                     }
                                 } else {
                                     // Non-simple command
-                                    output.push_str(&generator.indent());
-                                    output.push_str("my $new_output = `");
-                                    output.push_str(&generator.generate_command_string_for_system(command));
-                                    output.push_str("`;\n");
-                                    output.push_str(&generator.indent());
-                                    output.push_str("$output = $new_output;\n");
+                                    output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                                 }
                             }
                         }
@@ -303,8 +297,9 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                         // Output the final result
                         output.push_str(&generator.indent());
                         output.push_str("print $output;\n");
+                        // End the last line, unless there was none or it was already ended
                         output.push_str(&generator.indent());
-                        output.push_str("print \"\\n\";\n");
+                        output.push_str("print \"\\n\" if length $output && $output !~ /\\n\\z/;\n");
                     }
                 } else {
                     // Fall back to array-based approach for non-simple commands
@@ -320,9 +315,9 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                         
                         if i == 0 {
                             // First command - generate output
-                            if let Command::Simple(cmd) = command {
+                            if let Command::Simple(cmd, _) = command {
                                 let cmd_name = match &cmd.name {
-                                    Word::Literal(s) => s,
+                                    Word::Literal(s, _) => s,
                                     _ => "unknown_command"
                                 };
                                 
@@ -342,22 +337,18 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                                 } else {
                                     // Generic first command
                                     output.push_str(&generator.indent());
-                                    output.push_str("$output = `");
-                                    output.push_str(&generator.generate_command_string_for_system(command));
-                                    output.push_str("`;\n");
+                                    output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                                 }
                             } else {
                                 // Non-simple first command
                                 output.push_str(&generator.indent());
-                                output.push_str("$output = `");
-                                output.push_str(&generator.generate_command_string_for_system(command));
-                                output.push_str("`;\n");
+                                output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                             }
                         } else {
                             // Subsequent commands - process the output from previous command
-                            if let Command::Simple(cmd) = command {
+                            if let Command::Simple(cmd, _) = command {
                                 let cmd_name = match &cmd.name {
-                                    Word::Literal(s) => s,
+                                    Word::Literal(s, _) => s,
                                     _ => "unknown_command"
                                 };
                                 
@@ -479,21 +470,11 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                                     // xargs command modifies $output directly
                                 } else {
                                     // Generic command
-                                    output.push_str(&generator.indent());
-                                    output.push_str("my $new_output = `");
-                                    output.push_str(&generator.generate_command_string_for_system(command));
-                                    output.push_str("`;\n");
-                                    output.push_str(&generator.indent());
-                                    output.push_str("$output = $new_output;\n");
+                                    output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                                 }
                             } else {
                                 // Non-simple command
-                                output.push_str(&generator.indent());
-                                output.push_str("my $new_output = `");
-                                output.push_str(&generator.generate_command_string_for_system(command));
-                                output.push_str("`;\n");
-                                output.push_str(&generator.indent());
-                                output.push_str("$output = $new_output;\n");
+                                output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                             }
                         }
                     }
@@ -501,8 +482,9 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                     // Output the final result
                     output.push_str(&generator.indent());
                     output.push_str("print $output;\n");
+                    // End the last line, unless there was none or it was already ended
                     output.push_str(&generator.indent());
-                    output.push_str("print \"\\n\";\n");
+                    output.push_str("print \"\\n\" if length $output && $output !~ /\\n\\z/;\n");
                 }
             } else {
                 // Fall back to array-based approach for longer pipelines
@@ -518,9 +500,9 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                     
                     if i == 0 {
                         // First command - generate output
-                        if let Command::Simple(cmd) = command {
+                        if let Command::Simple(cmd, _) = command {
                             let cmd_name = match &cmd.name {
-                                Word::Literal(s) => s,
+                                Word::Literal(s, _) => s,
                                 _ => "unknown_command"
                             };
                             
@@ -540,22 +522,18 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                             } else {
                                 // Generic first command
                                 output.push_str(&generator.indent());
-                                output.push_str("$output = `");
-                                output.push_str(&generator.generate_command_string_for_system(command));
-                                output.push_str("`;\n");
+                                output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                             }
                         } else {
                             // Non-simple first command
                             output.push_str(&generator.indent());
-                            output.push_str("$output = `");
-                            output.push_str(&generator.generate_command_string_for_system(command));
-                            output.push_str("`;\n");
+                            output.push_str(&format!("$output = {};\n", generator.shell_output(command, None)));
                         }
                     } else {
                         // Subsequent commands - process the output from previous command
-                        if let Command::Simple(cmd) = command {
+                        if let Command::Simple(cmd, _) = command {
                             let cmd_name = match &cmd.name {
-                                Word::Literal(s) => s,
+                                Word::Literal(s, _) => s,
                                 _ => "unknown_command"
                             };
                             
@@ -677,21 +655,11 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                                 // xargs command modifies $output directly
                             } else {
                                 // Generic command
-                                output.push_str(&generator.indent());
-                                output.push_str("my $new_output = `");
-                                output.push_str(&generator.generate_command_string_for_system(command));
-                                output.push_str("`;\n");
-                                output.push_str(&generator.indent());
-                                output.push_str("$output = $new_output;\n");
+                                output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                             }
                         } else {
                             // Non-simple command
-                            output.push_str(&generator.indent());
-                            output.push_str("my $new_output = `");
-                            output.push_str(&generator.generate_command_string_for_system(command));
-                            output.push_str("`;\n");
-                            output.push_str(&generator.indent());
-                            output.push_str("$output = $new_output;\n");
+                            output.push_str(&piped_stage(generator, command, i + 1 == pipeline.commands.len()));
                         }
                     }
                 }
@@ -699,20 +667,21 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                 // Output the final result
                 output.push_str(&generator.indent());
                 output.push_str("print $output;\n");
+                // End the last line, unless there was none or it was already ended
                 output.push_str(&generator.indent());
-                output.push_str("print \"\\n\";\n");
+                output.push_str("print \"\\n\" if length $output && $output !~ /\\n\\z/;\n");
             }
         } else {
             // For command substitution, use streaming approach - no arrays, no buffering
             // For ls | grep, we can stream directly without arrays
             if pipeline.commands.len() == 2 {
-                if let (Command::Simple(cmd1), Command::Simple(cmd2)) = (&pipeline.commands[0], &pipeline.commands[1]) {
+                if let (Command::Simple(cmd1, _), Command::Simple(cmd2, _)) = (&pipeline.commands[0], &pipeline.commands[1]) {
                     let cmd1_name = match &cmd1.name {
-                        Word::Literal(s) => s,
+                        Word::Literal(s, _) => s,
                         _ => "unknown_command"
                     };
                     let cmd2_name = match &cmd2.name {
-                        Word::Literal(s) => s,
+                        Word::Literal(s, _) => s,
                         _ => "unknown_command"
                     };
 
@@ -735,7 +704,7 @@ pub fn generate_pipeline_with_print_option(generator: &mut Generator, pipeline:
                         let mut invert_match = false;
 
                         for arg in &cmd2.args {
-                            if let Word::Literal(s) = arg {
+                            if let Word::Literal(s, _) = arg {
                                 if s.starts_with('-') {
                                     if s.contains('v') { invert_match = true; }
                                 } else {
@@ -867,3 +836,102 @@ use super::touch::generate_touch_command;
 use super::head::generate_head_command;
 use super::tail::generate_tail_command;
 use super::xargs::generate_xargs_command;
+
+/// A later stage of a pipeline that runs through the shell, reading what
+/// the stages before it printed. The command line is interpolated like
+/// backticks would be. The last stage's output is printed as it is.
+fn piped_stage(generator: &mut Generator, command: &Command, last: bool) -> String {
+    let stage = generator.shell_output(command, Some("$output"));
+    let mut output = format!("{}$output = {};\n", generator.indent(), stage);
+    if !last {
+        // Like the stages translated to Perl, leave off the last newline
+        output.push_str(&format!("{}$output =~ s/\\n\\z//;\n", generator.indent()));
+    }
+    output
+}
+
+/// An `&&` and `||` list as a Perl condition, which like the shell's groups
+/// from the left.
+pub fn and_or_condition(generator: &mut Generator, pipeline: &Pipeline) -> Option<String> {
+    let (first, rest) = pipeline.commands.split_first()?;
+    let mut condition = command_condition(generator, first)?;
+    for (operator, command) in pipeline.operators.iter().zip(rest) {
+        let operator = match operator {
+            PipeOperator::And => "&&",
+            PipeOperator::Or => "||",
+            _ => return None,
+        };
+        condition = format!("({} {} {})", condition, operator, command_condition(generator, command)?);
+    }
+    Some(condition)
+}
+
+/// `a && b || c` runs the last command when the list before it succeeds or
+/// fails. Only that command's failure sets off `set -e` or the ERR trap.
+fn generate_and_or_list(generator: &mut Generator, pipeline: &Pipeline) -> Option<String> {
+    let (last, rest) = pipeline.commands.split_last()?;
+    let (last_operator, operators) = pipeline.operators.split_last()?;
+    let keyword = match last_operator {
+        PipeOperator::And => "if",
+        PipeOperator::Or => "unless",
+        _ => return None,
+    };
+    let condition = and_or_condition(generator, &Pipeline { commands: rest.to_vec(), operators: operators.to_vec() })?;
+    let indent = generator.indent();
+    generator.indent_level += 1;
+    let body = generator.generate_command(last);
+    generator.indent_level -= 1;
+    Some(format!("{}{} ({}) {{\n{}{}}}\n", indent, keyword, condition, body, indent))
+}
+
+/// A pipeline of external commands run by the `run_pipeline` helper, once
+/// its status or its commands matter to `set -e`, `set -o pipefail`,
+/// `set -x` or an ERR trap.
+fn generate_status_pipeline(generator: &mut Generator, pipeline: &Pipeline) -> Option<String> {
+    if !generator.checks_status && !generator.shell_options.contains("xtrace") {
+        return None;
+    }
+    if pipeline.operators.iter().any(|operator| !matches!(operator, PipeOperator::Pipe)) {
+        return None;
+    }
+    let mut output = String::new();
+    let mut commands = Vec::new();
+    for command in &pipeline.commands {
+        let cmd = match command {
+            Command::Simple(cmd, _) if cmd.redirects.is_empty() && cmd.env_vars.is_empty() => cmd,
+            _ => return None,
+        };
+        match &cmd.name {
+            Word::Literal(name, _) if name != "read" && !generator.declared_functions.contains(name) => {}
+            _ => return None,
+        }
+        let mut words = vec![cmd.name.clone()];
+        words.extend(cmd.args.iter().cloned());
+        output.push_str(&generator.traced_command(&words));
+        let words: Vec<String> = words.iter().map(|word| text_to_perl(generator, word)).collect();
+        commands.push(format!("[{}]", words.join(", ")));
+    }
+    generator.runs_pipelines = true;
+    output.push_str(&format!("{}run_pipeline({});\n", generator.indent(), commands.join(", ")));
+    if generator.checks_status {
+        let line = pipeline.commands.first().map_or(0, |command| command.span().line);
+        output.push_str(&format!("{}check_status({});\n", generator.indent(), line));
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_pipeline_without_output_prints_nothing() {
+        let script = "false | true\necho hi | tee /dev/null >/dev/null\necho done";
+        assert_eq!(run_perl(script, ""), ("done\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_pipeline_keeps_trailing_blank_line() {
+        assert_eq!(run_perl("printf 'a\\n\\n' | cat", ""), ("a\n\n".to_string(), 0));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use super::utilities::text_to_perl;
+
+/// `printf` formats with the `shell_printf` helper, printing the result or
+/// assigning it to the variable named by `-v`.
+pub fn generate_printf_command(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let mut target = None;
+    let mut args = cmd.args.iter().peekable();
+    while let Some(Word::Literal(flag, _)) = args.peek() {
+        match flag.as_str() {
+            "-v" => {
+                args.next();
+                target = args.next().map(|word| word.to_string());
+            }
+            "--" => {
+                args.next();
+                break;
+            }
+            _ => break,
+        }
+    }
+    let args: Vec<String> = args.map(|word| text_to_perl(generator, word)).collect();
+    if args.is_empty() {
+        return format!("{}print STDERR \"printf: usage: printf [-v var] format [arguments]\\n\";\n", generator.indent());
+    }
+
+    generator.uses_printf = true;
+    let call = format!("shell_printf({})", args.join(", "));
+    match target {
+        Some(variable) => {
+            if !generator.declared_locals.contains(&variable) {
+                generator.declared_locals.insert(variable.clone());
+                generator.environment_variables.insert(variable.clone());
+            }
+            format!("{}${} = {};\n", generator.indent(), variable, call)
+        }
+        None => format!("{}print {};\n", generator.indent(), call),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_printf_reuses_format() {
+        let script = "printf '%s-%s\\n' a b c\nprintf '%s,%d;' x\necho";
+        assert_eq!(run_perl(script, "").0, "a-b\nc-\nx,0;\n");
+    }
+
+    #[test]
+    fn test_printf_conversions() {
+        let script = "printf '%b|\\n' 'x\\ty'\nprintf '%q\\n' \"it's here\" 'a$b'\nprintf '[%s] [%d] [%5.1f]\\n'\nprintf '%b' 'stop\\chere' more";
+        assert_eq!(run_perl(script, "").0, "x\ty|\nit\\'s\\ here\na\\$b\n[] [0] [  0.0]\nstop");
+    }
+}
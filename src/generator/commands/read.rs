@@ -0,0 +1,149 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use super::utilities::text_to_perl;
+
+/// `read` on its own sets `$?`, as `system` would: it fails at the end of
+/// the input.
+pub fn generate_read_command(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let (call, assign) = read_to_perl(generator, cmd);
+    let mut output = format!("{}{{ my ($status, @values) = {}; {} $? = $status << 8; }}\n", generator.indent(), call, assign);
+    if generator.checks_status {
+        output.push_str(&format!("{}check_status({});\n", generator.indent(), cmd.name.span().line));
+    }
+    output
+}
+
+/// `read` as a Perl expression that is true when a whole line was read, so
+/// `while read line` stops at the end of the input.
+pub fn read_condition(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let (call, assign) = read_to_perl(generator, cmd);
+    format!("do {{ my ($status, @values) = {}; {} $? = $status << 8; $status == 0 }}", call, assign)
+}
+
+/// The `read_input` call for a `read` command, and the statement assigning
+/// the `@values` it returns to the variables named.
+fn read_to_perl(generator: &mut Generator, cmd: &SimpleCommand) -> (String, String) {
+    generator.uses_read = true;
+    let mut options = Vec::new();
+    let mut array = None;
+    let mut fd = "0".to_string();
+    let mut args = cmd.args.iter();
+    let mut names = Vec::new();
+    while let Some(arg) = args.next() {
+        let flags = match arg {
+            Word::Literal(text, _) if names.is_empty() && text == "--" => {
+                names.extend(args.by_ref().cloned());
+                break;
+            }
+            Word::Literal(text, _) if names.is_empty() && text.len() > 1 && text.starts_with('-') => &text[1..],
+            _ => {
+                names.push(arg.clone());
+                continue;
+            }
+        };
+        for (i, flag) in flags.char_indices() {
+            match flag {
+                'r' => options.push("raw => 1".to_string()),
+                's' => options.push("silent => 1".to_string()),
+                'a' | 'd' | 'n' | 'N' | 'p' | 't' | 'u' => {
+                    // The value is the rest of this argument or the next one
+                    let rest = &flags[i + 1..];
+                    let value = if !rest.is_empty() {
+                        Word::Literal(rest.to_string(), Span::default())
+                    } else {
+                        match args.next() {
+                            Some(word) => word.clone(),
+                            None => break,
+                        }
+                    };
+                    match flag {
+                        'a' => array = Some(value.to_string()),
+                        'd' => {
+                            // An empty delimiter reads up to a NUL byte
+                            let text: String = value.to_string().chars().filter(|c| !matches!(c, '\'' | '"')).collect();
+                            let delimiter = text.chars().next().map_or("\\0".to_string(), |c| {
+                                generator.escape_perl_string(&c.to_string())
+                            });
+                            options.push(format!("delimiter => \"{}\"", delimiter));
+                        }
+                        'n' => options.push(format!("count => {}", number_to_perl(generator, &value))),
+                        'N' => options.push(format!("count => {}, exact => 1", number_to_perl(generator, &value))),
+                        'p' => options.push(format!("prompt => {}", text_to_perl(generator, &value))),
+                        't' => options.push(format!("timeout => {}", number_to_perl(generator, &value))),
+                        _ => fd = descriptor_to_perl(generator, &value),
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // A leading `IFS=...` applies to this read alone
+    let ifs = match cmd.env_vars.iter().find(|assignment| assignment.name == "IFS" && assignment.index.is_none()) {
+        Some(assignment) => text_to_perl(generator, &assignment.value),
+        None if generator.declared_locals.contains("IFS") => "$IFS // \" \\t\\n\"".to_string(),
+        None => "\" \\t\\n\"".to_string(),
+    };
+    options.insert(0, format!("ifs => {}", ifs));
+
+    let (count, assign) = if let Some(array) = array {
+        if !generator.declared_locals.contains(&array) {
+            generator.declared_locals.insert(array.clone());
+            generator.predeclared_arrays.insert(array.clone());
+        }
+        ("0".to_string(), format!("@{} = @values;", array))
+    } else {
+        let mut variables: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        // With no names the line goes to REPLY as read, without splitting
+        let count = if variables.is_empty() { "undef".to_string() } else { variables.len().to_string() };
+        if variables.is_empty() {
+            variables.push("REPLY".to_string());
+        }
+        for variable in &variables {
+            if !generator.declared_locals.contains(variable) {
+                generator.declared_locals.insert(variable.clone());
+                generator.environment_variables.insert(variable.clone());
+            }
+        }
+        let targets: Vec<String> = variables.iter().map(|variable| format!("${}", variable)).collect();
+        (count, format!("({}) = @values;", targets.join(", ")))
+    };
+    (format!("read_input({}, {}, {})", fd, count, options.join(", ")), assign)
+}
+
+fn number_to_perl(generator: &mut Generator, word: &Word) -> String {
+    match word {
+        Word::Literal(text, _) if text.parse::<f64>().is_ok() => text.clone(),
+        _ => generator.word_to_perl(word),
+    }
+}
+
+/// The descriptor `-u` reads, looked up in `%fd_table` once `exec` may have
+/// put it there.
+fn descriptor_to_perl(generator: &mut Generator, word: &Word) -> String {
+    let fd = number_to_perl(generator, word);
+    let standard = fd.parse::<i32>().is_ok_and(|fd| fd <= 2);
+    if generator.uses_fd_table && !standard {
+        format!("(exists $fd_table{{{0}}} ? fileno($fd_table{{{0}}}) : {0})", fd)
+    } else {
+        fd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_read_status_at_end_of_input() {
+        let script = "while read -r line; do echo \"<$line>\"; done\necho \"left: $line\"\nread x || echo \"eof $?\"";
+        assert_eq!(run_perl(script, "a b\nc\nlast"), ("<a b>\n<c>\nleft: last\neof 1\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_read_delimiter() {
+        let script = "IFS= read -r -d : first\necho \"[$first] $?\"\nwhile read -d : field; do echo \"<$field>\"; done";
+        assert_eq!(run_perl(script, "a b:c:d"), ("[a b] 0\n<c>\n".to_string(), 0));
+    }
+}
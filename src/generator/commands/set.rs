@@ -0,0 +1,134 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use super::utilities::text_to_perl;
+
+/// `set` turns shell options on with `-x` or `-o name` and off with `+x` or
+/// `+o name`, in `%shell_options`; words after `--` become the positional
+/// parameters.
+pub fn generate_set_command(generator: &mut Generator, cmd: &BuiltinCommand) -> String {
+    let indent = generator.indent();
+    let mut output = String::new();
+    let words = joined_words(&cmd.args);
+    let mut words = words.iter();
+    while let Some(word) = words.next() {
+        let text = match word {
+            Word::Literal(text, _) => text.as_str(),
+            _ => break,
+        };
+        let value = match text.chars().next() {
+            Some('-') => 1,
+            Some('+') => 0,
+            _ => break,
+        };
+        if text == "--" {
+            let parameters: Vec<String> = words.map(|word| text_to_perl(generator, word)).collect();
+            output.push_str(&format!("{}{} = ({});\n", indent, generator.positional_parameters(), parameters.join(", ")));
+            return output;
+        }
+        for flag in text[1..].chars() {
+            let option = match flag {
+                'o' => match words.next() {
+                    Some(Word::Literal(name, _)) => name.clone(),
+                    _ => {
+                        output.push_str(&format!("{}# set {}o without an option name not translated\n", indent, &text[..1]));
+                        continue;
+                    }
+                },
+                _ => match option_name(flag) {
+                    Some(name) => name.to_string(),
+                    None => {
+                        output.push_str(&format!("{}# set {}{} not translated\n", indent, &text[..1], flag));
+                        continue;
+                    }
+                },
+            };
+            // Only the options that change how commands run need the status checked
+            generator.checks_status |= option == "errexit" || option == "pipefail";
+            output.push_str(&format!("{}$shell_options{{{}}} = {};\n", indent, option, value));
+            generator.shell_options.insert(option);
+        }
+    }
+    if output.is_empty() {
+        output.push_str(&format!("{}# set without options not translated\n", indent));
+    }
+    output
+}
+
+/// The long name of an option given by letter, as `set -o` lists it.
+fn option_name(flag: char) -> Option<&'static str> {
+    let name = match flag {
+        'a' => "allexport",
+        'b' => "notify",
+        'e' => "errexit",
+        'f' => "noglob",
+        'h' => "hashall",
+        'm' => "monitor",
+        'n' => "noexec",
+        'u' => "nounset",
+        'v' => "verbose",
+        'x' => "xtrace",
+        'B' => "braceexpand",
+        'C' => "noclobber",
+        'E' => "errtrace",
+        'H' => "histexpand",
+        'P' => "physical",
+        'T' => "functrace",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The words of the command with split option groups joined again: the
+/// lexer reads `-euo` as the `-e` test operator followed by `uo`.
+fn joined_words(args: &[Word]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for word in args {
+        if let (Some(Word::Literal(previous, previous_span)), Word::Literal(text, span)) = (words.last_mut(), word) {
+            if previous.starts_with(['-', '+']) && previous_span.end == span.start && span.start != 0 {
+                previous.push_str(text);
+                previous_span.end = span.end;
+                continue;
+            }
+        }
+        words.push(word.clone());
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_errexit_on_substitution_and_arithmetic() {
+        let script = "set -e\necho start\nx=$(true)\necho ok\nx=$(false)\necho unreached";
+        assert_eq!(run_perl(script, ""), ("start\nok\n".to_string(), 1));
+        let script = "set -e\ni=0\n(( i++ )) || echo zero\n(( i++ ))\necho \"i=$i\"\n(( i - 2 ))\necho unreached";
+        assert_eq!(run_perl(script, ""), ("zero\ni=2\n".to_string(), 1));
+    }
+
+    #[test]
+    fn test_errexit_on_test() {
+        let script = "set -e\n[ 1 -eq 2 ] || echo or\nif [ 1 -eq 2 ]; then echo then; fi\n[ 1 -eq 1 ]\n[ 1 -eq 2 ]\necho unreached";
+        assert_eq!(run_perl(script, ""), ("or\n".to_string(), 1));
+    }
+
+    #[test]
+    fn test_nounset_values() {
+        let script = "set -u\nn=1\nx=\"a  b\"\necho $n y\necho \"${x}\" | cat\necho \"v=$x\" | cat\necho \"$unset\"\necho unreached";
+        assert_eq!(run_perl(script, ""), ("1 y\na  b\nv=a  b\n".to_string(), 1));
+    }
+
+    #[test]
+    fn test_xtrace_prints_assignments() {
+        let script = "exec 2>&1\nset -x\na=\"p q\"\nb=1 sh -c 'echo $b'\narr=(1 \"x y\")";
+        let expected = "+ a='p q'\n+ b=1\n+ sh -c 'echo $b'\n1\n+ arr=(1 \"x y\")\n";
+        assert_eq!(run_perl(script, ""), (expected.to_string(), 0));
+    }
+
+    #[test]
+    fn test_errexit_on_native_command() {
+        let script = "set -e\nls /nonexistent_dir 2>/dev/null || echo failed\nls /nonexistent_dir 2>/dev/null\necho unreached";
+        assert_eq!(run_perl(script, ""), ("failed\n".to_string(), 2));
+    }
+}
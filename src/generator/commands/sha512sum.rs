@@ -10,7 +10,7 @@ pub fn generate_sha512sum_command(generator: &mut Generator, cmd: &SimpleCommand
     
     // Parse sha512sum options
     for arg in &cmd.args {
-        if let Word::Literal(arg_str) = arg {
+        if let Word::Literal(arg_str, _) = arg {
             if arg_str == "-c" {
                 check_mode = true;
             } else if !arg_str.starts_with('-') {
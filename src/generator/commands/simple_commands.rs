@@ -1,19 +1,23 @@
 use crate::ast::*;
 use crate::generator::Generator;
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-// Static counter for generating unique temp file names
-static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+use crate::generator::words::{interpolated_variable, word_list_to_perl};
+use super::utilities::text_to_perl;
 
 /// Dispatch to command-specific generators
 fn generate_command_specific(generator: &mut Generator, cmd: &SimpleCommand, input_var: &str) -> Option<String> {
     let cmd_name = match &cmd.name {
-        Word::Literal(s) => s,
+        Word::Literal(s, _) => s,
         _ => return None
     };
     
-    // Use default input variable for commands that need it
-    let default_input = if input_var.is_empty() { "input_data" } else { input_var };
+    // The filters work on the output of an earlier pipeline stage; on their
+    // own they run as programs, which read their files or standard input
+    let filter = matches!(cmd_name.as_str(), "grep" | "wc" | "sort" | "uniq" | "xargs" | "awk" | "sed" | "comm" | "tr"
+        | "cut" | "basename" | "dirname" | "strings" | "tee" | "sha256sum" | "sha512sum" | "gzip" | "head" | "tail");
+    if filter && input_var.is_empty() {
+        return None;
+    }
+    let default_input = input_var;
     
     match cmd_name.as_str() {
         "grep" => Some(super::grep::generate_grep_command(generator, cmd, default_input, 0)),
@@ -59,22 +63,57 @@ fn generate_command_specific(generator: &mut Generator, cmd: &SimpleCommand, inp
 }
 
 pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleCommand) -> String {
-    let mut output = String::new();
+    // `(( expr ))` is evaluated for its side effects and its status
+    if let (Word::Arithmetic(expr, _), true) = (&cmd.name, cmd.args.is_empty()) {
+        let mut output = generator.generate_arithmetic_command(expr);
+        if generator.checks_status {
+            output.push_str(&format!("{}check_status({});\n", generator.indent(), cmd.name.span().line));
+        }
+        return output;
+    }
+    // `set -x` prints the command before it runs, after the assignments in
+    // front of it, which are printed once they have their values
+    let mut words = vec![cmd.name.clone()];
+    words.extend(cmd.args.iter().cloned());
+    let mut output = if cmd.env_vars.is_empty() || cmd.name == "read" || cmd.name == "getopts" {
+        generator.traced_command(&words)
+    } else {
+        String::new()
+    };
+    // `read` sets variables of the script, so it cannot run as a child
+    if cmd.name == "read" {
+        return output + &super::read::generate_read_command(generator, cmd);
+    }
+    // `printf -v` assigns a variable of the script too
+    if cmd.name == "printf" && cmd.env_vars.is_empty() {
+        return output + &super::printf::generate_printf_command(generator, cmd);
+    }
+    if cmd.name == "getopts" {
+        return output + &super::getopts::generate_getopts_command(generator, cmd);
+    }
+    // Prefix assignments last for the command, which runs inside their block.
+    // Variables of the script are shadowed there, except for a function,
+    // which sees them through the variables it closes over.
     let has_env = !cmd.env_vars.is_empty() && cmd.name != "true";
+    let shadows = !matches!(&cmd.name, Word::Literal(name, _) if generator.declared_functions.contains(name));
+    let mut block_locals = Vec::new();
     if has_env {
         output.push_str(&generator.indent());
         output.push_str("{\n");
         generator.indent_level += 1;
-        for (var, value) in &cmd.env_vars {
+        for assignment in &cmd.env_vars {
+            let (var, value) = (&assignment.name, &assignment.value);
             // Check if this is an associative array assignment like map[foo]=bar
-            if let Some((array_name, key)) = generator.extract_array_key(var) {
+            if let Some(key) = &assignment.index {
+                let array_name = var;
                 let val = generator.perl_string_literal(value);
                 // For associative array assignments, generate $array{key} = value instead of $ENV{var}
                 // Quote the key to avoid bareword errors in strict mode
-                let quoted_key = format!("\"{}\"", generator.escape_perl_string(&key));
+                let quoted_key = format!("\"{}\"", generator.escape_perl_string(key));
                 output.push_str(&generator.indent());
                 output.push_str(&format!("${}{{{}}} = {};\n", array_name, quoted_key, val));
-            } else if let Word::Literal(s) = value {
+                output.push_str(&generator.traced_assignment(&format!("{}[{}]", var, key), &val, None));
+            } else if let Word::Literal(s, _) = value {
                 if let Some(elements) = generator.extract_array_elements(s) {
                     // Check if this is an indexed array assignment like arr=(one two three)
                     let elements_perl: Vec<String> = elements.iter()
@@ -82,108 +121,47 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                         .collect();
                     output.push_str(&generator.indent());
                     output.push_str(&format!("@{} = ({});\n", var, elements_perl.join(", ")));
+                    output.push_str(&generator.traced_assignment(var, "", Some(s)));
                 } else {
                     // Regular string assignment
                     let val = generator.perl_string_literal(value);
                     // Always assign the value, but only declare if not already declared
-                    if !generator.declared_locals.contains(var) {
+                    if shadows || !generator.declared_locals.contains(var) {
                         output.push_str(&generator.indent());
                         output.push_str(&format!("my ${} = {};\n", var, val));
-                        generator.declared_locals.insert(var.clone());
+                        if generator.declared_locals.insert(var.clone()) {
+                            block_locals.push(var.clone());
+                        }
                     } else {
                         // Variable already declared, just assign the value
                         output.push_str(&generator.indent());
                         output.push_str(&format!("${} = {};\n", var, val));
                     }
                     output.push_str(&generator.indent());
-                    output.push_str(&format!("local $ENV{{{}}} = {};;\n", var, val));
+                    output.push_str(&format!("local $ENV{{{}}} = ${};\n", var, var));
+                    output.push_str(&generator.traced_assignment(var, &format!("${}", var), None));
                 }
             } else {
                 // Handle other Word types
                 let val = generator.perl_string_literal(value);
                 // Always assign the value, but only declare if not already declared
-                if !generator.declared_locals.contains(var) {
+                if shadows || !generator.declared_locals.contains(var) {
                     output.push_str(&generator.indent());
                     output.push_str(&format!("my ${} = {};\n", var, val));
-                    generator.declared_locals.insert(var.clone());
+                    if generator.declared_locals.insert(var.clone()) {
+                        block_locals.push(var.clone());
+                    }
                 } else {
                     // Variable already declared, just assign the value
                     output.push_str(&generator.indent());
                     output.push_str(&format!("${} = {};\n", var, val));
                 }
                 output.push_str(&generator.indent());
-                output.push_str(&format!("local $ENV{{{}}} = {};;\n", var, val));
+                output.push_str(&format!("local $ENV{{{}}} = ${};\n", var, var));
+                output.push_str(&generator.traced_assignment(var, &format!("${}", var), None));
             }
         }
-        generator.indent_level -= 1;
-        output.push_str(&generator.indent());
-        output.push_str("}\n");
-    }
-
-    // Pre-process process substitution and here-string redirects to create temporary files
-    let mut process_sub_files = Vec::new();
-    let mut _has_here_string = false;
-    let mut temp_file_counter = 0;
-    for redir in &cmd.redirects {
-        match &redir.operator {
-            RedirectOperator::ProcessSubstitutionInput(cmd) => {
-                // Process substitution input: <(command)
-                temp_file_counter += 1;
-                let global_counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                let temp_file = format!("/tmp/process_sub_{}_{}.tmp", global_counter, temp_file_counter);
-                let temp_var = format!("temp_file_ps_{}_{}", global_counter, temp_file_counter);
-                output.push_str(&generator.indent());
-                output.push_str(&format!("my ${} = '{}';\n", temp_var, temp_file));
-                
-                // Generate the command for system call
-                let cmd_str = generator.generate_command_string_for_system(&**cmd);
-                
-                // Clean up the command string for system call and properly escape it
-                let _clean_cmd = cmd_str.replace('\n', " ").replace("  ", " ");
-                // Use proper Perl system call syntax with list form to avoid shell interpretation
-                let fh_var = format!("fh_ps_{}_{}", global_counter, temp_file_counter);
-                output.push_str(&generator.indent());
-                output.push_str(&format!("open(my ${}, '>', ${}) or die \"Cannot create temp file: $!\\n\";\n", fh_var, temp_var));
-                output.push_str(&generator.indent());
-                output.push_str(&format!("close(${});\n", fh_var));
-                // For now, just create the file - the actual command execution would need more complex handling
-                process_sub_files.push((temp_var, temp_file));
-            }
-            RedirectOperator::ProcessSubstitutionOutput(_cmd) => {
-                // Process substitution output: >(command)
-                temp_file_counter += 1;
-                let global_counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                let temp_file = format!("/tmp/process_sub_out_{}_{}.tmp", global_counter, temp_file_counter);
-                let temp_var = format!("temp_file_out_{}_{}", global_counter, temp_file_counter);
-                output.push_str(&generator.indent());
-                output.push_str(&format!("my ${} = '{}';\n", temp_var, temp_file));
-                process_sub_files.push((temp_var, temp_file));
-            }
-            RedirectOperator::HereString => {
-                // Here-string: <<< content
-                _has_here_string = true;
-                temp_file_counter += 1;
-                let global_counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                let temp_file = format!("/tmp/here_string_{}_{}.tmp", global_counter, temp_file_counter);
-                let temp_var = format!("temp_file_hs_{}_{}", global_counter, temp_file_counter);
-                output.push_str(&generator.indent());
-                output.push_str(&format!("my ${} = '{}';\n", temp_var, temp_file));
-                
-                // Create the temporary file with the here-string content
-                if let Some(content) = &redir.heredoc_body {
-                    let fh_var = format!("fh_hs_{}_{}", global_counter, temp_file_counter);
-                    output.push_str(&generator.indent());
-                    output.push_str(&format!("open(my ${}, '>', ${}) or die \"Cannot create temp file: $!\\n\";\n", fh_var, temp_var));
-                    output.push_str(&generator.indent());
-                    output.push_str(&format!("print ${} {};\n", fh_var, generator.perl_string_literal(&Word::Literal(content.clone()))));
-                    output.push_str(&generator.indent());
-                    output.push_str(&format!("close(${});\n", fh_var));
-                }
-                
-                process_sub_files.push((temp_var, temp_file));
-            }
-            _ => {}
-        }
+        output.push_str(&generator.traced_command(&words));
     }
 
     // Generate the actual command
@@ -199,7 +177,7 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
             
             for arg in &cmd.args {
                 match arg {
-                    Word::Literal(s) => {
+                    Word::Literal(s, _) => {
                         // Properly quote literal strings for Perl
                         // Check if the string is already quoted (starts and ends with same quote)
                         let trimmed = s.trim();
@@ -224,21 +202,25 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                             }
                         }
                     },
-                    Word::Variable(var) => {
+                    Word::Variable(var, _) => {
                         // Convert shell variables to Perl variables
-                        processed_args.push(format!("${}", var));
+                        let value = match var.as_str() {
+                            "?" => generator.word_to_perl(arg),
+                            _ => generator.checked_variable(var, format!("${}", var)),
+                        };
+                        processed_args.push(value);
                     },
-                    Word::ParameterExpansion(pe) => {
+                    Word::ParameterExpansion(pe, _) => {
                         // Handle parameter expansions
                         processed_args.push(generator.generate_parameter_expansion(pe));
                     },
-                    Word::StringInterpolation(interp) => {
+                    Word::StringInterpolation(interp, _) => {
                         // Handle string interpolation specially for echo
                         // For variables like $#, we want to evaluate them, not treat them as literals
                         let mut can_handle_interp = true;
                         let mut interp_result = String::new();
                         
-                        for part in &interp.parts {
+                        for (i, part) in interp.parts.iter().enumerate() {
                             match part {
                                 StringPart::Literal(s) => {
                                     interp_result.push_str(s);
@@ -264,15 +246,23 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                                             can_handle_interp = false;
                                             break;
                                         },
+                                        "?" => {
+                                            // The exit code is worked out from Perl's `$?`
+                                            can_handle_interp = false;
+                                            break;
+                                        },
                                         _ => {
                                             // Check if this is a shell positional parameter ($1, $2, etc.)
                                             if var.chars().all(|c| c.is_digit(10)) {
-                                                // Convert $1 to $_[0], $2 to $_[1], etc.
                                                 let index = var.parse::<usize>().unwrap_or(0);
-                                                interp_result.push_str(&format!("$_[{}]", index - 1));
+                                                interp_result.push_str(&generator.positional_parameter(index));
+                                            } else if generator.shell_options.contains("nounset") {
+                                                // The check for an unset variable needs concatenation
+                                                can_handle_interp = false;
+                                                break;
                                             } else {
                                                 // Regular variable - add for interpolation
-                                                interp_result.push_str(&format!("${}", var));
+                                                interp_result.push_str(&interpolated_variable(var, interp.parts.get(i + 1)));
                                             }
                                         }
                                     }
@@ -319,12 +309,15 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                 }
             }
             
-            let args = processed_args;
+            // `print (a), b` would print only `a`
+            let args: Vec<String> = processed_args.into_iter().enumerate()
+                .map(|(i, arg)| if i == 0 && arg.starts_with('(') { format!("+{}", arg) } else { arg })
+                .collect();
             
             // Use proper Perl print statement formatting
             if needs_comma_separated_print {
                 // Force comma-separated printing for special variables
-                let args_str = args.join(", ");
+                let args_str = args.join(", \" \", ");
                 output.push_str(&generator.indent());
                 output.push_str(&format!("print {}, \"\\n\";\n", args_str));
             } else if args.len() == 1 {
@@ -335,7 +328,7 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                 } else {
                     // Check if this argument contains command substitution
                     let has_command_substitution = cmd.args.iter().any(|arg| {
-                        matches!(arg, Word::CommandSubstitution(_))
+                        matches!(arg, Word::CommandSubstitution(..))
                     });
                     
                     if has_command_substitution {
@@ -357,21 +350,25 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                     }
                     
                     match word {
-                        Word::Literal(s) => {
+                        Word::Literal(s, _) => {
                             // Add the literal text directly
                             combined_string.push_str(s);
                         }
-                        Word::Variable(var) => {
+                        Word::Variable(var, _) if var == "?" || generator.shell_options.contains("nounset") => {
+                            can_interpolate = false;
+                            break;
+                        }
+                        Word::Variable(var, _) => {
                             // Add the variable for interpolation
                             combined_string.push_str(&format!("${}", var));
                         }
-                        Word::StringInterpolation(interp) => {
+                        Word::StringInterpolation(interp, _) => {
                             // Handle string interpolation specially for echo
                             // For variables like $#, we want to evaluate them, not treat them as literals
                             let mut can_handle_interp = true;
                             let mut interp_result = String::new();
                             
-                            for part in &interp.parts {
+                            for (i, part) in interp.parts.iter().enumerate() {
                                 match part {
                                     StringPart::Literal(s) => {
                                         interp_result.push_str(s);
@@ -394,15 +391,22 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                                                 can_handle_interp = false;
                                                 break;
                                             },
+                                            "?" => {
+                                                // The exit code is worked out from Perl's `$?`
+                                                can_handle_interp = false;
+                                                break;
+                                            },
                                             _ => {
                                                 // Check if this is a shell positional parameter ($1, $2, etc.)
                                                 if var.chars().all(|c| c.is_digit(10)) {
-                                                    // Convert $1 to $_[0], $2 to $_[1], etc.
                                                     let index = var.parse::<usize>().unwrap_or(0);
-                                                    interp_result.push_str(&format!("$_[{}]", index - 1));
+                                                    interp_result.push_str(&generator.positional_parameter(index));
+                                                } else if generator.shell_options.contains("nounset") {
+                                                    can_handle_interp = false;
+                                                    break;
                                                 } else {
                                                     // Regular variable - add for interpolation
-                                                    interp_result.push_str(&format!("${}", var));
+                                                    interp_result.push_str(&interpolated_variable(var, interp.parts.get(i + 1)));
                                                 }
                                             }
                                         }
@@ -435,7 +439,7 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                     // Create a single interpolated string
                     // Check if any of the arguments contain command substitution
                     let has_command_substitution = cmd.args.iter().any(|arg| {
-                        matches!(arg, Word::CommandSubstitution(_))
+                        matches!(arg, Word::CommandSubstitution(..))
                     });
                     
                     if has_command_substitution {
@@ -448,7 +452,7 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                     }
                 } else {
                     // Fall back to the original comma-separated approach
-                    let args_str = args.join(", ");
+                    let args_str = args.join(", \" \", ");
                     output.push_str(&generator.indent());
                     output.push_str(&format!("print {}, \"\\n\";\n", args_str));
                 }
@@ -457,17 +461,40 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
     } else if cmd.name == "true" && !cmd.env_vars.is_empty() && cmd.args.is_empty() {
         // This is a standalone assignment (e.g., i=$((i + 1)))
         // Generate proper Perl assignment statements
-        for (var, value) in &cmd.env_vars {
+        for assignment in &cmd.env_vars {
+            if let Some(index) = &assignment.index {
+                output.push_str(&element_assignment(generator, &assignment.name, index, &assignment.value));
+                let value = text_to_perl(generator, &assignment.value);
+                output.push_str(&generator.traced_assignment(&format!("{}[{}]", assignment.name, index), &value, None));
+                continue;
+            }
+            let (var, value) = (&assignment.name, &assignment.value);
             match value {
-                Word::Arithmetic(expr) => {
+                Word::Array(_, elements, _) => {
+                    let source = format!("({})", elements.join(" "));
+                    // Like its elements, the array is declared at the top
+                    generator.declared_locals.insert(var.clone());
+                    generator.predeclared_arrays.insert(var.clone());
+                    let elements: Vec<String> = elements.iter()
+                        .map(|element| {
+                            let text = ['"', '\''].iter()
+                                .find_map(|&quote| element.strip_prefix(quote)?.strip_suffix(quote))
+                                .unwrap_or(element);
+                            format!("\"{}\"", generator.escape_perl_string(text))
+                        })
+                        .collect();
+                    output.push_str(&format!("{}@{} = ({});\n", generator.indent(), var, elements.join(", ")));
+                    output.push_str(&generator.traced_assignment(var, "", Some(&source)));
+                }
+                Word::Arithmetic(expr, _) => {
                     // Convert arithmetic expression to Perl
-                    let perl_expr = generator.convert_arithmetic_to_perl(&expr.expression);
+                    let perl_expr = generator.convert_arithmetic_to_perl(expr);
                     // Check if variable is already declared in current scope
                     if !generator.declared_locals.contains(var) {
-                        // For now, assume this is a loop variable or already in scope
-                        // and just assign to it without redeclaring
+                        // The first assignment declares the variable, as `use strict` requires
                         output.push_str(&generator.indent());
-                        output.push_str(&format!("${} = {};\n", var, perl_expr));
+                        output.push_str(&format!("my ${} = {};\n", var, perl_expr));
+                        generator.declared_locals.insert(var.clone());
                     } else {
                         // Variable already declared, just assign the value
                         output.push_str(&generator.indent());
@@ -478,10 +505,10 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                     // Handle other value types
                     let val = generator.perl_string_literal(value);
                     if !generator.declared_locals.contains(var) {
-                        // For now, assume this is a loop variable or already in scope
-                        // and just assign to it without redeclaring
+                        // The first assignment declares the variable, as `use strict` requires
                         output.push_str(&generator.indent());
-                        output.push_str(&format!("${} = {};\n", var, val));
+                        output.push_str(&format!("my ${} = {};\n", var, val));
+                        generator.declared_locals.insert(var.clone());
                     } else {
                         // Variable already declared, just assign the value
                         output.push_str(&generator.indent());
@@ -489,43 +516,175 @@ pub fn generate_simple_command_impl(generator: &mut Generator, cmd: &SimpleComma
                     }
                 }
             }
+            if !matches!(value, Word::Array(..)) {
+                output.push_str(&generator.traced_assignment(var, &format!("${}", var), None));
+            }
+        }
+        // An assignment has the status of its last command substitution
+        let substituted = cmd.env_vars.iter().rev().find(|assignment| match &assignment.value {
+            Word::CommandSubstitution(..) => true,
+            Word::StringInterpolation(interp, _) => interp.parts.iter().any(|part| matches!(part, StringPart::CommandSubstitution(_))),
+            _ => false,
+        });
+        if let (Some(assignment), true) = (substituted, generator.checks_status) {
+            output.push_str(&format!("{}check_status({});\n", generator.indent(), assignment.value.span().line));
         }
     } else {
         // Handle other commands
         let cmd_name = match &cmd.name {
-            Word::Literal(s) => s,
+            Word::Literal(s, _) => s,
             _ => "unknown_command"
         };
         
         // First try to use command-specific generators
         if let Some(specific_output) = generate_command_specific(generator, cmd, "") {
+            // A command done in Perl succeeds unless it sets its own status
+            output.push_str(&format!("{}$? = 0;\n", generator.indent()));
             output.push_str(&specific_output);
+            if generator.checks_status {
+                output.push_str(&format!("{}check_status({});\n", generator.indent(), cmd.name.span().line));
+            }
+        } else if cmd_name == ":" {
+            // `:` only expands its arguments, as in `: ${x:=1}`
+            let args: Vec<String> = cmd.args.iter().map(|arg| text_to_perl(generator, arg)).collect();
+            if !args.is_empty() {
+                output.push_str(&format!("{}() = ({});\n", generator.indent(), args.join(", ")));
+            }
+            output.push_str(&format!("{}$? = 0;\n", generator.indent()));
         } else if generator.declared_functions.contains(cmd_name) {
             // Check if this is a function call
             if cmd.args.is_empty() {
                 output.push_str(&generator.indent());
                 output.push_str(&format!("{}();\n", cmd_name));
             } else {
+                // Arguments are strings, and "$@" passes each parameter on
                 let args: Vec<String> = cmd.args.iter()
-                    .map(|arg| generator.word_to_perl(arg))
+                    .map(|arg| word_list_to_perl(generator, arg).unwrap_or_else(|| text_to_perl(generator, arg)))
                     .collect();
                 output.push_str(&generator.indent());
                 output.push_str(&format!("{}({});\n", cmd_name, args.join(", ")));
             }
         } else {
             // Fallback to system call
-            if cmd.args.is_empty() {
-                output.push_str(&generator.indent());
-                output.push_str(&format!("system('{}');\n", cmd_name));
-            } else {
-                let args: Vec<String> = cmd.args.iter()
-                    .map(|arg| generator.word_to_perl(arg))
-                    .collect();
+            let arguments = system_arguments(generator, cmd);
+            output.push_str(&generator.indent());
+            output.push_str(&format!("system({});\n", arguments));
+            if generator.checks_status {
                 output.push_str(&generator.indent());
-                output.push_str(&format!("system('{}', {});\n", cmd_name, args.join(", ")));
+                output.push_str(&format!("check_status({});\n", cmd.name.span().line));
             }
         }
     }
 
+    if has_env {
+        generator.indent_level -= 1;
+        output.push_str(&generator.indent());
+        output.push_str("}\n");
+        for var in block_locals {
+            generator.declared_locals.remove(&var);
+        }
+    }
     output
 }
+
+/// `name[index]=value` on its own: a numeric index sets an element of the
+/// array `@name`, declared once at the top of the script, and any other
+/// index a key of the hash `%name`.
+fn element_assignment(generator: &mut Generator, name: &str, index: &str, value: &Word) -> String {
+    let mut output = String::new();
+    let value = match value {
+        Word::Arithmetic(expr, _) => generator.convert_arithmetic_to_perl(expr),
+        _ => generator.perl_string_literal(value),
+    };
+    if index.parse::<usize>().is_ok() {
+        if generator.declared_locals.insert(name.to_string()) {
+            generator.predeclared_arrays.insert(name.to_string());
+        }
+        output.push_str(&format!("{}${}[{}] = {};\n", generator.indent(), name, index, value));
+    } else {
+        if generator.declared_locals.insert(name.to_string()) {
+            output.push_str(&format!("{}my %{};\n", generator.indent(), name));
+        }
+        let key = generator.escape_perl_string(index);
+        output.push_str(&format!("{}${}{{\"{}\"}} = {};\n", generator.indent(), name, key, value));
+    }
+    output
+}
+
+/// The command name and arguments `system` runs a command with.
+pub fn system_arguments(generator: &mut Generator, cmd: &SimpleCommand) -> String {
+    let name = match &cmd.name {
+        Word::Literal(s, _) => s,
+        _ => "unknown_command"
+    };
+    let mut arguments = vec![format!("'{}'", name)];
+    arguments.extend(cmd.args.iter().map(|arg| super::utilities::text_to_perl(generator, arg)));
+    arguments.join(", ")
+}
+
+/// A command as a Perl expression that is true when it succeeds, for `if`
+/// and `while` conditions and the commands of `&&` and `||` lists, where a
+/// failure does not set off `set -e` or the ERR trap. `None` when the status
+/// of the command is not known.
+pub fn command_condition(generator: &mut Generator, command: &Command) -> Option<String> {
+    let cmd = match command {
+        Command::Simple(cmd, _) => cmd,
+        Command::TestExpression(test_expr, _) => return Some(generator.generate_test_expression(test_expr)),
+        Command::Pipeline(pipeline, _) => return super::pipeline_commands::and_or_condition(generator, pipeline),
+        _ => return None,
+    };
+    if !cmd.redirects.is_empty() || !cmd.env_vars.is_empty() {
+        return None;
+    }
+    let name = match &cmd.name {
+        Word::Arithmetic(expr, _) if cmd.args.is_empty() => return Some(generator.convert_arithmetic_condition_to_perl(expr)),
+        Word::Literal(name, _) => name.clone(),
+        _ => return None,
+    };
+    let condition = match name.as_str() {
+        "true" | ":" => "1".to_string(),
+        "false" => "0".to_string(),
+        "[" | "test" => {
+            let mut condition = String::new();
+            generator.generate_test_command(cmd, &mut condition);
+            condition
+        }
+        "read" => super::read::read_condition(generator, cmd),
+        "getopts" => super::getopts::getopts_condition(generator, cmd),
+        // Functions and `cd` run in the script itself
+        "cd" => return None,
+        _ if generator.declared_functions.contains(&name) => return None,
+        _ => format!("system({}) == 0", system_arguments(generator, cmd)),
+    };
+    let mut words = vec![cmd.name.clone()];
+    words.extend(cmd.args.iter().cloned());
+    let trace = generator.traced_command(&words);
+    if trace.is_empty() {
+        Some(condition)
+    } else {
+        Some(format!("do {{ {} {} }}", trace.trim(), condition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_element_assignments() {
+        let script = "arr[2]=x\narr[0]=y\ndeclare -A m\nm[k]=v\necho \"${arr[2]} ${arr[0]} ${m[k]}\"\nf() { arr[1]=z; }\nf\necho \"${arr[1]}\"";
+        assert_eq!(run_perl(script, ""), ("x y v\nz\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_prefix_assignments_reach_the_command() {
+        let script = "a=0\na=1 b=$a sh -c 'echo $a $b'\necho \"$a\"";
+        assert_eq!(run_perl(script, ""), ("1 1\n0\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_adjacent_parts_form_one_word() {
+        let script = "x=2\necho a=$x pre$x \"$x\"/y ${x}.bak'q r' --opt=$x";
+        assert_eq!(run_perl(script, ""), ("a=2 pre2 2/y 2.bakq r --opt=2\n".to_string(), 0));
+    }
+}
@@ -9,7 +9,7 @@ pub fn generate_sort_command(generator: &mut Generator, cmd: &SimpleCommand, inp
     
     // Check for flags
     for arg in &cmd.args {
-        if let Word::Literal(arg_str) = arg {
+        if let Word::Literal(arg_str, _) = arg {
             if arg_str == "-n" {
                 numeric = true;
             } else if arg_str == "r" || arg_str == "-r" {
@@ -1,11 +1,13 @@
 use crate::ast::*;
 use crate::generator::Generator;
+use crate::generator::redirects::heredoc_to_perl;
+use crate::printer::ShellPrinter;
 
 pub fn generate_command_string_for_system_impl(generator: &mut Generator, cmd: &Command) -> String {
     match cmd {
-        Command::Simple(simple_cmd) => {
+        Command::Simple(simple_cmd, _) => {
             let args: Vec<String> = simple_cmd.args.iter()
-                .map(|arg| generator.word_to_perl(arg))
+                .map(|arg| system_word(generator, arg))
                 .collect();
             if args.is_empty() {
                 simple_cmd.name.to_string()
@@ -13,11 +15,11 @@ pub fn generate_command_string_for_system_impl(generator: &mut Generator, cmd: &
                 format!("{} {}", simple_cmd.name, args.join(" "))
             }
         }
-        Command::Subshell(subshell_cmd) => {
+        Command::Subshell(subshell_cmd, _) => {
             match &**subshell_cmd {
-                Command::Simple(simple_cmd) => {
+                Command::Simple(simple_cmd, _) => {
                     let args: Vec<String> = simple_cmd.args.iter()
-                        .map(|arg| generator.word_to_perl(arg))
+                        .map(|arg| system_word(generator, arg))
                         .collect();
                     if args.is_empty() {
                         simple_cmd.name.to_string()
@@ -25,12 +27,12 @@ pub fn generate_command_string_for_system_impl(generator: &mut Generator, cmd: &
                         format!("{} {}", simple_cmd.name, args.join(" "))
                     }
                 }
-                Command::Pipeline(pipeline) => {
+                Command::Pipeline(pipeline, _) => {
                     let commands: Vec<String> = pipeline.commands.iter()
                         .filter_map(|cmd| {
-                            if let Command::Simple(simple_cmd) = cmd {
+                            if let Command::Simple(simple_cmd, _) = cmd {
                                 let args: Vec<String> = simple_cmd.args.iter()
-                                    .map(|arg| generator.word_to_perl(arg))
+                                    .map(|arg| system_word(generator, arg))
                                     .collect();
                                 Some(format!("{} {}", simple_cmd.name, args.join(" ")))
                             } else {
@@ -43,6 +45,75 @@ pub fn generate_command_string_for_system_impl(generator: &mut Generator, cmd: &
                 _ => format!("{:?}", cmd)
             }
         }
+        Command::Redirect(redirect_cmd, _) => {
+            // The shell running the backticks applies the redirects itself.
+            // A heredoc's text comes in on standard input instead, see
+            // `shell_output_impl`, so only its descriptor is named here.
+            let mut printer = ShellPrinter::new();
+            let mut parts = vec![generate_command_string_for_system_impl(generator, &redirect_cmd.command)];
+            for redirect in &redirect_cmd.redirects {
+                match (redirect.heredoc.is_some(), redirect.fd) {
+                    (false, _) => parts.push(printer.print_redirect(redirect)),
+                    (true, Some(fd)) if fd != 0 => parts.push(format!("{}<&0", fd)),
+                    (true, _) => {}
+                }
+            }
+            parts.join(" ")
+        }
         _ => format!("{:?}", cmd)
     }
 }
+
+/// What a command run through the shell prints, as a Perl expression.
+/// A heredoc's text is written to the command from memory rather than
+/// spliced into the command line, where the shell would parse it; without
+/// one the command reads `input` if given.
+pub fn shell_output_impl(generator: &mut Generator, cmd: &Command, input: Option<&str>) -> String {
+    let heredoc = match cmd {
+        Command::Redirect(redirect_cmd, _) => redirect_cmd.redirects.iter().rev().find(|redirect| redirect.heredoc.is_some()),
+        _ => None,
+    };
+    let input = match heredoc {
+        Some(redirect) => Some(heredoc_to_perl(generator, redirect)),
+        None => input.map(str::to_string),
+    };
+    let command_line = generate_command_string_for_system_impl(generator, cmd);
+    match input {
+        Some(input) => {
+            generator.uses_pipe_through = true;
+            format!("pipe_through({}, qq`{}`)", input, command_line)
+        }
+        None => format!("`{}`", command_line),
+    }
+}
+
+/// An argument as it appears inside a backtick command line; a process
+/// substitution's `/dev/fd` path, and a variable `set -u` checks, are
+/// computed in Perl and interpolated.
+fn system_word(generator: &mut Generator, word: &Word) -> String {
+    let text = generator.word_to_perl(word);
+    match word {
+        Word::ProcessSubstitution(..) => format!("@{{[ {} ]}}", text),
+        _ if !text.contains("unset_variable(") => text,
+        Word::StringInterpolation(..) => format!("\"@{{[ {} ]}}\"", text),
+        _ => format!("@{{[ {} ]}}", text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_heredoc_text_is_not_shell_syntax() {
+        let script = "tr a-z A-Z <<'EOF' | sort\n`not run` $(nor this) 'quote\nEOF in the text\nEOF\n";
+        assert_eq!(run_perl(script, ""), ("EOF IN THE TEXT\n`NOT RUN` $(NOR THIS) 'QUOTE\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_heredoc_feeds_only_the_first_stage() {
+        // The later stages read the stage before them, not the script's input
+        let script = "cat <<EOF | sort | rev\nb\na\nEOF\n";
+        assert_eq!(run_perl(script, "z\n"), ("a\nb\n".to_string(), 0));
+    }
+}
@@ -10,7 +10,7 @@ pub fn generate_tee_command(generator: &mut Generator, cmd: &SimpleCommand, inpu
     
     // Parse tee options
     for arg in &cmd.args {
-        if let Word::Literal(arg_str) = arg {
+        if let Word::Literal(arg_str, _) = arg {
             if arg_str == "-a" {
                 append_mode = true;
             } else if !arg_str.starts_with('-') {
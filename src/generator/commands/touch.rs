@@ -9,7 +9,7 @@ pub fn generate_touch_command(generator: &mut Generator, cmd: &SimpleCommand) ->
     
     // Parse touch options (currently just collecting files)
     for arg in &cmd.args {
-        if let Word::Literal(arg_str) = arg {
+        if let Word::Literal(arg_str, _) = arg {
             if !arg_str.starts_with('-') {
                 files.push(generator.word_to_perl(arg));
             }
@@ -3,7 +3,7 @@ use crate::generator::Generator;
 
 fn extract_character_set_from_word(generator: &mut Generator, word: &Word) -> String {
     let raw_pattern = match word {
-        Word::StringInterpolation(interp) => {
+        Word::StringInterpolation(interp, _) => {
             // Extract the literal content without quotes
             interp.parts.iter()
                 .map(|part| match part {
@@ -13,7 +13,7 @@ fn extract_character_set_from_word(generator: &mut Generator, word: &Word) -> St
                 .collect::<Vec<_>>()
                 .join("")
         }
-        Word::Literal(s) => s.clone(),
+        Word::Literal(s, _) => s.clone(),
         _ => generator.word_to_perl(word)
     };
     
@@ -34,7 +34,7 @@ pub fn generate_tr_command(generator: &mut Generator, cmd: &SimpleCommand, input
         
         for arg in &cmd.args {
             match arg {
-                Word::Literal(arg_str) => {
+                Word::Literal(arg_str, _) => {
                     if arg_str == "-d" {
                         delete_mode = true;
                     } else if arg_str == "-s" {
@@ -0,0 +1,217 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use crate::parser::commands::Parser;
+
+enum Action {
+    Reset,
+    Ignore,
+    /// The commands to run, and the values of the `trap_value_N` variables
+    /// they use.
+    Run(String, Vec<String>),
+}
+
+/// `trap` sets `%SIG` handlers for real signals, and the `$exit_trap` and
+/// `$err_trap` closures the trap helper runs for EXIT and ERR.
+pub fn generate_trap_command(generator: &mut Generator, cmd: &BuiltinCommand) -> String {
+    let words: Vec<String> = cmd.args.iter().map(|word| word.to_string()).collect();
+    let (action, signals) = match words.split_first() {
+        None => return format!("{}# trap without a signal not translated\n", generator.indent()),
+        // A lone signal, `-` or a leading signal number resets the signals named
+        Some((first, rest)) if rest.is_empty() || first.chars().all(|c| c.is_ascii_digit()) => (Action::Reset, words.as_slice()),
+        Some((first, rest)) if first == "-" => (Action::Reset, rest),
+        Some((first, rest)) => {
+            let (text, values) = match &cmd.args[0] {
+                Word::StringInterpolation(interp, span) => expanded_action(generator, interp, *span),
+                _ => (action_text(first), Vec::new()),
+            };
+            if text.is_empty() { (Action::Ignore, rest) } else { (Action::Run(text, values), rest) }
+        }
+    };
+    generator.uses_traps = true;
+    generator.declared_locals.insert("LINENO".to_string());
+
+    let indent = generator.indent();
+    let mut output = String::new();
+    let mut targets = Vec::new();
+    for signal in signals {
+        let signal = signal_name(signal);
+        let target = match signal.as_str() {
+            "EXIT" => "$exit_trap".to_string(),
+            "ERR" => "$err_trap".to_string(),
+            "DEBUG" | "RETURN" => {
+                output.push_str(&format!("{}# trap on {} not translated\n", indent, signal));
+                continue;
+            }
+            _ => format!("$SIG{{{}}}", signal),
+        };
+        match action {
+            Action::Run(..) => {
+                generator.checks_status |= signal == "ERR";
+                targets.push(target);
+            }
+            _ if !target.starts_with("$SIG") => output.push_str(&format!("{}undef {};\n", indent, target)),
+            Action::Ignore => output.push_str(&format!("{}{} = 'IGNORE';\n", indent, target)),
+            // Signals that would end the script still run the EXIT trap
+            Action::Reset if matches!(signal.as_str(), "HUP" | "INT" | "TERM") => {
+                output.push_str(&format!("{}{} = \\&signal_exit;\n", indent, target));
+            }
+            Action::Reset => output.push_str(&format!("{}{} = 'DEFAULT';\n", indent, target)),
+        }
+    }
+
+    if let (Action::Run(text, values), false) = (&action, targets.is_empty()) {
+        match Parser::new(text).parse() {
+            Ok(commands) => {
+                // The handler is compiled here but may run after the script
+                // assigns its variables, so they are declared at the top
+                let variables: Vec<(String, bool)> = generator.script_variables.iter()
+                    .map(|(name, &is_array)| (name.clone(), is_array))
+                    .collect();
+                for (name, is_array) in variables {
+                    if generator.declared_locals.insert(name.clone()) {
+                        if is_array {
+                            generator.predeclared_arrays.insert(name);
+                        } else {
+                            generator.environment_variables.insert(name);
+                        }
+                    }
+                }
+                // The values of a double-quoted action's expansions are
+                // kept in a block around the handler
+                let names: Vec<String> = (1..=values.len()).map(|n| format!("trap_value_{}", n)).collect();
+                let (indent, outer) = if values.is_empty() {
+                    (indent.clone(), 0)
+                } else {
+                    output.push_str(&format!("{}{{\n", indent));
+                    generator.indent_level += 1;
+                    (generator.indent(), 1)
+                };
+                for (name, value) in names.iter().zip(values) {
+                    output.push_str(&format!("{}my ${} = {};\n", indent, name, value));
+                    generator.declared_locals.insert(name.clone());
+                }
+                generator.indent_level += 1;
+                let body: String = commands.iter().map(|command| generator.generate_command(command)).collect();
+                generator.indent_level -= 1 + outer;
+                output.push_str(&format!("{}{} = sub {{\n{}{}}};\n", indent, targets.join(" = "), body, indent));
+                if outer > 0 {
+                    output.push_str(&format!("{}}}\n", generator.indent()));
+                }
+                for name in names {
+                    generator.declared_locals.remove(&name);
+                }
+            }
+            Err(_) => {
+                output.push_str(&format!("{}# Not translated (parse error): trap {}\n", indent, words.join(" ")));
+            }
+        }
+    }
+    output
+}
+
+/// The commands of a trap action, without the quotes around them.
+fn action_text(word: &str) -> String {
+    if let Some(inner) = word.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+        return inner.to_string();
+    }
+    match word.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(inner) => unescape(inner),
+        None => word.to_string(),
+    }
+}
+
+/// The commands of a double-quoted action, which the shell expands where
+/// the trap is set. Each expansion becomes a `trap_value_N` variable, and
+/// its Perl value is returned alongside.
+fn expanded_action(generator: &mut Generator, interp: &StringInterpolation, span: Span) -> (String, Vec<String>) {
+    let mut text = String::new();
+    let mut values = Vec::new();
+    for part in &interp.parts {
+        match part {
+            StringPart::Literal(literal) => text.push_str(&unescape(literal)),
+            _ => {
+                let word = Word::StringInterpolation(StringInterpolation { parts: vec![part.clone()] }, span);
+                values.push(generator.word_to_perl(&word));
+                text.push_str(&format!("${{trap_value_{}}}", values.len()));
+            }
+        }
+    }
+    (text, values)
+}
+
+/// Double-quoted text with its backslash escapes removed.
+fn unescape(inner: &str) -> String {
+    let mut text = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('\\' | '"' | '$' | '`')) => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// The name `%SIG` uses for a signal given by name, with or without `SIG`,
+/// or by number; 0 is EXIT.
+fn signal_name(signal: &str) -> String {
+    let name = signal.trim_start_matches("SIG").to_ascii_uppercase();
+    let named = match name.as_str() {
+        "0" => "EXIT",
+        "1" => "HUP",
+        "2" => "INT",
+        "3" => "QUIT",
+        "6" => "ABRT",
+        "9" => "KILL",
+        "10" => "USR1",
+        "12" => "USR2",
+        "13" => "PIPE",
+        "14" => "ALRM",
+        "15" => "TERM",
+        _ => return name,
+    };
+    named.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_exit_trap_sees_later_assignments() {
+        let script = "trap 'echo \"bye $x ${#arr[@]}\"' EXIT\nx=1\narr=(a b)\necho hi";
+        let (output, status) = run_perl(script, "");
+        assert_eq!(status, 0);
+        assert_eq!(output, "hi\nbye 1 2\n");
+    }
+
+    #[test]
+    fn test_exit_trap_sees_current_value() {
+        let script = "x=1\ntrap 'echo \"x=$x\"' EXIT\nx=2\nexit 3";
+        let (output, status) = run_perl(script, "");
+        assert_eq!(status, 3);
+        assert_eq!(output, "x=2\n");
+    }
+
+    #[test]
+    fn test_double_quoted_trap_expands_when_set() {
+        let script = "x=1\ntrap \"echo \\\"x=$x\\\" \\$x\" EXIT\nx=2";
+        let (output, status) = run_perl(script, "");
+        assert_eq!(status, 0);
+        assert_eq!(output, "x=1 2\n");
+    }
+
+    #[test]
+    fn test_exit_trap_sees_exit_status() {
+        let (output, status) = run_perl("trap 'echo \"rc=$?\"' EXIT\nexit 3", "");
+        assert_eq!(status, 3);
+        assert_eq!(output, "rc=3\n");
+    }
+
+    #[test]
+    fn test_int_trap_fires_during_child() {
+        let script = "trap 'echo got INT' INT\nsh -c 'kill -INT $PPID; kill -INT $$'\necho after";
+        let (output, _) = run_perl(script, "");
+        assert_eq!(output, "got INT\nafter\n");
+    }
+}
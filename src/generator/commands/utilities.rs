@@ -1,3 +1,7 @@
+use crate::ast::*;
+use crate::generator::Generator;
+use crate::generator::expansions::shell_text_to_perl;
+
 // Helper method for escaping Perl strings
 pub fn escape_perl_string(s: &str) -> String {
     s.replace("\\", "\\\\")
@@ -6,3 +10,11 @@ pub fn escape_perl_string(s: &str) -> String {
      .replace("\t", "\\t")
      .replace("\r", "\\r")
 }
+
+/// An argument as a Perl string, with the quotes of a literal removed.
+pub fn text_to_perl(generator: &mut Generator, word: &Word) -> String {
+    match word {
+        Word::Literal(text, _) => shell_text_to_perl(generator, text),
+        _ => generator.word_to_perl(word),
+    }
+}
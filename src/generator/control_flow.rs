@@ -1,5 +1,12 @@
 use crate::ast::*;
 use super::Generator;
+use super::arithmetic::declare_arithmetic_names;
+use super::words::word_list_to_perl;
+use super::patterns::{glob_to_regex, MatchLength};
+use super::commands::read::read_condition;
+use super::commands::getopts::getopts_condition;
+use super::commands::simple_commands::command_condition;
+use super::commands::utilities::text_to_perl;
 
 pub fn generate_if_statement_impl(generator: &mut Generator, if_stmt: &IfStatement) -> String {
     let mut output = String::new();
@@ -7,12 +14,19 @@ pub fn generate_if_statement_impl(generator: &mut Generator, if_stmt: &IfStateme
     // Generate condition
     output.push_str("if (");
     match &*if_stmt.condition {
-        Command::Simple(cmd) if cmd.name == "[" || cmd.name == "test" => {
+        Command::Simple(cmd, _) if cmd.name == "[" || cmd.name == "test" => {
             generator.generate_test_command(cmd, &mut output);
         }
-        _ => {
-            output.push_str(&generator.generate_command(&if_stmt.condition));
+        Command::Simple(cmd, _) if cmd.name == "read" => {
+            output.push_str(&read_condition(generator, cmd));
+        }
+        Command::Simple(cmd, _) if cmd.name == "getopts" => {
+            output.push_str(&getopts_condition(generator, cmd));
         }
+        condition => match command_condition(generator, condition) {
+            Some(condition) => output.push_str(&condition),
+            None => output.push_str(&generator.generate_command(condition)),
+        },
     }
     output.push_str(") {\n");
     
@@ -21,7 +35,7 @@ pub fn generate_if_statement_impl(generator: &mut Generator, if_stmt: &IfStateme
     
     // Check if the then branch is a single command that doesn't need block wrapping
     match &*if_stmt.then_branch {
-        Command::Block(block) if block.commands.len() == 1 => {
+        Command::Block(block, _) if block.commands.len() == 1 => {
             // Single command in block - generate it directly without block wrapper
             // The command will add its own indentation, so we don't add it here
             output.push_str(&generator.generate_command(&block.commands[0]));
@@ -43,7 +57,7 @@ pub fn generate_if_statement_impl(generator: &mut Generator, if_stmt: &IfStateme
         
         // Check if the else branch is a single command that doesn't need block wrapping
         match &**else_branch {
-            Command::Block(block) if block.commands.len() == 1 => {
+            Command::Block(block, _) if block.commands.len() == 1 => {
                 // Single command in block - generate it directly without block wrapper
                 // The command will add its own indentation, so we don't add it here
                 output.push_str(&generator.generate_command(&block.commands[0]));
@@ -81,36 +95,35 @@ pub fn generate_case_statement_impl(generator: &mut Generator, case_stmt: &CaseS
             output.push_str("} elsif (");
         }
         
+        // Create conditions: $operation =~ /\Apattern\z/s
+        let word_str = generator.word_to_perl(&case_stmt.word);
+        
+        // Handle positional parameters in case statements
+        let processed_word = if word_str.contains("$1") || word_str.contains("$2") || word_str.contains("$3") {
+            // Replace positional parameters with generic names that will be replaced later
+            word_str.replace("$1", "$arg1").replace("$2", "$arg2").replace("$3", "$arg3")
+        } else if word_str.contains("$name") {
+            // The word_to_perl converted $1 to $name, but we need $arg1 for parameter replacement
+            word_str.replace("$name", "$arg1")
+        } else {
+            word_str
+        };
+        
         // Handle multiple patterns in a single case clause
         let mut pattern_conditions = Vec::new();
         for pattern in &case_clause.patterns {
-            let pattern_str = generator.perl_string_literal(pattern);
-            if pattern_str == "\"*\"" {
+            match pattern {
                 // Default case - this should be the last one
-                pattern_conditions.push("1".to_string()); // Always true
-            } else {
-                // Convert bash glob patterns to Perl regex
-                let mut perl_pattern = pattern_str.trim_matches('"').to_string();
-                perl_pattern = perl_pattern.replace("*", ".*");
-                perl_pattern = perl_pattern.replace("?", ".");
-                perl_pattern = perl_pattern.replace("[", "\\[");
-                perl_pattern = perl_pattern.replace("]", "\\]");
-                
-                // Create condition: $operation =~ /^pattern$/
-                let word_str = generator.word_to_perl(&case_stmt.word);
-                
-                // Handle positional parameters in case statements
-                let processed_word = if word_str.contains("$1") || word_str.contains("$2") || word_str.contains("$3") {
-                    // Replace positional parameters with generic names that will be replaced later
-                    word_str.replace("$1", "$arg1").replace("$2", "$arg2").replace("$3", "$arg3")
-                } else if word_str.contains("$name") {
-                    // The word_to_perl converted $1 to $name, but we need $arg1 for parameter replacement
-                    word_str.replace("$name", "$arg1")
-                } else {
-                    word_str
-                };
-                
-                pattern_conditions.push(format!("{} =~ /^{}$/", processed_word, perl_pattern));
+                Word::Literal(text, _) if text == "*" => pattern_conditions.push("1".to_string()),
+                Word::Literal(text, _) => {
+                    let regex = glob_to_regex(text, true, MatchLength::Longest);
+                    pattern_conditions.push(format!("{} =~ /\\A{}\\z/s", processed_word, regex));
+                }
+                // Quoted patterns and expansions match as plain strings
+                _ => {
+                    let value = generator.word_to_perl(pattern);
+                    pattern_conditions.push(format!("{} eq {}", processed_word, value));
+                }
             }
         }
         
@@ -139,7 +152,7 @@ pub fn generate_while_loop_impl(generator: &mut Generator, while_loop: &WhileLoo
     
     // Check if the while loop condition uses variables that might need initialization
     // This is needed for shell compatibility where loop variables persist
-    if let Command::Simple(cmd) = &*while_loop.condition {
+    if let Command::Simple(cmd, _) = &*while_loop.condition {
         if cmd.name == "[" || cmd.name == "test" {
             // For test commands, check if variables need initialization
             if cmd.args.len() >= 3 {
@@ -148,7 +161,7 @@ pub fn generate_while_loop_impl(generator: &mut Generator, while_loop: &WhileLoo
                 let operand2 = &cmd.args[2];
                 
                 // Initialize first operand if it's a variable
-                if let Word::Variable(var_name) = operand1 {
+                if let Word::Variable(var_name, _) = operand1 {
                     if !generator.declared_locals.contains(var_name) {
                         // Check if this variable was used in a previous for loop
                         if var_name == "i" {
@@ -164,7 +177,7 @@ pub fn generate_while_loop_impl(generator: &mut Generator, while_loop: &WhileLoo
                 }
                 
                 // Initialize second operand if it's a variable
-                if let Word::Variable(var_name) = operand2 {
+                if let Word::Variable(var_name, _) = operand2 {
                     if !generator.declared_locals.contains(var_name) {
                         output.push_str(&generator.indent());
                         output.push_str(&format!("my ${} = 0;\n", var_name));
@@ -173,7 +186,7 @@ pub fn generate_while_loop_impl(generator: &mut Generator, while_loop: &WhileLoo
                 }
             }
         }
-    } else if let Command::TestExpression(test_expr) = &*while_loop.condition {
+    } else if let Command::TestExpression(test_expr, _) = &*while_loop.condition {
         // For test expressions, parse to find variables that need initialization
         let expr = &test_expr.expression;
         
@@ -186,15 +199,25 @@ pub fn generate_while_loop_impl(generator: &mut Generator, while_loop: &WhileLoo
         }
     }
     
-    // Generate while loop
-    output.push_str("while (");
+    // Generate while loop; Perl's `until` has the same inverted sense as the shell's
+    output.push_str(if while_loop.until { "until (" } else { "while (" });
     match &*while_loop.condition {
-        Command::Simple(cmd) if cmd.name == "[" || cmd.name == "test" => {
+        Command::Simple(cmd, _) if cmd.name == "[" || cmd.name == "test" => {
             generator.generate_test_command(cmd, &mut output);
         }
-        _ => {
-            output.push_str(&generator.generate_command(&while_loop.condition));
+        Command::Simple(SimpleCommand { name: Word::Arithmetic(expr, _), args, .. }, _) if args.is_empty() => {
+            output.push_str(&generator.convert_arithmetic_condition_to_perl(expr));
         }
+        Command::Simple(cmd, _) if cmd.name == "read" => {
+            output.push_str(&read_condition(generator, cmd));
+        }
+        Command::Simple(cmd, _) if cmd.name == "getopts" => {
+            output.push_str(&getopts_condition(generator, cmd));
+        }
+        condition => match command_condition(generator, condition) {
+            Some(condition) => output.push_str(&condition),
+            None => output.push_str(&generator.generate_command(condition)),
+        },
     }
     output.push_str(") {\n");
     
@@ -215,31 +238,7 @@ pub fn generate_for_loop_impl(generator: &mut Generator, for_loop: &ForLoop) ->
     // Generate for loop using the actual variable name from the AST
     output.push_str(&format!("for my ${} (", for_loop.variable));
     
-    // Handle different types of for loop items
-    let items: Vec<String> = for_loop.items.iter()
-        .map(|word| {
-            // Special handling for for loop items to avoid quoting array variables
-            match word {
-                Word::StringInterpolation(interp) => {
-                    // Check if this is just a single array variable like "$@" or "$*"
-                    if interp.parts.len() == 1 {
-                        if let StringPart::Variable(var) = &interp.parts[0] {
-                            match var.as_str() {
-                                "@" => "@ARGV".to_string(),  // $@ -> @ARGV (no quotes)
-                                "*" => "@ARGV".to_string(),  // $* -> @ARGV (no quotes)
-                                _ => generator.word_to_perl(word)
-                            }
-                        } else {
-                            generator.word_to_perl(word)
-                        }
-                    } else {
-                        generator.word_to_perl(word)
-                    }
-                }
-                _ => generator.word_to_perl(word)
-            }
-        })
-        .collect();
+    let items = loop_items_to_perl(generator, &for_loop.items);
     output.push_str(&items.join(", "));
     
     output.push_str(") {\n");
@@ -255,11 +254,107 @@ pub fn generate_for_loop_impl(generator: &mut Generator, for_loop: &ForLoop) ->
     output
 }
 
+/// Items of a `for` or `select` list as Perl list elements.
+fn loop_items_to_perl(generator: &mut Generator, items: &[Word]) -> Vec<String> {
+    items.iter()
+        .map(|word| match word {
+            // An unquoted pattern expands to the files it matches
+            Word::Literal(text, _) if text.contains(['*', '?', '[']) && !text.starts_with(['"', '\'']) => {
+                format!("glob({})", text_to_perl(generator, word))
+            }
+            _ => word_list_to_perl(generator, word).unwrap_or_else(|| text_to_perl(generator, word)),
+        })
+        .collect()
+}
+
+pub fn generate_arithmetic_for_impl(generator: &mut Generator, arith_for: &ArithmeticFor) -> String {
+    let mut output = String::new();
+    let indent = generator.indent();
+
+    // The loop counter outlives the loop in the shell, so declare it outside
+    for part in [&arith_for.init, &arith_for.condition, &arith_for.step] {
+        output.push_str(&declare_arithmetic_names(generator, part));
+    }
+
+    let init = generator.convert_arithmetic_condition_to_perl(&arith_for.init);
+    let condition = generator.convert_arithmetic_condition_to_perl(&arith_for.condition);
+    let step = generator.convert_arithmetic_condition_to_perl(&arith_for.step);
+    output.push_str(&format!("{}for ({}; {}; {}) {{\n", indent, init, condition, step));
+
+    generator.indent_level += 1;
+    output.push_str(&generator.generate_block_commands(&arith_for.body));
+    generator.indent_level -= 1;
+
+    output.push_str(&format!("{}}}\n", indent));
+    output
+}
+
+pub fn generate_select_loop_impl(generator: &mut Generator, select_loop: &SelectLoop) -> String {
+    let mut output = String::new();
+    let indent = generator.indent();
+    let items: Vec<String> = if select_loop.items.is_empty() {
+        // `select x; do` offers the positional parameters
+        vec!["@ARGV".to_string()]
+    } else {
+        // Menu entries are shown verbatim, so plain words must be quoted
+        select_loop.items.iter()
+            .map(|word| match word {
+                Word::Literal(..) => generator.perl_string_literal(word),
+                _ => loop_items_to_perl(generator, std::slice::from_ref(word)).remove(0),
+            })
+            .collect()
+    };
+    // PS3 prompts for the choice; bash falls back to "#? " when it is unset
+    let prompt = if generator.declared_locals.contains("PS3") {
+        "$PS3".to_string()
+    } else {
+        "$ENV{PS3} // '#? '".to_string()
+    };
+
+    // The choice and REPLY keep their last values after the loop, as in bash
+    for name in [select_loop.variable.as_str(), "REPLY"] {
+        if generator.declared_locals.insert(name.to_string()) {
+            output.push_str(&format!("{}my ${};\n", indent, name));
+        }
+    }
+    output.push_str(&format!("{}{{\n", indent));
+    output.push_str(&format!("{}    my @select_items = ({});\n", indent, items.join(", ")));
+    output.push_str(&format!("{}    my $select_menu = 1;\n", indent));
+    // Keep the body's output in step with the prompts
+    output.push_str(&format!("{}    local $| = 1;\n", indent));
+    output.push_str(&format!("{}    while (1) {{\n", indent));
+    // The menu goes to STDERR, as in bash, and is shown again after an empty reply
+    output.push_str(&format!("{}        if ($select_menu) {{\n", indent));
+    output.push_str(&format!("{}            printf STDERR \"%d) %s\\n\", $_ + 1, $select_items[$_] for 0 .. $#select_items;\n", indent));
+    output.push_str(&format!("{}        }}\n", indent));
+    output.push_str(&format!("{}        print STDERR {};\n", indent, prompt));
+    output.push_str(&format!("{}        my $select_line = <STDIN>;\n", indent));
+    output.push_str(&format!("{}        if (!defined $select_line) {{\n", indent));
+    output.push_str(&format!("{}            print STDERR \"\\n\";\n", indent));
+    output.push_str(&format!("{}            last;\n", indent));
+    output.push_str(&format!("{}        }}\n", indent));
+    output.push_str(&format!("{}        chomp $select_line;\n", indent));
+    output.push_str(&format!("{}        $REPLY = $select_line;\n", indent));
+    output.push_str(&format!("{}        $select_menu = $REPLY eq '';\n", indent));
+    output.push_str(&format!("{}        next if $select_menu;\n", indent));
+    output.push_str(&format!("{}        ${} = ($REPLY =~ /^\\d+$/ && $REPLY >= 1 && $REPLY <= @select_items) ? $select_items[$REPLY - 1] : '';\n",
+                             indent, select_loop.variable));
+
+    generator.indent_level += 2;
+    output.push_str(&generator.generate_block_commands(&select_loop.body));
+    generator.indent_level -= 2;
+
+    output.push_str(&format!("{}    }}\n", indent));
+    output.push_str(&format!("{}}}\n", indent));
+    output
+}
+
 pub fn generate_function_impl(generator: &mut Generator, func: &Function) -> String {
     let mut output = String::new();
     
     // Generate function definition
     output.push_str(&format!("sub {} {{\n", func.name));
+    let in_function = std::mem::replace(&mut generator.in_function, true);
     
     // Handle function parameters
     if !func.parameters.is_empty() {
@@ -286,6 +381,7 @@ pub fn generate_function_impl(generator: &mut Generator, func: &Function) -> Str
     }
     
     output.push_str("}\n");
+    generator.in_function = in_function;
     
     // Mark function as declared
     generator.declared_functions.insert(func.name.clone());
@@ -348,4 +444,32 @@ pub fn generate_block_commands_impl(generator: &mut Generator, block: &Block) ->
         }
     }
     output
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_until_loop() {
+        let script = "i=0\nuntil [ $i -ge 3 ]; do\n  echo \"i=$i\"\n  i=$((i + 1))\ndone\nuntil false; do echo once; break; done";
+        assert_eq!(run_perl(script, "").0, "i=0\ni=1\ni=2\nonce\n");
+    }
+
+    #[test]
+    fn test_select_variables_outlive_loop() {
+        let script = "select fruit in apple pear; do\n  echo \"picked $fruit\"\n  break\ndone\necho \"last $fruit $REPLY\"\nselect fruit in apple pear; do break; done\necho \"again $fruit $REPLY\"";
+        assert_eq!(run_perl(script, "2\n1\n").0, "picked pear\nlast pear 2\nagain apple 1\n");
+    }
+
+    #[test]
+    fn test_arithmetic_for_loop() {
+        let script = "for ((i = 0; i < 3; i++)); do echo \"i=$i\"; done\nfor ((j = 10; j > 0; j -= 4)); do echo $j; done\ntotal=0\nfor ((k = 1; k <= 4; k++)); do total=$((total + k)); done\necho \"total=$total\"";
+        assert_eq!(run_perl(script, "").0, "i=0\ni=1\ni=2\n10\n6\n2\ntotal=10\n");
+    }
+
+    #[test]
+    fn test_for_loop_words() {
+        let script = "set -- p \"q r\"\nfor w in a b-c \"d e\" \"$@\" 7; do echo \"<$w>\"; done\nfor f in Cargo.t?ml; do echo \"$f\"; done";
+        assert_eq!(run_perl(script, "").0, "<a>\n<b-c>\n<d e>\n<p>\n<q r>\n<7>\nCargo.toml\n");
+    }
+}
@@ -1,103 +1,325 @@
 use crate::ast::*;
+use crate::parser::arithmetic::arithmetic_expression;
+use crate::parser::words::parameter_expansion_word;
 use super::Generator;
+use super::patterns::{glob_to_regex, MatchLength};
 
-pub fn generate_parameter_expansion_impl(_generator: &mut Generator, pe: &ParameterExpansion) -> String {
+pub fn generate_parameter_expansion_impl(generator: &mut Generator, pe: &ParameterExpansion) -> String {
+    if let ParameterExpansionOperator::PrefixNames(_) = pe.operator {
+        // ${!prefix*} - the names of the set variables starting with prefix
+        let mut names: Vec<String> = generator.declared_locals.iter()
+            .filter(|name| name.starts_with(pe.variable.as_str()))
+            .map(|name| format!("defined(${}) ? '{}' : ()", name, name))
+            .collect();
+        names.sort();
+        names.push(format!("grep {{ /^{}/ }} keys %ENV", pe.variable));
+        return format!("join(' ', do {{ my %seen; sort grep {{ !$seen{{$_}}++ }} ({}) }})", names.join(", "));
+    }
+    let value = parameter_value(generator, &pe.variable);
+    let is_set = parameter_is_set(&pe.variable, &value);
+    let is_nonempty = format!("{} && {} ne ''", is_set, value);
+    // Unset variables read as the empty string, unless `set -u` may be on
+    let text = if generator.shell_options.contains("nounset") {
+        generator.checked_variable(&pe.variable, value.clone())
+    } else {
+        format!("({} // '')", value)
+    };
     match &pe.operator {
-        ParameterExpansionOperator::None => {
-            // ${var} - just the variable
-            format!("${{{}}}", pe.variable)
-        }
+        ParameterExpansionOperator::None => text,
+        ParameterExpansionOperator::Length => format!("length({})", text),
         ParameterExpansionOperator::DefaultValue(default) => {
-            // ${var:-default} - use default if var is empty
-            format!("defined(${{{}}}) && ${{{}}} ne '' ? ${{{}}} : {}", 
-                   pe.variable, pe.variable, pe.variable, default)
+            // ${var:-default} - use default if var is unset or empty
+            format!("({} ? {} : {})", is_nonempty, value, shell_text_to_perl(generator, default))
+        }
+        ParameterExpansionOperator::DefaultIfUnset(default) => {
+            // ${var-default} - use default only if var is unset
+            format!("({} ? {} : {})", is_set, value, shell_text_to_perl(generator, default))
         }
         ParameterExpansionOperator::AssignDefault(default) => {
-            // ${var:=default} - assign default if var is empty
-            format!("defined(${{{}}}) && ${{{}}} ne '' ? ${{{}}} : do {{ ${{{}}} = {}; ${{{}}} }}", 
-                   pe.variable, pe.variable, pe.variable, pe.variable, default, pe.variable)
+            // ${var:=default} - assign default if var is unset or empty
+            let default = shell_text_to_perl(generator, default);
+            assign_default(&pe.variable, &is_nonempty, &value, &default)
+        }
+        ParameterExpansionOperator::AssignIfUnset(default) => {
+            // ${var=default} - assign default only if var is unset
+            let default = shell_text_to_perl(generator, default);
+            assign_default(&pe.variable, &is_set, &value, &default)
+        }
+        ParameterExpansionOperator::ErrorIfUnset(message) => {
+            // ${var:?message} - fail if var is unset or empty
+            format!("({} ? {} : {})", is_nonempty, value,
+                    parameter_error(generator, &pe.variable, message, "parameter null or not set"))
+        }
+        ParameterExpansionOperator::ErrorIfNotSet(message) => {
+            // ${var?message} - fail only if var is unset
+            format!("({} ? {} : {})", is_set, value,
+                    parameter_error(generator, &pe.variable, message, "parameter not set"))
         }
-        ParameterExpansionOperator::ErrorIfUnset(error) => {
-            // ${var:?error} - error if var is empty
-            format!("defined(${{{}}}) && ${{{}}} ne '' ? ${{{}}} : die({})", 
-                   pe.variable, pe.variable, pe.variable, error)
+        ParameterExpansionOperator::AlternativeValue(alternative) => {
+            // ${var:+alternative} - use alternative if var is set and not empty
+            format!("({} ? {} : '')", is_nonempty, shell_text_to_perl(generator, alternative))
+        }
+        ParameterExpansionOperator::AlternativeIfSet(alternative) => {
+            // ${var+alternative} - use alternative if var is set, even to ''
+            format!("({} ? {} : '')", is_set, shell_text_to_perl(generator, alternative))
         }
         ParameterExpansionOperator::RemoveShortestSuffix(pattern) => {
             // ${var%suffix} - remove shortest suffix
-            format!("${{{}}} =~ s/{}$//r", pe.variable, escape_regex_pattern(pattern))
+            format!("{} =~ s/\\A.*\\K(?:{})\\z//sr", text, glob(pattern, MatchLength::Longest))
         }
         ParameterExpansionOperator::RemoveLongestSuffix(pattern) => {
             // ${var%%suffix} - remove longest suffix
-            format!("${{{}}} =~ s/{}$//gr", pe.variable, escape_regex_pattern(pattern))
+            format!("{} =~ s/\\A.*?\\K(?:{})\\z//sr", text, glob(pattern, MatchLength::Longest))
         }
         ParameterExpansionOperator::RemoveShortestPrefix(pattern) => {
             // ${var#prefix} - remove shortest prefix
-            format!("${{{}}} =~ s/^{}//r", pe.variable, escape_regex_pattern(pattern))
+            format!("{} =~ s/\\A{}//sr", text, glob(pattern, MatchLength::Shortest))
         }
         ParameterExpansionOperator::RemoveLongestPrefix(pattern) => {
             // ${var##prefix} - remove longest prefix
-            format!("${{{}}} =~ s/^{}//gr", pe.variable, escape_regex_pattern(pattern))
+            format!("{} =~ s/\\A{}//sr", text, glob(pattern, MatchLength::Longest))
         }
         ParameterExpansionOperator::SubstituteAll(pattern, replacement) => {
             // ${var//pattern/replacement} - substitute all occurrences
-            format!("${{{}}} =~ s/{}/{}/gr", 
-                   pe.variable, 
-                   escape_regex_pattern(pattern),
-                   escape_regex_replacement(replacement))
+            substitute(generator, &text, "", pattern, "", replacement, "g")
+        }
+        ParameterExpansionOperator::SubstituteFirst(pattern, replacement) => {
+            // ${var/pattern/replacement} - substitute the first occurrence
+            substitute(generator, &text, "", pattern, "", replacement, "")
+        }
+        ParameterExpansionOperator::SubstitutePrefix(pattern, replacement) => {
+            // ${var/#pattern/replacement} - substitute a match at the start
+            substitute(generator, &text, "\\A", pattern, "", replacement, "")
+        }
+        ParameterExpansionOperator::SubstituteSuffix(pattern, replacement) => {
+            // ${var/%pattern/replacement} - substitute a match at the end
+            substitute(generator, &text, "", pattern, "\\z", replacement, "")
         }
         ParameterExpansionOperator::UppercaseAll => {
             // ${var^^} - uppercase all characters
-            format!("uc(${{{}}})", pe.variable)
+            format!("uc({})", text)
         }
         ParameterExpansionOperator::LowercaseAll => {
             // ${var,,} - lowercase all characters
-            format!("lc(${{{}}})", pe.variable)
+            format!("lc({})", text)
         }
         ParameterExpansionOperator::UppercaseFirst => {
             // ${var^} - uppercase first character
-            format!("ucfirst(${{{}}})", pe.variable)
+            format!("ucfirst({})", text)
+        }
+        ParameterExpansionOperator::LowercaseFirst => {
+            // ${var,} - lowercase first character
+            format!("lcfirst({})", text)
+        }
+        ParameterExpansionOperator::ToggleCaseAll => {
+            // ${var~~} - swap the case of every character
+            format!("{} =~ tr/a-zA-Z/A-Za-z/r", text)
+        }
+        ParameterExpansionOperator::ToggleCaseFirst => {
+            // ${var~} - swap the case of the first character
+            format!("do {{ my $s = {}; substr($s, 0, 1) =~ tr/a-zA-Z/A-Za-z/; $s }}", text)
         }
         ParameterExpansionOperator::Basename => {
-            // ${var##*/} - get basename
-            format!("basename(${{{}}})", pe.variable)
+            // ${var##*/} - strip everything up to the last slash
+            format!("{} =~ s{{.*/}}{{}}sr", text)
         }
         ParameterExpansionOperator::Dirname => {
-            // ${var%/*} - get dirname
-            format!("dirname(${{{}}})", pe.variable)
+            // ${var%/*} - strip the last slash and what follows it
+            format!("{} =~ s{{/[^/]*\\z}}{{}}r", text)
         }
         ParameterExpansionOperator::ArraySlice(offset, length) => {
-            // ${var:offset:length} - array slice
-            if let Some(length_str) = length {
-                format!("@${{{}}}[{}..{}]", pe.variable, offset, length_str)
+            // ${var:offset:length} - a negative offset counts from the end,
+            // and one before the start gives nothing
+            let offset = generator.convert_arithmetic_to_perl(&arithmetic_expression(offset));
+            let length = length.as_ref()
+                .map(|length| format!(", {}", generator.convert_arithmetic_to_perl(&arithmetic_expression(length))))
+                .unwrap_or_default();
+            match pe.variable.as_str() {
+                // ${@:offset:length} counts $0 as the first parameter
+                "@" | "*" => format!(
+                    "do {{ my @p = ($0, @ARGV); my $o = {}; $o += @p if $o < 0; $o < 0 || $o > @p ? '' : join(' ', splice(@p, $o{})) }}",
+                    offset, length),
+                _ => format!(
+                    "do {{ my $s = {}; my $o = {}; $o += length($s) if $o < 0; $o < 0 || $o > length($s) ? '' : substr($s, $o{}) }}",
+                    text, offset, length),
+            }
+        }
+        ParameterExpansionOperator::PrefixNames(_) => unreachable!(),
+        ParameterExpansionOperator::Transform(op) => match op {
+            // ${var@Q} - quoted so the shell would read it back unchanged
+            'Q' => format!("do {{ my $s = {}; $s =~ s/'/'\\\\''/g; \"'$s'\" }}", text),
+            'U' => format!("uc({})", text),
+            'L' => format!("lc({})", text),
+            'u' => format!("ucfirst({})", text),
+            _ => text,
+        },
+    }
+}
+
+/// The Perl expression for a parameter, which is undef while it is unset.
+/// A variable the script has not assigned yet is declared at the top.
+fn parameter_value(generator: &mut Generator, variable: &str) -> String {
+    match variable {
+        "@" | "*" => return "join(' ', @ARGV)".to_string(),
+        "#" => return "scalar(@ARGV)".to_string(),
+        "?" => return "($? >> 8)".to_string(),
+        "$" | "0" => return format!("${}", variable),
+        _ => {}
+    }
+    if let Ok(n) = variable.parse::<usize>() {
+        return generator.positional_parameter(n);
+    }
+    if let Some((name, key)) = variable.strip_suffix(']').and_then(|rest| rest.split_once('[')) {
+        return match key {
+            "@" | "*" => format!("join(' ', @{})", name),
+            _ if key.parse::<i64>().is_ok() => format!("${}[{}]", name, key),
+            _ => format!("${}{{{}}}", name, key),
+        };
+    }
+    if !generator.declared_locals.contains(variable) {
+        generator.declared_locals.insert(variable.to_string());
+        generator.environment_variables.insert(variable.to_string());
+    }
+    format!("${}", variable)
+}
+
+fn parameter_is_set(variable: &str, value: &str) -> String {
+    match variable {
+        "@" | "*" => "@ARGV".to_string(),
+        "#" | "?" | "$" | "0" => "1".to_string(),
+        _ => format!("defined({})", value),
+    }
+}
+
+/// `${var:=default}` and `${var=default}`; positional and special
+/// parameters cannot be assigned this way.
+fn assign_default(variable: &str, keep: &str, value: &str, default: &str) -> String {
+    if variable.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        format!("({} ? {} : ({} = {}))", keep, value, value, default)
+    } else {
+        format!("({} ? {} : {})", keep, value, default)
+    }
+}
+
+/// `${var:?message}` reports the message and exits the script with status 1.
+fn parameter_error(generator: &mut Generator, variable: &str, message: &str, default: &str) -> String {
+    let message = if message.is_empty() {
+        format!("'{}'", default)
+    } else {
+        shell_text_to_perl(generator, message)
+    };
+    format!("do {{ print STDERR '{}: ', {}, \"\\n\"; exit(1) }}", variable, message)
+}
+
+/// Convert the raw shell text of an operand, such as a default value, to a
+/// Perl string expression.
+pub fn shell_text_to_perl(generator: &mut Generator, text: &str) -> String {
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return perl_single_quoted(&text[1..text.len() - 1]);
+    }
+    let (inner, quoted) = match text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(inner) => (inner, true),
+        None => (text, false),
+    };
+
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = inner;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' && rest.len() > 1 {
+            let next = rest[1..].chars().next().unwrap();
+            if quoted && !"\\\"$`".contains(next) {
+                literal.push(c);
+            }
+            literal.push(next);
+            rest = &rest[1 + next.len_utf8()..];
+            continue;
+        }
+        if c == '$' {
+            let after = &rest[1..];
+            let (expr, len) = if after.starts_with('{') {
+                match find_closing_brace(after) {
+                    Some(end) => match parameter_expansion_word(&after[1..end]) {
+                        Word::ParameterExpansion(pe, _) => (Some(generate_parameter_expansion_impl(generator, &pe)), end + 1),
+                        Word::Variable(name, _) => (Some(format!("({} // '')", parameter_value(generator, &name))), end + 1),
+                        _ => (None, 0),
+                    },
+                    None => (None, 0),
+                }
             } else {
-                format!("@${{{}}}[{}..]", pe.variable, offset)
+                let name_len = match after.chars().next() {
+                    Some(n) if n.is_ascii_digit() || "@*#?$".contains(n) => 1,
+                    Some(n) if n.is_ascii_alphabetic() || n == '_' => {
+                        after.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_').unwrap_or(after.len())
+                    }
+                    _ => 0,
+                };
+                if name_len > 0 {
+                    (Some(format!("({} // '')", parameter_value(generator, &after[..name_len]))), name_len)
+                } else {
+                    (None, 0)
+                }
+            };
+            if let Some(expr) = expr {
+                if !literal.is_empty() {
+                    pieces.push(perl_single_quoted(&std::mem::take(&mut literal)));
+                }
+                pieces.push(expr);
+                rest = &after[len..];
+                continue;
             }
         }
+        if quoted || c != '"' {
+            literal.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(perl_single_quoted(&literal));
+    }
+    if pieces.len() == 1 {
+        pieces.pop().unwrap()
+    } else {
+        format!("({})", pieces.join(" . "))
     }
 }
 
-// Helper methods for regex escaping
-fn escape_regex_pattern(pattern: &str) -> String {
-    // Escape special regex characters in the pattern
-    pattern.replace("\\", "\\\\")
-           .replace(".", "\\.")
-           .replace("+", "\\+")
-           .replace("*", "\\*")
-           .replace("?", "\\?")
-           .replace("^", "\\^")
-           .replace("$", "\\$")
-           .replace("[", "\\[")
-           .replace("]", "\\]")
-           .replace("(", "\\(")
-           .replace(")", "\\)")
-           .replace("|", "\\|")
+/// The offset of the `}` that closes the `{` `text` starts with.
+fn find_closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
 }
 
-fn escape_regex_replacement(replacement: &str) -> String {
-    // Escape special regex characters in the replacement string
-    replacement.replace("\\", "\\\\")
-               .replace("$", "\\$")
-               .replace("&", "\\&")
-               .replace("`", "\\`")
-               .replace("'", "\\'")
+fn perl_single_quoted(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// A parameter expansion pattern as the body of a Perl regex. Extglob groups
+/// are read as such, as scripts that write them have `shopt -s extglob` on.
+fn glob(pattern: &str, length: MatchLength) -> String {
+    glob_to_regex(pattern, true, length)
+}
+
+/// `text` with the longest matches of `pattern` between the `before` and
+/// `after` anchors replaced. An empty pattern only matches with an anchor.
+fn substitute(generator: &mut Generator, text: &str, before: &str, pattern: &str, after: &str,
+              replacement: &str, flags: &str) -> String {
+    if pattern.is_empty() && before.is_empty() && after.is_empty() {
+        return text.to_string();
+    }
+    let replacement = shell_text_to_perl(generator, replacement);
+    format!("do {{ my ($value, $replacement) = ({}, {}); $value =~ s/{}{}{}/$replacement/s{}r }}",
+            text, replacement, before, glob(pattern, MatchLength::Longest), after, flags)
 }
@@ -1,19 +1,529 @@
 use crate::ast::*;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 pub mod commands;
 pub mod control_flow;
 pub mod words;
+pub mod arithmetic;
 pub mod expansions;
 pub mod redirects;
 pub mod test_expressions;
+pub mod patterns;
 pub mod utils;
+pub mod backend;
+pub mod python;
+pub mod rust;
+#[cfg(test)]
+pub mod tests;
+
+pub use backend::Backend;
+pub use python::PythonGenerator;
+pub use rust::RustGenerator;
+
+/// Feeds a heredoc's text from memory through a pipe, which a command
+/// reads as its input. Text too large for the pipe is written by a child.
+const HERE_DOCUMENT_HELPER: &str = r#"sub here_document {
+    my ($text) = @_;
+    open(my $source, '<', \$text) or die "Cannot read here document: $!\n";
+    pipe(my $reader, my $writer) or die "Cannot create pipe: $!\n";
+    if (length($text) < 65536) {
+        # Small enough to sit in the pipe until the command reads it
+        print {$writer} <$source>;
+    } elsif (!(fork() // die "Cannot fork: $!\n")) {
+        # Anything larger is written by a process of its own
+        require POSIX;
+        close($reader);
+        print {$writer} <$source>;
+        close($writer);
+        POSIX::_exit(0);
+    }
+    close($writer);
+    return $reader;
+}
+"#;
+
+/// Reads one line, or what `-d`, `-n` and `-N` ask for, a byte at a time so
+/// that commands run later still see the rest of the input. Returns the exit
+/// status followed by the values for `$fields` variables split on IFS: every
+/// field for `-a` when `$fields` is 0, or the line as read when it is undef.
+const READ_HELPER: &str = r#"sub read_input {
+    my ($fd, $fields, %options) = @_;
+    require POSIX;
+    require Time::HiRes;
+    my $delimiter = $options{delimiter} // "\n";
+    my $count = $options{count};
+    my $deadline = defined $options{timeout} ? Time::HiRes::time() + $options{timeout} : undef;
+    if (defined $options{timeout} && $options{timeout} == 0) {
+        # -t 0 only asks whether there is input
+        my $ready = '';
+        vec($ready, $fd, 1) = 1;
+        return (select($ready, undef, undef, 0) ? 0 : 1, ('') x ($fields // 1));
+    }
+    my $terminal = POSIX::isatty($fd);
+    STDOUT->flush();
+    if (defined $options{prompt} && $terminal) {
+        print STDERR $options{prompt};
+        STDERR->flush();
+    }
+    my $saved;
+    if ($terminal && ($options{silent} || defined $count)) {
+        # Characters arrive as typed, and unechoed with -s
+        $saved = POSIX::Termios->new;
+        $saved->getattr($fd);
+        my $raw = POSIX::Termios->new;
+        $raw->getattr($fd);
+        my $lflag = $raw->getlflag;
+        $lflag &= ~POSIX::ECHO() if $options{silent};
+        $lflag &= ~POSIX::ICANON() if defined $count;
+        $raw->setlflag($lflag);
+        $raw->setcc(POSIX::VMIN(), 1);
+        $raw->setcc(POSIX::VTIME(), 0);
+        $raw->setattr($fd, POSIX::TCSANOW());
+    }
+    my $status = 1;
+    my (@chars, %escaped);
+    while (!defined $count || @chars < $count) {
+        if (defined $deadline) {
+            my $ready = '';
+            vec($ready, $fd, 1) = 1;
+            my $left = $deadline - Time::HiRes::time();
+            if (!select($ready, undef, undef, $left > 0 ? $left : 0)) {
+                $status = 142;
+                last;
+            }
+        }
+        last unless (POSIX::read($fd, my $char, 1) // 0) > 0;
+        if ($char eq $delimiter && !$options{exact}) {
+            $status = 0;
+            last;
+        }
+        if ($char eq "\\" && !$options{raw}) {
+            # A backslash quotes the next character and joins lines
+            last unless (POSIX::read($fd, $char, 1) // 0) > 0;
+            next if $char eq "\n";
+            $escaped{scalar @chars} = 1;
+        }
+        push @chars, $char;
+    }
+    $status = 0 if defined $count && @chars >= $count;
+    $saved->setattr($fd, POSIX::TCSANOW()) if $saved;
+    return ($status, join('', @chars)) unless defined $fields;
+
+    my $ifs = $options{ifs};
+    my $is_ifs = sub { $_[0] < @chars && !$escaped{$_[0]} && index($ifs, $chars[$_[0]]) >= 0 };
+    my $is_space = sub { $is_ifs->($_[0]) && $chars[$_[0]] =~ /[ \t\n]/ };
+    my ($i, @values) = (0);
+    $i++ while $is_space->($i);
+    while ($i < @chars && ($fields == 0 || @values < $fields - 1)) {
+        my $start = $i;
+        $i++ while $i < @chars && !$is_ifs->($i);
+        push @values, join('', @chars[$start .. $i - 1]);
+        # A separator is IFS whitespace around at most one other IFS character
+        $i++ while $is_space->($i);
+        if ($is_ifs->($i)) {
+            $i++;
+            $i++ while $is_space->($i);
+        }
+    }
+    if ($fields > 0 && $i < @chars) {
+        # The last variable takes the rest of the line, less a trailing separator
+        my $end = @chars;
+        $end-- while $end > $i && $is_space->($end - 1);
+        my $field_end = $i;
+        $field_end++ while $field_end < $end && !$is_ifs->($field_end);
+        $end = $field_end if $field_end == $end - 1;
+        push @values, join('', @chars[$i .. $end - 1]);
+    }
+    push @values, '' while @values < $fields;
+    return ($status, @values);
+}
+"#;
+
+/// Formats like bash's `printf`: escapes in the format, `%b`, `%q` and
+/// `%(fmt)T` on top of what `sprintf` does, with the format used again
+/// while arguments remain.
+const PRINTF_HELPER: &str = r#"sub shell_printf {
+    my ($format, @args) = @_;
+    my %escapes = (a => "\a", b => "\b", e => "\e", E => "\e", f => "\f", n => "\n", r => "\r", t => "\t", v => "\013", "\\" => "\\");
+    my $unescape = sub {
+        my ($text, $argument) = @_;
+        # %b takes \0NNN and stops at \c, the format takes \" \' and \?
+        my $octal = $argument ? qr/0?([0-7]{1,3})/ : qr/([0-7]{1,3})/;
+        my $result = '';
+        pos($text) = 0;
+        while (pos($text) < length($text)) {
+            if ($text =~ /\G([^\\]+)/gc) {
+                $result .= $1;
+            } elsif ($text =~ /\G\\$octal/gc) {
+                $result .= chr(oct($1) & 255);
+            } elsif ($text =~ /\G\\x([0-9a-fA-F]{1,2})/gc) {
+                $result .= chr(hex($1));
+            } elsif ($text =~ /\G\\(?:u([0-9a-fA-F]{1,4})|U([0-9a-fA-F]{1,8}))/gc) {
+                my $char = chr(hex($1 // $2));
+                utf8::encode($char);
+                $result .= $char;
+            } elsif ($argument && $text =~ /\G\\c/gc) {
+                return ($result, 1);
+            } elsif ($text =~ /\G\\(.)/gcs) {
+                my $char = $1;
+                $result .= $escapes{$char} // (!$argument && $char =~ /["'?]/ ? $char : "\\$char");
+            } else {
+                $text =~ /\G(.)/gcs;
+                $result .= $1;
+            }
+        }
+        return ($result, 0);
+    };
+    my $invalid = sub {
+        my ($text, $kind) = @_;
+        print STDERR "printf: $text: invalid ${kind}number\n";
+    };
+    my $integer = sub {
+        my ($text) = @_;
+        return 0 if !defined $text || $text eq '';
+        return ord(substr($text, 1)) if $text =~ /\A['"]./s;
+        my ($sign, $digits, $rest) = $text =~ /\A\s*([-+]?)(0[xX][0-9a-fA-F]+|0[0-7]*|[0-9]*)(.*)\z/s;
+        if ($digits eq '' || $rest ne '') {
+            $invalid->($text, $text =~ /\A\s*[-+]?0[xX]/ ? 'hex ' : $text =~ /\A\s*[-+]?0[0-9]/ ? 'octal ' : '');
+        }
+        my $value = $digits =~ /\A0/ ? oct($digits) : $digits || 0;
+        return $sign eq '-' ? -$value : $value;
+    };
+    my $float = sub {
+        my ($text) = @_;
+        return 0 if !defined $text || $text eq '';
+        return ord(substr($text, 1)) if $text =~ /\A['"]./s;
+        my ($number, $rest) = $text =~ /\A\s*([-+]?(?:0[xX][0-9a-fA-F]+|(?:[0-9]+\.?[0-9]*|\.[0-9]+)(?:[eE][-+]?[0-9]+)?|inf(?:inity)?|nan)|)(.*)\z/si;
+        $invalid->($text, '') if $number eq '' || $rest ne '';
+        return $number =~ /\A([-+]?)(0[xX].*)\z/ ? ($1 eq '-' ? -oct($2) : oct($2)) : $number || 0;
+    };
+    my $quote = sub {
+        my ($text) = @_;
+        return "''" if $text eq '';
+        if ($text =~ /[^\x20-\x7e]/) {
+            # Control characters and other bytes need ANSI-C quoting
+            my %names = ("\a" => 'a', "\b" => 'b', "\e" => 'E', "\f" => 'f', "\n" => 'n', "\r" => 'r', "\t" => 't', "\013" => 'v', "'" => "'", "\\" => "\\");
+            return "\$'" . join('', map { exists $names{$_} ? "\\$names{$_}" : /[\x20-\x7e]/ ? $_ : sprintf("\\%03o", ord) } split(//, $text)) . "'";
+        }
+        $text =~ s/([^\w.\/:=+\@%~#-])/\\$1/g;
+        $text =~ s/\A([~#])/\\$1/;
+        return $text;
+    };
+
+    my $output = '';
+    while (1) {
+        my $used = 0;
+        my $next = sub { $used++; return shift(@args) };
+        pos($format) = 0;
+        while (pos($format) < length($format)) {
+            if ($format =~ /\G([^%\\]+)/gc) {
+                $output .= $1;
+            } elsif ($format =~ /\G(\\(?:[0-7]{1,3}|x[0-9a-fA-F]{1,2}|u[0-9a-fA-F]{1,4}|U[0-9a-fA-F]{1,8}|.)?)/gcs) {
+                $output .= ($unescape->($1, 0))[0];
+            } elsif ($format =~ /\G%%/gc) {
+                $output .= '%';
+            } elsif ($format =~ /\G%([-+ #0]*)(\*|[0-9]*)(?:\.(\*|[0-9]*))?(?:\(([^)]*)\))?[hlLjzt]*(.?)/gcs) {
+                my ($flags, $width, $precision, $time, $conversion) = ($1, $2, $3, $4, $5);
+                $width = $integer->($next->()) if $width eq '*';
+                if (defined $precision) {
+                    $precision = $precision eq '*' ? $integer->($next->()) : $precision || 0;
+                    undef $precision if $precision < 0;
+                }
+                my $padding = "%$flags$width";
+                my $spec = $padding . (defined $precision ? ".$precision" : '');
+                if ($conversion eq '') {
+                    print STDERR "printf: `%': missing format character\n";
+                    return $output;
+                } elsif (defined $time ? $conversion ne 'T' : $conversion !~ /[diouxXeEfFgGaAcsbq]/) {
+                    print STDERR "printf: `$conversion': invalid format character\n";
+                    return $output;
+                }
+                my $argument = $next->();
+                if (defined $time) {
+                    require POSIX;
+                    my $when = !defined $argument || $argument eq '' || $argument eq '-1' ? time : $argument eq '-2' ? $^T : $integer->($argument);
+                    $output .= sprintf("${spec}s", POSIX::strftime($time eq '' ? '%X' : $time, localtime($when)));
+                } elsif ($conversion =~ /[diouxX]/) {
+                    $output .= sprintf("$spec$conversion", $integer->($argument));
+                } elsif ($conversion =~ /[eEfFgGaA]/) {
+                    $output .= sprintf("$spec$conversion", $float->($argument));
+                } elsif ($conversion eq 'c') {
+                    $output .= sprintf("${padding}s", length($argument // '') ? substr($argument, 0, 1) : "\0");
+                } elsif ($conversion eq 'b') {
+                    my ($text, $stop) = $unescape->($argument // '', 1);
+                    $output .= sprintf("${spec}s", $text);
+                    return $output if $stop;
+                } elsif ($conversion eq 'q') {
+                    $output .= sprintf("${spec}s", $quote->($argument // ''));
+                } else {
+                    $output .= sprintf("${spec}s", $argument // '');
+                }
+            }
+        }
+        last unless $used && @args;
+    }
+    return $output;
+}
+"#;
+
+/// Takes the next option from `$args` the way `getopts` does, keeping its
+/// place in a group like `-vx` between calls. Returns the exit status and
+/// the option, `?` for an unknown one and `:` for a missing argument in
+/// silent mode.
+const GETOPTS_HELPER: &str = r#"my ($getopts_position, $getopts_index) = (1, 1);
+sub getopts_next {
+    my ($optstring, $args, $optind, $optarg) = @_;
+    my $silent = $optstring =~ s/\A://;
+    $$optind //= 1;
+    # Start again at the first character when the script moves OPTIND
+    $getopts_position = 1 if $getopts_index != $$optind;
+    undef $$optarg;
+    my $arg = $args->[$$optind - 1];
+    if ($getopts_position == 1 && (!defined $arg || $arg !~ /\A-./s || $arg eq '--')) {
+        $$optind++ if defined $arg && $arg eq '--';
+        $getopts_index = $$optind;
+        return (1, '?');
+    }
+    my $option = substr($arg, $getopts_position++, 1);
+    my $last = $getopts_position >= length($arg);
+    my $spec = $option eq ':' ? -1 : index($optstring, $option);
+    if ($spec < 0) {
+        print STDERR "$0: illegal option -- $option\n" unless $silent;
+        $$optarg = $option if $silent;
+        $option = '?';
+    } elsif (substr($optstring, $spec + 1, 1) eq ':') {
+        if (!$last) {
+            $$optarg = substr($arg, $getopts_position);
+            $last = 1;
+        } elsif ($$optind < @$args) {
+            $$optarg = $args->[$$optind];
+            $$optind++;
+        } elsif ($silent) {
+            $$optarg = $option;
+            $option = ':';
+        } else {
+            print STDERR "$0: option requires an argument -- $option\n";
+            $option = '?';
+        }
+    }
+    if ($last) {
+        $$optind++;
+        $getopts_position = 1;
+    }
+    $getopts_index = $$optind;
+    return (0, $option);
+}
+"#;
+
+/// Runs the traps the script sets. The EXIT trap runs at the end, seeing
+/// the status the script exits with, or before a signal without a trap of
+/// its own ends the script. Perl's `system` ignores SIGINT and SIGQUIT while
+/// the command runs, so it is replaced by one that lets them reach the
+/// script's handlers, as they reach the shell's.
+const TRAP_HELPER: &str = r#"my $exit_trap;
+sub run_exit_trap {
+    my ($status) = @_;
+    my $trap = $exit_trap or return;
+    # It runs once, even if it calls exit itself
+    undef $exit_trap;
+    local $? = defined $status ? $status << 8 : $?;
+    $trap->();
+}
+BEGIN {
+    *CORE::GLOBAL::system = sub {
+        my $pid = fork // return -1;
+        if (!$pid) {
+            exec(@_) or do { require POSIX; POSIX::_exit(127) };
+        }
+        waitpid($pid, 0);
+        return $?;
+    };
+}
+sub signal_exit {
+    my ($signal) = @_;
+    run_exit_trap();
+    STDOUT->flush(); STDERR->flush();
+    $SIG{$signal} = 'DEFAULT';
+    kill($signal, $$);
+}
+$SIG{$_} = \&signal_exit for qw(HUP INT TERM);
+END {
+    my $status = $?;
+    run_exit_trap($status);
+    $? = $status;
+}
+"#;
+
+/// Acts on the status of a command that failed: the ERR trap runs, with
+/// `$LINENO` set to the command's line, and then under `set -e` the script
+/// exits with the status.
+const STATUS_HELPER: &str = r#"my %shell_options;
+my ($err_trap, $LINENO);
+sub check_status {
+    my ($line) = @_;
+    my $status = $? == -1 ? 127 : $? & 127 ? 128 + ($? & 127) : $? >> 8;
+    if ($status != 0 && $err_trap) {
+        # Commands the trap runs do not set it off again
+        my $trap = $err_trap;
+        undef $err_trap;
+        local $?;
+        $LINENO = $line;
+        $trap->();
+        $err_trap = $trap;
+    }
+    exit($status) if $status != 0 && $shell_options{errexit};
+    return $status;
+}
+"#;
+
+/// What `set -u` and `set -x` change: reading an unset variable stops the
+/// script, and commands are printed to STDERR before they run.
+const SHELL_OPTIONS_HELPER: &str = r#"sub unset_variable {
+    my ($name) = @_;
+    return '' unless $shell_options{nounset};
+    STDOUT->flush();
+    print STDERR "$0: $name: unbound variable\n";
+    exit(1);
+}
+sub trace_quote {
+    my ($word) = @_;
+    return $word =~ /\A[\w.\/:=+\@%,-]+\z/ ? $word : "'" . $word =~ s/'/'\\''/gr . "'";
+}
+sub trace_command {
+    return unless $shell_options{xtrace};
+    # Output so far comes first, as it would from the shell
+    STDOUT->flush();
+    print STDERR join(' ', '+', map { trace_quote($_) } @_), "\n";
+}
+sub trace_assignment {
+    my ($name, $value, $source) = @_;
+    return unless $shell_options{xtrace};
+    # An array is shown as it was written
+    $value = $source // (length $value ? trace_quote($value) : '');
+    STDOUT->flush();
+    print STDERR "+ $name=$value\n";
+}
+"#;
+
+/// Runs the commands of a pipeline as children joined by pipes, and sets
+/// `$?` to the status of the last one or, under `set -o pipefail`, of the
+/// last one that failed.
+const PIPELINE_HELPER: &str = r#"sub run_pipeline {
+    my @commands = @_;
+    STDOUT->flush();
+    my ($input, @pids);
+    for my $index (0 .. $#commands) {
+        my ($reader, $writer);
+        pipe($reader, $writer) or die "pipe: $!\n" if $index < $#commands;
+        my $pid = fork() // die "fork: $!\n";
+        if ($pid == 0) {
+            open(STDIN, '<&', $input) or die "dup: $!\n" if $input;
+            open(STDOUT, '>&', $writer) or die "dup: $!\n" if $writer;
+            my ($name, @args) = @{$commands[$index]};
+            { no warnings 'exec'; exec { $name } $name, @args; }
+            print STDERR "$0: $name: command not found\n";
+            require POSIX;
+            POSIX::_exit(127);
+        }
+        close($input) if $input;
+        close($writer) if $writer;
+        ($input, $pids[$index]) = ($reader, $pid);
+    }
+    my $status = 0;
+    for my $pid (@pids) {
+        waitpid($pid, 0);
+        $status = $? if $? != 0 || !$shell_options{pipefail};
+    }
+    $? = $status;
+}
+"#;
+
+/// Runs a pipeline stage through the shell with the output of the stages
+/// before it on its standard input, and returns what it prints.
+const PIPE_THROUGH_HELPER: &str = r#"sub pipe_through {
+    my ($input, $command) = @_;
+    $input .= "\n" if length($input) && $input !~ /\n\z/;
+    my $pid = open(my $result, '-|') // die "Cannot fork: $!\n";
+    if (!$pid) {
+        open(my $stage, '|-', $command) or die "Cannot run $command: $!\n";
+        print {$stage} $input;
+        close($stage);
+        require POSIX;
+        POSIX::_exit($? >> 8);
+    }
+    local $/;
+    my $output = <$result> // '';
+    close($result);
+    return $output;
+}
+"#;
+
+/// Runs a process substitution's command in a child on one end of a pipe.
+/// The descriptor stays open across `exec`, so external commands can open
+/// its `/dev/fd` path too.
+const PROCESS_SUBSTITUTION_HELPER: &str = r#"my @process_subs;
+sub process_substitution {
+    my ($mode, $code) = @_;
+    require POSIX;
+    require Fcntl;
+    STDOUT->flush(); STDERR->flush();
+    my $pid = open(my $fh, $mode) // die "Cannot fork: $!\n";
+    if (!$pid) {
+        POSIX::close(fileno($_)) for @process_subs;
+        $code->();
+        STDOUT->flush(); STDERR->flush();
+        POSIX::_exit(0);
+    }
+    fcntl($fh, Fcntl::F_SETFD(), 0);
+    push @process_subs, $fh;
+    return "/dev/fd/" . fileno($fh);
+}
+"#;
 
 pub struct Generator {
     pub indent_level: usize,
     pub declared_locals: HashSet<String>,
     pub declared_functions: HashSet<String>,
     pub file_handle_counter: usize,
+    /// Set once an `exec` has kept descriptors open in `%fd_table`.
+    pub uses_fd_table: bool,
+    /// Set once a process substitution needs the `process_substitution` helper.
+    pub uses_process_subs: bool,
+    /// Whether a heredoc or here-string needs the `here_document` helper
+    pub uses_here_documents: bool,
+    /// Process substitutions started by the command being generated.
+    pub pending_process_subs: usize,
+    /// Variables expanded before the script assigns them, which start out
+    /// with their value from the environment.
+    pub environment_variables: BTreeSet<String>,
+    /// Set once a `read` needs the `read_input` helper.
+    pub uses_read: bool,
+    /// Arrays declared at the top of the script, as `read -a` or an element
+    /// assignment may fill them before the script assigns them whole.
+    pub predeclared_arrays: BTreeSet<String>,
+    /// Set once a `printf` needs the `shell_printf` helper.
+    pub uses_printf: bool,
+    /// Set once a `getopts` needs the `getopts_next` helper.
+    pub uses_getopts: bool,
+    /// Set once a `trap` needs the trap helper.
+    pub uses_traps: bool,
+    /// Set once an ERR trap, `set -e` or `set -o pipefail` makes the status
+    /// of commands matter, after which commands report it to `check_status`.
+    pub checks_status: bool,
+    /// Shell options a `set` in the script turns on or off. The generated
+    /// code keeps their current state in `%shell_options`.
+    pub shell_options: BTreeSet<String>,
+    /// Set once a pipeline runs through the `run_pipeline` helper.
+    pub runs_pipelines: bool,
+    /// Set once a pipeline stage needs the `pipe_through` helper.
+    pub uses_pipe_through: bool,
+    /// Set while generating a function body, whose positional parameters
+    /// are `@_` rather than the script's `@ARGV`.
+    pub in_function: bool,
+    /// Variables the script assigns at the top level, and whether each is
+    /// an array. A trap declares them up front, as its handler may run
+    /// after the script assigns them.
+    pub script_variables: BTreeMap<String, bool>,
 }
 
 impl Generator {
@@ -23,6 +533,22 @@ impl Generator {
             declared_locals: HashSet::new(),
             declared_functions: HashSet::new(),
             file_handle_counter: 0,
+            uses_fd_table: false,
+            uses_process_subs: false,
+            uses_here_documents: false,
+            pending_process_subs: 0,
+            environment_variables: BTreeSet::new(),
+            uses_read: false,
+            predeclared_arrays: BTreeSet::new(),
+            uses_printf: false,
+            uses_getopts: false,
+            uses_traps: false,
+            checks_status: false,
+            shell_options: BTreeSet::new(),
+            runs_pipelines: false,
+            uses_pipe_through: false,
+            in_function: false,
+            script_variables: BTreeMap::new(),
         }
     }
 
@@ -33,6 +559,22 @@ impl Generator {
         output.push_str("#!/usr/bin/env perl\n");
         output.push_str("use strict;\n");
         output.push_str("use warnings;\n\n");
+
+        for command in ast {
+            if let Command::Simple(cmd, _) = command {
+                if cmd.name == "true" && cmd.args.is_empty() {
+                    for assignment in &cmd.env_vars {
+                        let is_array = match &assignment.index {
+                            // Other subscripts are hash keys, and hashes are declared where first set
+                            Some(index) if index.parse::<usize>().is_err() => continue,
+                            Some(_) => true,
+                            None => matches!(assignment.value, Word::Array(..)),
+                        };
+                        *self.script_variables.entry(assignment.name.clone()).or_default() |= is_array;
+                    }
+                }
+            }
+        }
         
         for command in ast {
             // Reset indentation level for each top-level command to prevent staircase effect
@@ -46,6 +588,49 @@ impl Generator {
             }
         }
         
+        let mut helpers = String::new();
+        for name in &self.environment_variables {
+            helpers.push_str(&format!("my ${} = $ENV{{{}}};\n", name, name));
+        }
+        for name in &self.predeclared_arrays {
+            helpers.push_str(&format!("my @{};\n", name));
+        }
+        if self.uses_fd_table {
+            helpers.push_str("my %fd_table;\n");
+        }
+        if self.uses_here_documents {
+            helpers.push_str(HERE_DOCUMENT_HELPER);
+        }
+        if self.uses_read {
+            helpers.push_str(READ_HELPER);
+        }
+        if self.uses_printf {
+            helpers.push_str(PRINTF_HELPER);
+        }
+        if self.uses_getopts {
+            helpers.push_str(GETOPTS_HELPER);
+        }
+        if self.uses_traps {
+            helpers.push_str(TRAP_HELPER);
+        }
+        if self.uses_traps || self.checks_status || !self.shell_options.is_empty() {
+            helpers.push_str(STATUS_HELPER);
+        }
+        if !self.shell_options.is_empty() {
+            helpers.push_str(SHELL_OPTIONS_HELPER);
+        }
+        if self.runs_pipelines {
+            helpers.push_str(PIPELINE_HELPER);
+        }
+        if self.uses_pipe_through {
+            helpers.push_str(PIPE_THROUGH_HELPER);
+        }
+        if self.uses_process_subs {
+            helpers.push_str(PROCESS_SUBSTITUTION_HELPER);
+        }
+        if !helpers.is_empty() {
+            output = output.replacen("use warnings;\n\n", &format!("use warnings;\n\n{}", helpers), 1);
+        }
         output
     }
 
@@ -74,6 +659,10 @@ impl Generator {
         commands::generate_command_string_for_system_impl(self, cmd)
     }
 
+    pub fn shell_output(&mut self, cmd: &Command, input: Option<&str>) -> String {
+        commands::shell_output_impl(self, cmd, input)
+    }
+
     pub fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
         control_flow::generate_if_statement_impl(self, if_stmt)
     }
@@ -90,6 +679,14 @@ impl Generator {
         control_flow::generate_for_loop_impl(self, for_loop)
     }
 
+    pub fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        control_flow::generate_select_loop_impl(self, select_loop)
+    }
+
+    pub fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        control_flow::generate_arithmetic_for_impl(self, arith_for)
+    }
+
     pub fn generate_function(&mut self, func: &Function) -> String {
         control_flow::generate_function_impl(self, func)
     }
@@ -130,6 +727,18 @@ impl Generator {
         redirects::generate_redirect_impl(self, redirect)
     }
 
+    pub fn generate_process_substitution(&mut self, cmd: &Command, direction: ProcessDirection) -> String {
+        redirects::generate_process_substitution_impl(self, cmd, direction)
+    }
+
+    pub fn generate_exec_command(&mut self, cmd: &BuiltinCommand, redirects: &[Redirect]) -> String {
+        redirects::generate_exec_command_impl(self, cmd, redirects)
+    }
+
+    pub fn generate_redirect_command(&mut self, redirect_cmd: &RedirectCommand) -> String {
+        redirects::generate_redirect_command_impl(self, redirect_cmd)
+    }
+
     pub fn word_to_perl(&mut self, word: &Word) -> String {
         words::word_to_perl_impl(self, word)
     }
@@ -158,6 +767,26 @@ impl Generator {
         utils::get_unique_file_handle_impl(self)
     }
 
+    pub fn positional_parameters(&self) -> &'static str {
+        utils::positional_parameters_impl(self)
+    }
+
+    pub fn positional_parameter(&self, index: usize) -> String {
+        utils::positional_parameter_impl(self, index)
+    }
+
+    pub fn checked_variable(&mut self, name: &str, value: String) -> String {
+        utils::checked_variable_impl(self, name, value)
+    }
+
+    pub fn traced_command(&mut self, words: &[Word]) -> String {
+        utils::traced_command_impl(self, words)
+    }
+
+    pub fn traced_assignment(&mut self, name: &str, value: &str, source: Option<&str>) -> String {
+        utils::traced_assignment_impl(self, name, value, source)
+    }
+
     // Additional helper methods that are needed
     pub fn handle_range_expansion(&self, s: &str) -> String {
         words::handle_range_expansion_impl(self, s)
@@ -175,12 +804,20 @@ impl Generator {
         words::brace_item_to_word_impl(self, item)
     }
 
-    pub fn convert_string_interpolation_to_perl(&self, interp: &StringInterpolation) -> String {
+    pub fn convert_string_interpolation_to_perl(&mut self, interp: &StringInterpolation) -> String {
         words::convert_string_interpolation_to_perl_impl(self, interp)
     }
 
-    pub fn convert_arithmetic_to_perl(&self, expr: &str) -> String {
-        words::convert_arithmetic_to_perl_impl(self, expr)
+    pub fn convert_arithmetic_to_perl(&self, expr: &ArithmeticExpression) -> String {
+        arithmetic::convert_arithmetic_to_perl_impl(self, expr)
+    }
+
+    pub fn convert_arithmetic_condition_to_perl(&self, expr: &ArithmeticExpression) -> String {
+        arithmetic::convert_arithmetic_condition_to_perl_impl(self, expr)
+    }
+
+    pub fn generate_arithmetic_command(&mut self, expr: &ArithmeticExpression) -> String {
+        arithmetic::generate_arithmetic_command_impl(self, expr)
     }
 
     pub fn convert_extglob_to_perl_regex(&self, pattern: &str) -> String {
@@ -191,10 +828,6 @@ impl Generator {
         test_expressions::convert_glob_to_regex_impl(self, pattern)
     }
 
-    pub fn convert_test_args_to_expression(&self, args: &[Word]) -> TestExpression {
-        test_expressions::convert_test_args_to_expression_impl(self, args)
-    }
-
     pub fn indent(&self) -> String {
         control_flow::indent_impl(self)
     }
@@ -230,3 +863,101 @@ impl Generator {
         None
     }
 }
+
+impl Backend for Generator {
+    fn generate(&mut self, ast: &[Command]) -> String {
+        Generator::generate(self, ast)
+    }
+
+    fn generate_command(&mut self, command: &Command) -> String {
+        Generator::generate_command(self, command)
+    }
+
+    fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        Generator::generate_simple_command(self, cmd)
+    }
+
+    fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        Generator::generate_builtin_command(self, cmd)
+    }
+
+    fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String {
+        Generator::generate_shopt_command(self, cmd)
+    }
+
+    fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        Generator::generate_pipeline(self, pipeline)
+    }
+
+    fn generate_subshell(&mut self, command: &Command) -> String {
+        Generator::generate_subshell(self, command)
+    }
+
+    fn generate_background(&mut self, command: &Command) -> String {
+        Generator::generate_background(self, command)
+    }
+
+    fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        Generator::generate_if_statement(self, if_stmt)
+    }
+
+    fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        Generator::generate_case_statement(self, case_stmt)
+    }
+
+    fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        Generator::generate_while_loop(self, while_loop)
+    }
+
+    fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        Generator::generate_for_loop(self, for_loop)
+    }
+
+    fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        Generator::generate_select_loop(self, select_loop)
+    }
+
+    fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        Generator::generate_arithmetic_for(self, arith_for)
+    }
+
+    fn generate_function(&mut self, func: &Function) -> String {
+        Generator::generate_function(self, func)
+    }
+
+    fn generate_block(&mut self, block: &Block) -> String {
+        Generator::generate_block(self, block)
+    }
+
+    fn generate_break_statement(&mut self, level: &Option<String>) -> String {
+        Generator::generate_break_statement(self, level)
+    }
+
+    fn generate_continue_statement(&mut self, level: &Option<String>) -> String {
+        Generator::generate_continue_statement(self, level)
+    }
+
+    fn generate_return_statement(&mut self, value: &Option<Word>) -> String {
+        Generator::generate_return_statement(self, value)
+    }
+
+    fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        Generator::generate_test_expression(self, test_expr)
+    }
+
+    fn generate_redirect(&mut self, redirect: &Redirect) -> String {
+        Generator::generate_redirect(self, redirect)
+    }
+
+    fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String {
+        Generator::generate_parameter_expansion(self, pe)
+    }
+
+    fn word_to_code(&mut self, word: &Word) -> String {
+        self.word_to_perl(word)
+    }
+
+    fn word_to_code_for_test(&mut self, word: &Word) -> String {
+        self.word_to_perl_for_test(word)
+    }
+}
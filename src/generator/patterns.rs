@@ -0,0 +1,227 @@
+// Shell glob patterns as Perl regexes. `case`, `[[ == ]]` and the pattern
+// operators of parameter expansion all match through `glob_to_regex`.
+
+/// Which match to prefer when a pattern can match more or less of the text,
+/// as `${var#pattern}` and `${var##pattern}` differ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchLength {
+    Shortest,
+    Longest,
+}
+
+/// Converts a glob pattern as written, quotes and all, into the body of a
+/// Perl regex, which the caller anchors. With `extglob`, `@(...)`, `*(...)`,
+/// `+(...)`, `?(...)` and `!(...)` groups are converted too. The regex
+/// expects the `s` flag, as `*` matches newlines.
+pub fn glob_to_regex(pattern: &str, extglob: bool, length: MatchLength) -> String {
+    let lazy = if length == MatchLength::Shortest { "?" } else { "" };
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut result = String::new();
+    let mut quote = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (Some('"'), '\\') if matches!(chars.get(i + 1), Some('$' | '`' | '"' | '\\')) => {
+                i += 1;
+                push_quotemeta(&mut result, chars[i]);
+            }
+            (None | Some('"'), '$') if chars.get(i + 1).is_some_and(|next| next.is_ascii_alphabetic() || *next == '_' || *next == '{') => {
+                // An expanded variable matches literally
+                let braced = chars[i + 1] == '{';
+                let start = if braced { i + 2 } else { i + 1 };
+                let mut end = start;
+                while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                if braced && chars.get(end) == Some(&'}') {
+                    end += 1;
+                }
+                result.push_str(&format!("\\Q${{{}}}\\E", name));
+                i = end - 1;
+            }
+            (Some(_), _) => push_quotemeta(&mut result, c),
+            (None, '\\') => {
+                i += 1;
+                if let Some(&next) = chars.get(i) {
+                    push_quotemeta(&mut result, next);
+                }
+            }
+            (None, '*' | '?' | '@' | '+' | '!') if extglob && chars.get(i + 1) == Some(&'(') => {
+                if let Some(end) = matching_paren(&chars, i + 1) {
+                    let body: String = chars[i + 2..end].iter().collect();
+                    let alternatives: Vec<String> = split_alternatives(&body).iter()
+                        .map(|alternative| glob_to_regex(alternative, extglob, length))
+                        .collect();
+                    let group = alternatives.join("|");
+                    match c {
+                        '*' => result.push_str(&format!("(?:{})*{}", group, lazy)),
+                        '?' => result.push_str(&format!("(?:{})?{}", group, lazy)),
+                        '+' => result.push_str(&format!("(?:{})+{}", group, lazy)),
+                        '!' => result.push_str(&format!("(?:(?!(?:{})\\z).*{})", group, lazy)),
+                        _ => result.push_str(&format!("(?:{})", group)),
+                    }
+                    i = end;
+                } else {
+                    push_quotemeta(&mut result, c);
+                }
+            }
+            (None, '*') => {
+                result.push_str(".*");
+                result.push_str(lazy);
+            }
+            (None, '?') => result.push('.'),
+            (None, '[') => match bracket_expression(&chars, i) {
+                Some((class, end)) => {
+                    result.push_str(&class);
+                    i = end;
+                }
+                None => result.push_str("\\["),
+            },
+            _ => push_quotemeta(&mut result, c),
+        }
+        i += 1;
+    }
+    result
+}
+
+pub fn push_quotemeta(result: &mut String, c: char) {
+    if !c.is_alphanumeric() && c != '_' {
+        result.push('\\');
+    }
+    result.push(c);
+}
+
+/// The index of the `)` that closes the `(` at `open`.
+fn matching_paren(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the body of an extglob group on its top-level `|`s.
+fn split_alternatives(body: &str) -> Vec<String> {
+    let mut alternatives = vec![String::new()];
+    let mut depth = 0;
+    for c in body.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '|' if depth == 0 => {
+                alternatives.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(last) = alternatives.last_mut() {
+            last.push(c);
+        }
+    }
+    alternatives
+}
+
+/// A glob bracket expression starting at `open`, such as `[a-z]`, `[!0-9]`
+/// or `[[:alpha:]_]`, as a regex character class and the index of its
+/// closing `]`.
+fn bracket_expression(chars: &[char], open: usize) -> Option<(String, usize)> {
+    let mut i = open + 1;
+    let mut class = String::from("[");
+    if matches!(chars.get(i), Some('!' | '^')) {
+        class.push('^');
+        i += 1;
+    }
+    // A `]` straight after the opening bracket is part of the set
+    if chars.get(i) == Some(&']') {
+        class.push_str("\\]");
+        i += 1;
+    }
+    while i < chars.len() {
+        match chars[i] {
+            ']' => {
+                class.push(']');
+                return Some((class, i));
+            }
+            // `[:alpha:]` is the same in Perl; `[=c=]` and `[.c.]` name the character c
+            '[' if matches!(chars.get(i + 1), Some(':' | '=' | '.')) => {
+                let kind = chars[i + 1];
+                let end = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == kind && chars[j + 1] == ']')?;
+                let name: String = chars[i + 2..end].iter().collect();
+                if kind == ':' {
+                    class.push_str(&format!("[:{}:]", name));
+                } else {
+                    name.chars().for_each(|c| push_quotemeta(&mut class, c));
+                }
+                i = end + 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                push_quotemeta(&mut class, chars[i]);
+            }
+            '/' | '@' | '$' | '[' => {
+                class.push('\\');
+                class.push(chars[i]);
+            }
+            c => class.push(c),
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex(pattern: &str) -> String {
+        glob_to_regex(pattern, true, MatchLength::Longest)
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(regex("*.txt"), ".*\\.txt");
+        assert_eq!(glob_to_regex("*.txt", false, MatchLength::Shortest), ".*?\\.txt");
+        assert_eq!(regex("a?c"), "a.c");
+    }
+
+    #[test]
+    fn test_bracket_expressions() {
+        assert_eq!(regex("[!a-z]x"), "[^a-z]x");
+        assert_eq!(regex("[]a]"), "[\\]a]");
+        assert_eq!(regex("[[:digit:]_]"), "[[:digit:]_]");
+        // Without a closing bracket, `[` is just a character
+        assert_eq!(regex("[ab"), "\\[ab");
+    }
+
+    #[test]
+    fn test_extglob_groups() {
+        assert_eq!(regex("@(a|b).c"), "(?:a|b)\\.c");
+        assert_eq!(regex("!(x)"), "(?:(?!(?:x)\\z).*)");
+        assert_eq!(regex("+(ab|*.c)"), "(?:ab|.*\\.c)+");
+        // Without extglob, the group is literal text
+        assert_eq!(glob_to_regex("@(a|b)", false, MatchLength::Longest), "\\@\\(a\\|b\\)");
+    }
+
+    #[test]
+    fn test_escaping_and_quoting() {
+        assert_eq!(regex("\\*a"), "\\*a");
+        assert_eq!(regex("'*?'\"[x]\"*"), "\\*\\?\\[x\\].*");
+        assert_eq!(regex("a.b+c"), "a\\.b\\+c");
+        // A variable matches its value literally, quoted or not
+        assert_eq!(regex("$pre*\"${suf}\""), "\\Q${pre}\\E.*\\Q${suf}\\E");
+    }
+}
@@ -0,0 +1,460 @@
+use crate::ast::*;
+use super::PythonGenerator;
+use super::control_flow::arithmetic_statements;
+use super::test_expressions::test_words_to_python;
+use super::words::python_string_literal;
+
+pub fn generate_command_impl(generator: &mut PythonGenerator, command: &Command) -> String {
+    match command {
+        Command::Simple(cmd, _) => generator.generate_simple_command(cmd),
+        Command::BuiltinCommand(cmd, _) => generator.generate_builtin_command(cmd),
+        Command::ShoptCommand(cmd, _) => generator.generate_shopt_command(cmd),
+        Command::TestExpression(test_expr, _) => generator.generate_test_expression(test_expr),
+        Command::Pipeline(pipeline, _) => generator.generate_pipeline(pipeline),
+        Command::If(if_stmt, _) => generator.generate_if_statement(if_stmt),
+        Command::Case(case_stmt, _) => generator.generate_case_statement(case_stmt),
+        Command::While(while_loop, _) => generator.generate_while_loop(while_loop),
+        Command::For(for_loop, _) => generator.generate_for_loop(for_loop),
+        Command::Select(select_loop, _) => generator.generate_select_loop(select_loop),
+        Command::ArithmeticFor(arith_for, _) => generator.generate_arithmetic_for(arith_for),
+        Command::Function(func, _) => generator.generate_function(func),
+        Command::Subshell(cmd, _) => generator.generate_subshell(cmd),
+        Command::Background(cmd, _) => generator.generate_background(cmd),
+        Command::Block(block, _) => generator.generate_block(block),
+        Command::Break(level, _) => generator.generate_break_statement(level),
+        Command::Continue(level, _) => generator.generate_continue_statement(level),
+        Command::Return(value, _) => generator.generate_return_statement(value),
+        Command::BlankLine(_) => "\n".to_string(),
+        Command::Error(text, _) => {
+            let indent = generator.indent();
+            format!("{}# Not translated (parse error): {}\n", indent, text.lines().collect::<Vec<_>>().join(&format!("\n{}# ", indent)))
+        }
+        Command::Redirect(..) => format!("{}{}\n", generator.indent(), generator.command_expression(command)),
+    }
+}
+
+/// Render a command as a Python expression that runs it and evaluates to its exit status.
+pub fn command_expression_impl(generator: &mut PythonGenerator, command: &Command) -> String {
+    match command {
+        Command::Simple(cmd, _) => simple_command_expression(generator, cmd),
+        Command::BuiltinCommand(cmd, _) => builtin_command_expression(generator, cmd),
+        Command::TestExpression(test_expr, _) => generator.test_expression_call(test_expr),
+        Command::Pipeline(pipeline, _) => pipeline_expression(generator, pipeline),
+        Command::Subshell(cmd, _) => format!("_subshell(lambda: {})", generator.command_expression(cmd)),
+        Command::Background(cmd, _) => format!("_background(lambda: {})", generator.command_expression(cmd)),
+        Command::Block(block, _) if block.commands.len() == 1 => generator.command_expression(&block.commands[0]),
+        Command::ShoptCommand(..) => "_set_status(0)".to_string(),
+        Command::Redirect(redirect_cmd, _) => {
+            let redirects: Vec<String> = redirect_cmd.redirects.iter()
+                .map(|redirect| generator.generate_redirect(redirect))
+                .collect();
+            let inner = generator.command_expression(&redirect_cmd.command);
+            match &*redirect_cmd.command {
+                // `exec` keeps its redirects for the rest of the script
+                Command::BuiltinCommand(cmd, _) if cmd.name == "exec" => {
+                    format!("_redirect([{}], lambda: {}, True)", redirects.join(", "), inner)
+                }
+                _ => format!("_redirect([{}], lambda: {})", redirects.join(", "), inner),
+            }
+        }
+        _ => hoist_command(generator, command),
+    }
+}
+
+/// Compound commands have no expression form, so wrap them in a helper
+/// function that is emitted just before the current statement.
+fn hoist_command(generator: &mut PythonGenerator, command: &Command) -> String {
+    let name = generator.next_helper_name("block");
+    let mut def = format!("{}def {}():\n", generator.indent(), name);
+    generator.indent_level += 1;
+    def.push_str(&generator.generate_command(command));
+    def.push_str(&format!("{}return _status()\n", generator.indent()));
+    generator.indent_level -= 1;
+    generator.pending_defs.push(def);
+    format!("{}()", name)
+}
+
+pub fn generate_simple_command_impl(generator: &mut PythonGenerator, cmd: &SimpleCommand) -> String {
+    if let (Word::Arithmetic(expr, _), true) = (&cmd.name, cmd.args.is_empty()) {
+        // `(( expr ))` updates variables, and succeeds when the last value is non-zero
+        let mut statements = arithmetic_statements(generator, &expr.expression);
+        let status = match statements.pop() {
+            Some(last) if !last.starts_with("V[") => format!("_set_status(0 if {} else 1)", last),
+            Some(last) => {
+                statements.push(last);
+                "_set_status(0)".to_string()
+            }
+            None => "_set_status(1)".to_string(),
+        };
+        statements.push(status);
+        return statements.iter().map(|statement| format!("{}{}\n", generator.indent(), statement)).collect();
+    }
+    if is_assignment_only(cmd) {
+        let mut output = String::new();
+        for assignment in &cmd.env_vars {
+            output.push_str(&generator.indent());
+            output.push_str(&assignment_statement(generator, assignment));
+            output.push('\n');
+        }
+        return output;
+    }
+    format!("{}{}\n", generator.indent(), simple_command_expression(generator, cmd))
+}
+
+fn simple_command_expression(generator: &mut PythonGenerator, cmd: &SimpleCommand) -> String {
+    if is_assignment_only(cmd) {
+        return assignments_expression(generator, &cmd.env_vars);
+    }
+    if let (Word::Arithmetic(expr, _), true) = (&cmd.name, cmd.args.is_empty()) {
+        return format!("_set_status(0 if {} else 1)", generator.convert_arithmetic_to_python(&expr.expression));
+    }
+    // `if (( expr ))` arrives as `test` given the arithmetic word
+    if let (Some("test"), [Word::Arithmetic(expr, _)]) = (cmd.name.as_literal(), cmd.args.as_slice()) {
+        return format!("_set_status(0 if {} else 1)", generator.convert_arithmetic_to_python(&expr.expression));
+    }
+
+    let name = match &cmd.name {
+        Word::Literal(s, _) => s.as_str(),
+        _ => "",
+    };
+    let args = generator.words_to_python_list(&cmd.args);
+    let expr = match name {
+        "echo" => format!("_echo({})", args),
+        "printf" => format!("_printf({})", args),
+        "cd" => format!("_cd({})", args),
+        "pwd" => "_echo([os.getcwd()])".to_string(),
+        "true" | ":" => "_set_status(0)".to_string(),
+        "false" => "_set_status(1)".to_string(),
+        "test" => format!("_test({})", args),
+        "[" => {
+            // The parser captures everything up to the closing bracket as raw text
+            let raw: Vec<String> = cmd.args.iter().map(|arg| arg.to_string()).collect();
+            format!("_test({})", test_words_to_python(&raw.join(" ")))
+        }
+        "read" => format!("_read({})", args),
+        "exit" => match cmd.args.first() {
+            Some(code) => format!("_exit({})", generator.word_to_python(code)),
+            None => "_exit()".to_string(),
+        },
+        "wait" => "_wait()".to_string(),
+        _ => {
+            let mut argv = generator.word_to_python_args(&cmd.name);
+            let rest = args.trim_start_matches('[').trim_end_matches(']');
+            if !rest.is_empty() {
+                argv.push(rest.to_string());
+            }
+            let argv = format!("[{}]", argv.join(", "));
+            if cmd.env_vars.is_empty() {
+                format!("_run({})", argv)
+            } else {
+                // External commands also see the assignments in their environment
+                format!("_run({}, {})", argv, env_dict(generator, &cmd.env_vars))
+            }
+        }
+    };
+
+    let expr = with_assignments(generator, &cmd.env_vars, expr);
+    wrap_redirects(generator, &cmd.redirects, expr)
+}
+
+/// The parser cannot tell `a=1 cmd` from `a=1` on the line before `cmd`, so
+/// like the Perl backend we keep the assignments in the current scope.
+fn with_assignments(generator: &mut PythonGenerator, env_vars: &[Assignment], expr: String) -> String {
+    if env_vars.is_empty() {
+        expr
+    } else {
+        format!("({}, {})[-1]", assignments_expression(generator, env_vars), expr)
+    }
+}
+
+fn wrap_redirects(generator: &mut PythonGenerator, redirects: &[Redirect], expr: String) -> String {
+    if redirects.is_empty() {
+        return expr;
+    }
+    let redirects: Vec<String> = redirects.iter()
+        .map(|redirect| generator.generate_redirect(redirect))
+        .collect();
+    format!("_redirect([{}], lambda: {})", redirects.join(", "), expr)
+}
+
+fn is_assignment_only(cmd: &SimpleCommand) -> bool {
+    cmd.name == "true" && cmd.args.is_empty() && !cmd.env_vars.is_empty()
+}
+
+fn assignment_statement(generator: &mut PythonGenerator, assignment: &Assignment) -> String {
+    let value = generator.word_to_python(&assignment.value);
+    match &assignment.index {
+        Some(key) => format!("_set_index({}, {}, {})", python_string_literal(&assignment.name),
+                             super::words::raw_text_to_python(key), value),
+        None => format!("V[{}] = {}", python_string_literal(&assignment.name), value),
+    }
+}
+
+/// Each assignment is made in turn, so that `a=1 b=$a` sees the new `a`.
+fn assignments_expression(generator: &mut PythonGenerator, env_vars: &[Assignment]) -> String {
+    let mut steps: Vec<String> = env_vars.iter()
+        .map(|assignment| match assignment.index {
+            Some(_) => assignment_statement(generator, assignment),
+            None => format!("_assign({})", env_dict(generator, std::slice::from_ref(assignment))),
+        })
+        .collect();
+    if steps.len() == 1 {
+        steps.remove(0)
+    } else {
+        format!("({})[-1]", steps.join(", "))
+    }
+}
+
+fn env_dict(generator: &mut PythonGenerator, env_vars: &[Assignment]) -> String {
+    let entries: Vec<String> = env_vars.iter()
+        .filter(|assignment| assignment.index.is_none())
+        .map(|assignment| format!("{}: {}", python_string_literal(&assignment.name),
+                                  generator.word_to_python(&assignment.value)))
+        .collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+pub fn generate_builtin_command_impl(generator: &mut PythonGenerator, cmd: &BuiltinCommand) -> String {
+    let indent = generator.indent();
+    match cmd.name.as_str() {
+        "return" if generator.function_depth > 0 => {
+            let code = cmd.args.first().map(|arg| generator.word_to_python(arg)).unwrap_or_default();
+            format!("{}return _return({})\n", indent, code)
+        }
+        "break" | "continue" => format!("{}{}\n", indent, cmd.name),
+        name if !is_supported_builtin(name) => {
+            format!("{}_set_status(0)  # Builtin command '{}' not implemented\n", indent, name)
+        }
+        _ => format!("{}{}\n", indent, builtin_command_expression(generator, cmd)),
+    }
+}
+
+fn is_supported_builtin(name: &str) -> bool {
+    matches!(name,
+        "set" | "unset" | "export" | "readonly" | "declare" | "typeset" | "local" |
+        "shift" | "wait" | "shopt" | "exit" | "return" | "break" | "continue" | "exec"
+    )
+}
+
+fn builtin_command_expression(generator: &mut PythonGenerator, cmd: &BuiltinCommand) -> String {
+    let expr = match cmd.name.as_str() {
+        "export" | "local" | "declare" | "typeset" | "readonly" => declaration_expression(generator, cmd),
+        "unset" => format!("_unset({})", generator.words_to_python_list(&cmd.args)),
+        "shift" => match cmd.args.first() {
+            Some(n) => format!("_shift(_num({}))", generator.word_to_python(n)),
+            None => "_shift()".to_string(),
+        },
+        "set" => format!("_set({})", generator.words_to_python_list(&cmd.args)),
+        "exit" => match cmd.args.first() {
+            Some(code) => format!("_exit({})", generator.word_to_python(code)),
+            None => "_exit()".to_string(),
+        },
+        "return" => match cmd.args.first() {
+            Some(code) => format!("_exit({})", generator.word_to_python(code)),
+            None => "_exit()".to_string(),
+        },
+        "wait" => "_wait()".to_string(),
+        // The command takes over, and the script ends with its status
+        "exec" if !cmd.args.is_empty() => format!("_exit(_run({}))", generator.words_to_python_list(&cmd.args)),
+        _ => "_set_status(0)".to_string(),
+    };
+
+    let expr = with_assignments(generator, &cmd.env_vars, expr);
+    wrap_redirects(generator, &cmd.redirects, expr)
+}
+
+/// `export`, `local`, `declare` and friends: collect `name[=value]` operands
+/// and hand them to the matching runtime helper.
+fn declaration_expression(generator: &mut PythonGenerator, cmd: &BuiltinCommand) -> String {
+    let mut flags = String::new();
+    let mut entries = Vec::new();
+    for arg in &cmd.args {
+        match arg {
+            Word::Literal(s, _) if (s.starts_with('-') || s.starts_with('+')) && s.len() > 1 => {
+                flags.push_str(&s[1..]);
+            }
+            _ => {
+                if let Some((name, value)) = split_declaration(generator, arg) {
+                    entries.push((name, value));
+                }
+            }
+        }
+    }
+
+    let empty_value = if flags.contains('A') {
+        Some("{}".to_string())
+    } else if flags.contains('a') {
+        Some("[]".to_string())
+    } else {
+        None
+    };
+    let is_export = cmd.name == "export" || flags.contains('x');
+    let is_local = cmd.name == "local" || (generator.function_depth > 0 && cmd.name != "export" && cmd.name != "readonly");
+
+    let items: Vec<String> = entries.into_iter()
+        .filter_map(|(name, value)| {
+            let value = value.or_else(|| empty_value.clone());
+            match value {
+                Some(value) => Some(format!("{}: {}", python_string_literal(&name), value)),
+                None if is_export || is_local => Some(format!("{}: None", python_string_literal(&name))),
+                None => None,
+            }
+        })
+        .collect();
+    let dict = format!("{{{}}}", items.join(", "));
+
+    if is_export {
+        format!("_export({})", dict)
+    } else if is_local {
+        format!("_local({})", dict)
+    } else if items.is_empty() {
+        "_set_status(0)".to_string()
+    } else {
+        format!("_assign({})", dict)
+    }
+}
+
+fn split_declaration(generator: &mut PythonGenerator, arg: &Word) -> Option<(String, Option<String>)> {
+    match arg {
+        Word::Literal(s, _) => match s.split_once('=') {
+            Some((name, value)) => Some((name.to_string(), Some(super::words::raw_text_to_python(value)))),
+            None => Some((s.clone(), None)),
+        },
+        Word::StringInterpolation(interp, _) => {
+            // NAME="..." arrives as an interpolation whose first literal holds `NAME=`
+            let first = match interp.parts.first() {
+                Some(StringPart::Literal(s)) => s.clone(),
+                _ => return None,
+            };
+            let (name, rest) = first.split_once('=')?;
+            let mut parts = interp.parts.clone();
+            parts[0] = StringPart::Literal(rest.to_string());
+            let value = generator.word_to_python(&Word::StringInterpolation(StringInterpolation { parts }, Span::default()));
+            Some((name.to_string(), Some(value)))
+        }
+        Word::Concatenation(words, span) => {
+            split_declaration(generator, &Word::StringInterpolation(concatenated_parts(words), *span))
+        }
+        _ => None,
+    }
+}
+
+pub fn generate_shopt_command_impl(generator: &mut PythonGenerator, cmd: &ShoptCommand) -> String {
+    format!("{}_set_status(0)  # shopt -{} {}\n", generator.indent(),
+            if cmd.enable { "s" } else { "u" }, cmd.option)
+}
+
+pub fn generate_pipeline_impl(generator: &mut PythonGenerator, pipeline: &Pipeline) -> String {
+    let segments = split_and_or(pipeline);
+    if segments.len() == 1 {
+        return format!("{}{}\n", generator.indent(), segment_expression(generator, &segments[0].1));
+    }
+
+    // `a && b || c` runs left to right, each step guarded by the previous status
+    let mut output = String::new();
+    for (i, (operator, commands)) in segments.iter().enumerate() {
+        if i == 0 {
+            continue;
+        }
+        let test = if matches!(operator, Some(PipeOperator::Or)) { "!=" } else { "==" };
+        let subject = if i == 1 {
+            segment_expression(generator, &segments[0].1)
+        } else {
+            "_status()".to_string()
+        };
+        output.push_str(&format!("{}if {} {} 0:\n", generator.indent(), subject, test));
+        generator.indent_level += 1;
+        if commands.len() == 1 {
+            output.push_str(&generator.generate_command(&commands[0]));
+        } else {
+            let expr = segment_expression(generator, commands);
+            output.push_str(&format!("{}{}\n", generator.indent(), expr));
+        }
+        generator.indent_level -= 1;
+    }
+    output
+}
+
+fn pipeline_expression(generator: &mut PythonGenerator, pipeline: &Pipeline) -> String {
+    let segments = split_and_or(pipeline);
+    let first = segment_expression(generator, &segments[0].1);
+    if segments.len() == 1 {
+        return first;
+    }
+    let rest: Vec<String> = segments[1..].iter()
+        .map(|(operator, commands)| {
+            let op = if matches!(operator, Some(PipeOperator::Or)) { "||" } else { "&&" };
+            format!("('{}', lambda: {})", op, segment_expression(generator, commands))
+        })
+        .collect();
+    format!("_and_or(lambda: {}, [{}])", first, rest.join(", "))
+}
+
+/// Split a pipeline into `|`-connected segments joined by `&&` / `||`.
+fn split_and_or(pipeline: &Pipeline) -> Vec<(Option<PipeOperator>, Vec<Command>)> {
+    let mut segments: Vec<(Option<PipeOperator>, Vec<Command>)> = Vec::new();
+    let mut current = vec![pipeline.commands[0].clone()];
+    let mut current_op = None;
+    for (i, command) in pipeline.commands.iter().enumerate().skip(1) {
+        match pipeline.operators.get(i - 1) {
+            Some(PipeOperator::Pipe) | None => current.push(command.clone()),
+            Some(op) => {
+                segments.push((current_op.take(), std::mem::take(&mut current)));
+                current_op = Some(op.clone());
+                current.push(command.clone());
+            }
+        }
+    }
+    segments.push((current_op, current));
+    segments
+}
+
+fn segment_expression(generator: &mut PythonGenerator, commands: &[Command]) -> String {
+    if commands.len() == 1 {
+        return generator.command_expression(&commands[0]);
+    }
+    let stages: Vec<String> = commands.iter()
+        .map(|command| format!("lambda: {}", generator.command_expression(command)))
+        .collect();
+    format!("_pipe([{}])", stages.join(", "))
+}
+
+pub fn generate_subshell_impl(generator: &mut PythonGenerator, command: &Command) -> String {
+    let inner = generator.command_expression(command);
+    format!("{}_subshell(lambda: {})\n", generator.indent(), inner)
+}
+
+pub fn generate_background_impl(generator: &mut PythonGenerator, command: &Command) -> String {
+    let inner = generator.command_expression(command);
+    format!("{}_background(lambda: {})\n", generator.indent(), inner)
+}
+
+/// Render a redirect as the `(fd, operator, target)` tuple consumed by
+/// `_redirect`. A `{var}` redirect passes the variable name in place of the fd.
+pub fn generate_redirect_impl(generator: &mut PythonGenerator, redirect: &Redirect) -> String {
+    let fd = match &redirect.fd_var {
+        Some(name) => python_string_literal(name),
+        None => redirect.descriptor().to_string(),
+    };
+    let op = match &redirect.operator {
+        RedirectOperator::Input | RedirectOperator::InputOutput => "<",
+        RedirectOperator::Output => ">",
+        RedirectOperator::OutputClobber => ">|",
+        RedirectOperator::Append => ">>",
+        RedirectOperator::OutputAll => "&>",
+        RedirectOperator::AppendAll => "&>>",
+        RedirectOperator::DuplicateInput => "<&",
+        RedirectOperator::DuplicateOutput => ">&",
+        RedirectOperator::Close => return format!("({}, '-', None)", fd),
+        RedirectOperator::Heredoc | RedirectOperator::HeredocTabs => {
+            let body = match redirect.heredoc.as_ref().map(|heredoc| &heredoc.body) {
+                Some(Word::Literal(text, _)) => python_string_literal(text),
+                Some(body) => generator.word_to_python(body),
+                None => python_string_literal(""),
+            };
+            return format!("({}, '<<', {})", fd, body);
+        }
+        RedirectOperator::HereString => "<<<",
+    };
+    format!("({}, '{}', {})", fd, op, generator.word_to_python(&redirect.target))
+}
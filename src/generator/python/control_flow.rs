@@ -0,0 +1,280 @@
+use crate::ast::*;
+use super::PythonGenerator;
+use super::words::python_string_literal;
+
+pub fn generate_if_statement_impl(generator: &mut PythonGenerator, if_stmt: &IfStatement) -> String {
+    let mut output = String::new();
+    let condition = generator.command_expression(&if_stmt.condition);
+    output.push_str(&format!("{}if {} == 0:\n", generator.indent(), condition));
+    output.push_str(&branch_body(generator, &if_stmt.then_branch));
+
+    let mut else_branch = if_stmt.else_branch.as_deref();
+    while let Some(branch) = else_branch {
+        match branch {
+            Command::If(elif, _) => {
+                // Conditions of an elif may hoist helpers, which must be defined
+                // before the whole if statement rather than between its clauses
+                let condition = generator.command_expression(&elif.condition);
+                output.push_str(&format!("{}elif {} == 0:\n", generator.indent(), condition));
+                output.push_str(&branch_body(generator, &elif.then_branch));
+                else_branch = elif.else_branch.as_deref();
+            }
+            other => {
+                output.push_str(&format!("{}else:\n", generator.indent()));
+                output.push_str(&branch_body(generator, other));
+                else_branch = None;
+            }
+        }
+    }
+    output
+}
+
+pub fn generate_case_statement_impl(generator: &mut PythonGenerator, case_stmt: &CaseStatement) -> String {
+    let mut output = String::new();
+    let subject = generator.next_helper_name("case");
+    let word = generator.word_to_python(&case_stmt.word);
+    output.push_str(&format!("{}{} = {}\n", generator.indent(), subject, word));
+
+    for (i, clause) in case_stmt.cases.iter().enumerate() {
+        let is_default = clause.patterns.len() == 1 && clause.patterns[0] == "*";
+        if is_default && i > 0 {
+            output.push_str(&format!("{}else:\n", generator.indent()));
+        } else {
+            let tests: Vec<String> = clause.patterns.iter()
+                .map(|pattern| format!("_match({}, {})", subject, case_pattern_to_python(generator, pattern)))
+                .collect();
+            let keyword = if i == 0 { "if" } else { "elif" };
+            output.push_str(&format!("{}{} {}:\n", generator.indent(), keyword, tests.join(" or ")));
+        }
+        generator.indent_level += 1;
+        output.push_str(&body_or_pass(generator, &clause.body));
+        generator.indent_level -= 1;
+        if is_default {
+            break;
+        }
+    }
+    output
+}
+
+fn case_pattern_to_python(generator: &mut PythonGenerator, pattern: &Word) -> String {
+    match pattern {
+        // Quoted parts of a pattern match literally
+        Word::StringInterpolation(..) => format!("_glob_escape({})", generator.word_to_python(pattern)),
+        Word::Literal(s, _) if s.starts_with('\'') && s.ends_with('\'') && s.len() >= 2 => {
+            format!("_glob_escape({})", python_string_literal(&s[1..s.len() - 1]))
+        }
+        _ => generator.word_to_python(pattern),
+    }
+}
+
+pub fn generate_while_loop_impl(generator: &mut PythonGenerator, while_loop: &WhileLoop) -> String {
+    let mut output = String::new();
+    let condition = generator.command_expression(&while_loop.condition);
+    let test = if while_loop.until { "!=" } else { "==" };
+    output.push_str(&format!("{}while {} {} 0:\n", generator.indent(), condition, test));
+    generator.indent_level += 1;
+    output.push_str(&body_or_pass(generator, &while_loop.body.commands));
+    generator.indent_level -= 1;
+    output
+}
+
+pub fn generate_for_loop_impl(generator: &mut PythonGenerator, for_loop: &ForLoop) -> String {
+    let mut output = String::new();
+    let items = if for_loop.items.is_empty() {
+        // `for x; do` iterates over the positional parameters
+        "list(_ARGS[-1])".to_string()
+    } else {
+        generator.words_to_python_list(&for_loop.items)
+    };
+    output.push_str(&format!("{}for V[{}] in {}:\n", generator.indent(),
+                             python_string_literal(&for_loop.variable), items));
+    generator.indent_level += 1;
+    output.push_str(&body_or_pass(generator, &for_loop.body.commands));
+    generator.indent_level -= 1;
+    output
+}
+
+pub fn generate_arithmetic_for_impl(generator: &mut PythonGenerator, arith_for: &ArithmeticFor) -> String {
+    let mut output = String::new();
+    let indent = generator.indent();
+    let first = generator.next_helper_name("first");
+    for statement in arithmetic_statements(generator, &arith_for.init.expression) {
+        output.push_str(&format!("{}{}\n", indent, statement));
+    }
+    output.push_str(&format!("{}{} = True\n", indent, first));
+    output.push_str(&format!("{}while True:\n", indent));
+    // The step runs before every test but the first, so `continue` still steps
+    let step = arithmetic_statements(generator, &arith_for.step.expression);
+    if !step.is_empty() {
+        output.push_str(&format!("{}    if not {}:\n", indent, first));
+        for statement in step {
+            output.push_str(&format!("{}        {}\n", indent, statement));
+        }
+    }
+    output.push_str(&format!("{}    {} = False\n", indent, first));
+    if !arith_for.condition.expression.is_empty() {
+        let condition = generator.convert_arithmetic_to_python(&arith_for.condition.expression);
+        output.push_str(&format!("{}    if not {}:\n{}        break\n", indent, condition, indent));
+    }
+    generator.indent_level += 1;
+    output.push_str(&body_or_pass(generator, &arith_for.body.commands));
+    generator.indent_level -= 1;
+    output
+}
+
+/// Python statements for a comma-separated list of arithmetic expressions,
+/// turning assignments such as `i=0`, `i+=2` and `i++` into variable updates.
+pub fn arithmetic_statements(generator: &mut PythonGenerator, expr: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut parts = Vec::new();
+    for (i, c) in expr.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&expr[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+
+    for part in parts.into_iter().map(str::trim).filter(|part| !part.is_empty()) {
+        let name_end = |text: &str| text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(text.len());
+        let update = if let Some(name) = part.strip_prefix("++").or_else(|| part.strip_suffix("++")) {
+            Some((name.trim().to_string(), format!("{} + 1", name.trim())))
+        } else if let Some(name) = part.strip_prefix("--").or_else(|| part.strip_suffix("--")) {
+            Some((name.trim().to_string(), format!("{} - 1", name.trim())))
+        } else {
+            let end = name_end(part);
+            let (name, rest) = part.split_at(end);
+            let rest = rest.trim_start();
+            let op_end = rest.find('=').filter(|_| !rest.starts_with("=="));
+            match op_end {
+                Some(n) if !name.is_empty() && ["", "+", "-", "*", "/", "%", "<<", ">>", "&", "|", "^"].contains(&&rest[..n]) => {
+                    let value = rest[n + 1..].trim();
+                    if n == 0 {
+                        Some((name.to_string(), value.to_string()))
+                    } else {
+                        Some((name.to_string(), format!("{} {} ({})", name, &rest[..n], value)))
+                    }
+                }
+                _ => None,
+            }
+        };
+        match update {
+            Some((name, value)) if !name.is_empty() && name_end(&name) == name.len() => {
+                let value = generator.convert_arithmetic_to_python(&value);
+                statements.push(format!("V[{}] = str({})", python_string_literal(&name), value));
+            }
+            _ => statements.push(generator.convert_arithmetic_to_python(part)),
+        }
+    }
+    statements
+}
+
+pub fn generate_select_loop_impl(generator: &mut PythonGenerator, select_loop: &SelectLoop) -> String {
+    let mut output = String::new();
+    let items = if select_loop.items.is_empty() {
+        "list(_ARGS[-1])".to_string()
+    } else {
+        generator.words_to_python_list(&select_loop.items)
+    };
+    // `_select` prompts and sets the variable and REPLY before each pass
+    output.push_str(&format!("{}for _ in _select({}, {}):\n", generator.indent(),
+                             python_string_literal(&select_loop.variable), items));
+    generator.indent_level += 1;
+    output.push_str(&body_or_pass(generator, &select_loop.body.commands));
+    generator.indent_level -= 1;
+    output
+}
+
+pub fn generate_function_impl(generator: &mut PythonGenerator, func: &Function) -> String {
+    let mut output = String::new();
+    generator.declared_functions.insert(func.name.clone());
+
+    let indent = generator.indent();
+    output.push_str(&format!("{}@_function({})\n", indent, python_string_literal(&func.name)));
+    output.push_str(&format!("{}def {}(*args):\n", indent, function_identifier(&func.name)));
+    output.push_str(&format!("{}    with _frame(args):\n", indent));
+
+    generator.indent_level += 2;
+    generator.function_depth += 1;
+    output.push_str(&body_or_pass(generator, &func.body.commands));
+    generator.function_depth -= 1;
+    generator.indent_level -= 2;
+
+    output.push_str(&format!("{}    return _status()\n", indent));
+    output
+}
+
+/// Shell function names may contain characters that are not valid in Python identifiers.
+fn function_identifier(name: &str) -> String {
+    let sanitized: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("fn_{}", sanitized)
+}
+
+pub fn generate_block_impl(generator: &mut PythonGenerator, block: &Block) -> String {
+    generator.generate_block_body(&block.commands)
+}
+
+pub fn generate_block_body_impl(generator: &mut PythonGenerator, commands: &[Command]) -> String {
+    let mut output = String::new();
+    for command in commands {
+        output.push_str(&generator.generate_command(command));
+    }
+    output
+}
+
+/// Generate a suite at the current indent, falling back to `pass` so Python
+/// never sees an empty block.
+fn body_or_pass(generator: &mut PythonGenerator, commands: &[Command]) -> String {
+    let body = generator.generate_block_body(commands);
+    if body.trim().is_empty() {
+        format!("{}pass\n", generator.indent())
+    } else {
+        body
+    }
+}
+
+fn branch_body(generator: &mut PythonGenerator, branch: &Command) -> String {
+    generator.indent_level += 1;
+    let body = match branch {
+        Command::Block(block, _) => body_or_pass(generator, &block.commands),
+        other => body_or_pass(generator, std::slice::from_ref(other)),
+    };
+    generator.indent_level -= 1;
+    body
+}
+
+pub fn generate_break_statement_impl(generator: &mut PythonGenerator, level: &Option<String>) -> String {
+    loop_control(generator, "break", level)
+}
+
+pub fn generate_continue_statement_impl(generator: &mut PythonGenerator, level: &Option<String>) -> String {
+    loop_control(generator, "continue", level)
+}
+
+fn loop_control(generator: &mut PythonGenerator, keyword: &str, level: &Option<String>) -> String {
+    match level.as_deref() {
+        Some(n) if n != "1" => {
+            // Python has no multi-level break; only the innermost loop is affected
+            format!("{}{}  # {} {} is not supported\n", generator.indent(), keyword, keyword, n)
+        }
+        _ => format!("{}{}\n", generator.indent(), keyword),
+    }
+}
+
+pub fn generate_return_statement_impl(generator: &mut PythonGenerator, value: &Option<Word>) -> String {
+    let code = value.as_ref().map(|v| generator.word_to_python(v)).unwrap_or_default();
+    if generator.function_depth > 0 {
+        format!("{}return _return({})\n", generator.indent(), code)
+    } else {
+        format!("{}_exit({})\n", generator.indent(), code)
+    }
+}
@@ -0,0 +1,332 @@
+use crate::ast::*;
+use crate::generator::Backend;
+use std::collections::HashSet;
+
+pub mod commands;
+pub mod control_flow;
+pub mod words;
+pub mod test_expressions;
+
+/// Runtime helpers emitted at the top of every generated Python script.
+pub const PRELUDE: &str = include_str!("prelude.py");
+
+pub struct PythonGenerator {
+    pub indent_level: usize,
+    pub declared_functions: HashSet<String>,
+    pub function_depth: usize,
+    pub helper_counter: usize,
+    /// Helper `def`s that must be emitted before the statement currently being generated.
+    pub pending_defs: Vec<String>,
+    /// Whether the statement being generated starts process substitutions.
+    pub pending_process_subs: bool,
+}
+
+impl Default for PythonGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PythonGenerator {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            declared_functions: HashSet::new(),
+            function_depth: 0,
+            helper_counter: 0,
+            pending_defs: Vec::new(),
+            pending_process_subs: false,
+        }
+    }
+
+    pub fn generate(&mut self, ast: &[Command]) -> String {
+        let mut output = String::new();
+
+        // Add Python shebang and the shell runtime helpers
+        output.push_str("#!/usr/bin/env python3\n");
+        output.push_str(PRELUDE);
+        output.push_str("\n\n");
+
+        for command in ast {
+            self.indent_level = 0;
+            let command_output = self.generate_command(command);
+            output.push_str(&command_output);
+
+            if !command_output.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+
+        // A shell script exits with the status of its last command
+        output.push_str("\n_exit()\n");
+        output
+    }
+
+    pub fn generate_command(&mut self, command: &Command) -> String {
+        // Helpers hoisted while generating this command are emitted in front of it
+        let outer_defs = std::mem::take(&mut self.pending_defs);
+        let outer_subs = std::mem::take(&mut self.pending_process_subs);
+        let body = commands::generate_command_impl(self, command);
+        let hoisted = std::mem::replace(&mut self.pending_defs, outer_defs);
+        let mut output = hoisted.concat();
+        if std::mem::replace(&mut self.pending_process_subs, outer_subs) {
+            // Process substitutions finish once the statement using them has run
+            let indent = self.indent();
+            output.push_str(&format!("{}_PROCSUBS.append([])\n{}{}_finish_process_subs()\n", indent, body, indent));
+        } else {
+            output.push_str(&body);
+        }
+        output
+    }
+
+    // Delegate to submodules
+    pub fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        commands::generate_simple_command_impl(self, cmd)
+    }
+
+    pub fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        commands::generate_builtin_command_impl(self, cmd)
+    }
+
+    pub fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String {
+        commands::generate_shopt_command_impl(self, cmd)
+    }
+
+    pub fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        commands::generate_pipeline_impl(self, pipeline)
+    }
+
+    pub fn generate_subshell(&mut self, command: &Command) -> String {
+        commands::generate_subshell_impl(self, command)
+    }
+
+    pub fn generate_background(&mut self, command: &Command) -> String {
+        commands::generate_background_impl(self, command)
+    }
+
+    pub fn generate_redirect(&mut self, redirect: &Redirect) -> String {
+        commands::generate_redirect_impl(self, redirect)
+    }
+
+    pub fn command_expression(&mut self, command: &Command) -> String {
+        commands::command_expression_impl(self, command)
+    }
+
+    pub fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        control_flow::generate_if_statement_impl(self, if_stmt)
+    }
+
+    pub fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        control_flow::generate_case_statement_impl(self, case_stmt)
+    }
+
+    pub fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        control_flow::generate_while_loop_impl(self, while_loop)
+    }
+
+    pub fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        control_flow::generate_for_loop_impl(self, for_loop)
+    }
+
+    pub fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        control_flow::generate_select_loop_impl(self, select_loop)
+    }
+
+    pub fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        control_flow::generate_arithmetic_for_impl(self, arith_for)
+    }
+
+    pub fn generate_function(&mut self, func: &Function) -> String {
+        control_flow::generate_function_impl(self, func)
+    }
+
+    pub fn generate_block(&mut self, block: &Block) -> String {
+        control_flow::generate_block_impl(self, block)
+    }
+
+    pub fn generate_block_body(&mut self, commands: &[Command]) -> String {
+        control_flow::generate_block_body_impl(self, commands)
+    }
+
+    pub fn generate_break_statement(&mut self, level: &Option<String>) -> String {
+        control_flow::generate_break_statement_impl(self, level)
+    }
+
+    pub fn generate_continue_statement(&mut self, level: &Option<String>) -> String {
+        control_flow::generate_continue_statement_impl(self, level)
+    }
+
+    pub fn generate_return_statement(&mut self, value: &Option<Word>) -> String {
+        control_flow::generate_return_statement_impl(self, value)
+    }
+
+    pub fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        test_expressions::generate_test_expression_impl(self, test_expr)
+    }
+
+    pub fn test_expression_call(&mut self, test_expr: &TestExpression) -> String {
+        test_expressions::test_expression_call_impl(self, test_expr)
+    }
+
+    pub fn word_to_python(&mut self, word: &Word) -> String {
+        words::word_to_python_impl(self, word)
+    }
+
+    pub fn word_to_python_args(&mut self, word: &Word) -> Vec<String> {
+        words::word_to_python_args_impl(self, word)
+    }
+
+    pub fn words_to_python_list(&mut self, words: &[Word]) -> String {
+        words::words_to_python_list_impl(self, words)
+    }
+
+    pub fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String {
+        words::generate_parameter_expansion_impl(self, pe)
+    }
+
+    pub fn convert_arithmetic_to_python(&self, expr: &str) -> String {
+        words::convert_arithmetic_to_python_impl(self, expr)
+    }
+
+    pub fn python_string_literal(&self, s: &str) -> String {
+        words::python_string_literal(s)
+    }
+
+    pub fn indent(&self) -> String {
+        "    ".repeat(self.indent_level)
+    }
+
+    pub fn next_helper_name(&mut self, prefix: &str) -> String {
+        self.helper_counter += 1;
+        format!("_{}_{}", prefix, self.helper_counter)
+    }
+}
+
+impl Backend for PythonGenerator {
+    fn generate(&mut self, ast: &[Command]) -> String {
+        PythonGenerator::generate(self, ast)
+    }
+
+    fn generate_command(&mut self, command: &Command) -> String {
+        PythonGenerator::generate_command(self, command)
+    }
+
+    fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        PythonGenerator::generate_simple_command(self, cmd)
+    }
+
+    fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        PythonGenerator::generate_builtin_command(self, cmd)
+    }
+
+    fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String {
+        PythonGenerator::generate_shopt_command(self, cmd)
+    }
+
+    fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        PythonGenerator::generate_pipeline(self, pipeline)
+    }
+
+    fn generate_subshell(&mut self, command: &Command) -> String {
+        PythonGenerator::generate_subshell(self, command)
+    }
+
+    fn generate_background(&mut self, command: &Command) -> String {
+        PythonGenerator::generate_background(self, command)
+    }
+
+    fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        PythonGenerator::generate_if_statement(self, if_stmt)
+    }
+
+    fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        PythonGenerator::generate_case_statement(self, case_stmt)
+    }
+
+    fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        PythonGenerator::generate_while_loop(self, while_loop)
+    }
+
+    fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        PythonGenerator::generate_for_loop(self, for_loop)
+    }
+
+    fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        PythonGenerator::generate_select_loop(self, select_loop)
+    }
+
+    fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        PythonGenerator::generate_arithmetic_for(self, arith_for)
+    }
+
+    fn generate_function(&mut self, func: &Function) -> String {
+        PythonGenerator::generate_function(self, func)
+    }
+
+    fn generate_block(&mut self, block: &Block) -> String {
+        PythonGenerator::generate_block(self, block)
+    }
+
+    fn generate_break_statement(&mut self, level: &Option<String>) -> String {
+        PythonGenerator::generate_break_statement(self, level)
+    }
+
+    fn generate_continue_statement(&mut self, level: &Option<String>) -> String {
+        PythonGenerator::generate_continue_statement(self, level)
+    }
+
+    fn generate_return_statement(&mut self, value: &Option<Word>) -> String {
+        PythonGenerator::generate_return_statement(self, value)
+    }
+
+    fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        PythonGenerator::generate_test_expression(self, test_expr)
+    }
+
+    fn generate_redirect(&mut self, redirect: &Redirect) -> String {
+        PythonGenerator::generate_redirect(self, redirect)
+    }
+
+    fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String {
+        PythonGenerator::generate_parameter_expansion(self, pe)
+    }
+
+    fn word_to_code(&mut self, word: &Word) -> String {
+        self.word_to_python(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_python;
+
+    #[test]
+    fn test_arithmetic_matches_bash() {
+        let script = "x=5\necho $(( -7 / 2 )) $(( -7 % 3 )) $(( 1 << 62 << 1 ))\necho $(( x += 3 )) $(( x %= 7 )) $(( x++ )) $x";
+        assert_eq!(run_python(script, ""), ("-3 -1 -9223372036854775808\n8 1 1 2\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_arithmetic_condition() {
+        let script = "v=17\nif (( v % 2 == 0 )); then echo even; else echo odd; fi\nwhile (( v > 15 )); do v=$(( v - 1 )); done\necho $v";
+        assert_eq!(run_python(script, ""), ("odd\n15\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_sparse_array_order() {
+        let script = "arr[2]=z\narr[0]=q\necho \"${arr[@]}\" ${!arr[@]} ${#arr[@]}";
+        assert_eq!(run_python(script, ""), ("q z 0 2 2\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_quoted_regex_matches_literally() {
+        let script = "[[ axb =~ \"a.b\" ]] && echo quoted\n[[ axb =~ a.b ]] && echo pattern\n[[ ab12 =~ ([a-z]+)([0-9]+) ]] && echo \"${BASH_REMATCH[2]}\"";
+        assert_eq!(run_python(script, ""), ("pattern\n12\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_adjacent_parts_form_one_word() {
+        let script = "f() { local v=\"$1\"; echo \"$v\"; }\nf one\nx=2\nexport V=$x\necho a=$x --opt=$x \"$x\"/y\nsh -c 'echo $V'";
+        assert_eq!(run_python(script, ""), ("one\na=2 --opt=2 2/y\n2\n".to_string(), 0));
+    }
+}
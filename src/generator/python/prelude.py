@@ -0,0 +1,985 @@
+import fnmatch
+import glob as _globmod
+import io
+import os
+import re
+import subprocess
+import sys
+import tempfile
+import threading
+from contextlib import contextmanager
+
+# Shell state: variables, positional parameters and the I/O stacks used to
+# emulate redirections and pipelines.
+V = {'?': '0'}
+_ARGS = [sys.argv[1:]]
+_LOCALS = []
+_FUNCS = {}
+_EXPORTED = set()
+_OPTS = set()
+_IN = [None]
+_OUT = [sys.stdout]
+_ERR = [sys.stderr]
+_FDS = {}
+_JOBS = []
+_PROCSUBS = [[]]
+
+
+def _set_status(code):
+    V['?'] = str(code)
+    return code
+
+
+def _status():
+    return int(V.get('?', '0'))
+
+
+def _num(value):
+    value = str(value).strip()
+    try:
+        return int(value, 0) if value[:2].lower() in ('0x', '0o', '0b') else int(value or '0')
+    except ValueError:
+        return 0
+
+
+def _wrap(value):
+    # Arithmetic is on 64-bit integers, which wrap around
+    return (value + 2 ** 63) % 2 ** 64 - 2 ** 63
+
+
+def _div(a, b):
+    if b == 0:
+        _ERR[-1].write('division by 0\n')
+        _exit(1)
+    # Division truncates toward zero
+    quotient = abs(a) // abs(b)
+    return _wrap(quotient if (a < 0) == (b < 0) else -quotient)
+
+
+def _mod(a, b):
+    if b == 0:
+        _ERR[-1].write('division by 0\n')
+        _exit(1)
+    # The remainder has the sign of the dividend
+    remainder = abs(a) % abs(b)
+    return -remainder if a < 0 else remainder
+
+
+def _var(name):
+    value = V.get(name)
+    if value is None:
+        value = os.environ.get(name, '')
+    if isinstance(value, list):
+        return value[0] if value else ''
+    if isinstance(value, dict):
+        return value.get('0', '')
+    return value
+
+
+def _arg(n):
+    if n == 0:
+        return sys.argv[0]
+    args = _ARGS[-1]
+    return args[n - 1] if n <= len(args) else ''
+
+
+def _split(value):
+    ifs = V.get('IFS', ' \t\n')
+    if ifs == ' \t\n':
+        return value.split()
+    return [part for part in re.split('[' + re.escape(ifs) + ']', value) if part]
+
+
+def _glob(pattern):
+    matches = sorted(_globmod.glob(pattern))
+    return matches if matches else [pattern]
+
+
+def _ordered_keys(value):
+    # A dict with only numeric keys is a sparse indexed array, kept in the
+    # order of its indexes rather than the order they were set in
+    if all(key.lstrip('-').isdigit() for key in value):
+        return sorted(value, key=int)
+    return list(value)
+
+
+def _elements(name):
+    value = V.get(name, '')
+    if isinstance(value, list):
+        return value
+    if isinstance(value, dict):
+        return [value[key] for key in _ordered_keys(value)]
+    return [value] if value != '' else []
+
+
+def _index(name, key):
+    value = V.get(name, '')
+    if isinstance(value, dict):
+        return value.get(str(key), '')
+    if isinstance(value, list):
+        try:
+            return value[_num(key)]
+        except IndexError:
+            return ''
+    return value if str(key) == '0' else ''
+
+
+def _set_index(name, key, value):
+    current = V.get(name)
+    if isinstance(current, list) and str(key).lstrip('-').isdigit():
+        idx = int(key)
+        while len(current) <= idx:
+            current.append('')
+        current[idx] = value
+    else:
+        if not isinstance(current, dict):
+            current = {} if current in (None, '') else {'0': current}
+            V[name] = current
+        current[str(key)] = value
+    return _set_status(0)
+
+
+def _keys(name):
+    value = V.get(name, '')
+    if isinstance(value, dict):
+        return _ordered_keys(value)
+    if isinstance(value, list):
+        return [str(i) for i in range(len(value))]
+    return ['0'] if value != '' else []
+
+
+def _assign(values):
+    for name, value in values.items():
+        V[name] = value
+        if name in _EXPORTED:
+            os.environ[name] = value if isinstance(value, str) else ''
+    return _set_status(0)
+
+
+def _glob_escape(text):
+    return re.sub(r'([*?\[])', r'[\1]', text)
+
+
+def _match(text, pattern):
+    return fnmatch.fnmatchcase(text, pattern)
+
+
+def _pattern_regex(pattern, greedy):
+    out = []
+    i = 0
+    while i < len(pattern):
+        c = pattern[i]
+        if c == '*':
+            out.append('.*' if greedy else '.*?')
+        elif c == '?':
+            out.append('.')
+        elif c == '[':
+            end = pattern.find(']', i + 2)
+            if end == -1:
+                out.append(re.escape(c))
+            else:
+                body = pattern[i + 1:end]
+                if body.startswith('!'):
+                    body = '^' + body[1:]
+                out.append('[' + body.replace('\\', '\\\\') + ']')
+                i = end
+        elif c == '\\' and i + 1 < len(pattern):
+            i += 1
+            out.append(re.escape(pattern[i]))
+        else:
+            out.append(re.escape(c))
+        i += 1
+    return ''.join(out)
+
+
+def _trim(value, pattern, prefix, longest):
+    candidates = range(len(value) + 1)
+    if prefix:
+        order = reversed(candidates) if longest else candidates
+        for n in order:
+            if _match(value[:n], pattern):
+                return value[n:]
+    else:
+        order = candidates if longest else reversed(candidates)
+        for n in order:
+            if _match(value[n:], pattern):
+                return value[:n]
+    return value
+
+
+def _substitute(value, pattern, replacement, every):
+    regex = re.compile(_pattern_regex(pattern, True))
+    return regex.sub(lambda _m: replacement, value, count=0 if every else 1)
+
+
+def _substitute_anchored(value, pattern, replacement, prefix):
+    if prefix:
+        for n in reversed(range(len(value) + 1)):
+            if _match(value[:n], pattern):
+                return replacement + value[n:]
+    else:
+        for n in range(len(value) + 1):
+            if _match(value[n:], pattern):
+                return value[:n] + replacement
+    return value
+
+
+def _ucfirst(value):
+    return value[:1].upper() + value[1:]
+
+
+def _lcfirst(value):
+    return value[:1].lower() + value[1:]
+
+
+def _swapcase_first(value):
+    return value[:1].swapcase() + value[1:]
+
+
+def _single_quote(value):
+    return "'" + value.replace("'", "'\\''") + "'"
+
+
+def _is_set(name):
+    if name.isdigit():
+        return int(name) <= len(_ARGS[-1])
+    if name in ('@', '*'):
+        return len(_ARGS[-1]) > 0
+    if name in ('#', '?', '$', '-'):
+        return True
+    base, bracket, key = name.partition('[')
+    if bracket:
+        value = V.get(base)
+        key = key[:-1]
+        if isinstance(value, dict):
+            return key in value
+        if isinstance(value, list):
+            return -len(value) <= _num(key) < len(value)
+        return value is not None and _num(key) == 0
+    return name in V or name in os.environ
+
+
+def _store(name, value):
+    base, bracket, key = name.partition('[')
+    if bracket:
+        _set_index(base, key[:-1], value)
+    else:
+        _assign({name: value})
+    return value
+
+
+def _parameter_error(name, message, default):
+    _ERR[-1].write('%s: %s\n' % (name, message or default))
+    _exit(1)
+
+
+def _substring(value, offset, length=None):
+    if offset < 0:
+        offset += len(value)
+        if offset < 0:
+            return ''
+    if length is None:
+        return value[offset:]
+    end = offset + length if length >= 0 else len(value) + length
+    return value[offset:end]
+
+
+def _positional_slice(offset, length=None):
+    items = [sys.argv[0]] + _ARGS[-1]
+    if offset < 0:
+        offset += len(items)
+        if offset < 0:
+            return []
+    if length is None:
+        return items[offset:]
+    end = offset + length if length >= 0 else len(items) + length
+    return items[offset:end]
+
+
+def _prefix_names(prefix):
+    return sorted(name for name in set(V) | set(os.environ) if name.startswith(prefix))
+
+
+def _environ(extra=None):
+    env = dict(os.environ)
+    for name in _EXPORTED:
+        value = V.get(name)
+        if isinstance(value, str):
+            env[name] = value
+    if extra:
+        env.update(extra)
+    return env
+
+
+def _write(text):
+    _OUT[-1].write(text)
+
+
+def _flush():
+    streams = [sys.stdout, sys.stderr] + _OUT + _ERR + [value for kind, value in _FDS.values() if kind == '>']
+    for stream in streams:
+        try:
+            stream.flush()
+        except ValueError:
+            pass
+
+
+def _run(argv, env=None):
+    if not argv:
+        return _set_status(0)
+    if argv[0] in _FUNCS:
+        return _FUNCS[argv[0]](*argv[1:])
+    out, err, data = _OUT[-1], _ERR[-1], _IN[-1]
+    _flush()
+    try:
+        proc = subprocess.run(
+            argv,
+            input=data,
+            stdout=None if out is sys.stdout else subprocess.PIPE,
+            stderr=None if err is sys.stderr else subprocess.PIPE,
+            env=_environ(env),
+            text=True,
+        )
+    except FileNotFoundError:
+        err.write('%s: command not found\n' % argv[0])
+        return _set_status(127)
+    except PermissionError:
+        err.write('%s: Permission denied\n' % argv[0])
+        return _set_status(126)
+    if proc.stdout is not None:
+        out.write(proc.stdout)
+    if proc.stderr is not None:
+        err.write(proc.stderr)
+    code = proc.returncode if proc.returncode >= 0 else 128 - proc.returncode
+    return _set_status(code)
+
+
+def _capture(fn):
+    buf = io.StringIO()
+    _OUT.append(buf)
+    try:
+        fn()
+    except SystemExit as e:
+        _set_status(e.code if isinstance(e.code, int) else 1)
+    finally:
+        _OUT.pop()
+    return buf.getvalue().rstrip('\n')
+
+
+def _pipe(stages):
+    data = _IN[-1]
+    statuses = []
+    for n, stage in enumerate(stages):
+        last = n == len(stages) - 1
+        buf = None if last else io.StringIO()
+        _IN.append(data)
+        if buf is not None:
+            _OUT.append(buf)
+        try:
+            statuses.append(stage())
+        except SystemExit as e:
+            statuses.append(_set_status(e.code if isinstance(e.code, int) else 1))
+        finally:
+            _IN.pop()
+            if buf is not None:
+                _OUT.pop()
+        data = buf.getvalue() if buf is not None else None
+    if 'pipefail' in _OPTS:
+        failed = [s for s in statuses if s != 0]
+        return _set_status(failed[-1] if failed else 0)
+    return _set_status(statuses[-1])
+
+
+def _and_or(first, rest):
+    code = first()
+    for op, fn in rest:
+        if (op == '&&' and code == 0) or (op == '||' and code != 0):
+            code = fn()
+    return _set_status(code)
+
+
+def _not(code):
+    return _set_status(0 if code else 1)
+
+
+def _subshell(fn):
+    saved_vars = {k: (list(v) if isinstance(v, list) else dict(v) if isinstance(v, dict) else v)
+                  for k, v in V.items()}
+    saved_cwd = os.getcwd()
+    saved_env = dict(os.environ)
+    try:
+        code = fn()
+    except SystemExit as e:
+        code = e.code if isinstance(e.code, int) else 1
+    finally:
+        V.clear()
+        V.update(saved_vars)
+        os.chdir(saved_cwd)
+        os.environ.clear()
+        os.environ.update(saved_env)
+    return _set_status(code if code is not None else 0)
+
+
+def _process_sub_in(fn):
+    """<(cmd): run cmd in a subshell and stand in a file holding its output."""
+    buf = io.StringIO()
+    _OUT.append(buf)
+    try:
+        _subshell(fn)
+    finally:
+        _OUT.pop()
+    with tempfile.NamedTemporaryFile('w', delete=False) as handle:
+        handle.write(buf.getvalue())
+    _PROCSUBS[-1].append(lambda: os.unlink(handle.name))
+    return handle.name
+
+
+def _process_sub_out(fn):
+    """>(cmd): stand in a file, and run cmd in a subshell on what was
+    written to it once the command using it is done."""
+    with tempfile.NamedTemporaryFile('w', delete=False) as handle:
+        pass
+
+    def finish():
+        _flush()
+        with open(handle.name) as written:
+            _IN.append(written.read())
+        os.unlink(handle.name)
+        try:
+            _subshell(fn)
+        finally:
+            _IN.pop()
+    _PROCSUBS[-1].append(finish)
+    return handle.name
+
+
+def _finish_process_subs():
+    status = _status()
+    for finish in _PROCSUBS.pop():
+        finish()
+    return _set_status(status)
+
+
+def _background(fn):
+    job = threading.Thread(target=fn)
+    _JOBS.append(job)
+    job.start()
+    V['!'] = str(job.ident)
+    return _set_status(0)
+
+
+def _wait(args=()):
+    for job in list(_JOBS):
+        job.join()
+        _JOBS.remove(job)
+    return _set_status(0)
+
+
+def _open_target(target, mode, force=False):
+    if target == '/dev/stdout':
+        return _OUT[-1], False
+    if target == '/dev/stderr':
+        return _ERR[-1], False
+    if mode == 'w' and not force and ('C' in _OPTS or 'noclobber' in _OPTS) and os.path.isfile(target):
+        raise OSError('%s: cannot overwrite existing file' % target)
+    return open(target, mode), True
+
+
+def _output_fd(fd):
+    if fd == 1:
+        return _OUT[-1]
+    if fd == 2:
+        return _ERR[-1]
+    entry = _FDS.get(fd)
+    return entry[1] if entry and entry[0] == '>' else None
+
+
+def _redirect(redirects, fn, persist=False):
+    """Run fn with the redirects applied, then put every descriptor back.
+
+    Descriptors 0-2 are the _IN/_OUT/_ERR stacks and higher ones live in
+    _FDS. Input descriptors hold a one-item list so that reads through a
+    duplicate consume the same data. A {var} redirect allocates a descriptor
+    from 10 up that outlives the command, as in bash. With persist, as for
+    exec, no descriptor is put back.
+    """
+    opened = []
+    undo = []
+
+    def set_fd(fd, kind, value, keep):
+        if fd == 0 and kind == '<':
+            _IN.append(value[0])
+            if not keep:
+                undo.append(lambda: value.__setitem__(0, _IN.pop()))
+        elif fd in (1, 2) and kind == '>':
+            stack = _OUT if fd == 1 else _ERR
+            stack.append(value)
+            if not keep:
+                undo.append(stack.pop)
+        else:
+            old = _FDS.get(fd)
+            _FDS[fd] = (kind, value)
+            if not keep:
+                undo.append(lambda: _FDS.__setitem__(fd, old) if old else _FDS.pop(fd, None))
+
+    try:
+        try:
+            for fd, op, target in redirects:
+                keep = persist or isinstance(fd, str)
+                if isinstance(fd, str):
+                    name, fd = fd, max([9] + list(_FDS)) + 1
+                    V[name] = str(fd)
+                if op in ('<', '<<', '<<<'):
+                    if op == '<':
+                        with open(target) as handle:
+                            data = handle.read()
+                    elif op == '<<<':
+                        data = target + '\n'
+                    else:
+                        data = target
+                    set_fd(fd, '<', [data], keep)
+                elif op in ('>', '>|', '>>', '&>', '&>>'):
+                    mode = 'a' if op.endswith('>>') else 'w'
+                    stream, close = _open_target(target, mode, op == '>|')
+                    if close and not keep:
+                        opened.append(stream)
+                    set_fd(fd, '>', stream, keep)
+                    if op.startswith('&'):
+                        set_fd(2, '>', stream, keep)
+                elif op == '-':
+                    if fd == 0:
+                        set_fd(0, '<', [''], keep)
+                    elif fd in (1, 2):
+                        stream = open(os.devnull, 'w')
+                        if not keep:
+                            opened.append(stream)
+                        set_fd(fd, '>', stream, keep)
+                    else:
+                        old = _FDS.pop(fd, None)
+                        if old and not keep:
+                            undo.append(lambda fd=fd, old=old: _FDS.__setitem__(fd, old))
+                else:
+                    source = int(target.rstrip('-')) if target.rstrip('-').isdigit() else -1
+                    if op == '>&':
+                        stream = _output_fd(source)
+                        if stream is None:
+                            raise OSError('%s: Bad file descriptor' % target)
+                        set_fd(fd, '>', stream, keep)
+                    else:
+                        if source == 0:
+                            holder = [_IN[-1]]
+                        elif _FDS.get(source, ('',))[0] == '<':
+                            holder = _FDS[source][1]
+                        else:
+                            raise OSError('%s: Bad file descriptor' % target)
+                        set_fd(fd, '<', holder, keep)
+                    if target.endswith('-') and source > 2 and source in _FDS:
+                        old = _FDS.pop(source)
+                        if not keep:
+                            undo.append(lambda source=source, old=old: _FDS.__setitem__(source, old))
+        except OSError as e:
+            _ERR[-1].write('%s: %s\n' % (e.filename, e.strerror) if e.strerror else '%s\n' % e)
+            return _set_status(1)
+        return fn()
+    finally:
+        for action in reversed(undo):
+            action()
+        for handle in opened:
+            handle.close()
+
+
+def _echo(args):
+    newline = True
+    escapes = False
+    while args and re.fullmatch('-[neE]+', args[0]):
+        flags = args.pop(0)
+        if 'n' in flags:
+            newline = False
+        if 'e' in flags:
+            escapes = True
+        if 'E' in flags:
+            escapes = False
+    text = ' '.join(args)
+    if escapes:
+        text, stop = _backslash_escapes(text)
+        if stop:
+            newline = False
+    _write(text + ('\n' if newline else ''))
+    return _set_status(0)
+
+
+def _backslash_escapes(text):
+    simple = {'a': '\a', 'b': '\b', 'e': '\x1b', 'E': '\x1b', 'f': '\f', 'n': '\n',
+              'r': '\r', 't': '\t', 'v': '\v', '\\': '\\'}
+    out = []
+    i = 0
+    while i < len(text):
+        c = text[i]
+        if c != '\\' or i + 1 >= len(text):
+            out.append(c)
+            i += 1
+            continue
+        nxt = text[i + 1]
+        if nxt == 'c':
+            return ''.join(out), True
+        if nxt in simple:
+            out.append(simple[nxt])
+            i += 2
+        elif nxt == '0' or nxt in '1234567':
+            digits = re.match('[0-7]{1,3}', text[i + 2:i + 5] if nxt == '0' else text[i + 1:i + 4]).group(0)
+            out.append(chr(int(digits, 8)))
+            i += (2 if nxt == '0' else 1) + len(digits)
+        elif nxt == 'x' and re.match('[0-9a-fA-F]', text[i + 2:i + 3]):
+            digits = re.match('[0-9a-fA-F]{1,2}', text[i + 2:]).group(0)
+            out.append(chr(int(digits, 16)))
+            i += 2 + len(digits)
+        else:
+            out.append(c)
+            i += 1
+    return ''.join(out), False
+
+
+def _printf(args):
+    if args[:1] == ['-v'] and len(args) >= 3:
+        name = args[1]
+        V[name] = _capture(lambda: _printf(args[2:]))
+        return _set_status(0)
+    if not args:
+        return _set_status(2)
+    fmt, values = args[0], args[1:]
+    spec = re.compile(r'%([-+ #0]*)(\*|\d+)?(?:\.(\*|\d+))?([diouxXeEfFgGcsbq%])')
+    out = []
+    while True:
+        consumed = 0
+        pos = 0
+        for m in spec.finditer(fmt):
+            out.append(_backslash_escapes(fmt[pos:m.start()])[0])
+            pos = m.end()
+            flags, width, precision, conv = m.groups()
+            if conv == '%':
+                out.append('%')
+                continue
+            if width == '*':
+                width = str(_num(values[consumed]) if consumed < len(values) else 0)
+                consumed += 1
+            if precision == '*':
+                precision = str(_num(values[consumed]) if consumed < len(values) else 0)
+                consumed += 1
+            value = values[consumed] if consumed < len(values) else None
+            consumed += 1
+            pyspec = '%' + flags + (width or '') + ('.' + precision if precision is not None else '')
+            if conv in 'diouxX':
+                number = _num(value) if value is not None else 0
+                out.append((pyspec + ('d' if conv == 'i' or conv == 'u' else conv)) % number)
+            elif conv in 'eEfFgG':
+                try:
+                    number = float(value) if value is not None else 0.0
+                except ValueError:
+                    number = 0.0
+                out.append((pyspec + conv) % number)
+            elif conv == 'c':
+                out.append((value or '')[:1])
+            elif conv == 'b':
+                out.append((pyspec + 's') % _backslash_escapes(value or '')[0])
+            elif conv == 'q':
+                out.append((pyspec + 's') % _quote(value or ''))
+            else:
+                out.append((pyspec + 's') % (value if value is not None else ''))
+        out.append(_backslash_escapes(fmt[pos:])[0])
+        values = values[consumed:]
+        if not values or consumed == 0:
+            break
+    _write(''.join(out))
+    return _set_status(0)
+
+
+def _quote(text):
+    if text and re.fullmatch(r'[A-Za-z0-9_./=:,+@%-]+', text):
+        return text
+    return "'" + text.replace("'", "'\\''") + "'"
+
+
+def _read_line():
+    data = _IN[-1]
+    if data is None:
+        return sys.stdin.readline()
+    line, sep, rest = data.partition('\n')
+    _IN[-1] = rest
+    return line + sep
+
+
+def _read(args):
+    raw = False
+    names = []
+    prompt = None
+    i = 0
+    while i < len(args):
+        if args[i] == '-r':
+            raw = True
+        elif args[i] == '-p' and i + 1 < len(args):
+            prompt = args[i + 1]
+            i += 1
+        else:
+            names.append(args[i])
+        i += 1
+    if prompt is not None:
+        _ERR[-1].write(prompt)
+    line = _read_line()
+    if line == '':
+        for name in names or ['REPLY']:
+            V[name] = ''
+        return _set_status(1)
+    line = line.rstrip('\n')
+    if not raw:
+        line = re.sub(r'\\(.)', r'\1', line)
+    if not names:
+        V['REPLY'] = line
+        return _set_status(0)
+    fields = _split(line)
+    for n, name in enumerate(names):
+        if n == len(names) - 1:
+            V[name] = ' '.join(fields[n:])
+        else:
+            V[name] = fields[n] if n < len(fields) else ''
+    return _set_status(0)
+
+
+def _select(name, items):
+    menu = True
+    while True:
+        if menu:
+            for n, item in enumerate(items, 1):
+                _ERR[-1].write('%d) %s\n' % (n, item))
+        _ERR[-1].write(V['PS3'] if 'PS3' in V else os.environ.get('PS3', '#? '))
+        _flush()
+        line = _read_line()
+        if line == '':
+            _ERR[-1].write('\n')
+            _set_status(1)
+            return
+        V['REPLY'] = line.rstrip('\n')
+        menu = V['REPLY'] == ''
+        if menu:
+            continue
+        choice = V['REPLY']
+        V[name] = items[int(choice) - 1] if choice.isdigit() and 1 <= int(choice) <= len(items) else ''
+        yield
+
+
+def _cd(args):
+    target = args[0] if args else os.environ.get('HOME', '/')
+    if target == '-':
+        target = V.get('OLDPWD', os.getcwd())
+    try:
+        previous = os.getcwd()
+        os.chdir(target)
+        V['OLDPWD'] = previous
+        V['PWD'] = os.getcwd()
+        return _set_status(0)
+    except OSError:
+        _ERR[-1].write('cd: %s: No such file or directory\n' % target)
+        return _set_status(1)
+
+
+def _export(values):
+    for name, value in values.items():
+        if value is not None:
+            V[name] = value
+        _EXPORTED.add(name)
+        if isinstance(V.get(name), str):
+            os.environ[name] = V[name]
+    return _set_status(0)
+
+
+def _unset(names):
+    for name in names:
+        if name in ('-v', '-f'):
+            continue
+        V.pop(name, None)
+        _FUNCS.pop(name, None)
+        os.environ.pop(name, None)
+    return _set_status(0)
+
+
+def _local(values):
+    frame = _LOCALS[-1] if _LOCALS else None
+    for name, value in values.items():
+        if frame is not None and name not in frame:
+            frame[name] = V.get(name)
+        V[name] = value if value is not None else ''
+    return _set_status(0)
+
+
+def _shift(n=1):
+    args = _ARGS[-1]
+    if n > len(args):
+        return _set_status(1)
+    del args[:n]
+    return _set_status(0)
+
+
+def _set(args):
+    if not args:
+        return _set_status(0)
+    positional = None
+    i = 0
+    while i < len(args):
+        arg = args[i]
+        if arg == '--':
+            positional = args[i + 1:]
+            break
+        if arg in ('-o', '+o') and i + 1 < len(args):
+            if arg == '-o':
+                _OPTS.add(args[i + 1])
+            else:
+                _OPTS.discard(args[i + 1])
+            i += 2
+            continue
+        if arg[:1] in '-+' and len(arg) > 1:
+            for flag in arg[1:]:
+                if arg[0] == '-':
+                    _OPTS.add(flag)
+                else:
+                    _OPTS.discard(flag)
+        else:
+            positional = args[i:]
+            break
+        i += 1
+    if positional is not None:
+        _ARGS[-1][:] = positional
+    return _set_status(0)
+
+
+def _exit(code=None):
+    _wait()
+    _flush()
+    sys.exit(_status() if code is None else _num(code) & 255)
+
+
+@contextmanager
+def _frame(args):
+    _ARGS.append(list(args))
+    _LOCALS.append({})
+    try:
+        yield
+    finally:
+        _ARGS.pop()
+        for name, value in _LOCALS.pop().items():
+            if value is None:
+                V.pop(name, None)
+            else:
+                V[name] = value
+
+
+def _function(name):
+    def register(fn):
+        _FUNCS[name] = fn
+        return fn
+    return register
+
+
+def _return(code=None):
+    return _set_status(_status() if code is None else _num(code) & 255)
+
+
+def _test(args, extended=False):
+    args = list(args)
+    pos = [0]
+
+    def peek(offset=0):
+        n = pos[0] + offset
+        return args[n] if n < len(args) else None
+
+    def take():
+        pos[0] += 1
+        return args[pos[0] - 1]
+
+    def file_test(op, path):
+        if op == '-e' or op == '-a':
+            return os.path.exists(path)
+        if op == '-f':
+            return os.path.isfile(path)
+        if op == '-d':
+            return os.path.isdir(path)
+        if op == '-s':
+            return os.path.exists(path) and os.path.getsize(path) > 0
+        if op in ('-L', '-h'):
+            return os.path.islink(path)
+        if op == '-r':
+            return os.access(path, os.R_OK)
+        if op == '-w':
+            return os.access(path, os.W_OK)
+        if op == '-x':
+            return os.access(path, os.X_OK)
+        if op == '-p':
+            import stat
+            return os.path.exists(path) and stat.S_ISFIFO(os.stat(path).st_mode)
+        return False
+
+    def binary(left, op, right):
+        if op in ('=', '=='):
+            return _match(left, right) if extended else left == right
+        if op == '!=':
+            return not _match(left, right) if extended else left != right
+        if op == '=~':
+            found = re.search(right, left)
+            V['BASH_REMATCH'] = [found.group(0)] + [g or '' for g in found.groups()] if found else []
+            return bool(found)
+        if op == '<':
+            return left < right
+        if op == '>':
+            return left > right
+        a, b = _num(left), _num(right)
+        return {'-eq': a == b, '-ne': a != b, '-lt': a < b,
+                '-le': a <= b, '-gt': a > b, '-ge': a >= b}.get(op, False)
+
+    binary_ops = ('=', '==', '!=', '=~', '<', '>', '-eq', '-ne', '-lt', '-le', '-gt', '-ge')
+    unary_ops = ('-e', '-a', '-f', '-d', '-s', '-L', '-h', '-r', '-w', '-x', '-p', '-z', '-n')
+
+    def primary():
+        tok = peek()
+        if tok is None:
+            return False
+        if tok == '(':
+            take()
+            result = disjunction()
+            if peek() == ')':
+                take()
+            return result
+        if peek(1) in binary_ops and peek(2) is not None:
+            left = take()
+            op = take()
+            return binary(left, op, take())
+        if tok in unary_ops and peek(1) is not None:
+            op = take()
+            operand = take()
+            if op == '-z':
+                return operand == ''
+            if op == '-n':
+                return operand != ''
+            return file_test(op, operand)
+        return take() != ''
+
+    def negation():
+        if peek() == '!':
+            take()
+            return not negation()
+        return primary()
+
+    def conjunction():
+        result = negation()
+        while peek() in ('-a', '&&'):
+            take()
+            rhs = negation()
+            result = result and rhs
+        return result
+
+    def disjunction():
+        result = conjunction()
+        while peek() in ('-o', '||'):
+            take()
+            rhs = conjunction()
+            result = result or rhs
+        return result
+
+    return _set_status(0 if disjunction() else 1)
@@ -0,0 +1,80 @@
+use crate::ast::*;
+use super::PythonGenerator;
+use super::words::raw_text_to_python;
+
+pub fn generate_test_expression_impl(generator: &mut PythonGenerator, test_expr: &TestExpression) -> String {
+    format!("{}{}\n", generator.indent(), generator.test_expression_call(test_expr))
+}
+
+pub fn test_expression_call_impl(_generator: &mut PythonGenerator, test_expr: &TestExpression) -> String {
+    format!("_test({}, True)", test_words_to_python(&test_expr.expression))
+}
+
+/// Convert the text between `[` and `]` (or `[[` and `]]`) into a Python list
+/// of operands for the runtime `_test` helper.
+pub fn test_words_to_python(expression: &str) -> String {
+    let words = split_test_words(expression);
+    let mut items = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let quoted = word.starts_with('"') || word.starts_with('\'');
+        let after_match_op = i > 0 && matches!(words[i - 1].as_str(), "==" | "=" | "!=");
+        let expr = raw_text_to_python(word);
+        if quoted && after_match_op {
+            // A quoted right-hand side is compared literally, not as a pattern
+            items.push(format!("_glob_escape({})", expr));
+        } else if quoted && i > 0 && words[i - 1] == "=~" {
+            // and a quoted regular expression matches its text literally
+            items.push(format!("re.escape({})", expr));
+        } else {
+            items.push(expr);
+        }
+    }
+    format!("[{}]", items.join(", "))
+}
+
+pub fn split_test_words(expression: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                current.push(c);
+                if c == '\\' && q == '"' {
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => {
+                    current.push(c);
+                    quote = Some(c);
+                }
+                '\\' => {
+                    current.push(c);
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                }
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        words.push(std::mem::take(&mut current));
+                    }
+                }
+                _ => current.push(c),
+            },
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    // The closing bracket is not an operand
+    if matches!(words.last().map(String::as_str), Some("]") | Some("]]")) {
+        words.pop();
+    }
+    words
+}
@@ -0,0 +1,698 @@
+use crate::ast::*;
+use super::PythonGenerator;
+
+pub fn word_to_python_impl(generator: &mut PythonGenerator, word: &Word) -> String {
+    match word {
+        Word::Literal(s, _) => literal_to_python(s),
+        Word::Variable(var, _) => variable_to_python(var),
+        Word::ParameterExpansion(pe, _) => generator.generate_parameter_expansion(pe),
+        Word::Array(name, elements, _) => array_to_python(name, elements),
+        Word::MapAccess(map_name, key, _) => map_access_to_python(map_name, key),
+        Word::MapKeys(map_name, _) => format!("' '.join(_keys({}))", python_string_literal(map_name)),
+        Word::MapLength(map_name, _) => format!("str(len(_elements({})))", python_string_literal(map_name)),
+        Word::ArraySlice(name, offset, length, _) => {
+            format!("' '.join({})", array_slice_to_python(name, offset, length.as_deref()))
+        }
+        Word::Arithmetic(expr, _) => format!("str({})", generator.convert_arithmetic_to_python(&expr.expression)),
+        Word::BraceExpansion(expansion, _) => python_string_literal(&expand_braces(expansion).join(" ")),
+        Word::CommandSubstitution(cmd, _) => {
+            let expr = generator.command_expression(cmd);
+            format!("_capture(lambda: {})", expr)
+        }
+        Word::ProcessSubstitution(cmd, direction, _) => {
+            generator.pending_process_subs = true;
+            let expr = generator.command_expression(cmd);
+            match direction {
+                ProcessDirection::Input => format!("_process_sub_in(lambda: {})", expr),
+                ProcessDirection::Output => format!("_process_sub_out(lambda: {})", expr),
+            }
+        }
+        Word::StringInterpolation(interp, _) => string_interpolation_to_python(generator, interp),
+        Word::Concatenation(words, _) => string_interpolation_to_python(generator, &concatenated_parts(words)),
+    }
+}
+
+/// Convert a word in argument position, where unquoted expansions are
+/// subject to word splitting and globbing. Each returned entry is a
+/// Python list element (possibly starred).
+pub fn word_to_python_args_impl(generator: &mut PythonGenerator, word: &Word) -> Vec<String> {
+    match word {
+        Word::Literal(s, _) if !is_single_quoted(s) && has_glob_chars(s) => {
+            vec![format!("*_glob({})", python_string_literal(s))]
+        }
+        Word::Variable(var, _) => match var.as_str() {
+            "@" | "*" => vec!["*_ARGS[-1]".to_string()],
+            "#" | "?" | "$" | "!" => vec![variable_to_python(var)],
+            _ => vec![format!("*_split({})", variable_to_python(var))],
+        },
+        Word::ParameterExpansion(pe, _) => {
+            let expr = generator.generate_parameter_expansion(pe);
+            vec![format!("*_split({})", expr)]
+        }
+        Word::MapAccess(map_name, key, _) if key == "@" || key == "*" => {
+            vec![format!("*_elements({})", python_string_literal(map_name))]
+        }
+        Word::MapKeys(map_name, _) => vec![format!("*_keys({})", python_string_literal(map_name))],
+        Word::ArraySlice(name, offset, length, _) => {
+            vec![format!("*{}", array_slice_to_python(name, offset, length.as_deref()))]
+        }
+        Word::BraceExpansion(expansion, _) => expand_braces(expansion)
+            .iter()
+            .map(|item| python_string_literal(item))
+            .collect(),
+        Word::CommandSubstitution(..) => {
+            let expr = generator.word_to_python(word);
+            vec![format!("*_split({})", expr)]
+        }
+        Word::StringInterpolation(interp, _) if interp.parts.len() == 1 => match &interp.parts[0] {
+            // "$@" and "${arr[@]}" expand to one word per element even when quoted
+            StringPart::Variable(var) if var == "@" => vec!["*_ARGS[-1]".to_string()],
+            StringPart::MapAccess(map_name, key) if key == "@" => {
+                vec![format!("*_elements({})", python_string_literal(map_name))]
+            }
+            StringPart::ParameterExpansion(ParameterExpansion { variable, operator: ParameterExpansionOperator::ArraySlice(offset, length) })
+                if variable == "@" =>
+            {
+                vec![format!("*{}", positional_slice_to_python(generator, offset, length.as_deref()))]
+            }
+            _ => vec![generator.word_to_python(word)],
+        },
+        _ => vec![generator.word_to_python(word)],
+    }
+}
+
+pub fn words_to_python_list_impl(generator: &mut PythonGenerator, words: &[Word]) -> String {
+    let items: Vec<String> = words.iter()
+        .flat_map(|word| generator.word_to_python_args(word))
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+pub fn generate_parameter_expansion_impl(generator: &mut PythonGenerator, pe: &ParameterExpansion) -> String {
+    let name = python_string_literal(&pe.variable);
+    let value = parameter_value_to_python(&pe.variable);
+    let is_set = format!("_is_set({})", name);
+    match &pe.operator {
+        ParameterExpansionOperator::None => value,
+        ParameterExpansionOperator::Length => format!("str(len({}))", value),
+        ParameterExpansionOperator::UppercaseAll => format!("{}.upper()", value),
+        ParameterExpansionOperator::LowercaseAll => format!("{}.lower()", value),
+        ParameterExpansionOperator::UppercaseFirst => format!("_ucfirst({})", value),
+        ParameterExpansionOperator::LowercaseFirst => format!("_lcfirst({})", value),
+        ParameterExpansionOperator::ToggleCaseAll => format!("{}.swapcase()", value),
+        ParameterExpansionOperator::ToggleCaseFirst => format!("_swapcase_first({})", value),
+        ParameterExpansionOperator::RemoveLongestPrefix(pattern) => {
+            format!("_trim({}, {}, True, True)", value, raw_text_to_python(pattern))
+        }
+        ParameterExpansionOperator::RemoveShortestPrefix(pattern) => {
+            format!("_trim({}, {}, True, False)", value, raw_text_to_python(pattern))
+        }
+        ParameterExpansionOperator::RemoveLongestSuffix(pattern) => {
+            format!("_trim({}, {}, False, True)", value, raw_text_to_python(pattern))
+        }
+        ParameterExpansionOperator::RemoveShortestSuffix(pattern) => {
+            format!("_trim({}, {}, False, False)", value, raw_text_to_python(pattern))
+        }
+        ParameterExpansionOperator::SubstituteAll(pattern, replacement) => {
+            format!("_substitute({}, {}, {}, True)", value,
+                    raw_text_to_python(pattern), raw_text_to_python(replacement))
+        }
+        ParameterExpansionOperator::SubstituteFirst(pattern, replacement) => {
+            format!("_substitute({}, {}, {}, False)", value,
+                    raw_text_to_python(pattern), raw_text_to_python(replacement))
+        }
+        ParameterExpansionOperator::SubstitutePrefix(pattern, replacement) => {
+            format!("_substitute_anchored({}, {}, {}, True)", value,
+                    raw_text_to_python(pattern), raw_text_to_python(replacement))
+        }
+        ParameterExpansionOperator::SubstituteSuffix(pattern, replacement) => {
+            format!("_substitute_anchored({}, {}, {}, False)", value,
+                    raw_text_to_python(pattern), raw_text_to_python(replacement))
+        }
+        // The fallback is only evaluated when it is used, as in the shell
+        ParameterExpansionOperator::DefaultValue(default) => {
+            format!("({} or {})", value, raw_text_to_python(default))
+        }
+        ParameterExpansionOperator::DefaultIfUnset(default) => {
+            format!("({} if {} else {})", value, is_set, raw_text_to_python(default))
+        }
+        ParameterExpansionOperator::AssignDefault(default) => {
+            format!("({} or _store({}, {}))", value, name, raw_text_to_python(default))
+        }
+        ParameterExpansionOperator::AssignIfUnset(default) => {
+            format!("({} if {} else _store({}, {}))", value, is_set, name, raw_text_to_python(default))
+        }
+        ParameterExpansionOperator::ErrorIfUnset(message) => {
+            format!("({} or _parameter_error({}, {}, 'parameter null or not set'))",
+                    value, name, raw_text_to_python(message))
+        }
+        ParameterExpansionOperator::ErrorIfNotSet(message) => {
+            format!("({} if {} else _parameter_error({}, {}, 'parameter not set'))",
+                    value, is_set, name, raw_text_to_python(message))
+        }
+        ParameterExpansionOperator::AlternativeValue(alternative) => {
+            format!("({} if {} else '')", raw_text_to_python(alternative), value)
+        }
+        ParameterExpansionOperator::AlternativeIfSet(alternative) => {
+            format!("({} if {} else '')", raw_text_to_python(alternative), is_set)
+        }
+        ParameterExpansionOperator::Basename => format!("_trim({}, '*/', True, True)", value),
+        ParameterExpansionOperator::Dirname => format!("_trim({}, '/*', False, False)", value),
+        ParameterExpansionOperator::ArraySlice(offset, length) => {
+            match pe.variable.as_str() {
+                "@" | "*" => format!("' '.join({})", positional_slice_to_python(generator, offset, length.as_deref())),
+                _ => {
+                    let mut args = vec![generator.convert_arithmetic_to_python(offset)];
+                    args.extend(length.iter().map(|length| generator.convert_arithmetic_to_python(length)));
+                    format!("_substring({}, {})", value, args.join(", "))
+                }
+            }
+        }
+        ParameterExpansionOperator::PrefixNames(_) => {
+            format!("' '.join(_prefix_names({}))", name)
+        }
+        ParameterExpansionOperator::Transform(op) => match op {
+            'Q' => format!("_single_quote({})", value),
+            'U' => format!("{}.upper()", value),
+            'L' => format!("{}.lower()", value),
+            'u' => format!("_ucfirst({})", value),
+            'E' => format!("_backslash_escapes({})[0]", value),
+            _ => value,
+        },
+    }
+}
+
+pub fn convert_arithmetic_to_python_impl(_generator: &PythonGenerator, expr: &str) -> String {
+    match crate::parser::arithmetic::parse_arithmetic(expr) {
+        Ok(tree) => arithmetic_node_to_python(&tree),
+        Err(_) => format!("int({})", arithmetic_to_python(expr.trim())),
+    }
+}
+
+/// Python for a parsed arithmetic expression, with bash's 64-bit integers:
+/// results that could overflow wrap around, and division truncates.
+fn arithmetic_node_to_python(node: &ArithmeticNode) -> String {
+    use ArithmeticOperator::*;
+    match node {
+        ArithmeticNode::Number(value) => value.to_string(),
+        ArithmeticNode::Variable(name) if name == "#" => "len(_ARGS[-1])".to_string(),
+        ArithmeticNode::Variable(name) => format!("_num({})", variable_to_python(name)),
+        ArithmeticNode::Element(name, index) => {
+            format!("_num(_index({}, {}))", python_string_literal(name), arithmetic_node_to_python(index))
+        }
+        ArithmeticNode::Unary(op, operand) => {
+            let operand = arithmetic_node_to_python(operand);
+            match op {
+                LogicalNot => format!("int(not {})", operand),
+                Subtract if operand.parse::<i64>().is_ok() => format!("-{}", operand),
+                Subtract => format!("_wrap(-{})", operand),
+                BitNot => format!("(~{})", operand),
+                _ => operand,
+            }
+        }
+        ArithmeticNode::Binary(left, op, right) => {
+            let (left, right) = (arithmetic_node_to_python(left), arithmetic_node_to_python(right));
+            binary_to_python(&left, *op, &right)
+        }
+        ArithmeticNode::Assign(target, op, value) => {
+            let value = arithmetic_node_to_python(value);
+            let value = match op {
+                Some(op) => binary_to_python(&arithmetic_node_to_python(target), *op, &value),
+                None => value,
+            };
+            arithmetic_store(target, &value)
+        }
+        ArithmeticNode::PreIncrement(target) => {
+            arithmetic_store(target, &format!("_wrap({} + 1)", arithmetic_node_to_python(target)))
+        }
+        ArithmeticNode::PreDecrement(target) => {
+            arithmetic_store(target, &format!("_wrap({} - 1)", arithmetic_node_to_python(target)))
+        }
+        // The old value is what the new one was made from
+        ArithmeticNode::PostIncrement(target) => {
+            format!("({} - 1)", arithmetic_store(target, &format!("({} + 1)", arithmetic_node_to_python(target))))
+        }
+        ArithmeticNode::PostDecrement(target) => {
+            format!("({} + 1)", arithmetic_store(target, &format!("({} - 1)", arithmetic_node_to_python(target))))
+        }
+        ArithmeticNode::Conditional(condition, then_value, else_value) => {
+            format!("({} if {} else {})", arithmetic_node_to_python(then_value),
+                    arithmetic_node_to_python(condition), arithmetic_node_to_python(else_value))
+        }
+    }
+}
+
+fn binary_to_python(left: &str, op: ArithmeticOperator, right: &str) -> String {
+    use ArithmeticOperator::*;
+    match op {
+        Add | Subtract | Multiply => format!("_wrap({} {} {})", left, op.as_str(), right),
+        Power => format!("_wrap(pow({}, {}, 2 ** 64))", left, right),
+        Divide => format!("_div({}, {})", left, right),
+        Modulo => format!("_mod({}, {})", left, right),
+        ShiftLeft => format!("_wrap({} << ({} & 63))", left, right),
+        ShiftRight => format!("({} >> ({} & 63))", left, right),
+        Less | LessEqual | Greater | GreaterEqual | Equal | NotEqual => {
+            format!("int({} {} {})", left, op.as_str(), right)
+        }
+        BitAnd | BitXor | BitOr => format!("({} {} {})", left, op.as_str(), right),
+        LogicalAnd => format!("int(bool({}) and bool({}))", left, right),
+        LogicalOr => format!("int(bool({}) or bool({}))", left, right),
+        // Both sides are evaluated, in order, for the value of the right one
+        Comma => format!("({}, {})[1]", left, right),
+        BitNot | LogicalNot => right.to_string(),
+    }
+}
+
+/// Assigns a value to a variable or an array element, yielding the value.
+fn arithmetic_store(target: &ArithmeticNode, value: &str) -> String {
+    match target {
+        ArithmeticNode::Variable(name) => format!("_num(_store({}, str({})))", python_string_literal(name), value),
+        ArithmeticNode::Element(name, index) => format!("_num(_store({} % {}, str({})))",
+            python_string_literal(&format!("{}[%d]", name)), arithmetic_node_to_python(index), value),
+        // Not assignable, as `(( 1 = 2 ))`
+        _ => value.to_string(),
+    }
+}
+
+fn arithmetic_to_python(expr: &str) -> String {
+    // Ternaries have the lowest precedence, so split on them before tokenizing
+    if let Some((cond, then_part, else_part)) = split_ternary(expr) {
+        return format!("({} if {} else {})",
+                       arithmetic_to_python(then_part),
+                       arithmetic_to_python(cond),
+                       arithmetic_to_python(else_part));
+    }
+
+    let chars: Vec<char> = expr.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '#' || chars[i] == '_') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            out.push_str(&arithmetic_number(&literal));
+        } else if c == '$' && chars[i + 1..].starts_with(&['(', '(']) {
+            // A nested `$((...))` is just a parenthesized expression
+            i += 1;
+        } else if c == '$' || c.is_ascii_alphabetic() || c == '_' {
+            if c == '$' {
+                i += 1;
+            }
+            let braced = i < chars.len() && chars[i] == '{';
+            if braced {
+                i += 1;
+            }
+            let start = i;
+            if i < chars.len() && (chars[i].is_ascii_digit() || "#?@*".contains(chars[i])) && !braced {
+                i += 1;
+            } else {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            let name: String = chars[start..i].iter().collect();
+            if braced && i < chars.len() && chars[i] == '}' {
+                i += 1;
+            }
+            match name.as_str() {
+                "#" => out.push_str("len(_ARGS[-1])"),
+                _ => out.push_str(&format!("_num({})", variable_to_python(&name))),
+            }
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let (text, width) = match rest.as_str() {
+                "&&" => (" and ".to_string(), 2),
+                "||" => (" or ".to_string(), 2),
+                "!=" | "==" | "<=" | ">=" | "<<" | ">>" | "**" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "^=" | "|="
+                    => (format!(" {} ", rest), 2),
+                _ => match c {
+                    '!' => (" not ".to_string(), 1),
+                    '/' => (" // ".to_string(), 1),
+                    '(' | ')' | '~' => (c.to_string(), 1),
+                    _ => (format!(" {} ", c), 1),
+                },
+            };
+            out.push_str(&text);
+            i += width;
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ").replace("( ", "(").replace(" )", ")")
+}
+
+fn split_ternary(expr: &str) -> Option<(&str, &str, &str)> {
+    let mut depth = 0;
+    let mut question = None;
+    let mut nested = 0;
+    for (i, c) in expr.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '?' if depth == 0 => {
+                if question.is_none() {
+                    question = Some(i);
+                } else {
+                    nested += 1;
+                }
+            }
+            ':' if depth == 0 && question.is_some() => {
+                if nested == 0 {
+                    let q = question.unwrap();
+                    return Some((&expr[..q], &expr[q + 1..i], &expr[i + 1..]));
+                }
+                nested -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn arithmetic_number(literal: &str) -> String {
+    let lower = literal.to_ascii_lowercase();
+    let parsed = if let Some((base, digits)) = literal.split_once('#') {
+        base.parse::<u32>().ok().and_then(|b| i64::from_str_radix(digits, b).ok())
+    } else if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i64::from_str_radix(&literal[1..], 8).ok()
+    } else {
+        literal.parse::<i64>().ok()
+    };
+    parsed.map(|n| n.to_string()).unwrap_or_else(|| "0".to_string())
+}
+
+pub fn string_interpolation_to_python(generator: &mut PythonGenerator, interp: &StringInterpolation) -> String {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut literal = String::new();
+
+    for part in &interp.parts {
+        let expr = match part {
+            StringPart::Literal(s) => {
+                literal.push_str(&unescape_double_quoted(s));
+                continue;
+            }
+            StringPart::Variable(var) => variable_to_python(var),
+            StringPart::ParameterExpansion(pe) => generator.generate_parameter_expansion(pe),
+            StringPart::MapAccess(map_name, key) => map_access_to_python(map_name, key),
+            StringPart::MapKeys(map_name) => format!("' '.join(_keys({}))", python_string_literal(map_name)),
+            StringPart::MapLength(map_name) => format!("str(len(_elements({})))", python_string_literal(map_name)),
+            StringPart::ArraySlice(name, offset, length) => {
+                format!("' '.join({})", array_slice_to_python(name, offset, length.as_deref()))
+            }
+            StringPart::Arithmetic(expr) => format!("str({})", generator.convert_arithmetic_to_python(&expr.expression)),
+            StringPart::CommandSubstitution(cmd) => {
+                let expr = generator.command_expression(cmd);
+                format!("_capture(lambda: {})", expr)
+            }
+        };
+        if !literal.is_empty() {
+            pieces.push(python_string_literal(&std::mem::take(&mut literal)));
+        }
+        pieces.push(expr);
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(python_string_literal(&literal));
+    }
+    pieces.join(" + ")
+}
+
+pub fn variable_to_python(var: &str) -> String {
+    match var {
+        "#" => "str(len(_ARGS[-1]))".to_string(),
+        "@" | "*" => "' '.join(_ARGS[-1])".to_string(),
+        "?" => "V['?']".to_string(),
+        "$" => "str(os.getpid())".to_string(),
+        _ if !var.is_empty() && var.chars().all(|c| c.is_ascii_digit()) => {
+            format!("_arg({})", var.parse::<usize>().unwrap_or(0))
+        }
+        _ => format!("_var({})", python_string_literal(var)),
+    }
+}
+
+/// The value named by the inside of `${...}`, including `#name` and `name[key]` forms.
+fn parameter_value_to_python(variable: &str) -> String {
+    if let Some(name) = variable.strip_prefix('#') {
+        if !name.is_empty() {
+            if let Some(array) = name.strip_suffix("[@]").or_else(|| name.strip_suffix("[*]")) {
+                return format!("str(len(_elements({})))", python_string_literal(array));
+            }
+            return format!("str(len({}))", parameter_value_to_python(name));
+        }
+    }
+    if let (Some(open), true) = (variable.find('['), variable.ends_with(']')) {
+        let name = &variable[..open];
+        let key = &variable[open + 1..variable.len() - 1];
+        return map_access_to_python(name, key);
+    }
+    variable_to_python(variable)
+}
+
+fn map_access_to_python(map_name: &str, key: &str) -> String {
+    match key {
+        "@" | "*" => format!("' '.join(_elements({}))", python_string_literal(map_name)),
+        _ => format!("_index({}, {})", python_string_literal(map_name), raw_text_to_python(key)),
+    }
+}
+
+/// `${@:offset:length}`, which counts `$0` as the first positional parameter.
+fn positional_slice_to_python(generator: &mut PythonGenerator, offset: &str, length: Option<&str>) -> String {
+    let mut args = vec![generator.convert_arithmetic_to_python(offset)];
+    args.extend(length.map(|length| generator.convert_arithmetic_to_python(length)));
+    format!("_positional_slice({})", args.join(", "))
+}
+
+fn array_slice_to_python(name: &str, offset: &str, length: Option<&str>) -> String {
+    let offset_expr = format!("_num({})", raw_text_to_python(offset));
+    match length {
+        Some(length) => format!("_elements({})[{}:{} + _num({})]", python_string_literal(name),
+                                offset_expr, offset_expr, raw_text_to_python(length)),
+        None => format!("_elements({})[{}:]", python_string_literal(name), offset_expr),
+    }
+}
+
+fn array_to_python(_name: &str, elements: &[String]) -> String {
+    let last = elements.len().saturating_sub(1);
+    let items: Vec<String> = elements.iter().enumerate()
+        .filter_map(|(i, element)| {
+            // The standalone-assignment parser can leave the surrounding parentheses attached
+            let mut trimmed = element.as_str();
+            if i == 0 {
+                trimmed = trimmed.strip_prefix('(').unwrap_or(trimmed);
+            }
+            if i == last {
+                trimmed = trimmed.strip_suffix(')').unwrap_or(trimmed);
+            }
+            if trimmed.is_empty() && !element.is_empty() {
+                return None;
+            }
+            Some(raw_text_to_python(trimmed))
+        })
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn literal_to_python(s: &str) -> String {
+    if is_single_quoted(s) {
+        return python_string_literal(&s[1..s.len() - 1]);
+    }
+    let text = unescape_unquoted(s);
+    if text == "~" || text.starts_with("~/") {
+        return format!("os.path.expanduser({})", python_string_literal(&text));
+    }
+    python_string_literal(&text)
+}
+
+/// Convert raw shell text (as stored in operator arguments and test
+/// expressions) into a Python string expression, honouring quotes and
+/// `$name` / `${name}` references.
+pub fn raw_text_to_python(text: &str) -> String {
+    if is_single_quoted(text) {
+        return python_string_literal(&text[1..text.len() - 1]);
+    }
+    let (inner, quoted) = if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        (&text[1..text.len() - 1], true)
+    } else {
+        (text, false)
+    };
+
+    let chars: Vec<char> = inner.chars().collect();
+    let mut pieces: Vec<String> = Vec::new();
+    let mut literal = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            if !quoted || "\\\"$`".contains(next) {
+                literal.push(next);
+            } else {
+                literal.push(c);
+                literal.push(next);
+            }
+            i += 2;
+            continue;
+        }
+        if c == '$' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            let (expr, consumed) = if next == '{' {
+                match chars[i + 2..].iter().position(|&ch| ch == '}') {
+                    Some(end) => {
+                        let name: String = chars[i + 2..i + 2 + end].iter().collect();
+                        (parameter_value_to_python(&name), end + 3)
+                    }
+                    None => (String::new(), 0),
+                }
+            } else if next.is_ascii_digit() || "#?@*$!".contains(next) {
+                (variable_to_python(&next.to_string()), 2)
+            } else if next.is_ascii_alphabetic() || next == '_' {
+                let len = chars[i + 1..].iter()
+                    .take_while(|ch| ch.is_ascii_alphanumeric() || **ch == '_')
+                    .count();
+                let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                (variable_to_python(&name), len + 1)
+            } else {
+                (String::new(), 0)
+            };
+            if consumed > 0 {
+                if !literal.is_empty() {
+                    pieces.push(python_string_literal(&std::mem::take(&mut literal)));
+                }
+                pieces.push(expr);
+                i += consumed;
+                continue;
+            }
+        }
+        literal.push(c);
+        i += 1;
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(python_string_literal(&literal));
+    }
+    pieces.join(" + ")
+}
+
+pub fn expand_braces(expansion: &BraceExpansion) -> Vec<String> {
+    let prefix = expansion.prefix.clone().unwrap_or_default();
+    let suffix = expansion.suffix.clone().unwrap_or_default();
+    let mut result = Vec::new();
+    for item in &expansion.items {
+        let values = match item {
+            BraceItem::Literal(s) => vec![s.clone()],
+            BraceItem::Sequence(seq) => seq.clone(),
+            BraceItem::Range(range) => expand_brace_range(range),
+        };
+        for value in values {
+            result.push(format!("{}{}{}", prefix, value, suffix));
+        }
+    }
+    result
+}
+
+pub fn expand_brace_range(range: &BraceRange) -> Vec<String> {
+    let step = range.step.as_deref()
+        .and_then(|s| s.parse::<i64>().ok())
+        .map(|s| s.abs().max(1))
+        .unwrap_or(1);
+    if let (Ok(start), Ok(end)) = (range.start.parse::<i64>(), range.end.parse::<i64>()) {
+        // Zero-padded endpoints pad every generated value to the same width
+        let padded = range.start.starts_with('0') && range.start.len() > 1
+            || range.end.starts_with('0') && range.end.len() > 1;
+        let width = range.start.len().max(range.end.len());
+        let mut values = Vec::new();
+        let mut n = start;
+        while (start <= end && n <= end) || (start > end && n >= end) {
+            values.push(if padded { format!("{:0width$}", n, width = width) } else { n.to_string() });
+            n = if start <= end { n + step } else { n - step };
+        }
+        return values;
+    }
+    let (start, end) = (range.start.chars().next(), range.end.chars().next());
+    if let (Some(start), Some(end), 1, 1) = (start, end, range.start.len(), range.end.len()) {
+        let (lo, hi) = (start as u32, end as u32);
+        let codes: Vec<u32> = if lo <= hi {
+            (lo..=hi).step_by(step as usize).collect()
+        } else {
+            (hi..=lo).rev().step_by(step as usize).collect()
+        };
+        return codes.into_iter().filter_map(char::from_u32).map(|c| c.to_string()).collect();
+    }
+    vec![format!("{{{}..{}}}", range.start, range.end)]
+}
+
+pub fn is_single_quoted(s: &str) -> bool {
+    s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'')
+}
+
+pub fn has_glob_chars(s: &str) -> bool {
+    let mut escaped = false;
+    for c in s.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '*' || c == '?' || (c == '[' && s.contains(']')) {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn unescape_unquoted(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn unescape_double_quoted(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.peek() {
+                Some(&next) if "\\\"$`".contains(next) => {
+                    out.push(next);
+                    chars.next();
+                }
+                Some('\n') => {
+                    chars.next();
+                }
+                _ => out.push(c),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn python_string_literal(s: &str) -> String {
+    let mut out = String::from("'");
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
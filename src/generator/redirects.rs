@@ -1,43 +1,290 @@
 use crate::ast::*;
+use crate::generator::python::words::unescape_double_quoted;
 use super::Generator;
 
+/// Run a command with its redirects applied. Every descriptor the redirects
+/// change is saved with `POSIX::dup` first and put back afterwards, so that
+/// STDOUT and STDERR only point elsewhere for the duration of the command.
+pub fn generate_redirect_command_impl(generator: &mut Generator, redirect_cmd: &RedirectCommand) -> String {
+    let mut output = String::new();
+    // `{var}>file` descriptors outlive the command, so open them outside the block
+    for redirect in redirect_cmd.redirects.iter().filter(|r| r.fd_var.is_some()) {
+        output.push_str(&generator.generate_redirect(redirect));
+    }
+
+    let mut saved = Vec::new();
+    for redirect in redirect_cmd.redirects.iter().filter(|r| r.fd_var.is_none()) {
+        saved.push(redirect.descriptor());
+        if matches!(redirect.operator, RedirectOperator::OutputAll | RedirectOperator::AppendAll) {
+            saved.push(2);
+        }
+        if let Some(source) = moved_descriptor(redirect) {
+            saved.push(source);
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    saved.retain(|fd| seen.insert(*fd));
+
+    // No enclosing block, so that variables the command sets stay in scope
+    generator.file_handle_counter += 1;
+    let id = generator.file_handle_counter;
+    let indent = generator.indent();
+    output.push_str(&format!("{}require POSIX;\n", indent));
+    output.push_str(&format!("{}STDOUT->flush(); STDERR->flush();\n", indent));
+    for fd in &saved {
+        output.push_str(&format!("{}my $saved_fd{}_{} = POSIX::dup({});\n", indent, fd, id, fd));
+    }
+    for redirect in redirect_cmd.redirects.iter().filter(|r| r.fd_var.is_none()) {
+        output.push_str(&generator.generate_redirect(redirect));
+    }
+    output.push_str(&generator.generate_command(&redirect_cmd.command));
+    output.push_str(&format!("{}STDOUT->flush(); STDERR->flush();\n", indent));
+    for fd in saved.iter().rev() {
+        // A descriptor that was closed before the command is closed again
+        let saved_fd = format!("$saved_fd{}_{}", fd, id);
+        output.push_str(&format!(
+            "{}if (defined {saved}) {{ POSIX::dup2({saved}, {fd}); POSIX::close({saved}); }} else {{ POSIX::close({fd}); }}\n",
+            indent, saved = saved_fd, fd = fd));
+    }
+    output
+}
+
+/// The source of `N>&M-`, which is closed once duplicated.
+fn moved_descriptor(redirect: &Redirect) -> Option<i32> {
+    if !matches!(redirect.operator, RedirectOperator::DuplicateInput | RedirectOperator::DuplicateOutput) {
+        return None;
+    }
+    match &redirect.target {
+        Word::Literal(text, _) => text.strip_suffix('-')?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Point one descriptor at the redirect's target. A `{var}` redirect instead
+/// takes a fresh descriptor from 10 up and stores it in the variable.
 pub fn generate_redirect_impl(generator: &mut Generator, redirect: &Redirect) -> String {
+    let indent = generator.indent();
+    let fd = redirect.descriptor();
+    match (redirect.operator.clone(), &redirect.fd_var) {
+        // `{var}>&-` closes the descriptor the variable holds
+        (RedirectOperator::Close, Some(name)) => {
+            return close_descriptor(generator, &format!("${}", name));
+        }
+        (RedirectOperator::Close, None) => {
+            return close_descriptor(generator, &fd.to_string());
+        }
+        (operator @ (RedirectOperator::DuplicateInput | RedirectOperator::DuplicateOutput), Some(name)) => {
+            let fh = generator.get_unique_file_handle();
+            let mode = if operator == RedirectOperator::DuplicateInput { "<&" } else { ">&" };
+            let source = duplicate_source(generator, redirect);
+            let mut output = format!("{}open(my ${}, '{}', {}) or die \"{}: Bad file descriptor\\n\";\n",
+                                     indent, fh, mode, source, generator.escape_perl_string(&redirect.target.to_string()));
+            output.push_str(&store_descriptor(generator, name, &fh));
+            if let Some(moved) = moved_descriptor(redirect) {
+                output.push_str(&close_descriptor(generator, &moved.to_string()));
+            }
+            return output;
+        }
+        (RedirectOperator::DuplicateInput | RedirectOperator::DuplicateOutput, None) => {
+            let source = duplicate_source(generator, redirect);
+            let mut output = format!("{}defined(POSIX::dup2({}, {})) or die \"{}: Bad file descriptor\\n\";\n",
+                                     indent, source, fd, generator.escape_perl_string(&redirect.target.to_string()));
+            if let Some(moved) = moved_descriptor(redirect) {
+                output.push_str(&close_descriptor(generator, &moved.to_string()));
+            }
+            return output;
+        }
+        _ => {}
+    }
+
+    let fh = generator.get_unique_file_handle();
+    let mut output = open_redirect_target(generator, redirect, &fh);
+    match &redirect.fd_var {
+        Some(name) => output.push_str(&store_descriptor(generator, name, &fh)),
+        None => {
+            output.push_str(&format!("{}POSIX::dup2(fileno(${}), {});\n", indent, fh, fd));
+            if matches!(redirect.operator, RedirectOperator::OutputAll | RedirectOperator::AppendAll) {
+                output.push_str(&format!("{}POSIX::dup2(fileno(${}), 2);\n", indent, fh));
+            }
+            // The file may have been opened on the very descriptor it is meant for
+            output.push_str(&format!("{}close(${}) if fileno(${}) != {};\n", indent, fh, fh, fd));
+        }
+    }
+    output
+}
+
+/// Move the descriptor open on `$fh` to a fresh one from 10 up, kept in the
+/// `{var}` redirect's variable.
+fn store_descriptor(generator: &mut Generator, name: &str, fh: &str) -> String {
+    let indent = generator.indent();
+    let declare = if generator.declared_locals.insert(name.to_string()) { "my " } else { "" };
+    let mut output = format!("{}require Fcntl;\n", indent);
+    output.push_str(&format!("{}{}${} = fcntl(${}, Fcntl::F_DUPFD(), 10) + 0;\n", indent, declare, name, fh));
+    output.push_str(&format!("{}close(${});\n", indent, fh));
+    output
+}
+
+/// `<(cmd)` or `>(cmd)`: fork the translated command on a pipe and stand in
+/// `/dev/fd/N` for its other end. The command that uses the path waits for
+/// the child when it is done (see `generate_command_impl`).
+pub fn generate_process_substitution_impl(generator: &mut Generator, cmd: &Command, direction: ProcessDirection) -> String {
+    generator.uses_process_subs = true;
+    generator.pending_process_subs += 1;
+    let mode = match direction {
+        ProcessDirection::Input => "-|",
+        ProcessDirection::Output => "|-",
+    };
+    // The child's variables stay in the child
+    let declared = generator.declared_locals.clone();
+    let outer_indent = generator.indent();
+    generator.indent_level += 1;
+    let body = match cmd {
+        Command::Simple(simple, _) if runs_external_command(generator, simple) => {
+            // The child only has to become the command, whose output goes
+            // straight to the pipe
+            let indent = generator.indent();
+            let arguments = super::commands::simple_commands::system_arguments(generator, simple);
+            format!("{0}{{ no warnings 'exec'; exec({1}); }}\n{0}print STDERR \"$0: {2}: command not found\\n\";\n{0}POSIX::_exit(127);\n",
+                    indent, arguments, generator.escape_perl_string(&simple.name.to_string()))
+        }
+        _ => generator.generate_command(cmd),
+    };
+    generator.indent_level -= 1;
+    generator.declared_locals = declared;
+    format!("process_substitution('{}', sub {{\n{}{}}})", mode, body, outer_indent)
+}
+
+/// Whether a command runs a program rather than a function or a builtin
+/// the script itself carries out.
+fn runs_external_command(generator: &Generator, cmd: &SimpleCommand) -> bool {
+    if !cmd.redirects.is_empty() || !cmd.env_vars.is_empty() {
+        return false;
+    }
+    match &cmd.name {
+        Word::Literal(name, _) => {
+            !generator.declared_functions.contains(name)
+                && !matches!(name.as_str(), "echo" | "printf" | "read" | "cd" | "true" | "false" | ":" | "test" | "[")
+        }
+        _ => false,
+    }
+}
+
+/// `exec` without a command changes the script's own descriptors for good.
+/// Descriptors 0 to 2 are rewired in place; higher ones are Perl filehandles
+/// kept in `%fd_table`, which later `>&N` redirects look up.
+pub fn generate_exec_command_impl(generator: &mut Generator, cmd: &BuiltinCommand, redirects: &[Redirect]) -> String {
+    generator.uses_fd_table = true;
+    let indent = generator.indent();
     let mut output = String::new();
-    
-    match redirect.operator {
-        RedirectOperator::Input => {
-            // Input redirection: command < file
-            output.push_str(&format!("open(STDIN, '<', '{}') or die \"Cannot open file: $!\\n\";\n", redirect.target));
-        }
-        RedirectOperator::Output => {
-            // Output redirection: command > file
-            // Note: This function doesn't have access to the command name, so it can't handle echo specially
-            // The special handling is done in generate_simple_command
-            output.push_str(&format!("open(STDOUT, '>', '{}') or die \"Cannot open file: $!\\n\";\n", redirect.target));
-        }
-        RedirectOperator::Append => {
-            // Append redirection: command >> file
-            output.push_str(&format!("open(STDOUT, '>>', '{}') or die \"Cannot open file: $!\\n\";\n", redirect.target));
-        }
-        RedirectOperator::Heredoc | RedirectOperator::HeredocTabs => {
-            // Heredoc: command << delimiter
-            if let Some(body) = &redirect.heredoc_body {
-                // Create a temporary file with the heredoc content
-                output.push_str(&format!("my $temp_content = {};\n", generator.perl_string_literal(&Word::Literal(body.clone()))));
-                let fh = generator.get_unique_file_handle();
-                output.push_str(&format!("open(my ${}, '>', '/tmp/heredoc_temp') or die \"Cannot create temp file: $!\\n\";\n", fh));
-                output.push_str(&format!("print {} $temp_content;\n", fh));
-                output.push_str(&format!("close({});\n", fh));
-                output.push_str("open(STDIN, '<', '/tmp/heredoc_temp') or die \"Cannot open temp file: $!\\n\";\n");
+    output.push_str(&format!("{}require POSIX;\n", indent));
+    output.push_str(&format!("{}STDOUT->flush(); STDERR->flush();\n", indent));
+    for redirect in redirects {
+        let fd = redirect.descriptor();
+        if redirect.fd_var.is_some() || fd <= 2 || matches!(redirect.operator, RedirectOperator::Close) {
+            output.push_str(&generator.generate_redirect(redirect));
+            continue;
+        }
+        let fh = generator.get_unique_file_handle();
+        match redirect.operator {
+            RedirectOperator::DuplicateInput | RedirectOperator::DuplicateOutput => {
+                let mode = if matches!(redirect.operator, RedirectOperator::DuplicateInput) { "<&" } else { ">&" };
+                output.push_str(&format!("{}open(my ${}, '{}', {}) or die \"{}: Bad file descriptor\\n\";\n",
+                                         indent, fh, mode, duplicate_source(generator, redirect),
+                                         generator.escape_perl_string(&redirect.target.to_string())));
+                if let Some(moved) = moved_descriptor(redirect) {
+                    output.push_str(&close_descriptor(generator, &moved.to_string()));
+                }
             }
+            _ => output.push_str(&open_redirect_target(generator, redirect, &fh)),
+        }
+        output.push_str(&format!("{}$fd_table{{{}}} = ${};\n", indent, fd, fh));
+    }
+    if !cmd.args.is_empty() {
+        // With a command, exec replaces the script altogether
+        let args: Vec<String> = cmd.args.iter().map(|arg| generator.perl_string_literal(arg)).collect();
+        output.push_str(&format!("{}exec({}) or die \"{}: $!\\n\";\n",
+                                 indent, args.join(", "), generator.escape_perl_string(&cmd.args[0].to_string())));
+    }
+    output
+}
+
+/// The descriptor `N>&M` copies, looked up in `%fd_table` once `exec` may
+/// have put it there.
+fn duplicate_source(generator: &mut Generator, redirect: &Redirect) -> String {
+    let source = match &redirect.target {
+        Word::Literal(text, _) => text.trim_end_matches('-').to_string(),
+        word => generator.word_to_perl(word),
+    };
+    // Descriptors 0 to 2 never live in the table
+    let standard = source.parse::<i32>().is_ok_and(|fd| fd <= 2);
+    if generator.uses_fd_table && !standard {
+        format!("(exists $fd_table{{{0}}} ? fileno($fd_table{{{0}}}) : {0})", source)
+    } else {
+        source
+    }
+}
+
+fn close_descriptor(generator: &mut Generator, fd: &str) -> String {
+    let indent = generator.indent();
+    if generator.uses_fd_table {
+        format!("{}if (my $fh = delete $fd_table{{{}}}) {{ close($fh); }} else {{ POSIX::close({}); }}\n", indent, fd, fd)
+    } else {
+        format!("{}POSIX::close({});\n", indent, fd)
+    }
+}
+
+/// Open `$fh` on the file, heredoc or here-string a redirect reads or writes.
+fn open_redirect_target(generator: &mut Generator, redirect: &Redirect, fh: &str) -> String {
+    let indent = generator.indent();
+    let target = match &redirect.target {
+        Word::Literal(text, _) => format!("\"{}\"", generator.escape_perl_string(text)),
+        word => generator.word_to_perl(word),
+    };
+    match redirect.operator {
+        RedirectOperator::InputOutput => {
+            // `<>` creates the file if need be, which `+<` would not
+            format!("{}sysopen(my ${}, {}, POSIX::O_RDWR() | POSIX::O_CREAT()) or die \"Cannot open file: $!\\n\";\n",
+                    indent, fh, target)
+        }
+        RedirectOperator::Heredoc | RedirectOperator::HeredocTabs | RedirectOperator::HereString => {
+            // The text is fed through a pipe straight from memory
+            generator.uses_here_documents = true;
+            let text = match redirect.operator {
+                RedirectOperator::HereString => format!("{} . \"\\n\"", target),
+                _ => heredoc_to_perl(generator, redirect),
+            };
+            format!("{}my ${} = here_document({});\n", indent, fh, text)
         }
         _ => {
-            // Other redirects not yet implemented
-            output.push_str(&format!("# Redirect {:?} not yet implemented\n", redirect.operator));
+            let mode = match redirect.operator {
+                RedirectOperator::Input => "<",
+                RedirectOperator::Append | RedirectOperator::AppendAll => ">>",
+                _ => ">",
+            };
+            format!("{}open(my ${}, '{}', {}) or die \"Cannot open file: $!\\n\";\n", indent, fh, mode, target)
         }
     }
-    
-    output
+}
+
+/// The text a heredoc redirect feeds in, as a Perl expression.
+pub fn heredoc_to_perl(generator: &mut Generator, redirect: &Redirect) -> String {
+    let parts = match redirect.heredoc.as_ref().map(|heredoc| &heredoc.body) {
+        Some(Word::StringInterpolation(interp, _)) => interp.parts.clone(),
+        Some(Word::Literal(text, _)) => vec![StringPart::Literal(text.replace('\\', "\\\\"))],
+        _ => Vec::new(),
+    };
+    let pieces: Vec<String> = parts.into_iter().map(|part| match part {
+        // Heredoc text can hold anything, so spell out every character
+        StringPart::Literal(text) => {
+            let text = generator.escape_perl_string(&unescape_double_quoted(&text));
+            format!("\"{}\"", text.replace('$', "\\$").replace('@', "\\@"))
+        }
+        part => generator.convert_string_interpolation_to_perl(&StringInterpolation { parts: vec![part] }),
+    }).collect();
+    if pieces.is_empty() {
+        return "\"\"".to_string();
+    }
+    pieces.join(" . ")
 }
 
 pub fn generate_shopt_command_impl(_generator: &mut Generator, cmd: &ShoptCommand) -> String {
@@ -62,13 +309,15 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
     let has_env = !cmd.env_vars.is_empty();
     if has_env {
         output.push_str("{\n");
-        for (var, value) in &cmd.env_vars {
+        for assignment in &cmd.env_vars {
+            let (var, value) = (&assignment.name, &assignment.value);
             // Check if this is an associative array assignment like map[foo]=bar
-            if let Some((array_name, key)) = generator.extract_array_key(var) {
+            if let Some(key) = &assignment.index {
+                let array_name = var;
                 let val = generator.perl_string_literal(value);
                 // For associative array assignments, generate $array{key} = value instead of $ENV{var}
                 output.push_str(&format!("${}{{{}}} = {};\n", array_name, key, val));
-            } else if let Word::Literal(s) = value {
+            } else if let Word::Literal(s, _) = value {
                 if let Some(elements) = generator.extract_array_elements(s) {
                     // Check if this is an indexed array assignment like arr=(one two three)
                     let elements_perl: Vec<String> = elements.iter()
@@ -104,34 +353,17 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
     }
     
     // Generate the builtin command
+    let mut words = vec![Word::Literal(cmd.name.clone(), Span::default())];
+    words.extend(cmd.args.iter().cloned());
+    output.push_str(&generator.traced_command(&words));
     match cmd.name.as_str() {
         "set" => {
-            // Convert shell set options to Perl equivalents
-            for arg in &cmd.args {
-                if let Word::Literal(opt) = arg {
-                    match opt.as_str() {
-                        "-e" => output.push_str("$SIG{__DIE__} = sub { exit 1 };\n"),
-                        "-u" => output.push_str("use strict;\n"),
-                        "-o" => {
-                            // Handle pipefail and other options
-                            if let Some(next_arg) = cmd.args.get(cmd.args.iter().position(|a| a == arg).unwrap() + 1) {
-                                if let Word::Literal(opt_name) = next_arg {
-                                    match opt_name.as_str() {
-                                        "pipefail" => output.push_str("# set -o pipefail not implemented in Perl\n"),
-                                        _ => output.push_str(&format!("# set -o {} not implemented\n", opt_name)),
-                                    }
-                                }
-                            }
-                        }
-                        _ => output.push_str(&format!("# set {} not implemented\n", opt)),
-                    }
-                }
-            }
+            output.push_str(&super::commands::set::generate_set_command(generator, cmd));
         }
         "unset" => {
             // Handle unset command
             for arg in &cmd.args {
-                if let Word::Literal(var_name) = arg {
+                if let Word::Literal(var_name, _) = arg {
                     if let Some((array_name, key)) = generator.extract_array_key(var_name) {
                         // Unset array element
                         output.push_str(&format!("delete ${}{{{}}};\n", array_name, key));
@@ -145,35 +377,56 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
         }
         "export" => {
             // Handle export command
-            for arg in &cmd.args {
-                if let Word::Literal(var_name) = arg {
+            for arg in cmd.args.iter().filter(|arg| !is_option(arg)) {
+                if let Word::Literal(var_name, _) = arg {
                     if let Some((array_name, key)) = generator.extract_array_key(var_name) {
                         // Export array element
                         output.push_str(&format!("$ENV{{{}}} = ${}{{{}}};\n", var_name, array_name, key));
-                    } else {
-                        // Export variable
-                        output.push_str(&format!("$ENV{{{}}} = ${};\n", var_name, var_name));
+                        continue;
                     }
                 }
+                match declaration_operand(arg) {
+                    // `export name=value` assigns as well
+                    Some((name, Some(value))) => {
+                        output.push_str(&declare_variable(generator, &name, Some(&value), false));
+                        output.push_str(&format!("$ENV{{{}}} = ${};\n", name, name));
+                    }
+                    // Export variable
+                    Some((name, None)) => output.push_str(&format!("$ENV{{{}}} = ${};\n", name, name)),
+                    None => {}
+                }
+            }
+        }
+        "local" => {
+            // A function's own variables
+            for arg in cmd.args.iter().filter(|arg| !is_option(arg)) {
+                if let Some((name, value)) = declaration_operand(arg) {
+                    output.push_str(&declare_variable(generator, &name, value.as_ref(), true));
+                }
             }
         }
         "readonly" => {
             // Handle readonly command (not directly supported in Perl)
-            for arg in &cmd.args {
-                if let Word::Literal(var_name) = arg {
-                    output.push_str(&format!("# readonly {} not implemented in Perl\n", var_name));
+            for arg in cmd.args.iter().filter(|arg| !is_option(arg)) {
+                match declaration_operand(arg) {
+                    Some((name, Some(value))) => output.push_str(&declare_variable(generator, &name, Some(&value), false)),
+                    _ => output.push_str(&format!("# readonly {} not implemented in Perl\n", arg)),
                 }
             }
         }
-        "declare" => {
+        "declare" | "typeset" => {
             // Handle declare command
             for arg in &cmd.args {
-                if let Word::Literal(opt) = arg {
+                if let Some((name, Some(value))) = declaration_operand(arg).filter(|_| !is_option(arg)) {
+                    // Inside a function the variable is the function's own
+                    let scoped = generator.in_function;
+                    output.push_str(&declare_variable(generator, &name, Some(&value), scoped));
+                } else if let Word::Literal(opt, _) = arg {
                     match opt.as_str() {
                         "-a" => {
                             // Declare array
                             if let Some(next_arg) = cmd.args.get(cmd.args.iter().position(|a| a == arg).unwrap() + 1) {
-                                if let Word::Literal(var_name) = next_arg {
+                                if let Word::Literal(var_name, _) = next_arg {
                                     if !generator.declared_locals.contains(var_name) {
                                         output.push_str(&format!("my @{} = ();\n", var_name));
                                         generator.declared_locals.insert(var_name.clone());
@@ -184,7 +437,7 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
                         "-A" => {
                             // Declare associative array
                             if let Some(next_arg) = cmd.args.get(cmd.args.iter().position(|a| a == arg).unwrap() + 1) {
-                                if let Word::Literal(var_name) = next_arg {
+                                if let Word::Literal(var_name, _) = next_arg {
                                     if !generator.declared_locals.contains(var_name) {
                                         output.push_str(&format!("my %{} = ();\n", var_name));
                                         generator.declared_locals.insert(var_name.clone());
@@ -197,6 +450,32 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
                 }
             }
         }
+        "exec" => {
+            output.push_str(&generate_exec_command_impl(generator, cmd, &[]));
+        }
+        "trap" => {
+            output.push_str(&super::commands::trap::generate_trap_command(generator, cmd));
+        }
+        "exit" => {
+            match cmd.args.first() {
+                Some(status) => {
+                    let status = generator.word_to_perl(status);
+                    output.push_str(&format!("exit({});\n", status));
+                }
+                None => output.push_str("exit;\n"),
+            }
+        }
+        "shift" => {
+            // Shifts the script's arguments, or the function's inside one
+            let array = generator.positional_parameters();
+            match cmd.args.first() {
+                Some(count) => {
+                    let count = generator.word_to_perl(count);
+                    output.push_str(&format!("splice({}, 0, {});\n", array, count));
+                }
+                None => output.push_str(&format!("shift({});\n", array)),
+            }
+        }
         _ => {
             // Other builtin commands
             output.push_str(&format!("# Builtin command '{}' not implemented\n", cmd.name));
@@ -210,6 +489,46 @@ pub fn generate_builtin_command_impl(generator: &mut Generator, cmd: &BuiltinCom
     output
 }
 
+fn is_option(word: &Word) -> bool {
+    matches!(word, Word::Literal(text, _) if text.len() > 1 && (text.starts_with('-') || text.starts_with('+')))
+}
+
+/// A `name` or `name=value` operand of `export`, `local` and the like. The
+/// value may be any word, as in `local b="$1"`.
+fn declaration_operand(word: &Word) -> Option<(String, Option<Word>)> {
+    let interp = match word {
+        Word::Literal(text, _) => {
+            return Some(match text.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(Word::Literal(value.to_string(), Span::default()))),
+                None => (text.clone(), None),
+            });
+        }
+        Word::Concatenation(words, _) => concatenated_parts(words),
+        Word::StringInterpolation(interp, _) => interp.clone(),
+        _ => return None,
+    };
+    let Some(StringPart::Literal(first)) = interp.parts.first() else { return None };
+    let (name, rest) = first.split_once('=')?;
+    let mut parts = interp.parts.clone();
+    parts[0] = StringPart::Literal(rest.to_string());
+    Some((name.to_string(), Some(Word::StringInterpolation(StringInterpolation { parts }, Span::default()))))
+}
+
+/// Assigns a declared variable, declaring it with `my` the first time or,
+/// when `scoped`, every time, so that a function's variable hides the
+/// script's.
+fn declare_variable(generator: &mut Generator, name: &str, value: Option<&Word>, scoped: bool) -> String {
+    let declare = generator.declared_locals.insert(name.to_string()) || scoped;
+    match value {
+        Some(value) => {
+            let value = generator.perl_string_literal(value);
+            format!("{}${} = {};\n", if declare { "my " } else { "" }, name, value)
+        }
+        None if declare => format!("my ${};\n", name),
+        None => String::new(),
+    }
+}
+
 // Helper method for escaping Perl strings
 pub fn escape_perl_string(s: &str) -> String {
     s.replace("\\", "\\\\")
@@ -218,3 +537,49 @@ pub fn escape_perl_string(s: &str) -> String {
      .replace("\t", "\\t")
      .replace("\r", "\\r")
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_declarations_take_any_value() {
+        let script = "f() { local v=\"$1\" w=a${1}; echo \"$v $w\"; }\nf one\nx=2\nexport V=$x\nreadonly R=\"$x\"$x\necho $V $R\nsh -c 'echo $V'";
+        assert_eq!(run_perl(script, ""), ("one aone\n2 22\n2\n".to_string(), 0));
+    }
+
+    #[test]
+    fn test_close_descriptor_variable() {
+        let path = std::env::temp_dir().join(format!("debashc-fd-var-{}.txt", std::process::id()));
+        let script = format!("exec {{log}}>{0}\nexec {{log}}>&-\necho still stdout\ntest -s {0} || echo empty", path.display());
+        assert_eq!(run_perl(&script, "").0, "still stdout\nempty\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_exec_descriptor_round_trip() {
+        let path = std::env::temp_dir().join(format!("debashc-exec-{}.txt", std::process::id()));
+        let script = format!("exec {{out}}>{0}\necho first >&$out\nexec {{out}}>&-\nexec 3>>{0}\necho second >&3\nexec 3>&-\n\
+                              exec {{in}}<{0}\nread -r a <&$in\nread -r b <&$in\nexec {{in}}<&-\necho \"$a $b\"", path.display());
+        assert_eq!(run_perl(&script, "").0, "first second\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_process_substitution_arguments() {
+        let dir = std::env::temp_dir();
+        let (a, b) = (dir.join(format!("debashc-ps-a-{}", std::process::id())), dir.join(format!("debashc-ps-b-{}", std::process::id())));
+        std::fs::write(&a, "b\na\nc\n").unwrap();
+        std::fs::write(&b, "c\nb\nd\n").unwrap();
+        let script = format!("diff <(sort {0}) <(sort {1})\ncomm -3 <(sort {0}) <(sort {1})", a.display(), b.display());
+        assert_eq!(run_perl(&script, "").0, "1d0\n< a\n3a3\n> d\na\n\td\n");
+        let _ = std::fs::remove_file(&a);
+        let _ = std::fs::remove_file(&b);
+    }
+
+    #[test]
+    fn test_duplicate_stderr_in_earlier_stage() {
+        let script = "sh -c 'echo out; echo err >&2' 2>&1 | sort | cat\nls /nonexistent/debashc 2>&1 | cat | wc -l";
+        assert_eq!(run_perl(script, "").0, "err\nout\n1\n");
+    }
+}
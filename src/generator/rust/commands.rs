@@ -0,0 +1,467 @@
+use crate::ast::*;
+use super::RustGenerator;
+use super::test_expressions::test_words_to_rust;
+use super::words::{array_to_rust, arithmetic_to_rust, raw_text_to_rust, rust_string_literal, sh_call, string_to_rust};
+
+pub fn generate_command_impl(generator: &mut RustGenerator, command: &Command) -> String {
+    match command {
+        Command::Simple(cmd, _) => generator.generate_simple_command(cmd),
+        Command::BuiltinCommand(cmd, _) => generator.generate_builtin_command(cmd),
+        Command::ShoptCommand(cmd, _) => generator.generate_shopt_command(cmd),
+        Command::TestExpression(test_expr, _) => generator.generate_test_expression(test_expr),
+        Command::Pipeline(pipeline, _) => generator.generate_pipeline(pipeline),
+        Command::If(if_stmt, _) => generator.generate_if_statement(if_stmt),
+        Command::Case(case_stmt, _) => generator.generate_case_statement(case_stmt),
+        Command::While(while_loop, _) => generator.generate_while_loop(while_loop),
+        Command::For(for_loop, _) => generator.generate_for_loop(for_loop),
+        Command::Select(select_loop, _) => generator.generate_select_loop(select_loop),
+        Command::ArithmeticFor(arith_for, _) => generator.generate_arithmetic_for(arith_for),
+        Command::Function(func, _) => generator.generate_function(func),
+        Command::Subshell(cmd, _) => generator.generate_subshell(cmd),
+        Command::Background(cmd, _) => generator.generate_background(cmd),
+        Command::Block(block, _) => generator.generate_block(block),
+        Command::Break(level, _) => generator.generate_break_statement(level),
+        Command::Continue(level, _) => generator.generate_continue_statement(level),
+        Command::Return(value, _) => generator.generate_return_statement(value),
+        Command::BlankLine(_) => "\n".to_string(),
+        Command::Error(text, _) => {
+            let indent = generator.indent();
+            format!("{}// Not translated (parse error): {}\n", indent, text.lines().collect::<Vec<_>>().join(&format!("\n{}// ", indent)))
+        }
+        Command::Redirect(..) => format!("{}{};\n", generator.indent(), generator.command_expression(command)),
+    }
+}
+
+/// Render a command as a Rust expression that runs it and evaluates to its
+/// exit status as an `i32`.
+pub fn command_expression_impl(generator: &mut RustGenerator, command: &Command) -> String {
+    match command {
+        Command::Simple(cmd, _) => simple_command_expression(generator, cmd),
+        Command::BuiltinCommand(cmd, _) => builtin_command_expression(generator, cmd),
+        Command::TestExpression(test_expr, _) => generator.test_expression_call(test_expr),
+        Command::Pipeline(pipeline, _) => pipeline_expression(generator, pipeline),
+        Command::Subshell(cmd, _) => format!("sh.subshell({})", generator.closure(cmd)),
+        Command::Background(cmd, _) => format!("sh.background({})", generator.closure(cmd)),
+        Command::Block(block, _) if block.commands.len() == 1 => generator.command_expression(&block.commands[0]),
+        Command::ShoptCommand(..) => "sh.set_status(0)".to_string(),
+        Command::Redirect(redirect_cmd, _) => {
+            let redirects: Vec<String> = redirect_cmd.redirects.iter()
+                .map(|redirect| generator.generate_redirect(redirect))
+                .collect();
+            let inner = generator.closure(&redirect_cmd.command);
+            // `exec` keeps its redirects for the rest of the program
+            let method = match &*redirect_cmd.command {
+                Command::BuiltinCommand(cmd, _) if cmd.name == "exec" => "exec_redirect",
+                _ => "redirect",
+            };
+            sh_call(method, vec![format!("vec![{}]", redirects.join(", ")), inner])
+        }
+        _ => block_expression(generator, command),
+    }
+}
+
+/// Compound commands become a block that runs them as statements and then
+/// evaluates to the resulting status.
+fn block_expression(generator: &mut RustGenerator, command: &Command) -> String {
+    let mut output = "{\n".to_string();
+    generator.indent_level += 1;
+    output.push_str(&generator.generate_command(command));
+    output.push_str(&format!("{}sh.status()\n", generator.indent()));
+    generator.indent_level -= 1;
+    output.push_str(&format!("{}}}", generator.indent()));
+    output
+}
+
+/// A closure running `command` for the runtime helpers that take a body.
+/// Loop labels are not visible inside a closure, so they are hidden while
+/// its body is generated.
+pub fn closure_impl(generator: &mut RustGenerator, command: &Command) -> String {
+    let labels = std::mem::take(&mut generator.loop_labels);
+    let body = generator.command_expression(command);
+    generator.loop_labels = labels;
+    format!("|sh: &mut Sh| -> i32 {{ {} }}", body)
+}
+
+pub fn generate_simple_command_impl(generator: &mut RustGenerator, cmd: &SimpleCommand) -> String {
+    if is_assignment_only(cmd) {
+        let mut output = String::new();
+        for assignment in &cmd.env_vars {
+            output.push_str(&generator.indent());
+            output.push_str(&assignment_statement(generator, assignment));
+            output.push_str(";\n");
+        }
+        return output;
+    }
+    format!("{}{};\n", generator.indent(), simple_command_expression(generator, cmd))
+}
+
+fn simple_command_expression(generator: &mut RustGenerator, cmd: &SimpleCommand) -> String {
+    if is_assignment_only(cmd) {
+        return assignments_expression(generator, &cmd.env_vars);
+    }
+    // `if (( expr ))` arrives as `test` given the arithmetic word
+    let arithmetic = match (&cmd.name, cmd.args.as_slice()) {
+        (Word::Arithmetic(expr, _), []) => Some(expr),
+        (name, [Word::Arithmetic(expr, _)]) if name.as_literal() == Some("test") => Some(expr),
+        _ => None,
+    };
+    if let Some(expr) = arithmetic {
+        // `(( expr ))` succeeds when the expression is non-zero
+        let value = arithmetic_to_rust(&expr.expression);
+        return format!("{{ let value = {}; sh.set_status(if value != 0 {{ 0 }} else {{ 1 }}) }}", value);
+    }
+
+    let name = match &cmd.name {
+        Word::Literal(s, _) => s.as_str(),
+        _ => "",
+    };
+    let args = generator.words_to_rust_vec(&cmd.args);
+    let expr = match name {
+        "echo" => sh_call("echo", vec![args]),
+        "printf" => sh_call("printf", vec![args]),
+        "cd" => sh_call("cd", vec![args]),
+        "pwd" => "sh.pwd()".to_string(),
+        "true" | ":" => "sh.set_status(0)".to_string(),
+        "false" => "sh.set_status(1)".to_string(),
+        "test" => sh_call("test", vec![args, "false".to_string()]),
+        "[" => {
+            // The parser captures everything up to the closing bracket as raw text
+            let raw: Vec<String> = cmd.args.iter().map(|arg| arg.to_string()).collect();
+            sh_call("test", vec![test_words_to_rust(&raw.join(" ")), "false".to_string()])
+        }
+        "read" => sh_call("read", vec![args]),
+        "exit" => exit_expression(generator, cmd.args.first()),
+        "wait" => "sh.wait()".to_string(),
+        _ => {
+            let mut argv = generator.word_to_rust_args(&cmd.name);
+            argv.extend(cmd.args.iter().flat_map(|arg| generator.word_to_rust_args(arg)));
+            let argv = super::words::args_to_vec(argv);
+            if cmd.env_vars.is_empty() {
+                sh_call("run", vec![argv])
+            } else {
+                // External commands also see the assignments in their environment
+                sh_call("run_env", vec![argv, env_pairs(generator, &cmd.env_vars)])
+            }
+        }
+    };
+
+    let expr = with_assignments(generator, &cmd.env_vars, expr);
+    wrap_redirects(generator, &cmd.redirects, expr)
+}
+
+fn exit_expression(generator: &mut RustGenerator, code: Option<&Word>) -> String {
+    match code {
+        Some(code) => sh_call("exit", vec![format!("Some({})", generator.word_to_rust(code))]),
+        None => "sh.exit(None)".to_string(),
+    }
+}
+
+/// The parser cannot tell `a=1 cmd` from `a=1` on the line before `cmd`, so
+/// like the Perl backend we keep the assignments in the current scope.
+fn with_assignments(generator: &mut RustGenerator, env_vars: &[Assignment], expr: String) -> String {
+    if env_vars.is_empty() {
+        return expr;
+    }
+    let statements: Vec<String> = env_vars.iter()
+        .map(|assignment| assignment_statement(generator, assignment))
+        .collect();
+    format!("{{ {}; {} }}", statements.join("; "), expr)
+}
+
+fn wrap_redirects(generator: &mut RustGenerator, redirects: &[Redirect], expr: String) -> String {
+    if redirects.is_empty() {
+        return expr;
+    }
+    let redirects: Vec<String> = redirects.iter()
+        .map(|redirect| generator.generate_redirect(redirect))
+        .collect();
+    sh_call("redirect", vec![format!("vec![{}]", redirects.join(", ")),
+                             format!("|sh: &mut Sh| -> i32 {{ {} }}", expr)])
+}
+
+fn is_assignment_only(cmd: &SimpleCommand) -> bool {
+    cmd.name == "true" && cmd.args.is_empty() && !cmd.env_vars.is_empty()
+}
+
+fn assignment_statement(generator: &mut RustGenerator, assignment: &Assignment) -> String {
+    let name = rust_string_literal(&assignment.name);
+    if let Some(key) = &assignment.index {
+        let value = generator.word_to_rust(&assignment.value);
+        return sh_call("set_index", vec![name, raw_text_to_rust(key), value]);
+    }
+    match &assignment.value {
+        Word::Array(_, elements, _) => sh_call("set_array", vec![name, array_to_rust(elements)]),
+        value => {
+            let value = generator.word_to_rust(value);
+            sh_call("set_var", vec![name, value])
+        }
+    }
+}
+
+fn assignments_expression(generator: &mut RustGenerator, env_vars: &[Assignment]) -> String {
+    let statements: Vec<String> = env_vars.iter()
+        .map(|assignment| assignment_statement(generator, assignment))
+        .collect();
+    format!("{{ {}; sh.set_status(0) }}", statements.join("; "))
+}
+
+fn env_pairs(generator: &mut RustGenerator, env_vars: &[Assignment]) -> String {
+    let entries: Vec<String> = env_vars.iter()
+        .filter(|assignment| assignment.index.is_none())
+        .map(|assignment| format!("({}, {})", rust_string_literal(&assignment.name),
+                                  generator.word_to_rust(&assignment.value)))
+        .collect();
+    format!("vec![{}]", entries.join(", "))
+}
+
+pub fn generate_builtin_command_impl(generator: &mut RustGenerator, cmd: &BuiltinCommand) -> String {
+    let indent = generator.indent();
+    match cmd.name.as_str() {
+        "return" if generator.function_depth > 0 => {
+            format!("{}return {};\n", indent, return_expression(generator, cmd.args.first()))
+        }
+        "break" => generator.generate_break_statement(&None),
+        "continue" => generator.generate_continue_statement(&None),
+        name if !is_supported_builtin(name) => {
+            format!("{}sh.set_status(0); // Builtin command '{}' not implemented\n", indent, name)
+        }
+        _ => format!("{}{};\n", indent, builtin_command_expression(generator, cmd)),
+    }
+}
+
+pub fn return_expression(generator: &mut RustGenerator, code: Option<&Word>) -> String {
+    match code {
+        Some(code) => sh_call("ret", vec![format!("Some({})", generator.word_to_rust(code))]),
+        None => "sh.ret(None)".to_string(),
+    }
+}
+
+fn is_supported_builtin(name: &str) -> bool {
+    matches!(name,
+        "set" | "unset" | "export" | "readonly" | "declare" | "typeset" | "local" |
+        "shift" | "wait" | "shopt" | "exit" | "return" | "break" | "continue" | "exec"
+    )
+}
+
+fn builtin_command_expression(generator: &mut RustGenerator, cmd: &BuiltinCommand) -> String {
+    let expr = match cmd.name.as_str() {
+        "export" | "local" | "declare" | "typeset" | "readonly" => declaration_expression(generator, cmd),
+        "unset" => sh_call("unset", vec![generator.words_to_rust_vec(&cmd.args)]),
+        "shift" => match cmd.args.first() {
+            Some(n) => sh_call("shift", vec![format!("num(&{})", generator.word_to_rust(n))]),
+            None => "sh.shift(1)".to_string(),
+        },
+        "set" => sh_call("set", vec![generator.words_to_rust_vec(&cmd.args)]),
+        "exit" | "return" => exit_expression(generator, cmd.args.first()),
+        "wait" => "sh.wait()".to_string(),
+        // The command takes over, and the program ends with its status
+        "exec" if !cmd.args.is_empty() => {
+            format!("{{ let code = sh.run({}); sh.exit(Some(code.to_string())) }}", generator.words_to_rust_vec(&cmd.args))
+        }
+        _ => "sh.set_status(0)".to_string(),
+    };
+
+    let expr = with_assignments(generator, &cmd.env_vars, expr);
+    wrap_redirects(generator, &cmd.redirects, expr)
+}
+
+/// `export`, `local`, `declare` and friends: collect `name[=value]` operands
+/// and hand each one to the matching runtime helper.
+fn declaration_expression(generator: &mut RustGenerator, cmd: &BuiltinCommand) -> String {
+    let mut flags = String::new();
+    let mut entries = Vec::new();
+    for arg in &cmd.args {
+        match arg {
+            Word::Literal(s, _) if (s.starts_with('-') || s.starts_with('+')) && s.len() > 1 => {
+                flags.push_str(&s[1..]);
+            }
+            _ => {
+                if let Some((name, value)) = split_declaration(generator, arg) {
+                    entries.push((name, value));
+                }
+            }
+        }
+    }
+
+    let is_export = cmd.name == "export" || flags.contains('x');
+    let is_local = cmd.name == "local" || (generator.function_depth > 0 && cmd.name != "export" && cmd.name != "readonly");
+
+    let calls: Vec<String> = entries.into_iter()
+        .filter_map(|(name, value)| {
+            let value = value.or_else(|| {
+                if flags.contains('A') {
+                    Some("Value::Assoc(BTreeMap::new())".to_string())
+                } else if flags.contains('a') {
+                    Some("Value::Array(Vec::new())".to_string())
+                } else {
+                    None
+                }
+            });
+            let name = rust_string_literal(&name);
+            if is_export || is_local {
+                let method = if is_export { "export" } else { "local" };
+                let value = value.map(|v| format!("Some({})", v)).unwrap_or_else(|| "None".to_string());
+                return Some(sh_call(method, vec![name, value]));
+            }
+            value.map(|value| sh_call("set_value", vec![name, value]))
+        })
+        .collect();
+
+    match calls.len() {
+        0 => "sh.set_status(0)".to_string(),
+        1 => calls.into_iter().next().unwrap(),
+        _ => format!("{{ {}; sh.set_status(0) }}", calls.join("; ")),
+    }
+}
+
+/// Split a declaration operand into its name and an optional `Value` expression.
+fn split_declaration(generator: &mut RustGenerator, arg: &Word) -> Option<(String, Option<String>)> {
+    match arg {
+        Word::Literal(s, _) => match s.split_once('=') {
+            Some((name, value)) if value.starts_with('(') && value.ends_with(')') => {
+                let elements: Vec<String> = value[1..value.len() - 1].split_whitespace().map(String::from).collect();
+                Some((name.to_string(), Some(format!("Value::Array({})", array_to_rust(&elements)))))
+            }
+            Some((name, value)) => Some((name.to_string(), Some(format!("Value::Str({})", raw_text_to_rust(value))))),
+            None => Some((s.clone(), None)),
+        },
+        Word::StringInterpolation(interp, _) => {
+            // NAME="..." arrives as an interpolation whose first literal holds `NAME=`
+            let first = match interp.parts.first() {
+                Some(StringPart::Literal(s)) => s.clone(),
+                _ => return None,
+            };
+            let (name, rest) = first.split_once('=')?;
+            let mut parts = interp.parts.clone();
+            parts[0] = StringPart::Literal(rest.to_string());
+            let value = generator.word_to_rust(&Word::StringInterpolation(StringInterpolation { parts }, Span::default()));
+            Some((name.to_string(), Some(format!("Value::Str({})", value))))
+        }
+        Word::Concatenation(words, span) => {
+            split_declaration(generator, &Word::StringInterpolation(concatenated_parts(words), *span))
+        }
+        _ => None,
+    }
+}
+
+pub fn generate_shopt_command_impl(generator: &mut RustGenerator, cmd: &ShoptCommand) -> String {
+    format!("{}sh.set_status(0); // shopt -{} {}\n", generator.indent(),
+            if cmd.enable { "s" } else { "u" }, cmd.option)
+}
+
+pub fn generate_pipeline_impl(generator: &mut RustGenerator, pipeline: &Pipeline) -> String {
+    let segments = split_and_or(pipeline);
+    if segments.len() == 1 {
+        return format!("{}{};\n", generator.indent(), segment_expression(generator, &segments[0].1));
+    }
+
+    // `a && b || c` runs left to right, each step guarded by the previous status
+    let mut output = String::new();
+    for (i, (operator, commands)) in segments.iter().enumerate() {
+        if i == 0 {
+            continue;
+        }
+        let test = if matches!(operator, Some(PipeOperator::Or)) { "!=" } else { "==" };
+        let subject = if i == 1 {
+            segment_expression(generator, &segments[0].1)
+        } else {
+            "sh.status()".to_string()
+        };
+        output.push_str(&format!("{}if ({}) {} 0 {{\n", generator.indent(), subject, test));
+        generator.indent_level += 1;
+        if commands.len() == 1 {
+            output.push_str(&generator.generate_command(&commands[0]));
+        } else {
+            let expr = segment_expression(generator, commands);
+            output.push_str(&format!("{}{};\n", generator.indent(), expr));
+        }
+        generator.indent_level -= 1;
+        output.push_str(&format!("{}}}\n", generator.indent()));
+    }
+    output
+}
+
+fn pipeline_expression(generator: &mut RustGenerator, pipeline: &Pipeline) -> String {
+    let segments = split_and_or(pipeline);
+    let first = segment_expression(generator, &segments[0].1);
+    if segments.len() == 1 {
+        return first;
+    }
+    let mut output = format!("{{ let mut st = {};", first);
+    for (operator, commands) in &segments[1..] {
+        let test = if matches!(operator, Some(PipeOperator::Or)) { "!=" } else { "==" };
+        output.push_str(&format!(" if st {} 0 {{ st = {}; }}", test, segment_expression(generator, commands)));
+    }
+    output.push_str(" st }");
+    output
+}
+
+/// Split a pipeline into `|`-connected segments joined by `&&` / `||`.
+fn split_and_or(pipeline: &Pipeline) -> Vec<(Option<PipeOperator>, Vec<Command>)> {
+    let mut segments: Vec<(Option<PipeOperator>, Vec<Command>)> = Vec::new();
+    let mut current = vec![pipeline.commands[0].clone()];
+    let mut current_op = None;
+    for (i, command) in pipeline.commands.iter().enumerate().skip(1) {
+        match pipeline.operators.get(i - 1) {
+            Some(PipeOperator::Pipe) | None => current.push(command.clone()),
+            Some(op) => {
+                segments.push((current_op.take(), std::mem::take(&mut current)));
+                current_op = Some(op.clone());
+                current.push(command.clone());
+            }
+        }
+    }
+    segments.push((current_op, current));
+    segments
+}
+
+fn segment_expression(generator: &mut RustGenerator, commands: &[Command]) -> String {
+    if commands.len() == 1 {
+        return generator.command_expression(&commands[0]);
+    }
+    let stages: Vec<String> = commands.iter()
+        .map(|command| generator.closure(command))
+        .collect();
+    format!("sh.pipe(&[{}])", stages.join(", "))
+}
+
+pub fn generate_subshell_impl(generator: &mut RustGenerator, command: &Command) -> String {
+    let inner = generator.closure(command);
+    format!("{}sh.subshell({});\n", generator.indent(), inner)
+}
+
+pub fn generate_background_impl(generator: &mut RustGenerator, command: &Command) -> String {
+    let inner = generator.closure(command);
+    format!("{}sh.background({});\n", generator.indent(), inner)
+}
+
+/// Render a redirect as the `redir(fd, operator, target)` value consumed by
+/// `Sh::redirect`, or `redir_var` for a `{var}` redirect.
+pub fn generate_redirect_impl(generator: &mut RustGenerator, redirect: &Redirect) -> String {
+    let fd = redirect.descriptor();
+    let op = match &redirect.operator {
+        RedirectOperator::Input | RedirectOperator::InputOutput => "<",
+        RedirectOperator::Output => ">",
+        RedirectOperator::OutputClobber => ">|",
+        RedirectOperator::Append => ">>",
+        RedirectOperator::OutputAll => "&>",
+        RedirectOperator::AppendAll => "&>>",
+        RedirectOperator::DuplicateInput => "<&",
+        RedirectOperator::DuplicateOutput => ">&",
+        RedirectOperator::Close => return format!("redir({}, \"-\", s(\"\"))", fd),
+        RedirectOperator::Heredoc | RedirectOperator::HeredocTabs => {
+            let body = match redirect.heredoc.as_ref().map(|heredoc| &heredoc.body) {
+                Some(Word::Literal(text, _)) => string_to_rust(text),
+                Some(body) => generator.word_to_rust(body),
+                None => string_to_rust(""),
+            };
+            return format!("redir({}, \"<<\", {})", fd, body);
+        }
+        RedirectOperator::HereString => "<<<",
+    };
+    let target = generator.word_to_rust(&redirect.target);
+    match &redirect.fd_var {
+        Some(name) => format!("redir_var({}, \"{}\", {})", rust_string_literal(name), op, target),
+        None => format!("redir({}, \"{}\", {})", fd, op, target),
+    }
+}
@@ -0,0 +1,244 @@
+use crate::ast::*;
+use super::RustGenerator;
+use super::commands::return_expression;
+use super::words::{arithmetic_to_rust, rust_string_literal, string_to_rust};
+
+pub fn generate_if_statement_impl(generator: &mut RustGenerator, if_stmt: &IfStatement) -> String {
+    let mut output = String::new();
+    let condition = generator.command_expression(&if_stmt.condition);
+    output.push_str(&format!("{}if ({}) == 0 {{\n", generator.indent(), condition));
+    output.push_str(&branch_body(generator, &if_stmt.then_branch));
+
+    let mut else_branch = if_stmt.else_branch.as_deref();
+    while let Some(branch) = else_branch {
+        match branch {
+            Command::If(elif, _) => {
+                let condition = generator.command_expression(&elif.condition);
+                output.push_str(&format!("{}}} else if ({}) == 0 {{\n", generator.indent(), condition));
+                output.push_str(&branch_body(generator, &elif.then_branch));
+                else_branch = elif.else_branch.as_deref();
+            }
+            other => {
+                output.push_str(&format!("{}}} else {{\n", generator.indent()));
+                output.push_str(&branch_body(generator, other));
+                else_branch = None;
+            }
+        }
+    }
+    output.push_str(&format!("{}}}\n", generator.indent()));
+    output
+}
+
+pub fn generate_case_statement_impl(generator: &mut RustGenerator, case_stmt: &CaseStatement) -> String {
+    let mut output = String::new();
+    let subject = generator.next_helper_name("case");
+    let word = generator.word_to_rust(&case_stmt.word);
+    output.push_str(&format!("{}let {} = {};\n", generator.indent(), subject, word));
+
+    for (i, clause) in case_stmt.cases.iter().enumerate() {
+        let is_default = clause.patterns.len() == 1 && clause.patterns[0] == "*";
+        let prefix = if i == 0 { generator.indent() } else { format!("{}}} else ", generator.indent()) };
+        if is_default && i > 0 {
+            output.push_str(&format!("{}{{\n", prefix));
+        } else {
+            let tests: Vec<String> = clause.patterns.iter()
+                .map(|pattern| format!("glob_match(&{}, &{})", case_pattern_to_rust(generator, pattern), subject))
+                .collect();
+            output.push_str(&format!("{}if {} {{\n", prefix, tests.join(" || ")));
+        }
+        generator.indent_level += 1;
+        output.push_str(&generator.generate_block_body(&clause.body));
+        generator.indent_level -= 1;
+        if is_default {
+            break;
+        }
+    }
+    if !case_stmt.cases.is_empty() {
+        output.push_str(&format!("{}}}\n", generator.indent()));
+    }
+    output
+}
+
+fn case_pattern_to_rust(generator: &mut RustGenerator, pattern: &Word) -> String {
+    match pattern {
+        // Quoted parts of a pattern match literally
+        Word::StringInterpolation(..) => format!("glob_escape({})", generator.word_to_rust(pattern)),
+        Word::Literal(s, _) if s.starts_with('\'') && s.ends_with('\'') && s.len() >= 2 => {
+            format!("glob_escape({})", string_to_rust(&s[1..s.len() - 1]))
+        }
+        _ => generator.word_to_rust(pattern),
+    }
+}
+
+pub fn generate_while_loop_impl(generator: &mut RustGenerator, while_loop: &WhileLoop) -> String {
+    let mut output = String::new();
+    let label = generator.next_loop_label();
+    // The loop's status is that of the last body command, not the failed condition
+    let status = format!("status_{}", label.trim_start_matches('\''));
+    let condition = generator.command_expression(&while_loop.condition);
+    output.push_str(&format!("{}let mut {} = 0;\n", generator.indent(), status));
+    let test = if while_loop.until { "!=" } else { "==" };
+    output.push_str(&format!("{}{}: while ({}) {} 0 {{\n", generator.indent(), label, condition, test));
+    let record = format!("{} = sh.status();", status);
+    output.push_str(&loop_body(generator, label, &while_loop.body.commands, Some(record)));
+    output.push_str(&format!("{}sh.set_status({});\n", generator.indent(), status));
+    output
+}
+
+pub fn generate_for_loop_impl(generator: &mut RustGenerator, for_loop: &ForLoop) -> String {
+    let mut output = String::new();
+    let label = generator.next_loop_label();
+    let items = if for_loop.items.is_empty() {
+        // `for x; do` iterates over the positional parameters
+        "sh.args_list()".to_string()
+    } else {
+        generator.words_to_rust_vec(&for_loop.items)
+    };
+    output.push_str(&format!("{}{}: for item in {} {{\n", generator.indent(), label, items));
+    output.push_str(&format!("{}    sh.set_var({}, item);\n", generator.indent(),
+                             rust_string_literal(&for_loop.variable)));
+    output.push_str(&loop_body(generator, label, &for_loop.body.commands, None));
+    output
+}
+
+pub fn generate_arithmetic_for_impl(generator: &mut RustGenerator, arith_for: &ArithmeticFor) -> String {
+    let mut output = String::new();
+    let label = generator.next_loop_label();
+    let first = generator.next_helper_name("first");
+    if !arith_for.init.expression.is_empty() {
+        output.push_str(&format!("{}{};\n", generator.indent(), arithmetic_to_rust(&arith_for.init.expression)));
+    }
+    // The step runs before every test but the first, so `continue` still steps
+    let step = if arith_for.step.expression.is_empty() {
+        String::new()
+    } else {
+        output.push_str(&format!("{}let mut {} = true;\n", generator.indent(), first));
+        format!(" if !std::mem::replace(&mut {}, false) {{ {}; }}", first, arithmetic_to_rust(&arith_for.step.expression))
+    };
+    let condition = if arith_for.condition.expression.is_empty() {
+        "true".to_string()
+    } else {
+        format!("{} != 0", arithmetic_to_rust(&arith_for.condition.expression))
+    };
+    output.push_str(&format!("{}{}: while {{{} {} }} {{\n", generator.indent(), label, step, condition));
+    output.push_str(&loop_body(generator, label, &arith_for.body.commands, None));
+    output
+}
+
+pub fn generate_select_loop_impl(generator: &mut RustGenerator, select_loop: &SelectLoop) -> String {
+    let mut output = String::new();
+    let label = generator.next_loop_label();
+    let items = generator.next_helper_name("items");
+    let menu = generator.next_helper_name("menu");
+    let values = if select_loop.items.is_empty() {
+        "sh.args_list()".to_string()
+    } else {
+        generator.words_to_rust_vec(&select_loop.items)
+    };
+    output.push_str(&format!("{}let {} = {};\n", generator.indent(), items, values));
+    output.push_str(&format!("{}let mut {} = true;\n", generator.indent(), menu));
+    // `select` prompts and sets the variable and REPLY before each pass
+    output.push_str(&format!("{}{}: while sh.select({}, &{}, &mut {}) {{\n", generator.indent(), label,
+                             rust_string_literal(&select_loop.variable), items, menu));
+    output.push_str(&loop_body(generator, label, &select_loop.body.commands, None));
+    output
+}
+
+fn loop_body(generator: &mut RustGenerator, label: String, commands: &[Command], trailer: Option<String>) -> String {
+    generator.loop_labels.push(label);
+    generator.indent_level += 1;
+    let mut output = generator.generate_block_body(commands);
+    if let Some(trailer) = trailer {
+        output.push_str(&format!("{}{}\n", generator.indent(), trailer));
+    }
+    generator.indent_level -= 1;
+    generator.loop_labels.pop();
+    output.push_str(&format!("{}}}\n", generator.indent()));
+    output
+}
+
+pub fn generate_function_impl(generator: &mut RustGenerator, func: &Function) -> String {
+    let mut output = String::new();
+    generator.declared_functions.insert(func.name.clone());
+
+    let indent = generator.indent();
+    let identifier = function_identifier(generator, &func.name);
+    output.push_str(&format!("{}fn {}(sh: &mut Sh) -> i32 {{\n", indent, identifier));
+
+    // A function body is a separate item: enclosing loops are out of reach
+    let labels = std::mem::take(&mut generator.loop_labels);
+    generator.indent_level += 1;
+    generator.function_depth += 1;
+    output.push_str(&generator.generate_block_body(&func.body.commands));
+    output.push_str(&format!("{}sh.status()\n", generator.indent()));
+    generator.function_depth -= 1;
+    generator.indent_level -= 1;
+    generator.loop_labels = labels;
+
+    output.push_str(&format!("{}}}\n", indent));
+    output.push_str(&format!("{}sh.define({}, {});\n", indent, rust_string_literal(&func.name), identifier));
+    output
+}
+
+/// Shell function names may contain characters that are not valid in Rust
+/// identifiers, and a function may be redefined, so each definition gets a
+/// unique item name.
+fn function_identifier(generator: &mut RustGenerator, name: &str) -> String {
+    let sanitized: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    generator.next_helper_name(&format!("fn_{}", sanitized)).trim_start_matches('_').to_string()
+}
+
+pub fn generate_block_impl(generator: &mut RustGenerator, block: &Block) -> String {
+    generator.generate_block_body(&block.commands)
+}
+
+pub fn generate_block_body_impl(generator: &mut RustGenerator, commands: &[Command]) -> String {
+    let mut output = String::new();
+    for command in commands {
+        output.push_str(&generator.generate_command(command));
+    }
+    output
+}
+
+fn branch_body(generator: &mut RustGenerator, branch: &Command) -> String {
+    generator.indent_level += 1;
+    let body = match branch {
+        Command::Block(block, _) => generator.generate_block_body(&block.commands),
+        other => generator.generate_block_body(std::slice::from_ref(other)),
+    };
+    generator.indent_level -= 1;
+    body
+}
+
+pub fn generate_break_statement_impl(generator: &mut RustGenerator, level: &Option<String>) -> String {
+    loop_control(generator, "break", level)
+}
+
+pub fn generate_continue_statement_impl(generator: &mut RustGenerator, level: &Option<String>) -> String {
+    loop_control(generator, "continue", level)
+}
+
+/// `break N` / `continue N` map onto the label of the Nth enclosing loop.
+fn loop_control(generator: &mut RustGenerator, keyword: &str, level: &Option<String>) -> String {
+    let depth = level.as_deref().and_then(|n| n.parse::<usize>().ok()).unwrap_or(1).max(1);
+    if generator.loop_labels.is_empty() {
+        return format!("{}sh.set_status(0); // {} outside of a loop\n", generator.indent(), keyword);
+    }
+    let index = generator.loop_labels.len().saturating_sub(depth);
+    format!("{}{} {};\n", generator.indent(), keyword, generator.loop_labels[index])
+}
+
+pub fn generate_return_statement_impl(generator: &mut RustGenerator, value: &Option<Word>) -> String {
+    if generator.function_depth > 0 {
+        let code = return_expression(generator, value.as_ref());
+        format!("{}return {};\n", generator.indent(), code)
+    } else {
+        let code = match value {
+            Some(value) => format!("Some({})", generator.word_to_rust(value)),
+            None => "None".to_string(),
+        };
+        format!("{}{};\n", generator.indent(), super::words::sh_call("exit", vec![code]))
+    }
+}
@@ -0,0 +1,314 @@
+use crate::ast::*;
+use crate::generator::Backend;
+use std::collections::HashSet;
+
+pub mod commands;
+pub mod control_flow;
+pub mod words;
+pub mod test_expressions;
+
+/// Shell runtime emitted at the top of every generated Rust program.
+pub const PRELUDE: &str = include_str!("prelude.rs");
+
+pub struct RustGenerator {
+    pub indent_level: usize,
+    pub declared_functions: HashSet<String>,
+    pub function_depth: usize,
+    pub helper_counter: usize,
+    /// Labels of the enclosing loops that `break` / `continue` can reach.
+    pub loop_labels: Vec<String>,
+    /// Whether the statement being generated starts process substitutions.
+    pub pending_process_subs: bool,
+}
+
+impl Default for RustGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RustGenerator {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            declared_functions: HashSet::new(),
+            function_depth: 0,
+            helper_counter: 0,
+            loop_labels: Vec::new(),
+            pending_process_subs: false,
+        }
+    }
+
+    pub fn generate(&mut self, ast: &[Command]) -> String {
+        let mut output = String::new();
+
+        // The program only depends on std, so plain `rustc` can build it
+        output.push_str("#![allow(unused, unused_parens, unused_braces, unreachable_code, non_snake_case)]\n");
+        output.push_str(PRELUDE);
+        output.push_str("\nfn main() {\n");
+        output.push_str("    let mut sh = Sh::new();\n");
+        output.push_str("    let code = sh.guarded(script);\n");
+        output.push_str("    sh.finish(code)\n");
+        output.push_str("}\n\n");
+        output.push_str("fn script(sh: &mut Sh) -> i32 {\n");
+
+        for command in ast {
+            self.indent_level = 1;
+            let command_output = self.generate_command(command);
+            output.push_str(&command_output);
+
+            if !command_output.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+
+        // A shell script exits with the status of its last command
+        output.push_str("    sh.status()\n}\n");
+        output
+    }
+
+    pub fn generate_command(&mut self, command: &Command) -> String {
+        let outer_subs = std::mem::take(&mut self.pending_process_subs);
+        let body = commands::generate_command_impl(self, command);
+        if !std::mem::replace(&mut self.pending_process_subs, outer_subs) {
+            return body;
+        }
+        // Process substitutions finish once the statement using them has run
+        let indent = self.indent();
+        format!("{}sh.process_subs.push(Vec::new());\n{}{}sh.finish_process_subs();\n", indent, body, indent)
+    }
+
+    // Delegate to submodules
+    pub fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        commands::generate_simple_command_impl(self, cmd)
+    }
+
+    pub fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        commands::generate_builtin_command_impl(self, cmd)
+    }
+
+    pub fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String {
+        commands::generate_shopt_command_impl(self, cmd)
+    }
+
+    pub fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        commands::generate_pipeline_impl(self, pipeline)
+    }
+
+    pub fn generate_subshell(&mut self, command: &Command) -> String {
+        commands::generate_subshell_impl(self, command)
+    }
+
+    pub fn generate_background(&mut self, command: &Command) -> String {
+        commands::generate_background_impl(self, command)
+    }
+
+    pub fn generate_redirect(&mut self, redirect: &Redirect) -> String {
+        commands::generate_redirect_impl(self, redirect)
+    }
+
+    pub fn command_expression(&mut self, command: &Command) -> String {
+        commands::command_expression_impl(self, command)
+    }
+
+    pub fn closure(&mut self, command: &Command) -> String {
+        commands::closure_impl(self, command)
+    }
+
+    pub fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        control_flow::generate_if_statement_impl(self, if_stmt)
+    }
+
+    pub fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        control_flow::generate_case_statement_impl(self, case_stmt)
+    }
+
+    pub fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        control_flow::generate_while_loop_impl(self, while_loop)
+    }
+
+    pub fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        control_flow::generate_for_loop_impl(self, for_loop)
+    }
+
+    pub fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        control_flow::generate_select_loop_impl(self, select_loop)
+    }
+
+    pub fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        control_flow::generate_arithmetic_for_impl(self, arith_for)
+    }
+
+    pub fn generate_function(&mut self, func: &Function) -> String {
+        control_flow::generate_function_impl(self, func)
+    }
+
+    pub fn generate_block(&mut self, block: &Block) -> String {
+        control_flow::generate_block_impl(self, block)
+    }
+
+    pub fn generate_block_body(&mut self, commands: &[Command]) -> String {
+        control_flow::generate_block_body_impl(self, commands)
+    }
+
+    pub fn generate_break_statement(&mut self, level: &Option<String>) -> String {
+        control_flow::generate_break_statement_impl(self, level)
+    }
+
+    pub fn generate_continue_statement(&mut self, level: &Option<String>) -> String {
+        control_flow::generate_continue_statement_impl(self, level)
+    }
+
+    pub fn generate_return_statement(&mut self, value: &Option<Word>) -> String {
+        control_flow::generate_return_statement_impl(self, value)
+    }
+
+    pub fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        test_expressions::generate_test_expression_impl(self, test_expr)
+    }
+
+    pub fn test_expression_call(&mut self, test_expr: &TestExpression) -> String {
+        test_expressions::test_expression_call_impl(self, test_expr)
+    }
+
+    pub fn word_to_rust(&mut self, word: &Word) -> String {
+        words::word_to_rust_impl(self, word)
+    }
+
+    pub fn word_to_rust_args(&mut self, word: &Word) -> Vec<words::RustArg> {
+        words::word_to_rust_args_impl(self, word)
+    }
+
+    pub fn words_to_rust_vec(&mut self, words: &[Word]) -> String {
+        words::words_to_rust_vec_impl(self, words)
+    }
+
+    pub fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String {
+        words::generate_parameter_expansion_impl(self, pe)
+    }
+
+    pub fn indent(&self) -> String {
+        "    ".repeat(self.indent_level)
+    }
+
+    pub fn next_helper_name(&mut self, prefix: &str) -> String {
+        self.helper_counter += 1;
+        format!("_{}_{}", prefix, self.helper_counter)
+    }
+
+    pub fn next_loop_label(&mut self) -> String {
+        self.helper_counter += 1;
+        format!("'l{}", self.helper_counter)
+    }
+}
+
+impl Backend for RustGenerator {
+    fn generate(&mut self, ast: &[Command]) -> String {
+        RustGenerator::generate(self, ast)
+    }
+
+    fn generate_command(&mut self, command: &Command) -> String {
+        RustGenerator::generate_command(self, command)
+    }
+
+    fn generate_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        RustGenerator::generate_simple_command(self, cmd)
+    }
+
+    fn generate_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        RustGenerator::generate_builtin_command(self, cmd)
+    }
+
+    fn generate_shopt_command(&mut self, cmd: &ShoptCommand) -> String {
+        RustGenerator::generate_shopt_command(self, cmd)
+    }
+
+    fn generate_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        RustGenerator::generate_pipeline(self, pipeline)
+    }
+
+    fn generate_subshell(&mut self, command: &Command) -> String {
+        RustGenerator::generate_subshell(self, command)
+    }
+
+    fn generate_background(&mut self, command: &Command) -> String {
+        RustGenerator::generate_background(self, command)
+    }
+
+    fn generate_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        RustGenerator::generate_if_statement(self, if_stmt)
+    }
+
+    fn generate_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        RustGenerator::generate_case_statement(self, case_stmt)
+    }
+
+    fn generate_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        RustGenerator::generate_while_loop(self, while_loop)
+    }
+
+    fn generate_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        RustGenerator::generate_for_loop(self, for_loop)
+    }
+
+    fn generate_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        RustGenerator::generate_select_loop(self, select_loop)
+    }
+
+    fn generate_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        RustGenerator::generate_arithmetic_for(self, arith_for)
+    }
+
+    fn generate_function(&mut self, func: &Function) -> String {
+        RustGenerator::generate_function(self, func)
+    }
+
+    fn generate_block(&mut self, block: &Block) -> String {
+        RustGenerator::generate_block(self, block)
+    }
+
+    fn generate_break_statement(&mut self, level: &Option<String>) -> String {
+        RustGenerator::generate_break_statement(self, level)
+    }
+
+    fn generate_continue_statement(&mut self, level: &Option<String>) -> String {
+        RustGenerator::generate_continue_statement(self, level)
+    }
+
+    fn generate_return_statement(&mut self, value: &Option<Word>) -> String {
+        RustGenerator::generate_return_statement(self, value)
+    }
+
+    fn generate_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        RustGenerator::generate_test_expression(self, test_expr)
+    }
+
+    fn generate_redirect(&mut self, redirect: &Redirect) -> String {
+        RustGenerator::generate_redirect(self, redirect)
+    }
+
+    fn generate_parameter_expansion(&mut self, pe: &ParameterExpansion) -> String {
+        RustGenerator::generate_parameter_expansion(self, pe)
+    }
+
+    fn word_to_code(&mut self, word: &Word) -> String {
+        self.word_to_rust(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_rust;
+
+    #[test]
+    fn test_generated_program_runs() {
+        let script = "greet() { echo \"hello $1\"; }\nfor name in a b; do greet $name; done\nx=3\nif [ $x -gt 2 ]; then echo big; fi\nexit 4";
+        assert_eq!(run_rust(script, ""), ("hello a\nhello b\nbig\n".to_string(), 4));
+    }
+
+    #[test]
+    fn test_arithmetic_condition() {
+        let script = "v=17\nif (( v % 2 == 0 )); then echo even; else echo odd; fi";
+        assert_eq!(run_rust(script, ""), ("odd\n".to_string(), 0));
+    }
+}
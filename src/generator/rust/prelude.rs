@@ -0,0 +1,2319 @@
+// Shell runtime emitted verbatim at the top of every program generated by the
+// Rust backend. It only depends on std so the result builds with plain rustc.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+use std::process::{Child, Command, Stdio};
+
+type Stage = fn(&mut Sh) -> i32;
+
+#[derive(Clone, Debug)]
+enum Value {
+    Str(String),
+    Array(Vec<String>),
+    Assoc(BTreeMap<String, String>),
+}
+
+enum Sink {
+    Stdout,
+    Stderr,
+    Buffer(String),
+    File(std::fs::File),
+}
+
+/// Unwinding payload used by `exit` so subshells and pipeline stages can stop early.
+struct Exit(i32);
+
+struct Redir {
+    fd: i32,
+    var: Option<&'static str>,
+    op: &'static str,
+    target: String,
+}
+
+fn redir(fd: i32, op: &'static str, target: String) -> Redir {
+    Redir { fd, var: None, op, target }
+}
+
+/// `{var}>file`: the descriptor is picked at run time and stored in `var`.
+fn redir_var(var: &'static str, op: &'static str, target: String) -> Redir {
+    Redir { fd: -1, var: Some(var), op, target }
+}
+
+/// What a descriptor above 2 refers to. Input keeps its unread data so
+/// duplicates of it read on from the same place.
+enum Fd {
+    In(String),
+    Out(usize),
+}
+
+/// A process substitution's stand-in file, removed once the command using
+/// it is done. For `>(cmd)`, cmd then reads what was written to it.
+enum ProcessSub {
+    Input(std::path::PathBuf),
+    Output(std::path::PathBuf, Stage),
+}
+
+fn s(text: &str) -> String {
+    text.to_string()
+}
+
+fn temp_path() -> std::path::PathBuf {
+    static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    std::env::temp_dir().join(format!("debashc-{}-{}", std::process::id(), n))
+}
+
+struct Snapshot {
+    sinks: usize,
+    fds: usize,
+    out: usize,
+    err: usize,
+    input: usize,
+    args: usize,
+    locals: usize,
+}
+
+struct Sh {
+    vars: HashMap<String, Value>,
+    args: Vec<Vec<String>>,
+    locals: Vec<Vec<(String, Option<Value>)>>,
+    funcs: HashMap<String, Stage>,
+    exported: HashSet<String>,
+    opts: HashSet<String>,
+    input: Vec<Option<String>>,
+    sinks: Vec<Sink>,
+    out: Vec<usize>,
+    err: Vec<usize>,
+    fds: Vec<(i32, Option<Fd>)>,
+    process_subs: Vec<Vec<ProcessSub>>,
+    jobs: Vec<Child>,
+    background: bool,
+}
+
+impl Sh {
+    fn new() -> Self {
+        let mut vars = HashMap::new();
+        vars.insert(s("?"), Value::Str(s("0")));
+        Sh {
+            vars,
+            args: vec![std::env::args().skip(1).collect()],
+            locals: Vec::new(),
+            funcs: HashMap::new(),
+            exported: HashSet::new(),
+            opts: HashSet::new(),
+            input: vec![None],
+            sinks: vec![Sink::Stdout, Sink::Stderr],
+            out: vec![0],
+            err: vec![1],
+            fds: Vec::new(),
+            process_subs: vec![Vec::new()],
+            jobs: Vec::new(),
+            background: false,
+        }
+    }
+
+    // ----- status and variables -----
+
+    fn status(&self) -> i32 {
+        num(&self.var("?")) as i32
+    }
+
+    fn set_status(&mut self, code: i32) -> i32 {
+        self.vars.insert(s("?"), Value::Str(code.to_string()));
+        code
+    }
+
+    fn not(&mut self, code: i32) -> i32 {
+        self.set_status(if code == 0 { 1 } else { 0 })
+    }
+
+    fn var(&self, name: &str) -> String {
+        match name {
+            "#" => return self.args.last().map(|a| a.len()).unwrap_or(0).to_string(),
+            "@" | "*" => return self.args.last().map(|a| a.join(" ")).unwrap_or_default(),
+            "$" => return std::process::id().to_string(),
+            "0" => return std::env::args().next().unwrap_or_default(),
+            _ => {}
+        }
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+            let n: usize = name.parse().unwrap_or(0);
+            return self.args.last().and_then(|a| a.get(n - 1)).cloned().unwrap_or_default();
+        }
+        match self.vars.get(name) {
+            Some(Value::Str(v)) => v.clone(),
+            Some(Value::Array(a)) => a.first().cloned().unwrap_or_default(),
+            Some(Value::Assoc(m)) => m.get("0").cloned().unwrap_or_default(),
+            None => std::env::var(name).unwrap_or_default(),
+        }
+    }
+
+    fn is_set(&self, name: &str) -> bool {
+        let args = self.args.last().map(|a| a.len()).unwrap_or(0);
+        match name {
+            "@" | "*" => return args > 0,
+            "#" | "?" | "$" | "-" => return true,
+            _ => {}
+        }
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+            return name.parse::<usize>().map(|n| n <= args).unwrap_or(false);
+        }
+        if let Some((base, key)) = name.strip_suffix(']').and_then(|rest| rest.split_once('[')) {
+            return match self.vars.get(base) {
+                Some(Value::Assoc(m)) => m.contains_key(key),
+                Some(Value::Array(a)) => {
+                    let n = num(key);
+                    -(a.len() as i64) <= n && n < a.len() as i64
+                }
+                Some(Value::Str(_)) => num(key) == 0,
+                None => false,
+            };
+        }
+        self.vars.contains_key(name) || std::env::var(name).is_ok()
+    }
+
+    fn set_var(&mut self, name: &str, value: String) -> i32 {
+        if self.opts.contains("a") {
+            self.exported.insert(name.to_string());
+        }
+        self.vars.insert(name.to_string(), Value::Str(value));
+        self.set_status(0)
+    }
+
+    fn set_value(&mut self, name: &str, value: Value) -> i32 {
+        self.vars.insert(name.to_string(), value);
+        self.set_status(0)
+    }
+
+    fn set_array(&mut self, name: &str, values: Vec<String>) -> i32 {
+        self.vars.insert(name.to_string(), Value::Array(values));
+        self.set_status(0)
+    }
+
+    fn args_list(&self) -> Vec<String> {
+        self.args.last().cloned().unwrap_or_default()
+    }
+
+    fn elements(&self, name: &str) -> Vec<String> {
+        match self.vars.get(name) {
+            Some(Value::Array(a)) => a.clone(),
+            Some(Value::Assoc(m)) => m.values().cloned().collect(),
+            Some(Value::Str(v)) if !v.is_empty() => vec![v.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn keys(&self, name: &str) -> Vec<String> {
+        match self.vars.get(name) {
+            Some(Value::Array(a)) => (0..a.len()).map(|i| i.to_string()).collect(),
+            Some(Value::Assoc(m)) => m.keys().cloned().collect(),
+            Some(Value::Str(v)) if !v.is_empty() => vec![s("0")],
+            _ => Vec::new(),
+        }
+    }
+
+    fn index(&mut self, name: &str, key: String) -> String {
+        if let Some(Value::Assoc(m)) = self.vars.get(name) {
+            return m.get(&key).cloned().unwrap_or_default();
+        }
+        let items = self.elements(name);
+        let mut n = self.arith(&key);
+        if n < 0 {
+            n += items.len() as i64;
+        }
+        items.get(n.max(0) as usize).cloned().unwrap_or_default()
+    }
+
+    fn set_index(&mut self, name: &str, key: String, value: String) -> i32 {
+        if let Some(Value::Assoc(m)) = self.vars.get_mut(name) {
+            m.insert(key, value);
+            return self.set_status(0);
+        }
+        let n = self.arith(&key).max(0) as usize;
+        let entry = self.vars.entry(name.to_string()).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Str(v) = entry {
+            *entry = Value::Array(if v.is_empty() { Vec::new() } else { vec![v.clone()] });
+        }
+        if let Value::Array(a) = entry {
+            while a.len() <= n {
+                a.push(String::new());
+            }
+            a[n] = value;
+        }
+        self.set_status(0)
+    }
+
+    fn declare_assoc(&mut self, name: &str) -> i32 {
+        if !matches!(self.vars.get(name), Some(Value::Assoc(_))) {
+            self.vars.insert(name.to_string(), Value::Assoc(BTreeMap::new()));
+        }
+        self.set_status(0)
+    }
+
+    fn split(&self, value: &str) -> Vec<String> {
+        let ifs = match self.vars.get("IFS") {
+            Some(Value::Str(v)) => v.clone(),
+            _ => s(" \t\n"),
+        };
+        value.split(|c| ifs.contains(c)).filter(|part| !part.is_empty()).map(s).collect()
+    }
+
+    fn store(&mut self, name: &str, value: String) -> String {
+        match name.strip_suffix(']').and_then(|rest| rest.split_once('[')) {
+            Some((base, key)) => self.set_index(base, s(key), value.clone()),
+            None => self.set_var(name, value.clone()),
+        };
+        value
+    }
+
+    fn parameter_error(&mut self, name: &str, message: String, default: &str) -> String {
+        let message = if message.is_empty() { s(default) } else { message };
+        self.write_err(&format!("{}: {}\n", name, message));
+        self.exit(Some(s("1")));
+        String::new()
+    }
+
+    fn positional(&self) -> Vec<String> {
+        let mut items = vec![self.var("0")];
+        items.extend(self.args_list());
+        items
+    }
+
+    fn prefix_names(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.vars.keys().cloned()
+            .chain(std::env::vars().map(|(name, _)| name))
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn unset(&mut self, names: Vec<String>) -> i32 {
+        for name in names {
+            if name == "-v" || name == "-f" {
+                continue;
+            }
+            self.vars.remove(&name);
+            self.funcs.remove(&name);
+            self.exported.remove(&name);
+        }
+        self.set_status(0)
+    }
+
+    fn export(&mut self, name: &str, value: Option<Value>) -> i32 {
+        if let Some(value) = value {
+            self.vars.insert(name.to_string(), value);
+        }
+        self.exported.insert(name.to_string());
+        self.set_status(0)
+    }
+
+    fn local(&mut self, name: &str, value: Option<Value>) -> i32 {
+        let previous = self.vars.get(name).cloned();
+        if let Some(frame) = self.locals.last_mut() {
+            if !frame.iter().any(|(n, _)| n == name) {
+                frame.push((name.to_string(), previous));
+            }
+        }
+        self.vars.insert(name.to_string(), value.unwrap_or_else(|| Value::Str(String::new())));
+        self.set_status(0)
+    }
+
+    fn shift(&mut self, n: i64) -> i32 {
+        let args = self.args.last_mut().unwrap();
+        if n < 0 || n as usize > args.len() {
+            return self.set_status(1);
+        }
+        args.drain(..n as usize);
+        self.set_status(0)
+    }
+
+    fn set(&mut self, args: Vec<String>) -> i32 {
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            if arg == "--" {
+                *self.args.last_mut().unwrap() = args[i + 1..].to_vec();
+                break;
+            }
+            if (arg == "-o" || arg == "+o") && i + 1 < args.len() {
+                if arg == "-o" {
+                    self.opts.insert(args[i + 1].clone());
+                } else {
+                    self.opts.remove(&args[i + 1]);
+                }
+                i += 2;
+                continue;
+            }
+            if (arg.starts_with('-') || arg.starts_with('+')) && arg.len() > 1 {
+                for flag in arg[1..].chars() {
+                    if arg.starts_with('-') {
+                        self.opts.insert(flag.to_string());
+                    } else {
+                        self.opts.remove(&flag.to_string());
+                    }
+                }
+            } else {
+                *self.args.last_mut().unwrap() = args[i..].to_vec();
+                break;
+            }
+            i += 1;
+        }
+        self.set_status(0)
+    }
+
+    // ----- output -----
+
+    fn push_sink(&mut self, sink: Sink) -> usize {
+        self.sinks.push(sink);
+        self.sinks.len() - 1
+    }
+
+    fn write_to(&mut self, idx: usize, text: &str) {
+        match &mut self.sinks[idx] {
+            Sink::Stdout => {
+                let _ = std::io::stdout().write_all(text.as_bytes());
+            }
+            Sink::Stderr => {
+                let _ = std::io::stdout().flush();
+                let _ = std::io::stderr().write_all(text.as_bytes());
+            }
+            Sink::Buffer(buffer) => buffer.push_str(text),
+            Sink::File(file) => {
+                let _ = file.write_all(text.as_bytes());
+            }
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        let idx = *self.out.last().unwrap();
+        self.write_to(idx, text);
+    }
+
+    fn write_err(&mut self, text: &str) {
+        let idx = *self.err.last().unwrap();
+        self.write_to(idx, text);
+    }
+
+    fn flush(&mut self) {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+    }
+
+    fn stdio_for(&self, idx: usize, is_stdout: bool) -> Stdio {
+        match &self.sinks[idx] {
+            Sink::Stdout if is_stdout => Stdio::inherit(),
+            Sink::Stdout => Stdio::from(std::io::stdout()),
+            Sink::Stderr if !is_stdout => Stdio::inherit(),
+            Sink::Stderr => Stdio::from(std::io::stderr()),
+            Sink::Buffer(_) => Stdio::piped(),
+            Sink::File(file) => file.try_clone().map(Stdio::from).unwrap_or_else(|_| Stdio::null()),
+        }
+    }
+
+    // ----- control flow helpers -----
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            sinks: self.sinks.len(),
+            fds: self.fds.len(),
+            out: self.out.len(),
+            err: self.err.len(),
+            input: self.input.len(),
+            args: self.args.len(),
+            locals: self.locals.len(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        self.out.truncate(snapshot.out);
+        self.err.truncate(snapshot.err);
+        self.input.truncate(snapshot.input);
+        self.sinks.truncate(snapshot.sinks);
+        self.fds.truncate(snapshot.fds);
+        self.args.truncate(snapshot.args);
+        while self.locals.len() > snapshot.locals {
+            self.pop_locals();
+        }
+    }
+
+    /// Run `f`, turning an `exit` inside it into a plain status code.
+    fn guarded<F: FnOnce(&mut Sh) -> i32>(&mut self, f: F) -> i32 {
+        let snapshot = self.snapshot();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(&mut *self)));
+        match result {
+            Ok(code) => code,
+            Err(payload) => match payload.downcast::<Exit>() {
+                Ok(exit) => {
+                    self.restore(&snapshot);
+                    self.set_status(exit.0)
+                }
+                Err(other) => std::panic::resume_unwind(other),
+            },
+        }
+    }
+
+    fn exit(&mut self, code: Option<String>) -> i32 {
+        let code = match code {
+            Some(code) => (num(&code) & 255) as i32,
+            None => self.status(),
+        };
+        std::panic::resume_unwind(Box::new(Exit(code)))
+    }
+
+    fn finish(&mut self, code: i32) -> ! {
+        self.wait();
+        self.flush();
+        std::process::exit(code)
+    }
+
+    fn define(&mut self, name: &str, body: Stage) -> i32 {
+        self.funcs.insert(name.to_string(), body);
+        self.set_status(0)
+    }
+
+    fn call(&mut self, body: Stage, args: Vec<String>) -> i32 {
+        self.args.push(args);
+        self.locals.push(Vec::new());
+        let code = body(self);
+        self.args.pop();
+        self.pop_locals();
+        self.set_status(code)
+    }
+
+    fn pop_locals(&mut self) {
+        if let Some(frame) = self.locals.pop() {
+            for (name, value) in frame.into_iter().rev() {
+                match value {
+                    Some(value) => {
+                        self.vars.insert(name, value);
+                    }
+                    None => {
+                        self.vars.remove(&name);
+                    }
+                }
+            }
+        }
+    }
+
+    fn ret(&mut self, code: Option<String>) -> i32 {
+        let code = match code {
+            Some(code) => (num(&code) & 255) as i32,
+            None => self.status(),
+        };
+        self.set_status(code)
+    }
+
+    fn capture<F: FnOnce(&mut Sh) -> i32>(&mut self, f: F) -> String {
+        self.output_of(f).trim_end_matches('\n').to_string()
+    }
+
+    /// Everything `f` writes to stdout.
+    fn output_of<F: FnOnce(&mut Sh) -> i32>(&mut self, f: F) -> String {
+        let idx = self.push_sink(Sink::Buffer(String::new()));
+        self.out.push(idx);
+        let code = self.guarded(f);
+        self.out.truncate(self.out.len().saturating_sub(1));
+        self.sinks.truncate(idx + 1);
+        let text = match self.sinks.pop() {
+            Some(Sink::Buffer(text)) => text,
+            _ => String::new(),
+        };
+        self.set_status(code);
+        text
+    }
+
+    fn pipe(&mut self, stages: &[Stage]) -> i32 {
+        let mut data = self.input.last().cloned().flatten();
+        let mut statuses = Vec::new();
+        for (n, stage) in stages.iter().enumerate() {
+            let last = n + 1 == stages.len();
+            self.input.push(data.take());
+            let idx = if last { None } else { Some(self.push_sink(Sink::Buffer(String::new()))) };
+            if let Some(idx) = idx {
+                self.out.push(idx);
+            }
+            let code = self.guarded(|sh| stage(sh));
+            self.input.pop();
+            if let Some(idx) = idx {
+                self.out.pop();
+                self.sinks.truncate(idx + 1);
+                if let Some(Sink::Buffer(text)) = self.sinks.pop() {
+                    data = Some(text);
+                }
+            }
+            statuses.push(code);
+        }
+        let code = if self.opts.contains("pipefail") {
+            statuses.iter().rev().find(|&&c| c != 0).copied().unwrap_or(0)
+        } else {
+            statuses.last().copied().unwrap_or(0)
+        };
+        self.set_status(code)
+    }
+
+    fn subshell<F: FnOnce(&mut Sh) -> i32>(&mut self, f: F) -> i32 {
+        let vars = self.vars.clone();
+        let args = self.args.clone();
+        let funcs = self.funcs.clone();
+        let exported = self.exported.clone();
+        let opts = self.opts.clone();
+        let cwd = std::env::current_dir().ok();
+        let code = self.guarded(f);
+        self.vars = vars;
+        self.args = args;
+        self.funcs = funcs;
+        self.exported = exported;
+        self.opts = opts;
+        if let Some(cwd) = cwd {
+            let _ = std::env::set_current_dir(cwd);
+        }
+        self.set_status(code)
+    }
+
+    fn background<F: FnOnce(&mut Sh) -> i32>(&mut self, f: F) -> i32 {
+        self.background = true;
+        self.guarded(f);
+        self.background = false;
+        self.set_status(0)
+    }
+
+    fn wait(&mut self) -> i32 {
+        let mut code = 0;
+        for mut job in std::mem::take(&mut self.jobs) {
+            if let Ok(status) = job.wait() {
+                code = status.code().unwrap_or(1);
+            }
+        }
+        self.set_status(code)
+    }
+
+    /// Run `f` with the redirects applied, then put every descriptor back.
+    /// Descriptors 0-2 are the input/out/err stacks and higher ones live in
+    /// `fds`. `{var}` redirects are applied first and outlive the command.
+    fn redirect<F: FnOnce(&mut Sh) -> i32>(&mut self, redirects: Vec<Redir>, f: F) -> i32 {
+        let (persistent, scoped): (Vec<Redir>, Vec<Redir>) = redirects.into_iter().partition(|r| r.var.is_some());
+        if let Some(code) = self.apply_persistent(persistent) {
+            return code;
+        }
+        let snapshot = self.snapshot();
+        let mut copy_back = Vec::new();
+        for r in scoped {
+            if let Err(message) = self.apply_redirect(&r, &mut copy_back) {
+                self.write_err(&message);
+                self.restore(&snapshot);
+                return self.set_status(1);
+            }
+        }
+        let code = f(self);
+        // Input read through a duplicate is used up in the original too
+        for i in copy_back {
+            let rest = self.input.last().cloned().flatten().unwrap_or_default();
+            if let Some((_, Some(Fd::In(data)))) = self.fds.get_mut(i) {
+                *data = rest;
+            }
+        }
+        self.restore(&snapshot);
+        code
+    }
+
+    /// `exec` redirects: applied like any others, but never put back.
+    fn exec_redirect<F: FnOnce(&mut Sh) -> i32>(&mut self, redirects: Vec<Redir>, f: F) -> i32 {
+        match self.apply_persistent(redirects) {
+            Some(code) => code,
+            None => f(self),
+        }
+    }
+
+    /// Apply redirects that outlive the command, giving each `{var}` a fresh
+    /// descriptor from 10 up. On failure, returns the command's status.
+    fn apply_persistent(&mut self, redirects: Vec<Redir>) -> Option<i32> {
+        let mut copy_back = Vec::new();
+        for mut r in redirects {
+            if let Some(var) = r.var {
+                r.fd = self.fds.iter().map(|(fd, _)| *fd).max().unwrap_or(9).max(9) + 1;
+                self.set_var(var, r.fd.to_string());
+            }
+            if let Err(message) = self.apply_redirect(&r, &mut copy_back) {
+                self.write_err(&message);
+                return Some(self.set_status(1));
+            }
+        }
+        None
+    }
+
+    fn apply_redirect(&mut self, r: &Redir, copy_back: &mut Vec<usize>) -> Result<(), String> {
+        match r.op {
+            "<" => match std::fs::read_to_string(&r.target) {
+                Ok(data) => self.set_input(r.fd, data),
+                Err(_) => return Err(format!("{}: No such file or directory\n", r.target)),
+            },
+            "<<" => self.set_input(r.fd, r.target.clone()),
+            "<<<" => self.set_input(r.fd, format!("{}\n", r.target)),
+            "-" => match r.fd {
+                0 => self.set_input(0, String::new()),
+                1 | 2 => {
+                    let null = std::fs::OpenOptions::new().write(true).open("/dev/null").map_err(|e| format!("{}\n", e))?;
+                    let idx = self.push_sink(Sink::File(null));
+                    self.set_output(r.fd, idx);
+                }
+                fd => self.fds.push((fd, None)),
+            },
+            ">&" | "<&" => {
+                let source: i32 = r.target.trim_end_matches('-').parse().unwrap_or(-1);
+                let bad = || format!("{}: Bad file descriptor\n", r.target);
+                if r.op == ">&" {
+                    let idx = self.output_sink(source).ok_or_else(bad)?;
+                    self.set_output(r.fd, idx);
+                } else if source == 0 {
+                    let data = self.input.last().cloned().flatten().unwrap_or_default();
+                    self.set_input(r.fd, data);
+                } else {
+                    let i = self.fd_index(source).ok_or_else(bad)?;
+                    let data = match &self.fds[i].1 {
+                        Some(Fd::In(data)) => data.clone(),
+                        _ => return Err(bad()),
+                    };
+                    self.set_input(r.fd, data);
+                    if r.fd == 0 {
+                        copy_back.push(i);
+                    }
+                }
+                if r.target.ends_with('-') && source > 2 {
+                    self.fds.push((source, None));
+                }
+            }
+            _ => {
+                let append = r.op.ends_with(">>");
+                let idx = match r.target.as_str() {
+                    "/dev/stdout" => *self.out.last().unwrap(),
+                    "/dev/stderr" => *self.err.last().unwrap(),
+                    path => {
+                        let noclobber = self.opts.contains("C") || self.opts.contains("noclobber");
+                        if !append && r.op != ">|" && noclobber && std::path::Path::new(path).is_file() {
+                            return Err(format!("{}: cannot overwrite existing file\n", path));
+                        }
+                        let file = std::fs::OpenOptions::new()
+                            .write(true)
+                            .create(true)
+                            .append(append)
+                            .truncate(!append)
+                            .open(path)
+                            .map_err(|e| format!("{}: {}\n", path, e))?;
+                        self.push_sink(Sink::File(file))
+                    }
+                };
+                self.set_output(r.fd, idx);
+                if r.op.starts_with('&') {
+                    self.set_output(2, idx);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_input(&mut self, fd: i32, data: String) {
+        if fd == 0 {
+            self.input.push(Some(data));
+        } else {
+            self.fds.push((fd, Some(Fd::In(data))));
+        }
+    }
+
+    fn set_output(&mut self, fd: i32, idx: usize) {
+        match fd {
+            1 => self.out.push(idx),
+            2 => self.err.push(idx),
+            _ => self.fds.push((fd, Some(Fd::Out(idx)))),
+        }
+    }
+
+    /// Where the latest entry for an open descriptor above 2 sits in `fds`.
+    fn fd_index(&self, fd: i32) -> Option<usize> {
+        let i = self.fds.iter().rposition(|(n, _)| *n == fd)?;
+        self.fds[i].1.as_ref().map(|_| i)
+    }
+
+    fn output_sink(&self, fd: i32) -> Option<usize> {
+        match fd {
+            1 => self.out.last().copied(),
+            2 => self.err.last().copied(),
+            _ => match &self.fds[self.fd_index(fd)?].1 {
+                Some(Fd::Out(idx)) => Some(*idx),
+                _ => None,
+            },
+        }
+    }
+
+    // ----- process substitution -----
+
+    /// `<(cmd)`: run cmd in a subshell and stand in a file holding its output.
+    fn process_sub_in(&mut self, f: Stage) -> String {
+        let text = self.output_of(|sh| sh.subshell(f));
+        let path = temp_path();
+        let _ = std::fs::write(&path, text);
+        self.process_subs.last_mut().map(|subs| subs.push(ProcessSub::Input(path.clone())));
+        path.to_string_lossy().into_owned()
+    }
+
+    /// `>(cmd)`: stand in a file, and run cmd in a subshell on what was
+    /// written to it once the command using it is done.
+    fn process_sub_out(&mut self, f: Stage) -> String {
+        let path = temp_path();
+        let _ = std::fs::write(&path, "");
+        self.process_subs.last_mut().map(|subs| subs.push(ProcessSub::Output(path.clone(), f)));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn finish_process_subs(&mut self) -> i32 {
+        let status = self.status();
+        for sub in self.process_subs.pop().unwrap_or_default() {
+            match sub {
+                ProcessSub::Input(path) => {
+                    let _ = std::fs::remove_file(path);
+                }
+                ProcessSub::Output(path, f) => {
+                    self.flush();
+                    let data = std::fs::read_to_string(&path).unwrap_or_default();
+                    let _ = std::fs::remove_file(&path);
+                    self.input.push(Some(data));
+                    self.subshell(f);
+                    self.input.pop();
+                }
+            }
+        }
+        self.set_status(status)
+    }
+
+    // ----- external commands -----
+
+    fn run(&mut self, argv: Vec<String>) -> i32 {
+        self.run_env(argv, Vec::new())
+    }
+
+    fn run_env(&mut self, argv: Vec<String>, env: Vec<(&str, String)>) -> i32 {
+        if argv.is_empty() {
+            return self.set_status(0);
+        }
+        if let Some(body) = self.funcs.get(&argv[0]).copied() {
+            return self.call(body, argv[1..].to_vec());
+        }
+        self.flush();
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        for name in &self.exported {
+            if let Some(Value::Str(value)) = self.vars.get(name) {
+                cmd.env(name, value);
+            }
+        }
+        for (name, value) in env {
+            cmd.env(name, value);
+        }
+        let input = self.input.last().cloned().flatten();
+        cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() });
+        let out_idx = *self.out.last().unwrap();
+        let err_idx = *self.err.last().unwrap();
+        cmd.stdout(self.stdio_for(out_idx, true));
+        cmd.stderr(self.stdio_for(err_idx, false));
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let (message, code) = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    ("Permission denied", 126)
+                } else {
+                    ("command not found", 127)
+                };
+                self.write_err(&format!("{}: {}\n", argv[0], message));
+                return self.set_status(code);
+            }
+        };
+        let buffered = matches!(self.sinks[out_idx], Sink::Buffer(_)) || matches!(self.sinks[err_idx], Sink::Buffer(_));
+        if self.background && !buffered && input.is_none() {
+            self.jobs.push(child);
+            return self.set_status(0);
+        }
+        let writer = child.stdin.take().map(|mut stdin| {
+            let data = input.unwrap_or_default();
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(data.as_bytes());
+            })
+        });
+        let output = child.wait_with_output();
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+        match output {
+            Ok(output) => {
+                if !output.stdout.is_empty() {
+                    self.write_to(out_idx, &String::from_utf8_lossy(&output.stdout));
+                }
+                if !output.stderr.is_empty() {
+                    self.write_to(err_idx, &String::from_utf8_lossy(&output.stderr));
+                }
+                self.set_status(output.status.code().unwrap_or(1))
+            }
+            Err(_) => self.set_status(1),
+        }
+    }
+
+    // ----- builtins -----
+
+    fn echo(&mut self, mut args: Vec<String>) -> i32 {
+        let mut newline = true;
+        let mut escapes = false;
+        while !args.is_empty() && args[0].len() > 1 && args[0].starts_with('-')
+            && args[0][1..].chars().all(|c| "neE".contains(c))
+        {
+            let flags = args.remove(0);
+            newline &= !flags.contains('n');
+            if flags.contains('e') {
+                escapes = true;
+            }
+            if flags.contains('E') {
+                escapes = false;
+            }
+        }
+        let mut text = args.join(" ");
+        if escapes {
+            let (expanded, stop) = backslash_escapes(&text);
+            text = expanded;
+            if stop {
+                newline = false;
+            }
+        }
+        if newline {
+            text.push('\n');
+        }
+        self.write(&text);
+        self.set_status(0)
+    }
+
+    fn printf(&mut self, args: Vec<String>) -> i32 {
+        if args.len() >= 3 && args[0] == "-v" {
+            let name = args[1].clone();
+            let rest = args[2..].to_vec();
+            let mut text = String::new();
+            let code = format_printf(&rest, &mut text);
+            self.set_var(&name, text);
+            return self.set_status(code);
+        }
+        if args.is_empty() {
+            self.write_err("printf: usage: printf [-v var] format [arguments]\n");
+            return self.set_status(2);
+        }
+        let mut text = String::new();
+        let code = format_printf(&args, &mut text);
+        self.write(&text);
+        self.set_status(code)
+    }
+
+    fn read_line(&mut self) -> String {
+        match self.input.last_mut().unwrap() {
+            Some(data) => {
+                let end = data.find('\n').map(|n| n + 1).unwrap_or(data.len());
+                data.drain(..end).collect::<String>()
+            }
+            None => {
+                let mut line = String::new();
+                let _ = std::io::stdin().lock().read_line(&mut line);
+                line
+            }
+        }
+    }
+
+    fn read(&mut self, args: Vec<String>) -> i32 {
+        let mut raw = false;
+        let mut names = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "-r" => raw = true,
+                "-p" if i + 1 < args.len() => {
+                    let prompt = args[i + 1].clone();
+                    self.write_err(&prompt);
+                    i += 1;
+                }
+                name => names.push(name.to_string()),
+            }
+            i += 1;
+        }
+        let line = self.read_line();
+        if line.is_empty() {
+            for name in if names.is_empty() { vec![s("REPLY")] } else { names } {
+                self.set_var(&name, String::new());
+            }
+            return self.set_status(1);
+        }
+        let mut line = line.strip_suffix('\n').unwrap_or(&line).to_string();
+        if !raw {
+            let mut out = String::new();
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    if let Some(next) = chars.next() {
+                        out.push(next);
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            line = out;
+        }
+        if names.is_empty() {
+            self.set_var("REPLY", line);
+            return self.set_status(0);
+        }
+        let fields = self.split(&line);
+        let count = names.len();
+        for (n, name) in names.iter().enumerate() {
+            let value = if n + 1 == count {
+                fields.get(n..).map(|rest| rest.join(" ")).unwrap_or_default()
+            } else {
+                fields.get(n).cloned().unwrap_or_default()
+            };
+            self.set_var(name, value);
+        }
+        self.set_status(0)
+    }
+
+    fn select(&mut self, name: &str, items: &[String], menu: &mut bool) -> bool {
+        loop {
+            if *menu {
+                for (n, item) in items.iter().enumerate() {
+                    self.write_err(&format!("{}) {}\n", n + 1, item));
+                }
+            }
+            let prompt = if self.is_set("PS3") { self.var("PS3") } else { s("#? ") };
+            self.write_err(&prompt);
+            self.flush();
+            let line = self.read_line();
+            if line.is_empty() {
+                self.write_err("\n");
+                self.set_status(1);
+                return false;
+            }
+            let reply = line.strip_suffix('\n').unwrap_or(&line).to_string();
+            *menu = reply.is_empty();
+            self.set_var("REPLY", reply.clone());
+            if !*menu {
+                let choice = reply.parse::<usize>().ok().filter(|n| (1..=items.len()).contains(n));
+                self.set_var(name, choice.map(|n| items[n - 1].clone()).unwrap_or_default());
+                return true;
+            }
+        }
+    }
+
+    fn cd(&mut self, args: Vec<String>) -> i32 {
+        let mut target = args.first().cloned().unwrap_or_else(|| std::env::var("HOME").unwrap_or_else(|_| s("/")));
+        if target == "-" {
+            target = self.var("OLDPWD");
+        }
+        let previous = std::env::current_dir().map(|p| p.display().to_string()).unwrap_or_default();
+        match std::env::set_current_dir(&target) {
+            Ok(_) => {
+                self.set_var("OLDPWD", previous);
+                let pwd = std::env::current_dir().map(|p| p.display().to_string()).unwrap_or_default();
+                self.set_var("PWD", pwd);
+                self.set_status(0)
+            }
+            Err(_) => {
+                self.write_err(&format!("cd: {}: No such file or directory\n", target));
+                self.set_status(1)
+            }
+        }
+    }
+
+    fn pwd(&mut self) -> i32 {
+        let cwd = std::env::current_dir().map(|p| p.display().to_string()).unwrap_or_default();
+        self.write(&format!("{}\n", cwd));
+        self.set_status(0)
+    }
+
+    // ----- arithmetic -----
+
+    fn arith(&mut self, expr: &str) -> i64 {
+        let tokens = arith_tokens(expr);
+        let mut parser = Arith { tokens, pos: 0 };
+        match parser.comma(self, true) {
+            Ok(value) => value,
+            Err(message) => {
+                self.write_err(&format!("{}: {}\n", expr.trim(), message));
+                0
+            }
+        }
+    }
+
+    fn arith_var(&mut self, name: &str) -> i64 {
+        let value = self.var(name);
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return 0;
+        }
+        match parse_number(trimmed) {
+            Some(n) => n,
+            None if trimmed.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && trimmed != name => {
+                self.arith_var(trimmed)
+            }
+            None => 0,
+        }
+    }
+
+    // ----- tests -----
+
+    fn test(&mut self, args: Vec<String>, extended: bool) -> i32 {
+        let mut t = TestParser { args, pos: 0, extended };
+        let result = t.disjunction(self);
+        self.set_status(if result { 0 } else { 1 })
+    }
+}
+
+// ----- arithmetic evaluation -----
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Num(i64),
+    Name(String),
+    Op(String),
+}
+
+fn arith_tokens(expr: &str) -> Vec<Tok> {
+    const OPS: [&str; 36] = [
+        "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+        "/=", "%=", "&=", "|=", "^=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "?",
+        ":", "=",
+    ];
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '#' || chars[i] == '_') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            tokens.push(Tok::Num(parse_number(&literal).unwrap_or(0)));
+        } else if c == '$' && chars[i + 1..].starts_with(&['(', '(']) {
+            i += 1;
+        } else if c == '$' || c.is_ascii_alphabetic() || c == '_' {
+            if c == '$' {
+                i += 1;
+            }
+            let braced = i < chars.len() && chars[i] == '{';
+            if braced {
+                i += 1;
+            }
+            let start = i;
+            if i < chars.len() && !braced && (chars[i].is_ascii_digit() || "#?@*".contains(chars[i])) {
+                i += 1;
+            } else {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            let name: String = chars[start..i].iter().collect();
+            if braced && i < chars.len() && chars[i] == '}' {
+                i += 1;
+            }
+            tokens.push(Tok::Name(name));
+        } else if c == '(' || c == ')' || c == ',' {
+            tokens.push(Tok::Op(c.to_string()));
+            i += 1;
+        } else {
+            let rest: String = chars[i..].iter().take(3).collect();
+            match OPS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Tok::Op(op.to_string()));
+                    i += op.len();
+                }
+                None => i += 1,
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_number(literal: &str) -> Option<i64> {
+    let (negative, literal) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    let lower = literal.to_ascii_lowercase();
+    let value = if let Some((base, digits)) = literal.split_once('#') {
+        base.parse::<u32>().ok().filter(|b| (2..=36).contains(b)).and_then(|b| i64::from_str_radix(digits, b).ok())
+    } else if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i64::from_str_radix(&literal[1..], 8).ok()
+    } else {
+        literal.parse::<i64>().ok()
+    };
+    value.map(|v| if negative { -v } else { v })
+}
+
+/// Integer value of a string the way the shell coerces operands of `-eq` and friends.
+fn num(text: &str) -> i64 {
+    parse_number(text.trim()).unwrap_or(0)
+}
+
+struct Arith {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Arith {
+    fn peek_op(&self) -> Option<&str> {
+        match self.tokens.get(self.pos) {
+            Some(Tok::Op(op)) => Some(op.as_str()),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn comma(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        let mut value = self.assignment(sh, live)?;
+        while self.eat(",") {
+            value = self.assignment(sh, live)?;
+        }
+        Ok(value)
+    }
+
+    fn assignment(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        if let (Some(Tok::Name(name)), Some(Tok::Op(op))) = (self.tokens.get(self.pos).cloned(), self.tokens.get(self.pos + 1).cloned()) {
+            let compound = ["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "|=", "^="];
+            if compound.contains(&op.as_str()) {
+                self.pos += 2;
+                let rhs = self.assignment(sh, live)?;
+                if !live {
+                    return Ok(rhs);
+                }
+                let current = sh.arith_var(&name);
+                let value = if op == "=" { rhs } else { binary(current, &op[..op.len() - 1], rhs)? };
+                sh.set_var(&name, value.to_string());
+                return Ok(value);
+            }
+        }
+        self.ternary(sh, live)
+    }
+
+    fn ternary(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        let cond = self.binary_level(sh, live, 0)?;
+        if self.eat("?") {
+            let then_value = self.assignment(sh, live && cond != 0)?;
+            if !self.eat(":") {
+                return Err(s("expected `:'"));
+            }
+            let else_value = self.assignment(sh, live && cond == 0)?;
+            return Ok(if cond != 0 { then_value } else { else_value });
+        }
+        Ok(cond)
+    }
+
+    fn binary_level(&mut self, sh: &mut Sh, live: bool, level: usize) -> Result<i64, String> {
+        const LEVELS: [&[&str]; 10] = [
+            &["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<", "<=", ">", ">="], &["<<", ">>"],
+            &["+", "-"], &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.power(sh, live);
+        }
+        let mut left = self.binary_level(sh, live, level + 1)?;
+        while let Some(op) = self.peek_op().map(s) {
+            if !LEVELS[level].contains(&op.as_str()) {
+                break;
+            }
+            self.pos += 1;
+            left = match op.as_str() {
+                "||" => {
+                    let right = self.binary_level(sh, live && left == 0, level + 1)?;
+                    (left != 0 || right != 0) as i64
+                }
+                "&&" => {
+                    let right = self.binary_level(sh, live && left != 0, level + 1)?;
+                    (left != 0 && right != 0) as i64
+                }
+                _ => {
+                    let right = self.binary_level(sh, live, level + 1)?;
+                    if live { binary(left, &op, right)? } else { 0 }
+                }
+            };
+        }
+        Ok(left)
+    }
+
+    fn power(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        let base = self.unary(sh, live)?;
+        if self.eat("**") {
+            let exponent = self.power(sh, live)?;
+            return binary(base, "**", exponent);
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        if let Some(op) = self.peek_op().map(s) {
+            match op.as_str() {
+                "!" | "~" | "-" | "+" => {
+                    self.pos += 1;
+                    let value = self.unary(sh, live)?;
+                    return Ok(match op.as_str() {
+                        "!" => (value == 0) as i64,
+                        "~" => !value,
+                        "-" => value.wrapping_neg(),
+                        _ => value,
+                    });
+                }
+                "++" | "--" => {
+                    self.pos += 1;
+                    if let Some(Tok::Name(name)) = self.tokens.get(self.pos).cloned() {
+                        self.pos += 1;
+                        let value = sh.arith_var(&name) + if op == "++" { 1 } else { -1 };
+                        if live {
+                            sh.set_var(&name, value.to_string());
+                        }
+                        return Ok(value);
+                    }
+                    return Err(s("syntax error: operand expected"));
+                }
+                _ => {}
+            }
+        }
+        self.postfix(sh, live)
+    }
+
+    fn postfix(&mut self, sh: &mut Sh, live: bool) -> Result<i64, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Tok::Num(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Tok::Name(name)) => {
+                self.pos += 1;
+                let value = sh.arith_var(&name);
+                for (op, delta) in [("++", 1), ("--", -1)] {
+                    if self.eat(op) {
+                        if live {
+                            sh.set_var(&name, (value + delta).to_string());
+                        }
+                        return Ok(value);
+                    }
+                }
+                Ok(value)
+            }
+            Some(Tok::Op(op)) if op == "(" => {
+                self.pos += 1;
+                let value = self.comma(sh, live)?;
+                if !self.eat(")") {
+                    return Err(s("missing `)'"));
+                }
+                Ok(value)
+            }
+            Some(Tok::Op(op)) => Err(format!("syntax error: operand expected (error token is \"{}\")", op)),
+            None => Ok(0),
+        }
+    }
+}
+
+fn binary(left: i64, op: &str, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err(s("division by 0")),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => return Err(s("exponent less than 0")),
+        "**" => left.wrapping_pow(right as u32),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "&" => left & right,
+        "|" => left | right,
+        "^" => left ^ right,
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        _ => return Err(format!("unknown operator {}", op)),
+    })
+}
+
+// ----- test / [ / [[ -----
+
+struct TestParser {
+    args: Vec<String>,
+    pos: usize,
+    extended: bool,
+}
+
+impl TestParser {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.args.get(self.pos + offset).map(|a| a.as_str())
+    }
+
+    fn take(&mut self) -> String {
+        self.pos += 1;
+        self.args[self.pos - 1].clone()
+    }
+
+    fn disjunction(&mut self, sh: &mut Sh) -> bool {
+        let mut result = self.conjunction(sh);
+        while matches!(self.peek(0), Some("-o") | Some("||")) {
+            self.take();
+            let rhs = self.conjunction(sh);
+            result = result || rhs;
+        }
+        result
+    }
+
+    fn conjunction(&mut self, sh: &mut Sh) -> bool {
+        let mut result = self.negation(sh);
+        while matches!(self.peek(0), Some("-a") | Some("&&")) {
+            self.take();
+            let rhs = self.negation(sh);
+            result = result && rhs;
+        }
+        result
+    }
+
+    fn negation(&mut self, sh: &mut Sh) -> bool {
+        if self.peek(0) == Some("!") && self.peek(1).is_some() {
+            self.take();
+            return !self.negation(sh);
+        }
+        self.primary(sh)
+    }
+
+    fn primary(&mut self, sh: &mut Sh) -> bool {
+        const BINARY: [&str; 12] = ["=", "==", "!=", "=~", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge"];
+        const UNARY: [&str; 13] = ["-e", "-a", "-f", "-d", "-s", "-L", "-h", "-r", "-w", "-x", "-p", "-z", "-n"];
+        let tok = match self.peek(0) {
+            Some(tok) => tok.to_string(),
+            None => return false,
+        };
+        if tok == "(" {
+            self.take();
+            let result = self.disjunction(sh);
+            if self.peek(0) == Some(")") {
+                self.take();
+            }
+            return result;
+        }
+        if self.peek(1).map_or(false, |op| BINARY.contains(&op)) && self.peek(2).is_some() {
+            let left = self.take();
+            let op = self.take();
+            let right = self.take();
+            return self.binary(sh, &left, &op, &right);
+        }
+        if UNARY.contains(&tok.as_str()) && self.peek(1).is_some() {
+            let op = self.take();
+            let operand = self.take();
+            return match op.as_str() {
+                "-z" => operand.is_empty(),
+                "-n" => !operand.is_empty(),
+                _ => file_test(&op, &operand),
+            };
+        }
+        !self.take().is_empty()
+    }
+
+    fn binary(&mut self, sh: &mut Sh, left: &str, op: &str, right: &str) -> bool {
+        match op {
+            "=" | "==" => if self.extended { glob_match(right, left) } else { left == right },
+            "!=" => if self.extended { !glob_match(right, left) } else { left != right },
+            "=~" => match Regex::new(right) {
+                Some(regex) => match regex.search(left) {
+                    Some(groups) => {
+                        sh.set_array("BASH_REMATCH", groups);
+                        true
+                    }
+                    None => {
+                        sh.set_array("BASH_REMATCH", Vec::new());
+                        false
+                    }
+                },
+                None => false,
+            },
+            "<" => left < right,
+            ">" => left > right,
+            _ => {
+                let (a, b) = (sh.arith(left), sh.arith(right));
+                match op {
+                    "-eq" => a == b,
+                    "-ne" => a != b,
+                    "-lt" => a < b,
+                    "-le" => a <= b,
+                    "-gt" => a > b,
+                    _ => a >= b,
+                }
+            }
+        }
+    }
+}
+
+fn file_test(op: &str, path: &str) -> bool {
+    let meta = std::fs::metadata(path);
+    match op {
+        "-e" | "-a" => meta.is_ok(),
+        "-f" => meta.map(|m| m.is_file()).unwrap_or(false),
+        "-d" => meta.map(|m| m.is_dir()).unwrap_or(false),
+        "-s" => meta.map(|m| m.len() > 0).unwrap_or(false),
+        "-L" | "-h" => std::fs::symlink_metadata(path).map(|m| m.file_type().is_symlink()).unwrap_or(false),
+        "-r" => std::fs::File::open(path).is_ok(),
+        "-w" => meta.map(|m| !m.permissions().readonly()).unwrap_or(false),
+        "-x" => is_executable(path),
+        "-p" => is_fifo(path),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).map(|m| m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &str) -> bool {
+    std::path::Path::new(path).exists()
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).map(|m| m.file_type().is_fifo()).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &str) -> bool {
+    false
+}
+
+// ----- glob patterns -----
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    glob_match_at(&p, &t)
+}
+
+fn glob_match_at(p: &[char], t: &[char]) -> bool {
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() {
+            match p[pi] {
+                '*' => {
+                    star = Some((pi, ti));
+                    pi += 1;
+                    continue;
+                }
+                '?' => {
+                    pi += 1;
+                    ti += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((matched, next)) = match_class(p, pi, t[ti]) {
+                        if matched {
+                            pi = next;
+                            ti += 1;
+                            continue;
+                        }
+                    } else if t[ti] == '[' {
+                        pi += 1;
+                        ti += 1;
+                        continue;
+                    }
+                }
+                '\\' if pi + 1 < p.len() => {
+                    if p[pi + 1] == t[ti] {
+                        pi += 2;
+                        ti += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == t[ti] {
+                        pi += 1;
+                        ti += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        match star {
+            Some((sp, st)) => {
+                pi = sp + 1;
+                ti = st + 1;
+                star = Some((sp, st + 1));
+            }
+            None => return false,
+        }
+    }
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+/// Match `c` against the bracket expression starting at `p[start]`, returning
+/// whether it matched and the index just past the closing `]`.
+fn match_class(p: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = i < p.len() && (p[i] == '!' || p[i] == '^');
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < p.len() && (p[i] != ']' || first) {
+        first = false;
+        if p[i] == '[' && i + 1 < p.len() && p[i + 1] == ':' {
+            let rest: String = p[i + 2..].iter().collect();
+            if let Some(end) = rest.find(":]") {
+                let class = &rest[..end];
+                matched |= char_class(class, c);
+                i += 2 + class.chars().count() + 2;
+                continue;
+            }
+        }
+        let lo = if p[i] == '\\' && i + 1 < p.len() { i += 1; p[i] } else { p[i] };
+        if i + 2 < p.len() && p[i + 1] == '-' && p[i + 2] != ']' {
+            matched |= lo <= c && c <= p[i + 2];
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+    if i >= p.len() {
+        return None;
+    }
+    Some((matched != negate, i + 1))
+}
+
+fn char_class(class: &str, c: char) -> bool {
+    match class {
+        "alpha" => c.is_alphabetic(),
+        "digit" => c.is_ascii_digit(),
+        "alnum" => c.is_alphanumeric(),
+        "upper" => c.is_uppercase(),
+        "lower" => c.is_lowercase(),
+        "space" => c.is_whitespace(),
+        "blank" => c == ' ' || c == '\t',
+        "punct" => c.is_ascii_punctuation(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        "cntrl" => c.is_control(),
+        "print" => !c.is_control(),
+        "graph" => !c.is_control() && c != ' ',
+        "word" => c.is_alphanumeric() || c == '_',
+        _ => false,
+    }
+}
+
+fn glob_escape(text: String) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if "*?[]\\".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn has_glob_chars(text: &str) -> bool {
+    text.contains(|c| c == '*' || c == '?' || c == '[')
+}
+
+/// Expand a filename pattern; an unmatched pattern expands to itself.
+fn glob(pattern: &str) -> Vec<String> {
+    if !has_glob_chars(pattern) {
+        return vec![pattern.to_string()];
+    }
+    let absolute = pattern.starts_with('/');
+    let mut paths = vec![if absolute { s("/") } else { String::new() }];
+    for segment in pattern.split('/').filter(|seg| !seg.is_empty()) {
+        let mut next = Vec::new();
+        for base in &paths {
+            if !has_glob_chars(segment) {
+                next.push(format!("{}{}", base, segment));
+                continue;
+            }
+            let dir = if base.is_empty() { s(".") } else { base.clone() };
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                let mut names: Vec<String> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| (!name.starts_with('.') || segment.starts_with('.')) && glob_match(segment, name))
+                    .collect();
+                names.sort();
+                for name in names {
+                    next.push(format!("{}{}", base, name));
+                }
+            }
+        }
+        paths = next.into_iter().map(|p| format!("{}/", p)).collect();
+    }
+    let mut matches: Vec<String> = paths
+        .into_iter()
+        .map(|p| p.trim_end_matches('/').to_string())
+        .filter(|p| std::fs::symlink_metadata(p).is_ok())
+        .collect();
+    if matches.is_empty() {
+        return vec![pattern.to_string()];
+    }
+    matches.sort();
+    matches
+}
+
+fn trim(value: String, pattern: String, prefix: bool, longest: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let cuts: Vec<usize> = if prefix == longest { (0..=chars.len()).rev().collect() } else { (0..=chars.len()).collect() };
+    for n in cuts {
+        let (head, tail): (String, String) = (chars[..n].iter().collect(), chars[n..].iter().collect());
+        if prefix && glob_match(&pattern, &head) {
+            return tail;
+        }
+        if !prefix && glob_match(&pattern, &tail) {
+            return head;
+        }
+    }
+    value
+}
+
+fn substitute(value: String, pattern: String, replacement: String, every: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let mut replaced = false;
+    while i < chars.len() {
+        if !replaced || every {
+            let end = (i + 1..=chars.len()).rev().find(|&end| {
+                let candidate: String = chars[i..end].iter().collect();
+                glob_match(&pattern, &candidate)
+            });
+            if let Some(end) = end {
+                out.push_str(&replacement);
+                i = end;
+                replaced = true;
+                continue;
+            }
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+fn substitute_anchored(value: String, pattern: String, replacement: String, prefix: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let cuts: Vec<usize> = if prefix { (0..=chars.len()).rev().collect() } else { (0..=chars.len()).collect() };
+    for n in cuts {
+        let (head, tail): (String, String) = (chars[..n].iter().collect(), chars[n..].iter().collect());
+        if prefix && glob_match(&pattern, &head) {
+            return replacement + &tail;
+        }
+        if !prefix && glob_match(&pattern, &tail) {
+            return head + &replacement;
+        }
+    }
+    value
+}
+
+fn ucfirst(value: String) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => value,
+    }
+}
+
+fn lcfirst(value: String) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().collect::<String>() + chars.as_str(),
+        None => value,
+    }
+}
+
+fn swapcase(value: String, first_only: bool) -> String {
+    value.chars().enumerate().map(|(i, c)| {
+        if first_only && i > 0 {
+            c.to_string()
+        } else if c.is_uppercase() {
+            c.to_lowercase().collect()
+        } else {
+            c.to_uppercase().collect()
+        }
+    }).collect()
+}
+
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn substring(value: String, offset: i64, length: Option<i64>) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    slice(chars.iter().map(|c| c.to_string()).collect(), offset, length).concat()
+}
+
+fn slice(items: Vec<String>, offset: i64, length: Option<i64>) -> Vec<String> {
+    let len = items.len() as i64;
+    let start = if offset < 0 { len + offset } else { offset.min(len) };
+    if start < 0 {
+        return Vec::new();
+    }
+    let end = match length {
+        Some(length) if length < 0 => (len + length).max(start),
+        Some(length) => (start + length).min(len),
+        None => len,
+    };
+    items[start as usize..end as usize].to_vec()
+}
+
+fn home(path: &str) -> String {
+    let home = std::env::var("HOME").unwrap_or_default();
+    format!("{}{}", home, &path[1..])
+}
+
+// ----- echo -e / printf escapes -----
+
+fn backslash_escapes(text: &str) -> (String, bool) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\\' || i + 1 >= chars.len() {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let next = chars[i + 1];
+        i += 2;
+        match next {
+            'a' => out.push('\x07'),
+            'b' => out.push('\x08'),
+            'e' | 'E' => out.push('\x1b'),
+            'f' => out.push('\x0c'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'v' => out.push('\x0b'),
+            '\\' => out.push('\\'),
+            'c' => return (out, true),
+            '0'..='7' => {
+                let skip_zero = next == '0';
+                let start = if skip_zero { i } else { i - 1 };
+                let mut end = start;
+                while end < chars.len() && end < start + 3 && chars[end].is_digit(8) {
+                    end += 1;
+                }
+                let digits: String = chars[start..end].iter().collect();
+                let code = u32::from_str_radix(if digits.is_empty() { "0" } else { &digits }, 8).unwrap_or(0);
+                out.push(char::from_u32(code).unwrap_or('?'));
+                i = end;
+            }
+            'x' if i < chars.len() && chars[i].is_ascii_hexdigit() => {
+                let mut end = i;
+                while end < chars.len() && end < i + 2 && chars[end].is_ascii_hexdigit() {
+                    end += 1;
+                }
+                let digits: String = chars[i..end].iter().collect();
+                out.push(char::from_u32(u32::from_str_radix(&digits, 16).unwrap_or(0)).unwrap_or('?'));
+                i = end;
+            }
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    (out, false)
+}
+
+fn shell_quote(text: &str) -> String {
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || "_./=:,+@%-".contains(c)) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn printf_number(text: &str) -> i64 {
+    if let Some(rest) = text.strip_prefix('\'').or_else(|| text.strip_prefix('"')) {
+        return rest.chars().next().map(|c| c as i64).unwrap_or(0);
+    }
+    num(text)
+}
+
+fn pad(body: String, width: usize, left: bool, zero: bool) -> String {
+    let len = body.chars().count();
+    if len >= width {
+        return body;
+    }
+    let fill = width - len;
+    if left {
+        return body + &" ".repeat(fill);
+    }
+    if zero {
+        let sign_len = if body.starts_with('-') || body.starts_with('+') || body.starts_with(' ') { 1 } else { 0 };
+        let prefix_len = if body[sign_len..].starts_with("0x") || body[sign_len..].starts_with("0X") { sign_len + 2 } else { sign_len };
+        return format!("{}{}{}", &body[..prefix_len], "0".repeat(fill), &body[prefix_len..]);
+    }
+    " ".repeat(fill) + &body
+}
+
+fn format_exponent(value: f64, precision: usize, upper: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let result = format!("{}e{}{:02}", mantissa, sign, exponent.abs());
+    if upper { result.to_uppercase() } else { result }
+}
+
+fn format_general(value: f64, precision: usize, alternate: bool, upper: bool) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return s("0");
+    }
+    let exponent = format!("{:.*e}", precision - 1, value)
+        .split_once('e')
+        .and_then(|(_, e)| e.parse::<i32>().ok())
+        .unwrap_or(0);
+    let mut text = if exponent < -4 || exponent >= precision as i32 {
+        format_exponent(value, precision - 1, upper)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent).max(0) as usize, value)
+    };
+    if !alternate {
+        if let Some(e) = text.find(|c| c == 'e' || c == 'E') {
+            let (mantissa, exp) = text.split_at(e);
+            let mantissa = if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+            text = format!("{}{}", mantissa, exp);
+        } else if text.contains('.') {
+            text = text.trim_end_matches('0').trim_end_matches('.').to_string();
+        }
+    }
+    text
+}
+
+/// Expand a printf format, reusing it until all arguments are consumed.
+fn format_printf(args: &[String], out: &mut String) -> i32 {
+    let format: Vec<char> = args[0].chars().collect();
+    let mut values: &[String] = &args[1..];
+    let mut status = 0;
+    loop {
+        let mut consumed = 0;
+        let mut i = 0;
+        let mut next_value = |consumed: &mut usize| -> Option<String> {
+            let value = values.get(*consumed).cloned();
+            *consumed += 1;
+            value
+        };
+        while i < format.len() {
+            let c = format[i];
+            if c == '\\' {
+                let rest: String = format[i..].iter().collect();
+                let end = if i + 1 < format.len() && format[i + 1].is_digit(8) {
+                    i + 2 + format[i + 2..].iter().take(2).take_while(|d| d.is_digit(8)).count()
+                } else if i + 1 < format.len() && format[i + 1] == 'x' {
+                    i + 2 + format[i + 2..].iter().take(2).take_while(|d| d.is_ascii_hexdigit()).count()
+                } else {
+                    (i + 2).min(format.len())
+                };
+                let piece: String = rest.chars().take(end - i).collect();
+                let (text, stop) = backslash_escapes(&piece);
+                out.push_str(&text);
+                if stop {
+                    return status;
+                }
+                i = end;
+                continue;
+            }
+            if c != '%' || i + 1 >= format.len() {
+                out.push(c);
+                i += 1;
+                continue;
+            }
+            i += 1;
+            if format[i] == '%' {
+                out.push('%');
+                i += 1;
+                continue;
+            }
+            let mut flags = String::new();
+            while i < format.len() && "-+ #0".contains(format[i]) {
+                flags.push(format[i]);
+                i += 1;
+            }
+            let mut width: i64 = 0;
+            if i < format.len() && format[i] == '*' {
+                width = next_value(&mut consumed).map(|v| printf_number(&v)).unwrap_or(0);
+                i += 1;
+            } else {
+                while i < format.len() && format[i].is_ascii_digit() {
+                    width = width * 10 + format[i].to_digit(10).unwrap() as i64;
+                    i += 1;
+                }
+            }
+            let mut precision: Option<usize> = None;
+            if i < format.len() && format[i] == '.' {
+                i += 1;
+                let mut p: i64 = 0;
+                if i < format.len() && format[i] == '*' {
+                    p = next_value(&mut consumed).map(|v| printf_number(&v)).unwrap_or(0);
+                    i += 1;
+                } else {
+                    while i < format.len() && format[i].is_ascii_digit() {
+                        p = p * 10 + format[i].to_digit(10).unwrap() as i64;
+                        i += 1;
+                    }
+                }
+                precision = Some(p.max(0) as usize);
+            }
+            while i < format.len() && "hlLqjzt".contains(format[i]) {
+                i += 1;
+            }
+            if i >= format.len() {
+                break;
+            }
+            let conv = format[i];
+            i += 1;
+            let left = flags.contains('-') || width < 0;
+            let width = width.unsigned_abs() as usize;
+            let zero = flags.contains('0') && !left;
+            let value = next_value(&mut consumed);
+            let body = match conv {
+                'd' | 'i' | 'u' => {
+                    let text = value.unwrap_or_default();
+                    let n = printf_number(&text);
+                    if !text.is_empty() && parse_number(text.trim()).is_none() && !text.starts_with('\'') {
+                        status = 1;
+                    }
+                    let mut digits = n.unsigned_abs().to_string();
+                    if let Some(p) = precision {
+                        while digits.len() < p {
+                            digits.insert(0, '0');
+                        }
+                    }
+                    let sign = if n < 0 { "-" } else if flags.contains('+') { "+" } else if flags.contains(' ') { " " } else { "" };
+                    pad(format!("{}{}", sign, digits), width, left, zero && precision.is_none())
+                }
+                'o' | 'x' | 'X' => {
+                    let n = printf_number(&value.unwrap_or_default()) as u64;
+                    let mut digits = match conv {
+                        'o' => format!("{:o}", n),
+                        'x' => format!("{:x}", n),
+                        _ => format!("{:X}", n),
+                    };
+                    if let Some(p) = precision {
+                        while digits.len() < p {
+                            digits.insert(0, '0');
+                        }
+                    }
+                    if flags.contains('#') && n != 0 {
+                        digits = match conv {
+                            'o' => format!("0{}", digits),
+                            'x' => format!("0x{}", digits),
+                            _ => format!("0X{}", digits),
+                        };
+                    }
+                    pad(digits, width, left, zero && precision.is_none())
+                }
+                'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                    let n: f64 = value.as_deref().map(str::trim).unwrap_or("0").parse().unwrap_or(0.0);
+                    let p = precision.unwrap_or(6);
+                    let text = match conv {
+                        'e' | 'E' => format_exponent(n, p, conv == 'E'),
+                        'g' | 'G' => format_general(n, p, flags.contains('#'), conv == 'G'),
+                        _ => format!("{:.*}", p, n),
+                    };
+                    let sign = if n >= 0.0 && flags.contains('+') { "+" } else if n >= 0.0 && flags.contains(' ') { " " } else { "" };
+                    pad(format!("{}{}", sign, text), width, left, zero)
+                }
+                'c' => pad(value.unwrap_or_default().chars().take(1).collect(), width, left, false),
+                'b' => {
+                    let (text, stop) = backslash_escapes(&value.unwrap_or_default());
+                    out.push_str(&pad(text, width, left, false));
+                    if stop {
+                        return status;
+                    }
+                    continue;
+                }
+                'q' => pad(shell_quote(&value.unwrap_or_default()), width, left, false),
+                _ => {
+                    let text = value.unwrap_or_default();
+                    let text = match precision {
+                        Some(p) => text.chars().take(p).collect(),
+                        None => text,
+                    };
+                    pad(text, width, left, false)
+                }
+            };
+            out.push_str(&body);
+        }
+        if consumed == 0 || consumed >= values.len() {
+            break;
+        }
+        values = &values[consumed..];
+    }
+    status
+}
+
+// ----- minimal POSIX extended regular expressions for [[ =~ ]] -----
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Group(Box<Node>, usize),
+    Alt(Vec<Node>),
+    Concat(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Named(String),
+}
+
+struct Regex {
+    root: Node,
+    groups: usize,
+}
+
+impl Regex {
+    fn new(pattern: &str) -> Option<Regex> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = RegexParser { chars, pos: 0, groups: 0 };
+        let root = parser.alternation()?;
+        if parser.pos != parser.chars.len() {
+            return None;
+        }
+        Some(Regex { root, groups: parser.groups })
+    }
+
+    /// Leftmost match; returns the whole match followed by each capture group.
+    fn search(&self, text: &str) -> Option<Vec<String>> {
+        let chars: Vec<char> = text.chars().collect();
+        for start in 0..=chars.len() {
+            let mut caps = vec![None; self.groups + 1];
+            let mut found = None;
+            if match_node(&self.root, &chars, start, &mut caps, &mut |end, caps| {
+                found = Some((end, caps.to_vec()));
+                true
+            }) {
+                let (end, caps) = found.unwrap();
+                let mut groups = vec![chars[start..end].iter().collect::<String>()];
+                for cap in caps.iter().skip(1) {
+                    groups.push(cap.map(|(a, b): (usize, usize)| chars[a..b].iter().collect()).unwrap_or_default());
+                }
+                return Some(groups);
+            }
+        }
+        None
+    }
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl RegexParser {
+    fn alternation(&mut self) -> Option<Node> {
+        let mut branches = vec![self.concat()?];
+        while self.pos < self.chars.len() && self.chars[self.pos] == '|' {
+            self.pos += 1;
+            branches.push(self.concat()?);
+        }
+        Some(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alt(branches) })
+    }
+
+    fn concat(&mut self) -> Option<Node> {
+        let mut items = Vec::new();
+        while self.pos < self.chars.len() && self.chars[self.pos] != '|' && self.chars[self.pos] != ')' {
+            let atom = self.atom()?;
+            items.push(self.quantifier(atom)?);
+        }
+        Some(Node::Concat(items))
+    }
+
+    fn quantifier(&mut self, atom: Node) -> Option<Node> {
+        let mut node = atom;
+        while self.pos < self.chars.len() {
+            let (min, max) = match self.chars[self.pos] {
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                '{' => {
+                    let rest: String = self.chars[self.pos + 1..].iter().collect();
+                    let end = rest.find('}')?;
+                    let body = &rest[..end];
+                    let (min, max) = match body.split_once(',') {
+                        Some((a, "")) => (a.parse().ok()?, None),
+                        Some((a, b)) => (a.parse().ok()?, Some(b.parse().ok()?)),
+                        None => (body.parse().ok()?, Some(body.parse().ok()?)),
+                    };
+                    self.pos += end + 1;
+                    (min, max)
+                }
+                _ => break,
+            };
+            self.pos += 1;
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+        Some(node)
+    }
+
+    fn atom(&mut self) -> Option<Node> {
+        let c = self.chars[self.pos];
+        self.pos += 1;
+        Some(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                self.groups += 1;
+                let index = self.groups;
+                let inner = self.alternation()?;
+                if self.pos >= self.chars.len() || self.chars[self.pos] != ')' {
+                    return None;
+                }
+                self.pos += 1;
+                Node::Group(Box::new(inner), index)
+            }
+            '[' => self.class()?,
+            '\\' if self.pos < self.chars.len() => {
+                let escaped = self.chars[self.pos];
+                self.pos += 1;
+                match escaped {
+                    'd' => Node::Class(vec![ClassItem::Named(s("digit"))], false),
+                    'w' => Node::Class(vec![ClassItem::Named(s("word"))], false),
+                    's' => Node::Class(vec![ClassItem::Named(s("space"))], false),
+                    'D' => Node::Class(vec![ClassItem::Named(s("digit"))], true),
+                    'W' => Node::Class(vec![ClassItem::Named(s("word"))], true),
+                    'S' => Node::Class(vec![ClassItem::Named(s("space"))], true),
+                    other => Node::Char(other),
+                }
+            }
+            other => Node::Char(other),
+        })
+    }
+
+    fn class(&mut self) -> Option<Node> {
+        let mut items = Vec::new();
+        let negate = self.pos < self.chars.len() && self.chars[self.pos] == '^';
+        if negate {
+            self.pos += 1;
+        }
+        let mut first = true;
+        while self.pos < self.chars.len() && (self.chars[self.pos] != ']' || first) {
+            first = false;
+            if self.chars[self.pos] == '[' && self.chars.get(self.pos + 1) == Some(&':') {
+                let rest: String = self.chars[self.pos + 2..].iter().collect();
+                let end = rest.find(":]")?;
+                items.push(ClassItem::Named(rest[..end].to_string()));
+                self.pos += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+            let lo = self.chars[self.pos];
+            if self.pos + 2 < self.chars.len() && self.chars[self.pos + 1] == '-' && self.chars[self.pos + 2] != ']' {
+                items.push(ClassItem::Range(lo, self.chars[self.pos + 2]));
+                self.pos += 3;
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+                self.pos += 1;
+            }
+        }
+        if self.pos >= self.chars.len() {
+            return None;
+        }
+        self.pos += 1;
+        Some(Node::Class(items, negate))
+    }
+}
+
+type Caps = Vec<Option<(usize, usize)>>;
+
+/// Backtracking matcher in continuation-passing style: `k` is called with the
+/// end position of every way `node` can match at `pos`.
+fn match_node(node: &Node, text: &[char], pos: usize, caps: &mut Caps, k: &mut dyn FnMut(usize, &mut Caps) -> bool) -> bool {
+    match node {
+        Node::Char(c) => pos < text.len() && text[pos] == *c && k(pos + 1, caps),
+        Node::Any => pos < text.len() && text[pos] != '\n' && k(pos + 1, caps),
+        Node::Class(items, negate) => {
+            if pos >= text.len() {
+                return false;
+            }
+            let c = text[pos];
+            let hit = items.iter().any(|item| match item {
+                ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+                ClassItem::Named(name) => char_class(name, c),
+            });
+            hit != *negate && k(pos + 1, caps)
+        }
+        Node::Start => pos == 0 && k(pos, caps),
+        Node::End => pos == text.len() && k(pos, caps),
+        Node::Group(inner, index) => {
+            let index = *index;
+            let saved = caps[index];
+            let matched = match_node(inner, text, pos, caps, &mut |end, caps: &mut Caps| {
+                let previous = caps[index];
+                caps[index] = Some((pos, end));
+                if k(end, caps) {
+                    return true;
+                }
+                caps[index] = previous;
+                false
+            });
+            if !matched {
+                caps[index] = saved;
+            }
+            matched
+        }
+        Node::Alt(branches) => branches.iter().any(|branch| match_node(branch, text, pos, caps, k)),
+        Node::Concat(items) => match_sequence(items, text, pos, caps, k),
+        Node::Repeat(inner, min, max) => match_repeat(inner, *min, *max, 0, text, pos, caps, k),
+    }
+}
+
+fn match_sequence(items: &[Node], text: &[char], pos: usize, caps: &mut Caps, k: &mut dyn FnMut(usize, &mut Caps) -> bool) -> bool {
+    match items.split_first() {
+        None => k(pos, caps),
+        Some((first, rest)) => match_node(first, text, pos, caps, &mut |next, caps: &mut Caps| match_sequence(rest, text, next, caps, k)),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn match_repeat(inner: &Node, min: usize, max: Option<usize>, count: usize, text: &[char], pos: usize, caps: &mut Caps, k: &mut dyn FnMut(usize, &mut Caps) -> bool) -> bool {
+    // Greedy: try one more repetition first, then fall back to stopping here
+    if max.map_or(true, |max| count < max) {
+        let matched = match_node(inner, text, pos, caps, &mut |next, caps: &mut Caps| {
+            next != pos && match_repeat(inner, min, max, count + 1, text, next, caps, k)
+        });
+        if matched {
+            return true;
+        }
+    }
+    count >= min && k(pos, caps)
+}
@@ -0,0 +1,31 @@
+use crate::ast::*;
+use super::RustGenerator;
+use crate::generator::python::test_expressions::split_test_words;
+use super::words::{raw_text_to_rust, sh_call};
+
+pub fn generate_test_expression_impl(generator: &mut RustGenerator, test_expr: &TestExpression) -> String {
+    format!("{}{};\n", generator.indent(), generator.test_expression_call(test_expr))
+}
+
+pub fn test_expression_call_impl(_generator: &mut RustGenerator, test_expr: &TestExpression) -> String {
+    sh_call("test", vec![test_words_to_rust(&test_expr.expression), "true".to_string()])
+}
+
+/// Convert the text between `[` and `]` (or `[[` and `]]`) into a
+/// `Vec<String>` of operands for the runtime `Sh::test`.
+pub fn test_words_to_rust(expression: &str) -> String {
+    let words = split_test_words(expression);
+    let mut items = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let quoted = word.starts_with('"') || word.starts_with('\'');
+        let after_match_op = i > 0 && matches!(words[i - 1].as_str(), "==" | "=" | "!=");
+        let expr = raw_text_to_rust(word);
+        if quoted && after_match_op {
+            // A quoted right-hand side is compared literally, not as a pattern
+            items.push(format!("glob_escape({})", expr));
+        } else {
+            items.push(expr);
+        }
+    }
+    format!("vec![{}]", items.join(", "))
+}
@@ -0,0 +1,460 @@
+use crate::ast::*;
+use crate::generator::python::words::{expand_braces, has_glob_chars, is_single_quoted, unescape_double_quoted, unescape_unquoted};
+use super::RustGenerator;
+
+pub fn word_to_rust_impl(generator: &mut RustGenerator, word: &Word) -> String {
+    match word {
+        Word::Literal(s, _) => literal_to_rust(s),
+        Word::Variable(var, _) => variable_to_rust(var),
+        Word::ParameterExpansion(pe, _) => generator.generate_parameter_expansion(pe),
+        Word::Array(_, elements, _) => format!("{}.join(\" \")", array_to_rust(elements)),
+        Word::MapAccess(map_name, key, _) => map_access_to_rust(map_name, key),
+        Word::MapKeys(map_name, _) => format!("sh.keys({}).join(\" \")", rust_string_literal(map_name)),
+        Word::MapLength(map_name, _) => format!("sh.elements({}).len().to_string()", rust_string_literal(map_name)),
+        Word::ArraySlice(name, offset, length, _) => {
+            format!("{}.join(\" \")", array_slice_to_rust(name, offset, length.as_deref()))
+        }
+        Word::Arithmetic(expr, _) => format!("{}.to_string()", arithmetic_to_rust(&expr.expression)),
+        Word::BraceExpansion(expansion, _) => string_to_rust(&expand_braces(expansion).join(" ")),
+        Word::CommandSubstitution(cmd, _) => capture_to_rust(generator, cmd),
+        Word::StringInterpolation(interp, _) => string_interpolation_to_rust(generator, interp),
+        Word::Concatenation(words, _) => string_interpolation_to_rust(generator, &concatenated_parts(words)),
+        Word::ProcessSubstitution(cmd, direction, _) => {
+            generator.pending_process_subs = true;
+            let body = generator.closure(cmd);
+            match direction {
+                ProcessDirection::Input => format!("sh.process_sub_in({})", body),
+                ProcessDirection::Output => format!("sh.process_sub_out({})", body),
+            }
+        }
+    }
+}
+
+/// Convert a word in argument position, where unquoted expansions are
+/// subject to word splitting and globbing. Each entry is either a single
+/// `String` or, for splats, a `Vec<String>` expression.
+pub fn word_to_rust_args_impl(generator: &mut RustGenerator, word: &Word) -> Vec<RustArg> {
+    match word {
+        Word::Literal(s, _) if !is_single_quoted(s) && has_glob_chars(s) => {
+            vec![RustArg::Splat(format!("glob({})", rust_string_literal(s)))]
+        }
+        Word::Variable(var, _) => match var.as_str() {
+            "@" | "*" => vec![RustArg::Splat("sh.args_list()".to_string())],
+            "#" | "?" | "$" | "!" => vec![RustArg::Single(variable_to_rust(var))],
+            _ => vec![RustArg::Splat(split_to_rust(&variable_to_rust(var)))],
+        },
+        Word::ParameterExpansion(pe, _) => {
+            let expr = generator.generate_parameter_expansion(pe);
+            vec![RustArg::Splat(split_to_rust(&expr))]
+        }
+        Word::MapAccess(map_name, key, _) if key == "@" || key == "*" => {
+            vec![RustArg::Splat(format!("sh.elements({})", rust_string_literal(map_name)))]
+        }
+        Word::MapKeys(map_name, _) => vec![RustArg::Splat(format!("sh.keys({})", rust_string_literal(map_name)))],
+        Word::ArraySlice(name, offset, length, _) => {
+            vec![RustArg::Splat(array_slice_to_rust(name, offset, length.as_deref()))]
+        }
+        Word::BraceExpansion(expansion, _) => expand_braces(expansion)
+            .iter()
+            .map(|item| RustArg::Single(string_to_rust(item)))
+            .collect(),
+        Word::CommandSubstitution(..) => {
+            let expr = generator.word_to_rust(word);
+            vec![RustArg::Splat(split_to_rust(&expr))]
+        }
+        Word::StringInterpolation(interp, _) if interp.parts.len() == 1 => match &interp.parts[0] {
+            // "$@" and "${arr[@]}" expand to one word per element even when quoted
+            StringPart::Variable(var) if var == "@" => vec![RustArg::Splat("sh.args_list()".to_string())],
+            StringPart::MapAccess(map_name, key) if key == "@" => {
+                vec![RustArg::Splat(format!("sh.elements({})", rust_string_literal(map_name)))]
+            }
+            StringPart::ParameterExpansion(ParameterExpansion { variable, operator: ParameterExpansionOperator::ArraySlice(offset, length) })
+                if variable == "@" =>
+            {
+                let length = match length {
+                    Some(length) => format!("Some({})", arith_of_text(length)),
+                    None => "None".to_string(),
+                };
+                vec![RustArg::Splat(positional_slice_to_rust(offset, &length))]
+            }
+            _ => vec![RustArg::Single(generator.word_to_rust(word))],
+        },
+        _ => vec![RustArg::Single(generator.word_to_rust(word))],
+    }
+}
+
+/// One element of an argument list: a single word or a splatted list of words.
+pub enum RustArg {
+    Single(String),
+    Splat(String),
+}
+
+pub fn words_to_rust_vec_impl(generator: &mut RustGenerator, words: &[Word]) -> String {
+    let args: Vec<RustArg> = words.iter()
+        .flat_map(|word| generator.word_to_rust_args(word))
+        .collect();
+    args_to_vec(args)
+}
+
+/// Join argument pieces into a single `Vec<String>` expression.
+pub fn args_to_vec(args: Vec<RustArg>) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut singles: Vec<String> = Vec::new();
+    let has_splat = args.iter().any(|arg| matches!(arg, RustArg::Splat(_)));
+    for arg in args {
+        match arg {
+            RustArg::Single(expr) => singles.push(expr),
+            RustArg::Splat(expr) => {
+                if !singles.is_empty() {
+                    parts.push(format!("vec![{}]", std::mem::take(&mut singles).join(", ")));
+                }
+                parts.push(expr);
+            }
+        }
+    }
+    if !has_splat {
+        return format!("vec![{}]", singles.join(", "));
+    }
+    if !singles.is_empty() {
+        parts.push(format!("vec![{}]", singles.join(", ")));
+    }
+    if parts.len() == 1 {
+        return parts.pop().unwrap();
+    }
+    format!("[{}].concat()", parts.join(", "))
+}
+
+pub fn generate_parameter_expansion_impl(_generator: &mut RustGenerator, pe: &ParameterExpansion) -> String {
+    let name = rust_string_literal(&pe.variable);
+    let value = parameter_value_to_rust(&pe.variable);
+    // The fallback is only evaluated when it is used, as in the shell
+    let unless_empty = |otherwise: String| {
+        format!("{{ let v = {}; if !v.is_empty() {{ v }} else {{ {} }} }}", value, otherwise)
+    };
+    let unless_unset = |otherwise: String| {
+        format!("{{ let v = {}; if sh.is_set({}) {{ v }} else {{ {} }} }}", value, name, otherwise)
+    };
+    let store = |text: &str| sh_call("store", vec![name.clone(), raw_text_to_rust(text)]);
+    let error = |text: &str, default: &str| {
+        sh_call("parameter_error", vec![name.clone(), raw_text_to_rust(text), rust_string_literal(default)])
+    };
+    match &pe.operator {
+        ParameterExpansionOperator::None => value,
+        ParameterExpansionOperator::Length => format!("{}.chars().count().to_string()", value),
+        ParameterExpansionOperator::UppercaseAll => format!("{}.to_uppercase()", value),
+        ParameterExpansionOperator::LowercaseAll => format!("{}.to_lowercase()", value),
+        ParameterExpansionOperator::UppercaseFirst => format!("ucfirst({})", value),
+        ParameterExpansionOperator::LowercaseFirst => format!("lcfirst({})", value),
+        ParameterExpansionOperator::ToggleCaseAll => format!("swapcase({}, false)", value),
+        ParameterExpansionOperator::ToggleCaseFirst => format!("swapcase({}, true)", value),
+        ParameterExpansionOperator::RemoveLongestPrefix(pattern) => {
+            format!("trim({}, {}, true, true)", value, raw_text_to_rust(pattern))
+        }
+        ParameterExpansionOperator::RemoveShortestPrefix(pattern) => {
+            format!("trim({}, {}, true, false)", value, raw_text_to_rust(pattern))
+        }
+        ParameterExpansionOperator::RemoveLongestSuffix(pattern) => {
+            format!("trim({}, {}, false, true)", value, raw_text_to_rust(pattern))
+        }
+        ParameterExpansionOperator::RemoveShortestSuffix(pattern) => {
+            format!("trim({}, {}, false, false)", value, raw_text_to_rust(pattern))
+        }
+        ParameterExpansionOperator::SubstituteAll(pattern, replacement) => {
+            format!("substitute({}, {}, {}, true)", value,
+                    raw_text_to_rust(pattern), raw_text_to_rust(replacement))
+        }
+        ParameterExpansionOperator::SubstituteFirst(pattern, replacement) => {
+            format!("substitute({}, {}, {}, false)", value,
+                    raw_text_to_rust(pattern), raw_text_to_rust(replacement))
+        }
+        ParameterExpansionOperator::SubstitutePrefix(pattern, replacement) => {
+            format!("substitute_anchored({}, {}, {}, true)", value,
+                    raw_text_to_rust(pattern), raw_text_to_rust(replacement))
+        }
+        ParameterExpansionOperator::SubstituteSuffix(pattern, replacement) => {
+            format!("substitute_anchored({}, {}, {}, false)", value,
+                    raw_text_to_rust(pattern), raw_text_to_rust(replacement))
+        }
+        ParameterExpansionOperator::DefaultValue(default) => unless_empty(raw_text_to_rust(default)),
+        ParameterExpansionOperator::DefaultIfUnset(default) => unless_unset(raw_text_to_rust(default)),
+        ParameterExpansionOperator::AssignDefault(default) => unless_empty(store(default)),
+        ParameterExpansionOperator::AssignIfUnset(default) => unless_unset(store(default)),
+        ParameterExpansionOperator::ErrorIfUnset(message) => {
+            unless_empty(error(message, "parameter null or not set"))
+        }
+        ParameterExpansionOperator::ErrorIfNotSet(message) => unless_unset(error(message, "parameter not set")),
+        ParameterExpansionOperator::AlternativeValue(alternative) => {
+            format!("if {}.is_empty() {{ String::new() }} else {{ {} }}", value, raw_text_to_rust(alternative))
+        }
+        ParameterExpansionOperator::AlternativeIfSet(alternative) => {
+            format!("if sh.is_set({}) {{ {} }} else {{ String::new() }}", name, raw_text_to_rust(alternative))
+        }
+        ParameterExpansionOperator::Basename => format!("trim({}, s(\"*/\"), true, true)", value),
+        ParameterExpansionOperator::Dirname => format!("trim({}, s(\"/*\"), false, false)", value),
+        ParameterExpansionOperator::ArraySlice(offset, length) => {
+            let length = match length {
+                Some(length) => format!("Some({})", arith_of_text(length)),
+                None => "None".to_string(),
+            };
+            match pe.variable.as_str() {
+                "@" | "*" => format!("{}.join(\" \")", positional_slice_to_rust(offset, &length)),
+                _ => format!("substring({}, {}, {})", value, arith_of_text(offset), length),
+            }
+        }
+        ParameterExpansionOperator::PrefixNames(_) => format!("sh.prefix_names({}).join(\" \")", name),
+        ParameterExpansionOperator::Transform(op) => match op {
+            'Q' => format!("single_quote(&{})", value),
+            'U' => format!("{}.to_uppercase()", value),
+            'L' => format!("{}.to_lowercase()", value),
+            'u' => format!("ucfirst({})", value),
+            'E' => format!("backslash_escapes(&{}).0", value),
+            _ => value,
+        },
+    }
+}
+
+/// `${@:offset:length}`, which counts `$0` as the first positional parameter.
+fn positional_slice_to_rust(offset: &str, length: &str) -> String {
+    format!("slice(sh.positional(), {}, {})", arith_of_text(offset), length)
+}
+
+/// Arithmetic is evaluated at run time by the prelude's `Sh::arith`.
+pub fn arithmetic_to_rust(expr: &str) -> String {
+    format!("sh.arith({})", rust_string_literal(expr.trim()))
+}
+
+/// Evaluate raw shell text (which may reference variables) as arithmetic.
+fn arith_of_text(text: &str) -> String {
+    sh_call("arith", vec![format!("&{}", raw_text_to_rust(text))])
+}
+
+pub fn string_interpolation_to_rust(generator: &mut RustGenerator, interp: &StringInterpolation) -> String {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut literal = String::new();
+
+    for part in &interp.parts {
+        let expr = match part {
+            StringPart::Literal(s) => {
+                literal.push_str(&unescape_double_quoted(s));
+                continue;
+            }
+            StringPart::Variable(var) => variable_to_rust(var),
+            StringPart::ParameterExpansion(pe) => generator.generate_parameter_expansion(pe),
+            StringPart::MapAccess(map_name, key) => map_access_to_rust(map_name, key),
+            StringPart::MapKeys(map_name) => format!("sh.keys({}).join(\" \")", rust_string_literal(map_name)),
+            StringPart::MapLength(map_name) => format!("sh.elements({}).len().to_string()", rust_string_literal(map_name)),
+            StringPart::ArraySlice(name, offset, length) => {
+                format!("{}.join(\" \")", array_slice_to_rust(name, offset, length.as_deref()))
+            }
+            StringPart::Arithmetic(expr) => format!("{}.to_string()", arithmetic_to_rust(&expr.expression)),
+            StringPart::CommandSubstitution(cmd) => capture_to_rust(generator, cmd),
+        };
+        if !literal.is_empty() {
+            pieces.push(string_to_rust(&std::mem::take(&mut literal)));
+        }
+        pieces.push(expr);
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(string_to_rust(&literal));
+    }
+    concat_pieces(pieces)
+}
+
+fn capture_to_rust(generator: &mut RustGenerator, cmd: &Command) -> String {
+    let body = generator.closure(cmd);
+    format!("sh.capture({})", body)
+}
+
+pub fn variable_to_rust(var: &str) -> String {
+    format!("sh.var({})", rust_string_literal(var))
+}
+
+fn split_to_rust(expr: &str) -> String {
+    sh_call("split", vec![format!("&{}", expr)])
+}
+
+/// The value named by the inside of `${...}`, including `#name` and `name[key]` forms.
+fn parameter_value_to_rust(variable: &str) -> String {
+    if let Some(name) = variable.strip_prefix('#') {
+        if !name.is_empty() {
+            if let Some(array) = name.strip_suffix("[@]").or_else(|| name.strip_suffix("[*]")) {
+                return format!("sh.elements({}).len().to_string()", rust_string_literal(array));
+            }
+            return format!("{}.chars().count().to_string()", parameter_value_to_rust(name));
+        }
+    }
+    if let (Some(open), true) = (variable.find('['), variable.ends_with(']')) {
+        let name = &variable[..open];
+        let key = &variable[open + 1..variable.len() - 1];
+        return map_access_to_rust(name, key);
+    }
+    variable_to_rust(variable)
+}
+
+fn map_access_to_rust(map_name: &str, key: &str) -> String {
+    match key {
+        "@" | "*" => format!("sh.elements({}).join(\" \")", rust_string_literal(map_name)),
+        _ => sh_call("index", vec![rust_string_literal(map_name), raw_text_to_rust(key)]),
+    }
+}
+
+fn array_slice_to_rust(name: &str, offset: &str, length: Option<&str>) -> String {
+    let length = match length {
+        Some(length) => format!("Some({})", arith_of_text(length)),
+        None => "None".to_string(),
+    };
+    format!("slice(sh.elements({}), {}, {})", rust_string_literal(name), arith_of_text(offset), length)
+}
+
+/// Elements of an `arr=(...)` assignment as a `Vec<String>` expression.
+pub fn array_to_rust(elements: &[String]) -> String {
+    let last = elements.len().saturating_sub(1);
+    let items: Vec<String> = elements.iter().enumerate()
+        .filter_map(|(i, element)| {
+            // The standalone-assignment parser can leave the surrounding parentheses attached
+            let mut trimmed = element.as_str();
+            if i == 0 {
+                trimmed = trimmed.strip_prefix('(').unwrap_or(trimmed);
+            }
+            if i == last {
+                trimmed = trimmed.strip_suffix(')').unwrap_or(trimmed);
+            }
+            if trimmed.is_empty() && !element.is_empty() {
+                return None;
+            }
+            Some(raw_text_to_rust(trimmed))
+        })
+        .collect();
+    format!("vec![{}]", items.join(", "))
+}
+
+fn literal_to_rust(s: &str) -> String {
+    if is_single_quoted(s) {
+        return string_to_rust(&s[1..s.len() - 1]);
+    }
+    let text = unescape_unquoted(s);
+    if text == "~" || text.starts_with("~/") {
+        return format!("home({})", rust_string_literal(&text));
+    }
+    string_to_rust(&text)
+}
+
+/// Convert raw shell text (as stored in operator arguments and test
+/// expressions) into a Rust `String` expression, honouring quotes and
+/// `$name` / `${name}` references.
+pub fn raw_text_to_rust(text: &str) -> String {
+    if is_single_quoted(text) {
+        return string_to_rust(&text[1..text.len() - 1]);
+    }
+    let (inner, quoted) = if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        (&text[1..text.len() - 1], true)
+    } else {
+        (text, false)
+    };
+
+    let chars: Vec<char> = inner.chars().collect();
+    let mut pieces: Vec<String> = Vec::new();
+    let mut literal = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            if !quoted || "\\\"$`".contains(next) {
+                literal.push(next);
+            } else {
+                literal.push(c);
+                literal.push(next);
+            }
+            i += 2;
+            continue;
+        }
+        if c == '$' && i + 1 < chars.len() {
+            let next = chars[i + 1];
+            let (expr, consumed) = if next == '{' {
+                match chars[i + 2..].iter().position(|&ch| ch == '}') {
+                    Some(end) => {
+                        let name: String = chars[i + 2..i + 2 + end].iter().collect();
+                        (parameter_value_to_rust(&name), end + 3)
+                    }
+                    None => (String::new(), 0),
+                }
+            } else if next.is_ascii_digit() || "#?@*$!".contains(next) {
+                (variable_to_rust(&next.to_string()), 2)
+            } else if next.is_ascii_alphabetic() || next == '_' {
+                let len = chars[i + 1..].iter()
+                    .take_while(|ch| ch.is_ascii_alphanumeric() || **ch == '_')
+                    .count();
+                let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                (variable_to_rust(&name), len + 1)
+            } else {
+                (String::new(), 0)
+            };
+            if consumed > 0 {
+                if !literal.is_empty() {
+                    pieces.push(string_to_rust(&std::mem::take(&mut literal)));
+                }
+                pieces.push(expr);
+                i += consumed;
+                continue;
+            }
+        }
+        literal.push(c);
+        i += 1;
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(string_to_rust(&literal));
+    }
+    concat_pieces(pieces)
+}
+
+fn concat_pieces(mut pieces: Vec<String>) -> String {
+    if pieces.len() == 1 {
+        pieces.pop().unwrap()
+    } else {
+        format!("[{}].concat()", pieces.join(", "))
+    }
+}
+
+/// Call a `&mut self` method on the runtime. Arguments that themselves use
+/// `sh` are bound to locals first so the borrows do not overlap.
+pub fn sh_call(method: &str, args: Vec<String>) -> String {
+    let mut bindings = Vec::new();
+    let mut params = Vec::new();
+    for arg in args {
+        if arg.contains("sh.") && !arg.starts_with('|') {
+            let name = format!("a{}", bindings.len());
+            bindings.push(format!("let {} = {};", name, arg));
+            params.push(name);
+        } else {
+            params.push(arg);
+        }
+    }
+    if bindings.is_empty() {
+        format!("sh.{}({})", method, params.join(", "))
+    } else {
+        format!("{{ {} sh.{}({}) }}", bindings.join(" "), method, params.join(", "))
+    }
+}
+
+/// An owned `String` expression for a literal.
+pub fn string_to_rust(s: &str) -> String {
+    format!("s({})", rust_string_literal(s))
+}
+
+pub fn rust_string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,74 @@
+//! Helpers that translate a script and run the result, so tests can check
+//! what the generated program does rather than how it is spelled.
+
+use crate::generator::{Backend, Generator, PythonGenerator, RustGenerator};
+use crate::parser::commands::Parser;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh path in the temporary directory; tests run in parallel.
+fn scratch_path(extension: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("debashc-test-{}-{}.{}", std::process::id(), n, extension))
+}
+
+/// Translate a script with the given backend.
+pub fn translate(backend: &mut dyn Backend, script: &str) -> String {
+    let commands = Parser::new(script).parse().expect("script should parse");
+    backend.generate(&commands)
+}
+
+/// Run a program with `input` on its standard input and return its output
+/// and exit status.
+fn run(program: &mut Command, input: &str) -> (String, i32) {
+    let mut child = program
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .expect("program should start");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code().unwrap_or(-1))
+}
+
+/// Translate a script to Perl and run it with `input` on standard input.
+pub fn run_perl(script: &str, input: &str) -> (String, i32) {
+    let code = translate(&mut Generator::new(), script);
+    let path = scratch_path("pl");
+    std::fs::write(&path, &code).unwrap();
+    let result = run(Command::new("perl").arg(&path), input);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Translate a script to Python and run it with `input` on standard input.
+pub fn run_python(script: &str, input: &str) -> (String, i32) {
+    let code = translate(&mut PythonGenerator::new(), script);
+    let path = scratch_path("py");
+    std::fs::write(&path, &code).unwrap();
+    let result = run(Command::new("python3").arg(&path), input);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Translate a script to Rust, build it with `rustc` and run it.
+pub fn run_rust(script: &str, input: &str) -> (String, i32) {
+    let code = translate(&mut RustGenerator::new(), script);
+    let source = scratch_path("rs");
+    let binary = scratch_path("bin");
+    std::fs::write(&source, &code).unwrap();
+    let compiled = Command::new("rustc")
+        .arg("-o").arg(&binary)
+        .arg(&source)
+        .status()
+        .expect("rustc should start");
+    assert!(compiled.success(), "generated Rust should compile:\n{}", code);
+    let result = run(&mut Command::new(&binary), input);
+    let _ = std::fs::remove_file(&source);
+    let _ = std::fs::remove_file(&binary);
+    result
+}
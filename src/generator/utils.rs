@@ -1,5 +1,6 @@
 use crate::ast::*;
 use super::Generator;
+use super::commands::utilities::text_to_perl;
 
 pub fn extract_array_key_impl(var: &str) -> Option<(String, String)> {
     // Check if this is an associative array assignment like map[foo]=bar
@@ -32,7 +33,7 @@ pub fn extract_array_elements_impl(value: &str) -> Option<Vec<String>> {
 
 pub fn perl_string_literal_impl(generator: &mut Generator, word: &Word) -> String {
     match word {
-        Word::Literal(s) => {
+        Word::Literal(s, _) => {
             // Escape quotes and backslashes for Perl string literals
             let escaped = s.replace("\\", "\\\\")
                           .replace("\"", "\\\"")
@@ -41,15 +42,17 @@ pub fn perl_string_literal_impl(generator: &mut Generator, word: &Word) -> Strin
                           .replace("\r", "\\r");
             format!("\"{}\"", escaped)
         }
-        Word::Arithmetic(expr) => {
+        Word::Arithmetic(expr, _) => {
             // Handle arithmetic expressions by converting them to Perl
-            generator.convert_arithmetic_to_perl(&expr.expression)
+            generator.convert_arithmetic_to_perl(expr)
         }
-        Word::ParameterExpansion(pe) => {
+        Word::ParameterExpansion(pe, _) => {
             // Handle parameter expansion
             generator.generate_parameter_expansion(pe)
         }
-        _ => format!("{:?}", word)
+        // Variables and the like are read at the point of assignment, so a
+        // later assignment can use an earlier one
+        _ => generator.word_to_perl(word)
     }
 }
 
@@ -57,3 +60,55 @@ pub fn get_unique_file_handle_impl(generator: &mut Generator) -> String {
     generator.file_handle_counter += 1;
     format!("fh_{}", generator.file_handle_counter)
 }
+
+/// The array holding the positional parameters where code is generated.
+pub fn positional_parameters_impl(generator: &Generator) -> &'static str {
+    if generator.in_function { "@_" } else { "@ARGV" }
+}
+
+/// `$1`, `$2` and so on as an element of the positional parameters; `$0`
+/// is the script name.
+pub fn positional_parameter_impl(generator: &Generator, index: usize) -> String {
+    match index {
+        0 => "$0".to_string(),
+        _ => format!("${}[{}]", &positional_parameters_impl(generator)[1..], index - 1),
+    }
+}
+
+/// A read of a variable, which stops the script under `set -u` when the
+/// variable is unset.
+pub fn checked_variable_impl(generator: &mut Generator, name: &str, value: String) -> String {
+    if !generator.shell_options.contains("nounset") || matches!(name, "@" | "*" | "#" | "?" | "$" | "!" | "-" | "0") {
+        return value;
+    }
+    if value == format!("${}", name) && !generator.declared_locals.contains(name) {
+        generator.declared_locals.insert(name.to_string());
+        generator.environment_variables.insert(name.to_string());
+    }
+    format!("({} // unset_variable('{}'))", value, name)
+}
+
+/// The statement printing a command for `set -x`, or nothing when the
+/// script never turns tracing on.
+pub fn traced_command_impl(generator: &mut Generator, words: &[Word]) -> String {
+    if !generator.shell_options.contains("xtrace") {
+        return String::new();
+    }
+    let words: Vec<String> = words.iter().map(|word| text_to_perl(generator, word)).collect();
+    format!("{}trace_command({});\n", generator.indent(), words.join(", "))
+}
+
+/// The statement printing an assignment for `set -x` once the variable has
+/// its value, which is given in Perl, or for an array the source text of
+/// its elements.
+pub fn traced_assignment_impl(generator: &mut Generator, name: &str, value: &str, source: Option<&str>) -> String {
+    if !generator.shell_options.contains("xtrace") {
+        return String::new();
+    }
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+    let name = quote(name);
+    match source {
+        Some(source) => format!("{}trace_assignment({}, undef, {});\n", generator.indent(), name, quote(source)),
+        None => format!("{}trace_assignment({}, {});\n", generator.indent(), name, value),
+    }
+}
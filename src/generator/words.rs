@@ -3,7 +3,7 @@ use super::Generator;
 
 pub fn word_to_perl_impl(generator: &mut Generator, word: &Word) -> String {
     match word {
-        Word::Literal(s) => {
+        Word::Literal(s, _) => {
             // Handle literal strings
             if s.contains("..") {
                 generator.handle_range_expansion(s)
@@ -13,23 +13,25 @@ pub fn word_to_perl_impl(generator: &mut Generator, word: &Word) -> String {
                 s.clone()
             }
         },
-        Word::ParameterExpansion(pe) => generator.generate_parameter_expansion(pe),
-        Word::Array(name, elements) => {
+        Word::ParameterExpansion(pe, _) => generator.generate_parameter_expansion(pe),
+        Word::Array(name, elements, _) => {
             let elements_str = elements.iter()
                 .map(|e| format!("'{}'", e.replace("'", "\\'")))
                 .collect::<Vec<_>>()
                 .join(", ");
             format!("@{} = ({});", name, elements_str)
         },
-        Word::StringInterpolation(interp) => generator.convert_string_interpolation_to_perl(interp),
-        Word::Arithmetic(expr) => generator.convert_arithmetic_to_perl(&expr.expression),
-        Word::BraceExpansion(expansion) => generator.handle_brace_expansion(expansion),
-        Word::CommandSubstitution(cmd) => {
+        Word::StringInterpolation(interp, _) => generator.convert_string_interpolation_to_perl(interp),
+        Word::Concatenation(words, _) => generator.convert_string_interpolation_to_perl(&concatenated_parts(words)),
+        Word::Arithmetic(expr, _) => generator.convert_arithmetic_to_perl(expr),
+        Word::BraceExpansion(expansion, _) => generator.handle_brace_expansion(expansion),
+        Word::ProcessSubstitution(cmd, direction, _) => generator.generate_process_substitution(cmd, *direction),
+        Word::CommandSubstitution(cmd, _) => {
             // Execute the command and capture its output
             // For command substitution, we need to generate Perl code that produces the same output
             // as our custom command implementations
             match cmd.as_ref() {
-                Command::Simple(simple_cmd) => {
+                Command::Simple(simple_cmd, _) => {
                     let cmd_name = generator.word_to_perl(&simple_cmd.name);
                     let args: Vec<String> = simple_cmd.args.iter()
                         .map(|arg| generator.word_to_perl(arg))
@@ -53,7 +55,7 @@ pub fn word_to_perl_impl(generator: &mut Generator, word: &Word) -> String {
                     // For other command types (like pipelines), generate the command
                     // and wrap it in a way that ensures proper variable scoping
                     let command_code = match &**cmd {
-                        Command::Pipeline(pipeline) => {
+                        Command::Pipeline(pipeline, _) => {
                             // For pipelines in command substitution, don't print, just return the value
                             use crate::generator::commands::pipeline_commands::generate_pipeline_with_print_option;
                             generate_pipeline_with_print_option(generator, pipeline, false)
@@ -67,24 +69,108 @@ pub fn word_to_perl_impl(generator: &mut Generator, word: &Word) -> String {
                 }
             }
         },
-        Word::Variable(var) => {
+        Word::Variable(var, _) => {
             // Handle special shell variables
             match var.as_str() {
                 "#" => "scalar(@ARGV)".to_string(),  // $# -> scalar(@ARGV) for argument count
                 "@" => "@ARGV".to_string(),          // $@ -> @ARGV for arguments array
                 "*" => "@ARGV".to_string(),          // $* -> @ARGV for arguments array
-                _ => format!("${}", var)             // Regular variable
+                "?" => "($? >> 8)".to_string(),      // $? -> the exit code, not the wait status
+                _ if var.chars().all(|c| c.is_ascii_digit()) => {
+                    let value = generator.positional_parameter(var.parse().unwrap_or(0));
+                    generator.checked_variable(var, value)
+                }
+                _ => generator.checked_variable(var, format!("${}", var))   // Regular variable
             }
         },
-        _ => format!("{:?}", word)
+        Word::MapAccess(name, key, _) => element_to_perl(generator, name, key),
+        Word::MapKeys(name, _) => {
+            if generator.predeclared_arrays.contains(name) {
+                format!("join(' ', 0..$#{})", name)
+            } else {
+                format!("join(' ', sort keys %{})", name)
+            }
+        }
+        Word::MapLength(name, _) => {
+            if generator.predeclared_arrays.contains(name) {
+                format!("scalar(@{})", name)
+            } else {
+                format!("scalar(keys %{})", name)
+            }
+        }
+        Word::ArraySlice(name, offset, length, _) => {
+            let offset = index_to_perl(offset);
+            let end = match length {
+                Some(length) => format!("{} + {} - 1", offset, index_to_perl(length)),
+                None => format!("$#{}", name),
+            };
+            format!("join(' ', grep {{ defined }} @{}[{} .. {}])", name, offset, end)
+        }
     }
 }
 
 pub fn word_to_perl_for_test_impl(generator: &mut Generator, word: &Word) -> String {
     match word {
-        Word::Literal(s) => s.clone(),
-        Word::ParameterExpansion(pe) => generator.generate_parameter_expansion(pe),
-        _ => format!("{:?}", word)
+        Word::Literal(s, _) => s.clone(),
+        word => word_to_perl_impl(generator, word),
+    }
+}
+
+/// `${name[key]}`: an element of the array `@name` if the script made one,
+/// otherwise of the hash `%name`. `@` and `*` give every value.
+fn element_to_perl(generator: &mut Generator, name: &str, key: &str) -> String {
+    let is_array = generator.predeclared_arrays.contains(name);
+    match key {
+        "@" | "*" => format!("join(' ', {})", elements_to_perl(generator, name)),
+        _ if is_array => format!("${}[{}]", name, index_to_perl(key)),
+        _ => format!("${}{{\"{}\"}}", name, generator.escape_perl_string(key)),
+    }
+}
+
+/// `${name[@]}` as a Perl list, for where the shell keeps the elements
+/// apart, as in `for x in "${arr[@]}"`.
+pub fn elements_to_perl(generator: &Generator, name: &str) -> String {
+    if generator.predeclared_arrays.contains(name) {
+        format!("@{}", name)
+    } else {
+        format!("values %{}", name)
+    }
+}
+
+/// A word the shell expands to separate words, `"$@"` or `"${arr[@]}"`
+/// and unquoted `$*` or `${arr[*]}`, as a Perl list.
+pub fn word_list_to_perl(generator: &Generator, word: &Word) -> Option<String> {
+    match word {
+        Word::Variable(var, _) if var == "@" || var == "*" => Some(generator.positional_parameters().to_string()),
+        Word::MapAccess(name, key, _) if key == "@" || key == "*" => Some(elements_to_perl(generator, name)),
+        Word::StringInterpolation(interp, _) => match interp.parts.as_slice() {
+            [StringPart::Variable(var)] if var == "@" => Some(generator.positional_parameters().to_string()),
+            [StringPart::MapAccess(name, key)] if key == "@" => Some(elements_to_perl(generator, name)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// An array index or offset, which the shell reads as arithmetic: a bare
+/// name is a variable.
+fn index_to_perl(index: &str) -> String {
+    let index = index.trim();
+    if index.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+        format!("${}", index)
+    } else {
+        index.to_string()
+    }
+}
+
+/// `$name` inside a Perl string, braced when the text after it would run on
+/// into the name, as in `"${n}s"`.
+pub fn interpolated_variable(name: &str, next: Option<&StringPart>) -> String {
+    match next {
+        Some(StringPart::Literal(text)) if text.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == ':') => {
+            format!("${{{}}}", name)
+        }
+        _ => format!("${}", name),
     }
 }
 
@@ -128,17 +214,19 @@ pub fn handle_brace_expansion_impl(generator: &mut Generator, expansion: &BraceE
 
 pub fn brace_item_to_word_impl(_generator: &Generator, item: &BraceItem) -> Word {
     match item {
-        BraceItem::Literal(s) => Word::Literal(s.clone()),
-        BraceItem::Range(range) => Word::Literal(format!("{}..{}", range.start, range.end)),
-        BraceItem::Sequence(seq) => Word::Literal(seq.join(" ")),
+        BraceItem::Literal(s) => Word::Literal(s.clone(), Span::default()),
+        BraceItem::Range(range) => Word::Literal(format!("{}..{}", range.start, range.end), Span::default()),
+        BraceItem::Sequence(seq) => Word::Literal(seq.join(" "), Span::default()),
     }
 }
 
-pub fn convert_string_interpolation_to_perl_impl(generator: &Generator, interp: &StringInterpolation) -> String {
+pub fn convert_string_interpolation_to_perl_impl(generator: &mut Generator, interp: &StringInterpolation) -> String {
     // Convert string interpolation to a single Perl interpolated string
     let mut combined_string = String::new();
+    // Expansions Perl cannot interpolate are joined on with `.`
+    let mut pieces = Vec::new();
     
-    for part in &interp.parts {
+    for (i, part) in interp.parts.iter().enumerate() {
         match part {
             StringPart::Literal(s) => {
                 // Add the literal text directly to the interpolated string
@@ -147,61 +235,82 @@ pub fn convert_string_interpolation_to_perl_impl(generator: &Generator, interp:
             StringPart::Variable(var) => {
                 // Handle special shell variables
                 match var.as_str() {
-                    "#" => combined_string.push_str("scalar(@ARGV)"),  // $# -> scalar(@ARGV) for argument count
+                    "#" => {
+                        // The argument count is not something Perl interpolates
+                        pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                        pieces.push(format!("scalar({})", generator.positional_parameters()));
+                    }
+                    "?" => {
+                        // Perl's `$?` is the wait status, so shift out the exit code
+                        pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                        pieces.push("($? >> 8)".to_string());
+                    }
                     "@" => combined_string.push_str("@ARGV"),          // Arrays don't need $ in interpolation
                     "*" => combined_string.push_str("@ARGV"),          // Arrays don't need $ in interpolation
+                    _ if generator.shell_options.contains("nounset") && var != "0" => {
+                        // The check for an unset variable is not something Perl interpolates
+                        let value = generator.word_to_perl(&Word::Variable(var.clone(), Span::default()));
+                        pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                        pieces.push(value);
+                    }
                     _ => {
                         // Check if this is a shell positional parameter ($1, $2, etc.)
                         if var.chars().all(|c| c.is_digit(10)) {
-                            // Convert $1 to $_[0], $2 to $_[1], etc.
                             let index = var.parse::<usize>().unwrap_or(0);
-                            combined_string.push_str(&format!("$_[{}]", index - 1)); // Perl arrays are 0-indexed
+                            combined_string.push_str(&generator.positional_parameter(index));
                         } else {
                             // Regular variable - add directly for interpolation
-                            combined_string.push_str(&format!("${}", var));
+                            combined_string.push_str(&interpolated_variable(var, interp.parts.get(i + 1)));
                         }
                     }
                 }
             },
             StringPart::MapAccess(map_name, key) => {
-                if map_name == "map" {
-                    combined_string.push_str(&format!("$map{{{}}}", key));
-                } else {
-                    combined_string.push_str(&format!("${}{{{}}}", map_name, key));
-                }
+                let value = element_to_perl(generator, map_name, key);
+                pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                pieces.push(value);
+            }
+            StringPart::CommandSubstitution(cmd) => {
+                let output = generator.word_to_perl(&Word::CommandSubstitution(cmd.clone(), Span::default()));
+                pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                pieces.push(format!("do {{ my $s = {}; $s =~ s/\\n+\\z//; $s }}", output));
             }
             _ => {
-                // Handle other StringPart variants by converting them to debug format for now
-                combined_string.push_str(&format!("{:?}", part));
+                let word = match part {
+                    StringPart::ParameterExpansion(pe) => Word::ParameterExpansion(pe.clone(), Span::default()),
+                    StringPart::MapKeys(map_name) => Word::MapKeys(map_name.clone(), Span::default()),
+                    StringPart::MapLength(map_name) => Word::MapLength(map_name.clone(), Span::default()),
+                    StringPart::ArraySlice(name, offset, length) => {
+                        Word::ArraySlice(name.clone(), offset.clone(), length.clone(), Span::default())
+                    }
+                    StringPart::Arithmetic(expr) => Word::Arithmetic(expr.clone(), Span::default()),
+                    _ => continue,
+                };
+                pieces.push(format!("\"{}\"", std::mem::take(&mut combined_string)));
+                pieces.push(format!("({})", generator.word_to_perl(&word)));
             }
         }
     }
     
     // Return as a single interpolated string
-    format!("\"{}\"", combined_string)
+    if pieces.is_empty() {
+        return format!("\"{}\"", combined_string);
+    }
+    pieces.push(format!("\"{}\"", combined_string));
+    pieces.retain(|piece| piece != "\"\"");
+    let joined = pieces.join(" . ");
+    // A leading parenthesis would make e.g. `print (a) . b` a function call
+    if joined.starts_with('(') { format!("+{}", joined) } else { joined }
 }
 
-pub fn convert_arithmetic_to_perl_impl(_generator: &Generator, expr: &str) -> String {
-    // Convert shell arithmetic expression to Perl syntax
-    let result = expr.to_string();
-    
-    // Convert shell variables to Perl variables (e.g., i -> $i)
-    // This is a simple regex-based approach - in practice, the parser should handle this better
-    
-    // Split the expression into parts and convert each part
-    let parts: Vec<&str> = result.split_whitespace().collect();
-    let converted_parts: Vec<String> = parts.iter().map(|part| {
-        // Check if this part looks like a variable (starts with a letter and contains only alphanumeric chars)
-        if part.chars().next().map_or(false, |c| c.is_alphabetic()) && 
-           part.chars().all(|c| c.is_alphanumeric() || c == '_') {
-            // This looks like a variable, prefix with $
-            format!("${}", part)
-        } else {
-            // This is an operator, number, or already formatted variable
-            part.to_string()
-        }
-    }).collect();
-    
-    // Rejoin the parts
-    converted_parts.join(" ")
+#[cfg(test)]
+mod tests {
+    use crate::generator::tests::run_perl;
+
+    #[test]
+    fn test_variables_and_elements_in_words() {
+        let script = "n=1\necho \"${n}s\" \"$n:\"\narr=(a 'b c' d)\ni=2\necho ${arr[1]} \"${arr[i]}\" ${#arr[@]} ${!arr[@]} ${arr[@]:1}\n\
+                      for x in \"${arr[@]}\"; do echo \"[$x]\"; done\ndeclare -A m\nm[k]=v\necho ${m[k]} ${#m[@]}\n";
+        assert_eq!(run_perl(script, "").0, "1s 1:\nb c d 3 0 1 2 b c d\n[a]\n[b c]\n[d]\nv 1\n");
+    }
 }
@@ -12,19 +12,35 @@ pub fn show_help(program_name: &str) {
     println!("  file <filename>                - Parse shell script from file");
     println!("  interactive                    - Start interactive mode");
     println!();
+    println!("AST JSON OPTIONS:");
+    println!();
+    println!("  parse --json <input>           - Print the AST as tagged JSON");
+    println!("  generate --from-json <file>    - Generate Perl from an AST in JSON form ('-' reads stdin)");
+    println!("  generate --python --from-json <file> - Same, generating Python 3 (or --rust for Rust)");
+    println!();
+    println!("FORMATTING OPTIONS:");
+    println!();
+    println!("  fmt <input>                    - Print the script in canonical bash formatting");
+    println!("  fmt -w <filename>              - Reformat a script file in place");
+    println!("  fmt --check <input>            - Check that the formatted script parses to the same AST");
+    println!();
     println!("TRANSLATION OPTIONS:");
     println!();
     println!("  parse --perl <input>           - Convert shell script to Perl");
+    println!("  parse --python <input>         - Convert shell script to Python 3");
+    println!("  parse --rust <input>           - Convert shell script to a Rust main.rs");
     
     println!();
     println!("  file --perl <filename>         - Convert shell script file to Perl");
+    println!("  file --python <filename>       - Convert shell script file to Python 3");
+    println!("  file --rust <filename>         - Convert shell script file to a Rust main.rs");
     
     println!();
     println!("EXECUTION OPTIONS:");
     println!();
     println!("  parse --run <lang> <input>     - Generate and run code in specified language");
     println!("  file --run <lang> <filename>   - Generate and run code from file");
-            println!("  Supported languages: perl");
+            println!("  Supported languages: perl, python, rust");
     println!();
     println!("INPUT/OUTPUT OPTIONS:");
     println!();
@@ -59,12 +75,17 @@ pub fn show_help(program_name: &str) {
     println!("  {} lex 'echo hello world'", program_name);
     println!("  {} parse 'echo hello world'", program_name);
     println!("  {} parse --perl 'echo hello world'", program_name);
+    println!("  {} fmt examples/simple.sh", program_name);
+    println!("  {} parse --json examples/simple.sh | {} generate --rust --from-json -", program_name, program_name);
     println!("  {} file --perl examples/simple.sh", program_name);
     println!("  {} --test-file perl examples/simple.sh", program_name);
+    println!("  {} --test-file python examples/simple.sh", program_name);
+    println!("  {} --test-file rust examples/simple.sh", program_name);
     println!("  {} --test-eq", program_name);
             println!("  {} --next-fail", program_name);
         println!("  {} --next-fail 5", program_name);
         println!("  {} --next-fail perl", program_name);
+        println!("  {} --next-fail rust", program_name);
         println!("  {} --next-fail 10 perl --ast-pretty", program_name);
     println!("  {} --clear-cache", program_name);
     println!();
@@ -79,7 +100,7 @@ pub fn show_help(program_name: &str) {
     println!("  generate equivalent Perl code, and optionally run the generated code to verify");
     println!("  correctness against the original shell script.");
     println!();
-        println!("  The tool supports Perl as the target language. It can also generate pseudocode");
+        println!("  The tool supports Perl, Python 3 and Rust as target languages. It can also generate pseudocode");
     println!("  in English for educational purposes.");
     println!();
     println!("  The --next-fail command can be used to test the Perl generator.");
@@ -4,78 +4,27 @@ use std::cmp::Ordering;
 
 #[derive(Logos, Debug, PartialEq, Clone)]
 pub enum Token {
-    // Keywords
-    #[token("if")]
+    // Reserved words. These lex as `Identifier` and are only turned into
+    // their own tokens where the grammar expects them; see
+    // `recognize_reserved_words`.
     If,
-    #[token("then")]
     Then,
-    #[token("else")]
     Else,
-    #[token("elif")]
     Elif,
-    #[token("fi")]
     Fi,
-    #[token("while")]
     While,
-    #[token("do")]
     Do,
-    #[token("done")]
     Done,
-    #[token("for")]
     For,
-    #[token("in")]
     In,
-    #[token("function")]
     Function,
-    #[token("case")]
     Case,
-    #[token("esac")]
     Esac,
-    #[token("select")]
     Select,
-    #[token("until")]
     Until,
-    #[token("break")]
     Break,
-    #[token("continue")]
     Continue,
-    #[token("return")]
     Return,
-    #[token("exit")]
-    Exit,
-    #[token("export")]
-    Export,
-    #[token("readonly")]
-    Readonly,
-    #[token("local")]
-    Local,
-    #[token("declare")]
-    Declare,
-    #[token("typeset")]
-    Typeset,
-    #[token("unset")]
-    Unset,
-    #[token("shift")]
-    Shift,
-    #[token("set")]
-    Set,
-    #[token("eval")]
-    Eval,
-    #[token("exec")]
-    Exec,
-    #[token("source")]
-    Source,
-    // SourceDot removed - dots in filenames should be part of identifiers
-    #[token("trap")]
-    Trap,
-    #[token("wait")]
-    Wait,
-    #[token("shopt")]
-    Shopt,
-    #[token("true")]
-    True,
-    #[token("false")]
-    False,
     #[token("[")]
     TestBracket,
     #[token("]")]
@@ -191,8 +140,6 @@ pub enum Token {
     ArithmeticEvalClose,
     #[token("$[")]
     ArithmeticBracket,
-    #[token("let")]
-    Let,
 
     // Conditionals
     #[token("-eq", priority = 1)]
@@ -281,7 +228,8 @@ pub enum Token {
     DollarDoubleQuotedString,
 
     // Long options (must come before Identifier to avoid conflicts)
-    #[regex(r"--[a-zA-Z][a-zA-Z0-9_*?.-]*=[^ \t\n\r|&;(){}]*", priority = 3)]
+    // The value stops at an expansion or a quote, which carry on the word
+    #[regex(r#"--[a-zA-Z][a-zA-Z0-9_*?.-]*=[^ \t\n\r|&;(){}$"'`]*"#, priority = 3)]
     LongOption,
     
 
@@ -298,6 +246,9 @@ pub enum Token {
     HexNumber,
     #[regex(r"0[0-7]+")]
     OctalNumber,
+    // `base#digits`, as in `16#ff`; the `#` does not start a comment
+    #[regex(r"[0-9]+#[0-9a-zA-Z@_]+")]
+    BaseNumber,
 
     // Special characters
     #[token("!")]
@@ -379,23 +330,260 @@ pub struct Lexer {
     pub current: usize,
     pub input: String,
     pub line_starts: Vec<usize>,
+    /// Where the tokenized range of `input` ends
+    pub end: usize,
+    /// The newline token ending a line that has heredocs on it, and the
+    /// offset just past their lines, where commands carry on
+    pub heredoc_resume: Option<(usize, usize)>,
 }
 
-impl Lexer {
-    pub fn new(input: &str) -> Self {
-        let mut tokens = Vec::new();
-        let mut lexer = Token::lexer(input);
-        
-        while let Some(token_result) = lexer.next() {
-            let span = lexer.span();
-            match token_result {
-                Ok(token) => tokens.push((token, span.start, span.end)),
-                Err(_) => {
-                    // Skip invalid tokens
-                    continue;
+fn tokenize(input: &str, start: usize, end: usize) -> Vec<(Token, usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut lexer = Token::lexer(&input[start..end]);
+    
+    while let Some(token_result) = lexer.next() {
+        let span = lexer.span();
+        match token_result {
+            Ok(token) => tokens.push((token, start + span.start, start + span.end)),
+            Err(_) => {
+                // Skip invalid tokens
+                continue;
+            }
+        }
+    }
+    recognize_reserved_words(input, &mut tokens);
+    tokens
+}
+
+/// Where a word falls in the grammar, which decides whether a reserved word
+/// such as `done` is one there or just a word.
+#[derive(Clone, Copy, PartialEq)]
+enum Position {
+    /// The first word of a command
+    Command,
+    /// Inside an assignment before the command name, as in `a=1 cmd`
+    Assignment,
+    /// Any other word of a command
+    Argument,
+    /// The variable of `for` or `select`
+    LoopName,
+    /// After the variable of `for` or `select`, where `in` or `do` comes
+    LoopIn,
+    /// The word `case` matches
+    CaseWord,
+    /// After the word `case` matches, where `in` comes
+    CaseIn,
+    /// The start of a `case` pattern, where only `esac` is a reserved word
+    Pattern,
+    /// The rest of a `case` pattern, up to its `)`
+    PatternRest,
+}
+
+impl Position {
+    /// The position after a word that is not a reserved word.
+    fn after_word(self) -> Self {
+        match self {
+            Position::Command => Position::Argument,
+            Position::LoopName => Position::LoopIn,
+            Position::CaseWord => Position::CaseIn,
+            Position::Pattern => Position::PatternRest,
+            other => other,
+        }
+    }
+}
+
+/// An open bracket whose contents are lexed apart from the command around
+/// it, with the position to carry on from once it closes.
+enum Nesting {
+    /// `(` or `$(`, holding commands
+    Paren(Position),
+    /// `((` or `$((`, holding arithmetic, with the parentheses still open
+    Arithmetic(i32, Position),
+    /// `${`, holding a parameter expansion, with the braces still open
+    Brace(i32, Position),
+}
+
+/// The reserved word `text` is at `position`, if it is one there.
+fn reserved_word(text: &str, position: Position) -> Option<Token> {
+    match (position, text) {
+        (Position::Command, "if") => Some(Token::If),
+        (Position::Command, "then") => Some(Token::Then),
+        (Position::Command, "else") => Some(Token::Else),
+        (Position::Command, "elif") => Some(Token::Elif),
+        (Position::Command, "fi") => Some(Token::Fi),
+        (Position::Command, "while") => Some(Token::While),
+        (Position::Command, "until") => Some(Token::Until),
+        (Position::Command | Position::LoopIn, "do") => Some(Token::Do),
+        (Position::Command, "done") => Some(Token::Done),
+        (Position::Command, "for") => Some(Token::For),
+        (Position::Command, "select") => Some(Token::Select),
+        (Position::Command, "case") => Some(Token::Case),
+        (Position::Command | Position::Pattern, "esac") => Some(Token::Esac),
+        (Position::Command, "function") => Some(Token::Function),
+        (Position::Command, "break") => Some(Token::Break),
+        (Position::Command, "continue") => Some(Token::Continue),
+        (Position::Command, "return") => Some(Token::Return),
+        (Position::LoopIn | Position::CaseIn, "in") => Some(Token::In),
+        _ => None,
+    }
+}
+
+/// Whether `token` separates the word before it from the word after it.
+fn ends_word(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        None | Some(
+            Token::Space | Token::Tab | Token::Newline | Token::CarriageReturn | Token::Comment
+                | Token::Semicolon | Token::DoubleSemicolon | Token::Background | Token::And | Token::Or
+                | Token::Pipe | Token::ParenOpen | Token::ParenClose | Token::RedirectIn | Token::RedirectOut
+                | Token::RedirectAppend | Token::RedirectInOut | Token::Heredoc | Token::HeredocTabs
+                | Token::HereString | Token::RedirectOutErr | Token::RedirectInErr | Token::RedirectOutClobber
+                | Token::RedirectAll | Token::RedirectAllAppend
+        )
+    )
+}
+
+/// Reserved words lex as plain identifiers, so `echo done in time` is just
+/// words. Turn the ones that stand where the grammar expects a reserved
+/// word into their own tokens: the first word of a command, `in` and `do`
+/// after `for NAME` or `case WORD`, and `esac` in place of a `case` pattern.
+fn recognize_reserved_words(input: &str, tokens: &mut [(Token, usize, usize)]) {
+    let mut position = Position::Command;
+    let mut nesting: Vec<Nesting> = Vec::new();
+    for i in 0..tokens.len() {
+        // Arithmetic and parameter expansions hold no commands, so only
+        // track where they end
+        match nesting.last_mut() {
+            Some(Nesting::Arithmetic(depth, resume)) => {
+                *depth += match tokens[i].0 {
+                    Token::ParenOpen | Token::DollarParen => 1,
+                    Token::ArithmeticEval | Token::Arithmetic => 2,
+                    Token::ParenClose => -1,
+                    Token::ArithmeticEvalClose => -2,
+                    _ => 0,
+                };
+                if *depth <= 0 {
+                    position = *resume;
+                    nesting.pop();
+                }
+                continue;
+            }
+            Some(Nesting::Brace(depth, resume)) => {
+                *depth += match tokens[i].0 {
+                    Token::BraceOpen | Token::DollarBrace | Token::DollarBraceHash | Token::DollarBraceBang
+                    | Token::DollarBraceStar | Token::DollarBraceAt | Token::DollarBraceHashStar
+                    | Token::DollarBraceHashAt | Token::DollarBraceBangStar | Token::DollarBraceBangAt => 1,
+                    Token::BraceClose => -1,
+                    _ => 0,
+                };
+                if *depth <= 0 {
+                    position = *resume;
+                    nesting.pop();
                 }
+                continue;
             }
+            _ => {}
         }
+
+        position = match tokens[i].0 {
+            Token::Space | Token::Tab if position == Position::Assignment => Position::Command,
+            Token::Space | Token::Tab | Token::CarriageReturn | Token::Comment => position,
+            Token::Newline => match position {
+                Position::LoopIn | Position::CaseIn | Position::Pattern | Position::PatternRest => position,
+                _ => Position::Command,
+            },
+            Token::Pipe if matches!(position, Position::Pattern | Position::PatternRest) => Position::PatternRest,
+            Token::Semicolon | Token::Background | Token::And | Token::Or | Token::Pipe => Position::Command,
+            Token::DoubleSemicolon => Position::Pattern,
+            Token::Bang if position == Position::Command => position,
+            Token::ParenOpen => match position {
+                // `(pattern)`
+                Position::Pattern => position,
+                Position::Command => {
+                    nesting.push(Nesting::Paren(Position::Argument));
+                    Position::Command
+                }
+                // `name()` or `array=(...)`
+                _ => {
+                    nesting.push(Nesting::Paren(position.after_word()));
+                    Position::Argument
+                }
+            },
+            Token::DollarParen => {
+                nesting.push(Nesting::Paren(position.after_word()));
+                Position::Command
+            }
+            Token::ParenClose if matches!(position, Position::Pattern | Position::PatternRest) => Position::Command,
+            Token::ParenClose => match nesting.pop() {
+                Some(Nesting::Paren(resume)) => resume,
+                _ => Position::Argument,
+            },
+            // `))` closing two command substitutions, as in `$(a $(b))`
+            Token::ArithmeticEvalClose => {
+                let mut resume = Position::Argument;
+                for _ in 0..2 {
+                    if let Some(Nesting::Paren(outer)) = nesting.last() {
+                        resume = *outer;
+                        nesting.pop();
+                    }
+                }
+                resume
+            }
+            Token::ArithmeticEval if position == Position::Command => {
+                nesting.push(Nesting::Arithmetic(2, Position::Argument));
+                position
+            }
+            Token::ArithmeticEval | Token::Arithmetic => {
+                nesting.push(Nesting::Arithmetic(2, position.after_word()));
+                position
+            }
+            Token::DollarBrace | Token::DollarBraceHash | Token::DollarBraceBang | Token::DollarBraceStar
+            | Token::DollarBraceAt | Token::DollarBraceHashStar | Token::DollarBraceHashAt
+            | Token::DollarBraceBangStar | Token::DollarBraceBangAt => {
+                nesting.push(Nesting::Brace(1, position.after_word()));
+                position
+            }
+            // `{ commands; }` rather than a brace expansion like `{a,b}`
+            Token::BraceOpen if matches!(tokens.get(i + 1).map(|t| &t.0), Some(Token::Space | Token::Tab | Token::Newline)) => {
+                Position::Command
+            }
+            Token::BraceClose => Position::Argument,
+            Token::Identifier => {
+                let (_, start, end) = tokens[i];
+                let whole = ends_word(i.checked_sub(1).map(|j| &tokens[j].0)) && ends_word(tokens.get(i + 1).map(|t| &t.0));
+                match reserved_word(&input[start..end], position).filter(|_| whole) {
+                    Some(word) => {
+                        let next = match word {
+                            Token::If | Token::Then | Token::Else | Token::Elif | Token::While | Token::Until | Token::Do => Position::Command,
+                            Token::For | Token::Select => Position::LoopName,
+                            Token::Case => Position::CaseWord,
+                            Token::In if position == Position::CaseIn => Position::Pattern,
+                            _ => Position::Argument,
+                        };
+                        tokens[i].0 = word;
+                        next
+                    }
+                    None if position == Position::Command && matches!(tokens.get(i + 1).map(|t| &t.0), Some(Token::Assign | Token::PlusAssign)) => {
+                        Position::Assignment
+                    }
+                    None => position.after_word(),
+                }
+            }
+            _ => position.after_word(),
+        };
+    }
+}
+
+impl Lexer {
+    pub fn new(input: &str) -> Self {
+        Self::new_range(input, 0, input.len())
+    }
+
+    /// Tokenize only `input[start..end]`, keeping token offsets (and so
+    /// line/column positions) relative to the whole of `input`. Used to parse
+    /// nested source such as the body of `$(...)` in place.
+    pub fn new_range(input: &str, start: usize, end: usize) -> Self {
+        let tokens = tokenize(input, start, end);
         
         // Precompute starts of lines for quick offset->(line,col)
         let mut line_starts = Vec::new();
@@ -425,7 +613,29 @@ impl Lexer {
             }
         }
 
-        Self { tokens, current: 0, input: input.to_string(), line_starts }
+        Self { tokens, current: 0, input: input.to_string(), line_starts, end, heredoc_resume: None }
+    }
+
+    /// Carry on after the newline token at `newline` from `offset`, leaving
+    /// the heredoc lines in between out of the token stream. They are raw
+    /// text, so tokens lexed across them say nothing about what follows.
+    pub fn skip_heredoc_lines(&mut self, newline: usize, offset: usize) {
+        self.tokens.truncate(newline + 1);
+        let offset = offset.min(self.end);
+        self.tokens.extend(tokenize(&self.input, offset, self.end));
+        self.heredoc_resume = Some((newline, offset));
+    }
+
+    /// Move past the source up to `offset`, re-lexing from there if a token
+    /// runs across it, as one lexed without the context `offset` ends can.
+    pub fn skip_to(&mut self, offset: usize) {
+        while matches!(self.tokens.get(self.current), Some((_, _, end)) if *end <= offset) {
+            self.current += 1;
+        }
+        if matches!(self.tokens.get(self.current), Some((_, start, _)) if *start < offset) {
+            self.tokens.truncate(self.current);
+            self.tokens.extend(tokenize(&self.input, offset, self.end));
+        }
     }
 
     pub fn peek(&self) -> Option<&Token> {
@@ -454,11 +664,13 @@ impl Lexer {
                     let (line, col) = self.offset_to_line_col(*start);
                     Err(LexerError::UnexpectedChar { ch: actual_char, line, col })
                 } else {
-                    Err(LexerError::UnexpectedChar { ch: '?', line: 1, col: 1 })
+                    let (line, col) = self.offset_to_line_col(self.input.len());
+                    Err(LexerError::UnexpectedChar { ch: '?', line, col })
                 }
             }
         } else {
-            Err(LexerError::UnexpectedChar { ch: '?', line: 1, col: 1 })
+            let (line, col) = self.offset_to_line_col(self.input.len());
+            Err(LexerError::UnexpectedChar { ch: '?', line, col })
         }
     }
 
@@ -556,5 +768,37 @@ mod tests {
         assert_eq!(lexer.next(), Some(&Token::Identifier));
         assert_eq!(lexer.next(), Some(&Token::BraceClose));
     }
+
+    #[test]
+    fn test_reserved_words_only_where_expected() {
+        let kinds = |input: &str| -> Vec<Token> {
+            Lexer::new(input).tokens.into_iter()
+                .map(|(token, _, _)| token)
+                .filter(|token| !matches!(token, Token::Space))
+                .collect()
+        };
+        assert_eq!(kinds("echo done in time"), vec![Token::Identifier; 4]);
+        assert_eq!(
+            kinds("for in in do; do echo done; done"),
+            vec![Token::For, Token::Identifier, Token::In, Token::Identifier, Token::Semicolon,
+                 Token::Do, Token::Identifier, Token::Identifier, Token::Semicolon, Token::Done]
+        );
+        assert_eq!(
+            kinds("case $x in in|esac) x=if;; esac"),
+            vec![Token::Case, Token::Dollar, Token::Identifier, Token::In, Token::Identifier, Token::Pipe,
+                 Token::Identifier, Token::ParenClose, Token::Identifier, Token::Assign, Token::Identifier,
+                 Token::DoubleSemicolon, Token::Esac]
+        );
+        // `=` and `$?` in arguments are word text
+        assert_eq!(
+            kinds("echo a=b done"),
+            vec![Token::Identifier, Token::Identifier, Token::Assign, Token::Identifier, Token::Identifier]
+        );
+        assert_eq!(
+            kinds("rc=$?; echo $? in; done"),
+            vec![Token::Identifier, Token::Assign, Token::Dollar, Token::Question, Token::Semicolon,
+                 Token::Identifier, Token::Dollar, Token::Question, Token::Identifier, Token::Semicolon, Token::Done]
+        );
+    }
 } 
 
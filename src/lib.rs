@@ -4,6 +4,7 @@ pub mod ast;
 pub mod debug;
 pub mod shared_utils;
 pub mod generator;
+pub mod printer;
 pub mod wasm;
 
 // Only export the main types to avoid conflicts
@@ -11,4 +12,5 @@ pub use lexer::{Lexer, Token};
 pub use parser::commands::Parser;
 pub use parser::utilities::ParserUtilities;
 pub use ast::*;
-pub use generator::Generator;
+pub use generator::{Generator, Backend, PythonGenerator, RustGenerator};
+pub use printer::ShellPrinter;
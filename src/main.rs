@@ -1,8 +1,10 @@
 mod lexer;
+mod debug;
 mod parser;
 mod ast;
 mod shared_utils;
 mod generator;
+mod printer;
 mod cache;
 mod execution;
 mod utils;
@@ -21,10 +23,20 @@ use debashl::{Parser, Generator};
 use crate::utils::generate_unified_diff;
 use crate::testing::{test_all_examples, test_all_examples_next_fail, find_uses_of_system,
                     test_file_equivalence, AstFormatOptions};
-use crate::commands::{run_generated, lex_input, parse_input, parse_file, parse_to_perl, 
-                     parse_file_to_perl, interactive_mode};
+use crate::commands::{run_generated, lex_input, parse_input, parse_file, parse_to_code, parse_file_to_code,
+                     format_script, parse_to_json, generate_from_json, interactive_mode};
 use crate::help::show_help;
 
+/// The target language a `--perl`, `--python` or `--rust` flag names.
+fn target_language(flag: &str) -> Option<&'static str> {
+    match flag {
+        "--perl" => Some("perl"),
+        "--python" => Some("python"),
+        "--rust" => Some("rust"),
+        _ => None,
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program_name = &args[0];
@@ -192,11 +204,11 @@ fn main() {
                         break;
                     }
                     generator => {
-                        // Only perl generator is supported
-                        if generator == "perl" {
+                        // Only perl, python and rust generators are supported
+                        if generator == "perl" || generator == "python" || generator == "rust" {
                             generators.push(generator.to_string());
                         } else {
-                            println!("Warning: Only 'perl' generator is supported, skipping '{}'", generator);
+                            println!("Warning: Only 'perl', 'python' and 'rust' generators are supported, skipping '{}'", generator);
                         }
                     }
                 }
@@ -280,25 +292,33 @@ fn main() {
                 println!("Error: parse command requires input");
                 return;
             }
-            if args.len() >= 3 && args[2] == "--perl" {
+            if let Some(lang) = target_language(&args[2]) {
                 if args.len() < 4 {
-                    println!("Error: parse --perl command requires input");
+                    println!("Error: parse --{} command requires input", lang);
                     return;
                 }
                 let input = &args[3];
-                parse_to_perl(input);
+                parse_to_code(lang, input);
+            } else if args.len() >= 3 && args[2] == "--json" {
+                if args.len() < 4 {
+                    println!("Error: parse --json command requires input");
+                    return;
+                }
+                // Parser debug output would corrupt the JSON
+                set_debug_enabled(false);
+                parse_to_json(&args[3]);
             } else if args.len() >= 3 && args[2] == "--run" {
                 // parse --run <lang> <input>
                 if args.len() < 5 {
-                    println!("Error: parse --run <perl> <input>");
+                    println!("Error: parse --run <perl|python|rust> <input>");
                     return;
                 }
                 let lang = &args[3];
                 let input = &args[4];
-                if lang == "perl" {
+                if lang == "perl" || lang == "python" || lang == "rust" {
                     run_generated(lang, input);
                 } else {
-                    println!("Error: Only 'perl' language is supported");
+                    println!("Error: Only 'perl', 'python' and 'rust' languages are supported");
                     return;
                 }
             } else {
@@ -319,37 +339,37 @@ fn main() {
                 println!("Error: file command requires filename");
                 return;
             }
-            if args.len() >= 3 && args[2] == "--perl" {
+            if let Some(lang) = target_language(&args[2]) {
                 if args.len() < 4 {
-                    println!("Error: file --perl command requires filename");
+                    println!("Error: file --{} command requires filename", lang);
                     return;
                 }
                 let filename = &args[3];
-                parse_file_to_perl(filename);
+                parse_file_to_code(lang, filename);
             } else if args.len() >= 3 && args[2] == "--test-file" {
                 if args.len() < 5 {
-                    println!("Error: file --test-file <perl> <filename>");
+                    println!("Error: file --test-file <perl|python|rust> <filename>");
                     return;
                 }
                 let lang = &args[3];
                 let filename = &args[4];
-                if lang == "perl" {
+                if lang == "perl" || lang == "python" || lang == "rust" {
                     let _ = test_file_equivalence(lang, filename);
                 } else {
-                    println!("Error: Only 'perl' language is supported");
+                    println!("Error: Only 'perl', 'python' and 'rust' languages are supported");
                     return;
                 }
             } else if args.len() >= 3 && args[2] == "--run" {
                 if args.len() < 5 {
-                    println!("Error: file --run <perl> <filename>");
+                    println!("Error: file --run <perl|python|rust> <filename>");
                     return;
                 }
                 let lang = &args[3];
                 let filename = &args[4];
-                if lang == "perl" {
+                if lang == "perl" || lang == "python" || lang == "rust" {
                     run_generated(lang, filename);
                 } else {
-                    println!("Error: Only 'perl' language is supported");
+                    println!("Error: Only 'perl', 'python' and 'rust' languages are supported");
                     return;
                 }
             } else {
@@ -357,17 +377,64 @@ fn main() {
                 parse_file(filename);
             }
         }
+        "generate" => {
+            // generate [--perl|--python|--rust] --from-json <file|->
+            let mut lang = "perl";
+            let mut input: Option<&String> = None;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--perl" => lang = "perl",
+                    "--python" => lang = "python",
+                    "--rust" => lang = "rust",
+                    "--from-json" if i + 1 < args.len() => {
+                        input = Some(&args[i + 1]);
+                        i += 1;
+                    }
+                    _ => {
+                        println!("Error: generate [--perl|--python|--rust] --from-json <file|->");
+                        return;
+                    }
+                }
+                i += 1;
+            }
+            let Some(input) = input else {
+                println!("Error: generate [--perl|--python|--rust] --from-json <file|->");
+                return;
+            };
+            generate_from_json(lang, input);
+        }
+        "fmt" => {
+            // fmt [-w] [--check] <input>
+            let mut write = false;
+            let mut check = false;
+            let mut input: Option<&String> = None;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "-w" | "--write" => write = true,
+                    "--check" => check = true,
+                    _ => input = Some(arg),
+                }
+            }
+            let Some(input) = input else {
+                println!("Error: fmt [-w] [--check] <input>");
+                return;
+            };
+            // Parser debug output would end up in the formatted script
+            set_debug_enabled(false);
+            format_script(input, write, check);
+        }
         "--test-file" | "test-file" => {
             if args.len() < 4 {
-                println!("Error: --test-file <perl> <filename>");
+                println!("Error: --test-file <perl|python|rust> <filename>");
                 return;
             }
             let lang = &args[2];
             let filename = &args[3];
-            if lang == "perl" {
+            if lang == "perl" || lang == "python" || lang == "rust" {
                 let _ = test_file_equivalence(lang, filename);
             } else {
-                println!("Error: Only 'perl' language is supported");
+                println!("Error: Only 'perl', 'python' and 'rust' languages are supported");
                 return;
             }
         }
@@ -401,11 +468,11 @@ fn main() {
                         break;
                     }
                     generator => {
-                        // Only perl generator is supported
-                        if generator == "perl" {
+                        // Only perl, python and rust generators are supported
+                        if generator == "perl" || generator == "python" || generator == "rust" {
                             generators.push(generator.to_string());
                         } else {
-                            println!("Warning: Only 'perl' generator is supported, skipping '{}'", generator);
+                            println!("Warning: Only 'perl', 'python' and 'rust' generators are supported, skipping '{}'", generator);
                         }
                     }
                 }
@@ -579,4 +646,14 @@ mod tests {
         let result = Parser::new(input).parse();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_backend_for_each_language() {
+        let commands = Parser::new("echo hello world").parse().unwrap();
+        assert!(commands::backend_for("perl").generate(&commands).starts_with("#!/usr/bin/env perl"));
+        assert!(commands::backend_for("python").generate(&commands).starts_with("#!/usr/bin/env python3"));
+        assert!(commands::backend_for("rust").generate(&commands).contains("fn main()"));
+        assert_eq!(target_language("--python"), Some("python"));
+        assert_eq!(target_language("--c"), None);
+    }
 }
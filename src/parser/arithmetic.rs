@@ -0,0 +1,335 @@
+use crate::ast::*;
+use crate::parser::errors::ParserError;
+
+/// Builds an `ArithmeticExpression` from the text between `((` and `))`,
+/// parsing it into a tree where possible. Bash only evaluates arithmetic at
+/// runtime, so text we cannot parse is kept as-is rather than rejected.
+pub fn arithmetic_expression(text: &str) -> ArithmeticExpression {
+    let tree = if text.trim().is_empty() { None } else { parse_arithmetic(text).ok() };
+    ArithmeticExpression { expression: text.to_string(), tree }
+}
+
+/// Parses a bash arithmetic expression with a Pratt parser.
+pub fn parse_arithmetic(text: &str) -> Result<ArithmeticNode, ParserError> {
+    let mut parser = ArithmeticParser { tokens: tokenize(text)?, position: 0 };
+    let node = parser.expression(0)?;
+    match parser.peek() {
+        None => Ok(node),
+        Some(token) => Err(ParserError::InvalidSyntax(format!("Unexpected {:?} in arithmetic expression", token))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ArithmeticToken {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+}
+
+// Longest operators first so that e.g. `<<=` is not read as `<<` `=`
+const OPERATORS: &[&str] = &[
+    "**=", "<<=", ">>=",
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "++", "--",
+    "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=", ",", "(", ")", "[", "]",
+];
+
+fn tokenize(text: &str) -> Result<Vec<ArithmeticToken>, ParserError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '#' | '@')) {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            tokens.push(ArithmeticToken::Number(parse_number(&literal)?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(ArithmeticToken::Name(chars[start..i].iter().collect()));
+        } else if c == '$' && chars[i + 1..].starts_with(&['(', '(']) {
+            // A nested `$((...))` is just a parenthesized expression
+            i += 1;
+        } else if c == '$' {
+            // `$x` and `${x}` name the same variable as a bare `x`
+            i += 1;
+            let braced = chars.get(i) == Some(&'{');
+            if braced {
+                i += 1;
+            }
+            let start = i;
+            if chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+                i += 1;
+                while braced && i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            } else {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            if i == start || (braced && chars.get(i) != Some(&'}')) {
+                return Err(ParserError::InvalidSyntax(format!("Unsupported expansion in arithmetic expression: {}", text)));
+            }
+            tokens.push(ArithmeticToken::Name(chars[start..i].iter().collect()));
+            if braced {
+                i += 1;
+            }
+        } else {
+            let rest: String = chars[i..].iter().take(3).collect();
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(ParserError::InvalidSyntax(format!("Unexpected character '{}' in arithmetic expression", c)));
+            };
+            tokens.push(ArithmeticToken::Operator(op));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Integer constants: decimal, `0x` hex, leading-zero octal and `base#digits`
+/// for bases 2 to 64. Values wrap to 64 bits as in bash.
+fn parse_number(literal: &str) -> Result<i64, ParserError> {
+    let (base, digits) = if let Some((base, digits)) = literal.split_once('#') {
+        (base.parse::<u32>().unwrap_or(0), digits)
+    } else if let Some(digits) = literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        (16, digits)
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        (8, &literal[1..])
+    } else {
+        (10, literal)
+    };
+    let invalid = || ParserError::InvalidSyntax(format!("Invalid number in arithmetic expression: {}", literal));
+    if !(2..=64).contains(&base) || digits.is_empty() {
+        return Err(invalid());
+    }
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            // Upper and lower case only differ above base 36
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if digit >= base {
+            return Err(invalid());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+struct ArithmeticParser {
+    tokens: Vec<ArithmeticToken>,
+    position: usize,
+}
+
+// Binding powers, loosest first, following bash's operator precedence
+const COMMA: u8 = 1;
+const ASSIGNMENT: u8 = 3;
+const CONDITIONAL: u8 = 5;
+const PREFIX: u8 = 29;
+const POSTFIX: u8 = 31;
+
+fn binary_operator(op: &str) -> Option<(ArithmeticOperator, u8, u8)> {
+    use ArithmeticOperator::*;
+    let (operator, left) = match op {
+        "," => (Comma, COMMA),
+        "||" => (LogicalOr, 7),
+        "&&" => (LogicalAnd, 9),
+        "|" => (BitOr, 11),
+        "^" => (BitXor, 13),
+        "&" => (BitAnd, 15),
+        "==" => (Equal, 17),
+        "!=" => (NotEqual, 17),
+        "<" => (Less, 19),
+        "<=" => (LessEqual, 19),
+        ">" => (Greater, 19),
+        ">=" => (GreaterEqual, 19),
+        "<<" => (ShiftLeft, 21),
+        ">>" => (ShiftRight, 21),
+        "+" => (Add, 23),
+        "-" => (Subtract, 23),
+        "*" => (Multiply, 25),
+        "/" => (Divide, 25),
+        "%" => (Modulo, 25),
+        // `**` is right-associative
+        "**" => return Some((Power, 28, 27)),
+        _ => return None,
+    };
+    Some((operator, left, left + 1))
+}
+
+fn assignment_operator(op: &str) -> Option<Option<ArithmeticOperator>> {
+    if op == "=" {
+        return Some(None);
+    }
+    let operator = op.strip_suffix('=')?;
+    if matches!(operator, "<" | ">" | "=" | "!") {
+        return None;
+    }
+    binary_operator(operator).map(|(operator, _, _)| Some(operator))
+}
+
+fn is_lvalue(node: &ArithmeticNode) -> bool {
+    matches!(node, ArithmeticNode::Variable(_) | ArithmeticNode::Element(..))
+}
+
+impl ArithmeticParser {
+    fn peek(&self) -> Option<&ArithmeticToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<ArithmeticToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ParserError> {
+        match self.next() {
+            Some(ArithmeticToken::Operator(found)) if found == op => Ok(()),
+            _ => Err(ParserError::InvalidSyntax(format!("Expected '{}' in arithmetic expression", op))),
+        }
+    }
+
+    fn expression(&mut self, min_power: u8) -> Result<ArithmeticNode, ParserError> {
+        let mut left = self.prefix()?;
+        while let Some(ArithmeticToken::Operator(op)) = self.peek().cloned() {
+            if matches!(op, "++" | "--") && is_lvalue(&left) {
+                if POSTFIX < min_power {
+                    break;
+                }
+                self.next();
+                left = if op == "++" {
+                    ArithmeticNode::PostIncrement(Box::new(left))
+                } else {
+                    ArithmeticNode::PostDecrement(Box::new(left))
+                };
+            } else if op == "?" {
+                if CONDITIONAL < min_power {
+                    break;
+                }
+                self.next();
+                let then_value = self.expression(0)?;
+                self.expect(":")?;
+                let else_value = self.expression(CONDITIONAL)?;
+                left = ArithmeticNode::Conditional(Box::new(left), Box::new(then_value), Box::new(else_value));
+            } else if let Some(operator) = assignment_operator(op) {
+                if ASSIGNMENT < min_power {
+                    break;
+                }
+                if !is_lvalue(&left) {
+                    return Err(ParserError::InvalidSyntax("Attempted assignment to non-variable in arithmetic expression".to_string()));
+                }
+                self.next();
+                let value = self.expression(ASSIGNMENT)?;
+                left = ArithmeticNode::Assign(Box::new(left), operator, Box::new(value));
+            } else if let Some((operator, left_power, right_power)) = binary_operator(op) {
+                if left_power < min_power {
+                    break;
+                }
+                self.next();
+                let right = self.expression(right_power)?;
+                left = ArithmeticNode::Binary(Box::new(left), operator, Box::new(right));
+            } else {
+                break;
+            }
+        }
+        Ok(left)
+    }
+
+    fn prefix(&mut self) -> Result<ArithmeticNode, ParserError> {
+        match self.next() {
+            Some(ArithmeticToken::Number(value)) => Ok(ArithmeticNode::Number(value)),
+            Some(ArithmeticToken::Name(name)) => {
+                if self.peek() == Some(&ArithmeticToken::Operator("[")) {
+                    self.next();
+                    let index = self.expression(0)?;
+                    self.expect("]")?;
+                    Ok(ArithmeticNode::Element(name, Box::new(index)))
+                } else {
+                    Ok(ArithmeticNode::Variable(name))
+                }
+            }
+            Some(ArithmeticToken::Operator("(")) => {
+                let inner = self.expression(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(ArithmeticToken::Operator(op @ ("++" | "--"))) => {
+                let target = self.expression(PREFIX)?;
+                if !is_lvalue(&target) {
+                    return Err(ParserError::InvalidSyntax(format!("'{}' requires a variable in arithmetic expression", op)));
+                }
+                Ok(if op == "++" {
+                    ArithmeticNode::PreIncrement(Box::new(target))
+                } else {
+                    ArithmeticNode::PreDecrement(Box::new(target))
+                })
+            }
+            Some(ArithmeticToken::Operator(op @ ("+" | "-" | "!" | "~"))) => {
+                let operator = match op {
+                    "+" => ArithmeticOperator::Add,
+                    "-" => ArithmeticOperator::Subtract,
+                    "!" => ArithmeticOperator::LogicalNot,
+                    _ => ArithmeticOperator::BitNot,
+                };
+                let operand = self.expression(PREFIX)?;
+                Ok(ArithmeticNode::Unary(operator, Box::new(operand)))
+            }
+            Some(token) => Err(ParserError::InvalidSyntax(format!("Unexpected {:?} in arithmetic expression", token))),
+            None => Err(ParserError::InvalidSyntax("Unexpected end of arithmetic expression".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ArithmeticNode::*;
+    use ArithmeticOperator::*;
+
+    fn var(name: &str) -> Box<ArithmeticNode> {
+        Box::new(Variable(name.to_string()))
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        // Unary minus binds tighter than `**`, which is right-associative
+        assert_eq!(parse_arithmetic("-2**3**2").unwrap(),
+                   Binary(Box::new(Unary(Subtract, Box::new(Number(2)))), Power,
+                          Box::new(Binary(Box::new(Number(3)), Power, Box::new(Number(2))))));
+        assert_eq!(parse_arithmetic("a = b += c ? 1 : 2, $d++").unwrap(),
+                   Binary(Box::new(Assign(var("a"), None,
+                                          Box::new(Assign(var("b"), Some(Add),
+                                                          Box::new(Conditional(var("c"), Box::new(Number(1)), Box::new(Number(2)))))))),
+                          Comma, Box::new(PostIncrement(var("d")))));
+        assert_eq!(parse_arithmetic("x & 1 == 1 | ${y} ^ 2").unwrap(),
+                   Binary(Box::new(Binary(var("x"), BitAnd, Box::new(Binary(Box::new(Number(1)), Equal, Box::new(Number(1)))))),
+                          BitOr, Box::new(Binary(var("y"), BitXor, Box::new(Number(2))))));
+        assert!(parse_arithmetic("1 = 2").is_err());
+        assert!(parse_arithmetic("$(date) + 1").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_numbers() {
+        for (text, value) in [("0x1F", 31), ("017", 15), ("2#101", 5), ("16#ff", 255), ("64#_", 63), ("36#Z", 35)] {
+            assert_eq!(parse_arithmetic(text).unwrap(), Number(value), "{}", text);
+        }
+        assert!(parse_arithmetic("08").is_err());
+        assert!(parse_arithmetic("2#3").is_err());
+    }
+}
@@ -3,6 +3,50 @@ use crate::lexer::{Lexer, Token};
 use crate::parser::errors::ParserError;
 use crate::parser::utilities::ParserUtilities;
 use crate::parser::words::parse_word;
+use crate::parser::arithmetic::arithmetic_expression;
+
+/// The value of a `name=value` assignment, which may be empty, as in
+/// `IFS= read`, or contain colons and commas, as in `PATH=/bin:$HOME/bin`.
+pub fn parse_assignment_value(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    let mut pieces = Vec::new();
+    loop {
+        match lexer.peek() {
+            Some(Token::Colon | Token::Comma) => {
+                let separator = if matches!(lexer.peek(), Some(Token::Colon)) { ":" } else { "," };
+                lexer.next();
+                pieces.push(Word::Literal(separator.to_string(), Span::default()));
+            }
+            None | Some(Token::Space | Token::Tab | Token::Newline | Token::CarriageReturn | Token::Semicolon
+                         | Token::Comment | Token::Pipe | Token::And | Token::Or | Token::Background
+                         | Token::ParenClose | Token::DoubleSemicolon) => break,
+            // Only a colon or comma joins one word to the next
+            _ if !pieces.is_empty() && !matches!(pieces.last(), Some(Word::Literal(text, _)) if text == ":" || text == ",") => break,
+            _ => pieces.push(parse_word(lexer)?),
+        }
+    }
+    if let [Word::Concatenation(words, span)] = pieces.as_slice() {
+        return Ok(Word::StringInterpolation(concatenated_parts(words), *span));
+    }
+    if pieces.len() == 1 {
+        return Ok(pieces.remove(0));
+    }
+    if pieces.iter().all(|piece| matches!(piece, Word::Literal(..))) {
+        return Ok(Word::Literal(pieces.iter().map(|piece| piece.to_string()).collect(), Span::default()));
+    }
+    let mut parts = Vec::new();
+    for piece in pieces {
+        match piece {
+            Word::Variable(name, _) => parts.push(StringPart::Variable(name)),
+            Word::ParameterExpansion(pe, _) => parts.push(StringPart::ParameterExpansion(pe)),
+            Word::Arithmetic(expr, _) => parts.push(StringPart::Arithmetic(expr)),
+            Word::CommandSubstitution(command, _) => parts.push(StringPart::CommandSubstitution(command)),
+            Word::StringInterpolation(interp, _) => parts.extend(interp.parts),
+            Word::Concatenation(words, _) => parts.extend(concatenated_parts(&words).parts),
+            other => parts.push(StringPart::Literal(other.to_string())),
+        }
+    }
+    Ok(Word::StringInterpolation(StringInterpolation { parts }, Span::default()))
+}
 
 pub fn parse_environment_variable_value(lexer: &mut Lexer) -> Result<Word, ParserError> {
     if let Some(tok) = lexer.peek() {
@@ -18,17 +62,17 @@ pub fn parse_environment_variable_value(lexer: &mut Lexer) -> Result<Word, Parse
             Token::ParenOpen => {
                 // Parse parenthetical text as a literal
                 let text = lexer.capture_parenthetical_text()?;
-                Ok(Word::Literal(text))
+                Ok(Word::Literal(text, Span::default()))
             }
             Token::DoubleQuotedString | Token::SingleQuotedString => {
                 // Parse quoted string as a literal
                 let text = lexer.get_string_text()?;
-                Ok(Word::Literal(text))
+                Ok(Word::Literal(text, Span::default()))
             }
             Token::BacktickString => {
                 // Parse backtick string as a literal
                 let text = lexer.get_raw_token_text()?;
-                Ok(Word::Literal(text))
+                Ok(Word::Literal(text, Span::default()))
             }
             _ => {
                 // Parse as a literal string until separator
@@ -59,11 +103,11 @@ pub fn parse_environment_variable_value(lexer: &mut Lexer) -> Result<Word, Parse
                         }
                     }
                 }
-                Ok(Word::Literal(value))
+                Ok(Word::Literal(value, Span::default()))
             }
         }
     } else {
-        Ok(Word::Literal(String::new()))
+        Ok(Word::Literal(String::new(), Span::default()))
     }
 }
 
@@ -72,7 +116,7 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
     let mut current_element = String::new();
     let mut loop_count = 0;
     
-    println!("DEBUG: Starting parse_array_elements");
+    crate::debug_println!("DEBUG: Starting parse_array_elements");
     
     loop {
         loop_count += 1;
@@ -81,16 +125,16 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
         }
         
         let token = lexer.peek();
-        println!("DEBUG: Loop {}: token = {:?}, current_element = '{}'", loop_count, token, current_element);
+        crate::debug_println!("DEBUG: Loop {}: token = {:?}, current_element = '{}'", loop_count, token, current_element);
         
         match token {
             None => {
                 // End of tokens reached, break out of the loop
-                println!("DEBUG: End of tokens reached, breaking");
+                crate::debug_println!("DEBUG: End of tokens reached, breaking");
                 break;
             }
             Some(Token::ParenClose) => {
-                println!("DEBUG: Found closing parenthesis, adding current_element: '{}'", current_element);
+                crate::debug_println!("DEBUG: Found closing parenthesis, adding current_element: '{}'", current_element);
                 if !current_element.is_empty() {
                     elements.push(current_element.trim().to_string());
                 }
@@ -98,7 +142,7 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
                 break;
             }
             Some(Token::Space) | Some(Token::Tab) | Some(Token::Newline) => {
-                println!("DEBUG: Found whitespace/newline, adding current_element: '{}'", current_element);
+                crate::debug_println!("DEBUG: Found whitespace/newline, adding current_element: '{}'", current_element);
                 if !current_element.is_empty() {
                     elements.push(current_element.trim().to_string());
                     current_element.clear();
@@ -107,15 +151,14 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
             }
             Some(Token::Identifier) | Some(Token::Number) => {
                 let text = lexer.get_current_text().unwrap_or_default();
-                println!("DEBUG: Found identifier/number: '{}'", text);
+                crate::debug_println!("DEBUG: Found identifier/number: '{}'", text);
                 current_element.push_str(&text);
                 lexer.next(); // consume the token
             }
             Some(Token::DoubleQuotedString) | Some(Token::SingleQuotedString) => {
                 let text = lexer.get_string_text()?;
-                println!("DEBUG: Found string: '{}'", text);
+                crate::debug_println!("DEBUG: Found string: '{}'", text);
                 current_element.push_str(&text);
-                lexer.next(); // consume the string token
             }
             Some(Token::Dollar) => {
                 // For now, just consume the $ and treat it as part of the element
@@ -131,7 +174,7 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
             _ => {
                 // For any other token, get its text and advance
                 if let Some(text) = lexer.get_current_text() {
-                    println!("DEBUG: Found other token: '{}'", text);
+                    crate::debug_println!("DEBUG: Found other token: '{}'", text);
                     current_element.push_str(&text);
                 }
                 lexer.next(); // consume the token
@@ -139,7 +182,7 @@ pub fn parse_array_elements(lexer: &mut Lexer) -> Result<Vec<String>, ParserErro
         }
     }
     
-    println!("DEBUG: Final elements: {:?}", elements);
+    crate::debug_println!("DEBUG: Final elements: {:?}", elements);
     Ok(elements)
 }
 
@@ -169,92 +212,16 @@ pub fn parse_word_list(lexer: &mut Lexer) -> Result<Vec<Word>, ParserError> {
 // Placeholder functions - these would need to be implemented based on the actual AST structures
 fn parse_arithmetic_expression(lexer: &mut Lexer) -> Result<Word, ParserError> {
     // Handle arithmetic expressions like $((i + 1))
-    // First, consume the opening $(( or $( token
     match lexer.peek() {
         Some(Token::Arithmetic) | Some(Token::ArithmeticEval) => {
-            lexer.next(); // consume $(( or $(
+            lexer.next(); // consume $(( or ((
         }
         _ => {
             return Err(ParserError::InvalidSyntax("Expected arithmetic expression start".to_string()));
         }
     }
-    
-    let mut expression_parts = Vec::new();
-    
-    // Simple case: just parse until we find the closing ))
-    loop {
-        match lexer.peek() {
-            Some(Token::ArithmeticEvalClose) => {
-                // Found the closing )), consume it and break
-                lexer.next();
-                break;
-            }
-            Some(Token::Identifier) => {
-                let var_name = lexer.get_identifier_text()?;
-                expression_parts.push(var_name);
-                lexer.next(); // consume the identifier token
-            }
-            Some(Token::Number) => {
-                let num_text = lexer.get_number_text()?;
-                expression_parts.push(num_text);
-                lexer.next(); // consume the number token
-            }
-            Some(Token::Plus) => {
-                // Plus operator
-                lexer.next();
-                expression_parts.push("+".to_string());
-            }
-            Some(Token::Minus) => {
-                // Minus operator
-                lexer.next();
-                expression_parts.push("-".to_string());
-            }
-            Some(Token::Star) => {
-                // Multiplication operator
-                lexer.next();
-                expression_parts.push("*".to_string());
-            }
-            Some(Token::Slash) => {
-                // Division operator
-                lexer.next();
-                expression_parts.push("/".to_string());
-            }
-            Some(Token::Space) | Some(Token::Tab) => {
-                // Skip whitespace
-                lexer.next();
-            }
-            Some(Token::Dollar) => {
-                // Handle variable references like $i
-                lexer.next();
-                if let Some(Token::Identifier) = lexer.peek() {
-                    let var_name = lexer.get_identifier_text()?;
-                    expression_parts.push(format!("${}", var_name));
-                } else {
-                    return Err(ParserError::InvalidSyntax("Expected identifier after $ in arithmetic expression".to_string()));
-                }
-            }
-            None => {
-                return Err(ParserError::InvalidSyntax("Unexpected end of input in arithmetic expression".to_string()));
-            }
-            _ => {
-                // For any other token, just consume it and add its text
-                if let Some(text) = lexer.get_current_text() {
-                    expression_parts.push(text);
-                    lexer.next();
-                } else {
-                    break;
-                }
-            }
-        }
-    }
-    
-    let expression = expression_parts.join("");
-    
-    // Return as an Arithmetic Word variant
-    Ok(Word::Arithmetic(ArithmeticExpression {
-        expression,
-        tokens: vec![], // We don't need to store individual tokens for now
-    }))
+    let expression = lexer.arithmetic_text()?;
+    Ok(Word::Arithmetic(arithmetic_expression(&expression), Span::default()))
 }
 
 fn parse_variable_expansion(_lexer: &mut Lexer) -> Result<Word, ParserError> {
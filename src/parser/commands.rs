@@ -3,18 +3,21 @@ use crate::lexer::{Lexer, Token};
 use crate::parser::errors::ParserError;
 use crate::parser::utilities::ParserUtilities;
 use crate::parser::words::{parse_word, parse_word_no_newline_skip};
-use crate::parser::redirects::parse_redirect;
-use crate::parser::assignments::parse_array_elements;
+use crate::parser::redirects::{at_redirect, parse_redirect};
+use crate::parser::assignments::{parse_array_elements, parse_assignment_value};
+use crate::parser::arithmetic::arithmetic_expression;
+use crate::parser::test_expressions::parse_test_expression;
 use crate::parser::control_flow::{
     parse_if_statement, parse_case_statement, parse_while_loop, parse_for_loop,
     parse_function, parse_posix_function, parse_break_statement, parse_continue_statement,
     parse_return_statement, parse_block
 };
-use std::collections::HashMap;
 
 pub struct Parser {
     pub lexer: Lexer,
     shopt_state: TestModifiers,
+    /// The errors recovered from so far, when parsing with recovery.
+    recovered: Option<Vec<ParserError>>,
 }
 
 impl Parser {
@@ -22,6 +25,7 @@ impl Parser {
         Self {
             lexer: Lexer::new(input),
             shopt_state: TestModifiers::default(),
+            recovered: None,
         }
     }
 
@@ -29,11 +33,31 @@ impl Parser {
         Self {
             lexer,
             shopt_state: TestModifiers::default(),
+            recovered: None,
         }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Command>, ParserError> {
+        let (commands, mut errors) = self.parse_program(false);
+        if errors.is_empty() {
+            Ok(commands)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parse the whole input, carrying on past errors. Each statement that
+    /// fails to parse is recorded in the error list and skipped up to the
+    /// next statement boundary, leaving a `Command::Error` in its place.
+    pub fn parse_with_recovery(&mut self) -> (Vec<Command>, Vec<ParserError>) {
+        self.parse_program(true)
+    }
+
+    fn parse_program(&mut self, recover: bool) -> (Vec<Command>, Vec<ParserError>) {
         let mut commands = vec![];
+        if recover {
+            self.recovered = Some(Vec::new());
+        }
         
         // Skip initial whitespace but preserve newlines for proper command separation
         let mut newline_count = 0;
@@ -51,12 +75,6 @@ impl Parser {
         }
         
         while !self.lexer.is_eof() {
-            let current_token = self.lexer.peek();
-            
-            if self.lexer.is_eof() {
-                break;
-            }
-            
             // Check if we're at a newline before parsing the command
             if let Some(Token::Newline) = self.lexer.peek() {
                 // Consume the newline and continue to next iteration
@@ -64,10 +82,15 @@ impl Parser {
                 continue;
             }
             
-            let mut command = self.parse_command()?;
+            let checkpoint = self.lexer.current;
+            let command = match self.parse_command() {
+                Ok(command) => command,
+                Err(e) if recover => self.recover(e, checkpoint, false),
+                Err(e) => return (commands, vec![e]),
+            };
             
-            if let Command::Simple(ref simple_cmd) = command {
-                if simple_cmd.name.as_literal().unwrap_or("") == "" && simple_cmd.args.is_empty() {
+            if let Command::Simple(ref simple_cmd, _) = command {
+                if simple_cmd.name.as_literal() == Some("") && simple_cmd.args.is_empty() {
                     // This is an empty command from a newline, skip it
                     continue;
                 }
@@ -77,6 +100,7 @@ impl Parser {
             
             // Handle separators and comments after command
             newline_count = 0;
+            let mut blank_line = 0;
             loop {
                 match self.lexer.peek() {
                     Some(Token::Space) | Some(Token::Tab) | Some(Token::Comment) => {
@@ -84,6 +108,9 @@ impl Parser {
                     }
                     Some(Token::Newline) => {
                         newline_count += 1;
+                        if newline_count == 2 {
+                            blank_line = self.lexer.offset();
+                        }
                         self.lexer.next();
                     }
                     Some(Token::Semicolon) => {
@@ -92,10 +119,11 @@ impl Parser {
                     }
                     Some(Token::Background) => {
                         // Convert last command to background
+                        self.lexer.next();
                         if let Some(last_command) = commands.pop() {
-                            commands.push(Command::Background(Box::new(last_command)));
+                            let span = self.lexer.span_from(last_command.span().start);
+                            commands.push(Command::Background(Box::new(last_command), span));
                         }
-                        self.lexer.next();
                         // Skip whitespace and comments after & but preserve newlines
                         loop {
                             match self.lexer.peek() {
@@ -114,11 +142,79 @@ impl Parser {
             }
             
             if newline_count >= 2 {
-                commands.push(Command::BlankLine);
+                commands.push(Command::BlankLine(self.lexer.span_from(blank_line)));
             }
         }
         
-        Ok(commands)
+        (commands, self.recovered.take().unwrap_or_default())
+    }
+
+    /// Parse a command in the body of a compound command. When parsing with
+    /// recovery, a statement that fails to parse is recorded and skipped,
+    /// keeping the commands around it.
+    pub fn parse_body_command(&mut self) -> Result<Command, ParserError> {
+        let checkpoint = self.lexer.current;
+        match self.parse_command() {
+            Err(e) if self.recovered.is_some() => Ok(self.recover(e, checkpoint, true)),
+            result => result,
+        }
+    }
+
+    /// Record an error and skip the statement it is in, from `checkpoint`.
+    /// An error without a location is placed at the start of the statement.
+    fn recover(&mut self, e: ParserError, checkpoint: usize, nested: bool) -> Command {
+        self.lexer.current = checkpoint;
+        let skipped = self.skip_statement(nested);
+        let error = match e.location() {
+            Some(_) => e,
+            None => ParserError::Located { error: Box::new(e), line: skipped.span().line, col: skipped.span().col },
+        };
+        if let Some(errors) = &mut self.recovered {
+            errors.push(error);
+        }
+        skipped
+    }
+
+    /// Skip the statement starting at the current token, returning it as a
+    /// `Command::Error`. The statement ends at a newline or `;`, or at the
+    /// `fi`, `done`, `esac` or `}` closing the compound command it opened.
+    /// A `nested` statement, in the body of a compound command, also ends
+    /// before a keyword or `;;` of the command around it.
+    fn skip_statement(&mut self, nested: bool) -> Command {
+        self.lexer.skip_inline_whitespace_and_comments();
+        let start = self.lexer.offset();
+        let mut depth = 0;
+        let mut command_position = true;
+        while let Some(token) = self.lexer.peek().cloned() {
+            let at_command = command_position;
+            command_position = match token {
+                Token::Space | Token::Tab | Token::Comment => at_command,
+                Token::Newline | Token::Semicolon | Token::DoubleSemicolon | Token::And | Token::Or | Token::Pipe
+                | Token::Background | Token::Then | Token::Do | Token::Else | Token::Elif
+                | Token::BraceOpen | Token::ParenOpen | Token::ParenClose => true,
+                _ => false,
+            };
+            match token {
+                Token::Newline | Token::Semicolon if depth == 0 && self.lexer.offset() > start => break,
+                Token::DoubleSemicolon if nested && depth == 0 && self.lexer.offset() > start => break,
+                Token::Fi | Token::Done | Token::Esac | Token::BraceClose | Token::Then | Token::Do | Token::Else | Token::Elif
+                    if nested && depth == 0 && at_command && self.lexer.offset() > start => break,
+                Token::If | Token::While | Token::Until | Token::For | Token::Select | Token::Case | Token::BraceOpen if at_command => {
+                    depth += 1;
+                }
+                Token::Fi | Token::Done | Token::Esac | Token::BraceClose if at_command => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        self.lexer.next();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.lexer.next();
+        }
+        let span = self.lexer.span_from(start);
+        Command::Error(self.lexer.get_text(span.start, span.end), span)
     }
 
     pub fn parse_command(&mut self) -> Result<Command, ParserError> {
@@ -137,6 +233,7 @@ impl Parser {
             return Err(ParserError::UnexpectedEOF);
         }
 
+        let start = self.lexer.offset();
         let command = if let Some(Token::Identifier) = self.lexer.peek() {
             // Check if this is a function definition: identifier() { ... }
             let paren1 = self.lexer.peek_n(1);
@@ -161,25 +258,21 @@ impl Parser {
                     pos += 1;
                 }
                 if matches!(self.lexer.peek_n(pos), Some(Token::CasePattern)) && matches!(self.lexer.peek_n(pos + 1), Some(Token::Assign)) {
-                                    // This is an array assignment, parse it directly
-                let var_name = self.lexer.get_identifier_text()?;
-                // get_identifier_text() already advanced past the identifier
-                self.lexer.next(); // consume the =
-                self.lexer.next(); // consume the (
-                // Now parse the array elements (the lexer should be at the first element)
-                let elements = parse_array_elements(&mut self.lexer)?;
-                // The closing ) should already be consumed by parse_array_elements
-                    
+                    // This is an array element assignment like map[foo]=bar
+                    let mut var_name = self.lexer.get_identifier_text()?;
+                    // get_identifier_text() already advanced past the identifier
+                    var_name.push_str(&self.lexer.get_current_text().unwrap_or_default());
+                    self.lexer.next(); // consume the [key]
+                    self.lexer.next(); // consume the =
+                    let value = parse_word(&mut self.lexer)?;
+
                     // Create a simple command with environment variables
-                    let mut env_vars = HashMap::new();
-                    env_vars.insert(var_name.clone(), Word::Array(var_name, elements));
-                    
                     Command::Simple(SimpleCommand {
-                        name: Word::Literal("true".to_string()),
+                        name: Word::Literal("true".to_string(), Span::default()),
                         args: Vec::new(),
                         redirects: Vec::new(),
-                        env_vars,
-                    })
+                        env_vars: vec![Assignment::new(&var_name, value)],
+                    }, Span::default())
                 } else {
                     // Check if this is a standalone variable assignment: identifier=value
                     let mut pos = 1;
@@ -201,8 +294,8 @@ impl Parser {
                 }
                 Some(Token::If) => parse_if_statement(self)?,
                 Some(Token::Case) => parse_case_statement(self)?,
-                Some(Token::While) => parse_while_loop(self)?,
-                Some(Token::For) => parse_for_loop(self)?,
+                Some(Token::While) | Some(Token::Until) => parse_while_loop(self)?,
+                Some(Token::For) | Some(Token::Select) => parse_for_loop(self)?,
                 Some(Token::Function) => parse_function(self)?,
                 Some(Token::Break) => parse_break_statement(self)?,
                 Some(Token::Continue) => parse_continue_statement(self)?,
@@ -213,7 +306,6 @@ impl Parser {
                 }
                 Some(Token::ParenOpen) => self.parse_subshell()?,
                 Some(Token::BraceOpen) => parse_block(self)?,
-                Some(Token::TestBracket) => self.parse_test_expression()?,
                 Some(Token::Semicolon) => {
                     // Skip semicolon and continue parsing
                     self.lexer.next();
@@ -223,53 +315,48 @@ impl Parser {
                     // Newlines should be handled at the top level, not here
                     // Return an empty command to indicate we hit a newline
                     return Ok(Command::Simple(SimpleCommand {
-                        name: Word::Literal("".to_string()),
+                        name: Word::Literal("".to_string(), Span::default()),
                         args: vec![],
                         redirects: vec![],
-                        env_vars: HashMap::new(),
-                    }));
+                        env_vars: Vec::new(),
+                    }, Span::default()));
                 }
                 _ => self.parse_pipeline()?,
             }
         };
 
         // Check for redirects that follow the command
-        self.parse_command_redirects(command)
+        let command = self.parse_command_redirects(command)?;
+        Ok(command.with_span(self.lexer.span_from(start)))
     }
 
     fn parse_command_redirects(&mut self, command: Command) -> Result<Command, ParserError> {
         // Check if there are redirects following the command
         let mut redirects = Vec::new();
         
-        // Skip whitespace and comments
-        self.lexer.skip_whitespace_and_comments();
+        // Redirects must be on the same line as the command
+        self.lexer.skip_inline_whitespace_and_comments();
         
         // Parse redirects until we hit a command separator or other non-redirect token
-        while let Some(token) = self.lexer.peek() {
-            match token {
-                Token::Number | Token::RedirectIn | Token::RedirectOut | Token::RedirectAppend | 
-                Token::RedirectInOut | Token::Heredoc | Token::HeredocTabs | Token::HereString |
-                Token::RedirectOutErr | Token::RedirectInErr | Token::RedirectOutClobber | 
-                Token::RedirectAll | Token::RedirectAllAppend => {
-                    redirects.push(parse_redirect(&mut self.lexer)?);
-                }
-                _ => break,
-            }
+        while at_redirect(&mut self.lexer) {
+            redirects.push(parse_redirect(&mut self.lexer)?);
+            self.lexer.skip_inline_whitespace_and_comments();
         }
         
         if redirects.is_empty() {
             Ok(command)
         } else {
             // Wrap the command with redirects
+            let span = self.lexer.span_from(command.span().start);
             Ok(Command::Redirect(RedirectCommand {
                 command: Box::new(command),
                 redirects,
-            }))
+            }, span))
         }
     }
 
     fn parse_pipeline(&mut self) -> Result<Command, ParserError> {
-        let first_command = self.parse_simple_command()?;
+        let first_command = self.parse_pipeline_element()?;
         self.parse_pipeline_from_command(first_command)
     }
 
@@ -288,19 +375,19 @@ impl Parser {
                     self.lexer.next();
                     operators.push(PipeOperator::Pipe);
                     self.lexer.skip_whitespace_and_comments();
-                    commands.push(self.parse_simple_command()?);
+                    commands.push(self.parse_pipeline_element()?);
                 }
                 Token::And => {
                     self.lexer.next();
                     operators.push(PipeOperator::And);
                     self.lexer.skip_whitespace_and_comments();
-                    commands.push(self.parse_simple_command()?);
+                    commands.push(self.parse_pipeline_element()?);
                 }
                 Token::Or => {
                     self.lexer.next();
                     operators.push(PipeOperator::Or);
                     self.lexer.skip_whitespace_and_comments();
-                    commands.push(self.parse_simple_command()?);
+                    commands.push(self.parse_pipeline_element()?);
                 }
                 Token::Semicolon | Token::Newline => {
                     // Stop parsing pipeline when we hit a command separator
@@ -315,17 +402,28 @@ impl Parser {
         if commands.len() == 1 {
             Ok(commands.remove(0))
         } else {
-            Ok(Command::Pipeline(Pipeline { commands, operators }))
+            let span = self.lexer.span_from(commands[0].span().start);
+            Ok(Command::Pipeline(Pipeline { commands, operators }, span))
         }
     }
 
+    /// One command of a pipeline, located at the source it was parsed from.
+    fn parse_pipeline_element(&mut self) -> Result<Command, ParserError> {
+        self.lexer.skip_whitespace_and_comments();
+        let start = self.lexer.offset();
+        let command = self.parse_simple_command()?;
+        let span = self.lexer.span_from(start);
+        let command = self.parse_command_redirects(command.with_span(span))?;
+        Ok(command.with_span(self.lexer.span_from(start)))
+    }
+
     fn parse_simple_command(&mut self) -> Result<Command, ParserError> {
         // Skip whitespace and comments at the beginning
         self.lexer.skip_whitespace_and_comments();
         
         let mut args = Vec::new();
         let redirects = Vec::new();
-        let mut env_vars = HashMap::new();
+        let mut env_vars = Vec::new();
         
         // Parse environment variable-style assignments at the start
         while let Some(token) = self.lexer.peek() {
@@ -344,8 +442,8 @@ impl Parser {
                                     self.lexer.next(); // consume (
                                     let elements = parse_array_elements(&mut self.lexer)?;
                                     
-                                    let array_word = Word::Array(var_name.clone(), elements);
-                                    env_vars.insert(var_name, array_word);
+                                    let array_word = Word::Array(var_name.clone(), elements, Span::default());
+                                    env_vars.push(Assignment::new(&var_name, array_word));
                                     self.lexer.skip_whitespace_and_comments();
                                 } else {
                                     // Handle compound assignment like: var+=value
@@ -355,12 +453,9 @@ impl Parser {
                                     let value_word = parse_word(&mut self.lexer)?;
                                     
                                     let arithmetic_expr = format!("{}+{}", var_name, value_word.to_string());
-                                    let compound_word = Word::Arithmetic(ArithmeticExpression {
-                                        expression: arithmetic_expr,
-                                        tokens: vec![],
-                                    });
+                                    let compound_word = Word::Arithmetic(arithmetic_expression(&arithmetic_expr), Span::default());
                                     
-                                    env_vars.insert(var_name, compound_word);
+                                    env_vars.push(Assignment::new(&var_name, compound_word));
                                     self.lexer.skip_whitespace_and_comments();
                                 }
                             }
@@ -371,15 +466,15 @@ impl Parser {
                                     self.lexer.next(); // consume =
                                     self.lexer.next(); // consume (
                                     let elements = parse_array_elements(&mut self.lexer)?;
-                                    let array_word = Word::Array(var_name.clone(), elements);
-                                    env_vars.insert(var_name, array_word);
+                                    let array_word = Word::Array(var_name.clone(), elements, Span::default());
+                                    env_vars.push(Assignment::new(&var_name, array_word));
                                     self.lexer.skip_whitespace_and_comments();
                                 } else {
                                     // Handle regular assignment like: var=value or map[foo]=bar
                                     let var_name = self.lexer.get_identifier_text()?;
                                     self.lexer.next(); // consume =
-                                    let value_word = parse_word(&mut self.lexer)?;
-                                    env_vars.insert(var_name, value_word);
+                                    let value_word = parse_assignment_value(&mut self.lexer)?;
+                                    env_vars.push(Assignment::new(&var_name, value_word));
                                     self.lexer.skip_whitespace_and_comments();
                                 }
                             }
@@ -399,9 +494,7 @@ impl Parser {
                     if let Some(Token::Assign) = self.lexer.peek() {
                         self.lexer.next(); // consume =
                         let value_word = parse_word(&mut self.lexer)?;
-                        // Convert array access to a string representation for the environment variable key
-                        let key = array_access.to_string();
-                        env_vars.insert(key, value_word);
+                        env_vars.push(Assignment::new(&array_access.to_string(), value_word));
                         self.lexer.skip_whitespace_and_comments();
                     } else {
                         // Not an assignment, break out of assignment parsing
@@ -413,9 +506,11 @@ impl Parser {
             }
         }
         
-        // Check for double-bracket test [[ ... ]]
-        let is_double_bracket = matches!(self.lexer.peek(), Some(Token::TestBracket)) 
-            && matches!(self.lexer.peek_n(1), Some(Token::TestBracket));
+        // `[ ... ]` and `[[ ... ]]` are parsed as a whole
+        if matches!(self.lexer.peek(), Some(Token::TestBracket)) {
+            let modifiers = self.get_current_shopt_state();
+            return parse_test_expression(&mut self.lexer, modifiers);
+        }
 
         // Parse the command name first
         let name = parse_word(&mut self.lexer)?;
@@ -424,10 +519,11 @@ impl Parser {
         self.lexer.skip_inline_whitespace_and_comments();
         
         // Check if this is a builtin command
-        if let Word::Literal(name_str) = &name {
+        if let Word::Literal(name_str, _) = &name {
             if is_builtin_command(&name_str) {
                 // Parse as builtin command
-                while let Some(token) = self.lexer.peek() {
+                while !at_redirect(&mut self.lexer) {
+                    let Some(token) = self.lexer.peek() else { break; };
                     match token {
                         Token::Space | Token::Tab | Token::Comment => {
                             // Skip inline whitespace and comments, but continue parsing arguments
@@ -442,10 +538,13 @@ impl Parser {
                             // Stop parsing arguments when we hit a closing parenthesis
                             break;
                         }
+                        Token::RedirectIn | Token::RedirectOut if matches!(self.lexer.peek_n(1), Some(Token::ParenOpen)) => {
+                            args.push(parse_word_no_newline_skip(&mut self.lexer)?);
+                        }
                         Token::RedirectIn | Token::RedirectOut | Token::RedirectAppend | Token::RedirectInErr | Token::RedirectOutErr | Token::RedirectInOut | Token::Heredoc | Token::HeredocTabs | Token::HereString => {
                             break;
                         }
-                        Token::Pipe | Token::And | Token::Or | Token::Semicolon | Token::Background => {
+                        Token::Pipe | Token::And | Token::Or | Token::Semicolon | Token::DoubleSemicolon | Token::Background => {
                             break;
                         }
                         _ => {
@@ -460,29 +559,13 @@ impl Parser {
                     args,
                     redirects,
                     env_vars,
-                }));
+                }, Span::default()));
             }
         }
         
-        // Special handling for Bash double-bracket test: capture everything until closing ']]'
-        if is_double_bracket {
-            let expr = self.lexer.capture_double_bracket_expression()?;
-            return Ok(Command::TestExpression(TestExpression {
-                expression: expr,
-                modifiers: self.get_current_shopt_state(),
-            }));
-        }
-
-        // Special handling for Bash single-bracket test: capture everything until closing ']'
-        if let Word::Literal(name_str) = &name {
-            if name_str == "[" {
-                let expr = self.lexer.capture_single_bracket_expression()?;
-                args.push(Word::Literal(expr));
-            }
-        }
-
         // Parse arguments
-        while let Some(token) = self.lexer.peek() {
+        while !at_redirect(&mut self.lexer) {
+            let Some(token) = self.lexer.peek() else { break; };
             match token {
                 Token::Space | Token::Tab | Token::Comment => {
                     // Skip inline whitespace and comments, but continue parsing arguments
@@ -497,10 +580,13 @@ impl Parser {
                     // Stop parsing arguments when we hit a closing parenthesis
                     break;
                 }
+                Token::RedirectIn | Token::RedirectOut if matches!(self.lexer.peek_n(1), Some(Token::ParenOpen)) => {
+                    args.push(parse_word_no_newline_skip(&mut self.lexer)?);
+                }
                 Token::RedirectIn | Token::RedirectOut | Token::RedirectAppend | Token::RedirectInErr | Token::RedirectOutErr | Token::RedirectInOut | Token::Heredoc | Token::HeredocTabs | Token::HereString => {
                     break;
                 }
-                Token::Pipe | Token::And | Token::Or | Token::Semicolon | Token::Background => {
+                Token::Pipe | Token::And | Token::Or | Token::Semicolon | Token::DoubleSemicolon | Token::Background => {
                     break;
                 }
                 Token::Character | Token::NonZero | Token::Exists | Token::File | Token::Size | Token::Readable | Token::Writable | Token::Executable | Token::NewerThan | Token::OlderThan |
@@ -509,7 +595,7 @@ impl Parser {
                     args.push(parse_word_no_newline_skip(&mut self.lexer)?);
                     
                     // If this is a flag that takes an argument, continue parsing to get the argument
-                    if let Word::Literal(arg_str) = args.last().unwrap() {
+                    if let Word::Literal(arg_str, _) = args.last().unwrap() {
                         if arg_str == "-name" || arg_str == "-maxdepth" || arg_str == "-type" {
                             // Skip whitespace and comments
                             self.lexer.skip_whitespace_and_comments();
@@ -561,7 +647,7 @@ impl Parser {
             args,
             redirects,
             env_vars,
-        }))
+        }, Span::default()))
     }
 
     fn parse_standalone_assignment(&mut self) -> Result<Command, ParserError> {
@@ -580,60 +666,56 @@ impl Parser {
         // Parse the value
         let value_word = if matches!(self.lexer.peek(), Some(Token::ParenOpen)) {
             // This is an array assignment like arr=(one two three)
+            self.lexer.next(); // consume (
             let elements = parse_array_elements(&mut self.lexer)?;
-            Word::Array(var_name.clone(), elements)
+            Word::Array(var_name.clone(), elements, Span::default())
         } else {
-            parse_word(&mut self.lexer)?
+            parse_assignment_value(&mut self.lexer)?
         };
         
-        // Check if there's a command following this assignment
-        self.lexer.skip_whitespace_and_comments();
+        // Check if there's a command following this assignment on the same line
+        self.lexer.skip_inline_whitespace_and_comments();
         if let Some(Token::Identifier) = self.lexer.peek() {
             // There's a command following, parse it as a command with environment variables
-            let mut env_vars = HashMap::new();
-            env_vars.insert(var_name, value_word);
+            let assignment = Assignment::new(&var_name, value_word);
             
             let command = self.parse_command()?;
             
-            // Merge the environment variables with the command's environment variables
+            // This assignment comes before any the command itself starts with
             match command {
-                Command::Simple(mut simple_cmd) => {
-                    // Merge environment variables
-                    for (key, value) in env_vars {
-                        simple_cmd.env_vars.insert(key, value);
+                Command::Simple(mut simple_cmd, _) => {
+                    simple_cmd.env_vars.insert(0, assignment);
+                    Ok(Command::Simple(simple_cmd, Span::default()))
+                }
+                // `IFS=: read a b <<< "$x"` sets IFS for the redirected command alone
+                Command::Redirect(mut redirect_cmd, span) if matches!(*redirect_cmd.command, Command::Simple(..)) => {
+                    if let Command::Simple(simple_cmd, _) = redirect_cmd.command.as_mut() {
+                        simple_cmd.env_vars.insert(0, assignment);
                     }
-                    Ok(Command::Simple(simple_cmd))
+                    Ok(Command::Redirect(redirect_cmd, span))
                 }
                 _ => {
                     // For non-simple commands, wrap in a block with environment variables
-                    let mut env_vars_cmd = HashMap::new();
-                    for (key, value) in env_vars {
-                        env_vars_cmd.insert(key, value);
-                    }
-                    
                     let env_cmd = Command::Simple(SimpleCommand {
-                        name: Word::Literal("true".to_string()),
+                        name: Word::Literal("true".to_string(), Span::default()),
                         args: Vec::new(),
                         redirects: Vec::new(),
-                        env_vars: env_vars_cmd,
-                    });
+                        env_vars: vec![assignment],
+                    }, Span::default());
                     
                     Ok(Command::Block(Block {
                         commands: vec![env_cmd, command],
-                    }))
+                    }, Span::default()))
                 }
             }
         } else {
             // No command following, this is a standalone assignment
-            let mut env_vars = HashMap::new();
-            env_vars.insert(var_name, value_word);
-            
             Ok(Command::Simple(SimpleCommand {
-                name: Word::Literal("true".to_string()), // Use 'true' as a dummy command
+                name: Word::Literal("true".to_string(), Span::default()), // Use 'true' as a dummy command
                 args: Vec::new(),
                 redirects: Vec::new(),
-                env_vars,
-            }))
+                env_vars: vec![Assignment::new(&var_name, value_word)],
+            }, Span::default()))
         }
     }
 
@@ -657,7 +739,7 @@ impl Parser {
                     // Background marker inside subshell
                     if let Some(Token::Background) = self.lexer.peek() {
                         self.lexer.next();
-                        cmd = Command::Background(Box::new(cmd));
+                        cmd = Command::Background(Box::new(cmd), Span::default());
                     }
                     commands.push(cmd);
                 }
@@ -667,9 +749,9 @@ impl Parser {
         self.lexer.consume(Token::ParenClose)?;
         
         if commands.len() == 1 {
-            Ok(Command::Subshell(Box::new(commands.remove(0))))
+            Ok(Command::Subshell(Box::new(commands.remove(0)), Span::default()))
         } else {
-            Ok(Command::Subshell(Box::new(Command::Block(Block { commands }))))
+            Ok(Command::Subshell(Box::new(Command::Block(Block { commands }, Span::default())), Span::default()))
         }
     }
 
@@ -678,93 +760,6 @@ impl Parser {
         Err(ParserError::InvalidSyntax("Double paren commands not yet implemented".to_string()))
     }
 
-    fn parse_test_expression(&mut self) -> Result<Command, ParserError> {
-        use crate::ast::{TestExpression, TestModifiers};
-        
-        // Consume the opening [
-        if !matches!(self.lexer.peek(), Some(Token::TestBracket)) {
-            return Err(ParserError::InvalidSyntax("Expected '[' for test expression".to_string()));
-        }
-        self.lexer.next(); // consume '['
-        
-        // Capture the content between [ and ]
-        let mut expression_parts = Vec::new();
-        
-        loop {
-            match self.lexer.peek() {
-                Some(Token::TestBracketClose) => {
-                    self.lexer.next(); // consume ']'
-                    break;
-                }
-                Some(Token::File) => {
-                    expression_parts.push("-f".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Directory) => {
-                    expression_parts.push("-d".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Exists) => {
-                    expression_parts.push("-e".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Readable) => {
-                    expression_parts.push("-r".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Writable) => {
-                    expression_parts.push("-w".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Executable) => {
-                    expression_parts.push("-x".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Size) => {
-                    expression_parts.push("-s".to_string());
-                    self.lexer.next();
-                }
-                Some(Token::Symlink) => {
-                    expression_parts.push("-L".to_string());
-                    self.lexer.next();
-                }
-                                 Some(Token::Identifier) => {
-                     let identifier = self.lexer.get_identifier_text()?;
-                     expression_parts.push(identifier);
-                     self.lexer.next(); // consume the identifier token
-                 }
-                 Some(Token::DoubleQuotedString) | Some(Token::SingleQuotedString) => {
-                     let string_text = self.lexer.get_string_text()?;
-                     expression_parts.push(string_text);
-                     self.lexer.next(); // consume the string token
-                 }
-                Some(Token::Space) | Some(Token::Tab) => {
-                    self.lexer.next(); // skip whitespace
-                }
-                None => {
-                    return Err(ParserError::InvalidSyntax("Unexpected end of input in test expression".to_string()));
-                }
-                _ => {
-                    return Err(ParserError::InvalidSyntax("Unexpected token in test expression".to_string()));
-                }
-            }
-        }
-        
-        let expression = expression_parts.join(" ");
-        
-        Ok(Command::TestExpression(TestExpression {
-            expression,
-            modifiers: TestModifiers {
-                extglob: false,
-                nocasematch: false,
-                globstar: false,
-                nullglob: false,
-                failglob: false,
-                dotglob: false,
-            },
-        }))
-    }
-
     fn parse_variable_expansion(&mut self) -> Result<Word, ParserError> {
         // Check what type of variable expansion we have
         match self.lexer.peek() {
@@ -775,7 +770,7 @@ impl Parser {
                 // Expect an identifier after the $
                 if let Some(Token::Identifier) = self.lexer.peek() {
                     let var_name = self.lexer.get_identifier_text()?;
-                    Ok(Word::Variable(var_name))
+                    Ok(Word::Variable(var_name, Span::default()))
                 } else {
                     Err(ParserError::InvalidSyntax("Expected identifier after $ in variable expansion".to_string()))
                 }
@@ -825,7 +820,7 @@ impl Parser {
                 Ok(Word::ParameterExpansion(ParameterExpansion {
                     variable: var_name,
                     operator: ParameterExpansionOperator::None,
-                }))
+                }, Span::default()))
             }
             _ => {
                 Err(ParserError::InvalidSyntax("Expected $ or ${ in variable expansion".to_string()))
@@ -836,68 +831,8 @@ impl Parser {
     fn parse_arithmetic_expression(&mut self) -> Result<Word, ParserError> {
         // Handle arithmetic expressions like $((i + 1))
         // The lexer should have already consumed the opening $( tokens
-        // We need to parse the content until we find the closing ))
-        
-        let mut expression_parts = Vec::new();
-        
-        loop {
-            match self.lexer.peek() {
-                Some(Token::ArithmeticEvalClose) => {
-                    // Found the closing )), consume it and break
-                    self.lexer.next();
-                    break;
-                }
-                Some(Token::Identifier) => {
-                    // Variable reference like 'i'
-                    let var_name = self.lexer.get_identifier_text()?;
-                    expression_parts.push(var_name);
-                    self.lexer.next(); // consume the identifier token
-                }
-                Some(Token::Number) => {
-                    // Number like '1'
-                    let num_text = self.lexer.get_number_text()?;
-                    expression_parts.push(num_text);
-                    self.lexer.next(); // consume the number token
-                }
-                Some(Token::Plus) => {
-                    // Plus operator
-                    self.lexer.next();
-                    expression_parts.push("+".to_string());
-                }
-                Some(Token::Minus) => {
-                    // Minus operator
-                    self.lexer.next();
-                    expression_parts.push("-".to_string());
-                }
-                Some(Token::Star) => {
-                    // Multiplication operator
-                    self.lexer.next();
-                    expression_parts.push("*".to_string());
-                }
-                Some(Token::Slash) => {
-                    // Division operator
-                    self.lexer.next();
-                    expression_parts.push("/".to_string());
-                }
-                Some(Token::Space) | Some(Token::Tab) => {
-                    // Skip whitespace
-                    self.lexer.next();
-                }
-                None => {
-                    return Err(ParserError::InvalidSyntax("Unexpected end of input in arithmetic expression".to_string()));
-                }
-                _ => {
-                    return Err(ParserError::InvalidSyntax("Unexpected token in arithmetic expression".to_string()));
-                }
-            }
-        }
-        
-        // Create an arithmetic expression word
-        let expression = expression_parts.join("");
-        Ok(Word::Arithmetic(ArithmeticExpression {
-            expression,
-            tokens: vec![], // For now, leave tokens empty
-        }))
+        let expression = self.lexer.arithmetic_text()?;
+        Ok(Word::Arithmetic(arithmetic_expression(&expression), Span::default()))
     }
 
 
@@ -914,7 +849,7 @@ impl Parser {
         }
     }
 
-    fn get_current_shopt_state(&self) -> TestModifiers {
+    pub fn get_current_shopt_state(&self) -> TestModifiers {
         self.shopt_state.to_owned()
     }
 }
@@ -933,3 +868,235 @@ pub fn parse(input: &str) -> Result<Vec<Command>, ParserError> {
     parser.parse()
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_with_recovery() {
+        let input = "echo a\nif [[ ]]; then echo b; fi\necho c\nfor ; do x; done\necho d\n";
+        let (commands, errors) = Parser::new(input).parse_with_recovery();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].location(), Some((4, 5)));
+        let skipped: Vec<&str> = commands.iter().filter_map(|c| match c {
+            Command::Error(text, _) => Some(text.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(skipped, vec!["if [[ ]]; then echo b; fi", "for ; do x; done"]);
+        assert_eq!(commands.len(), 5);
+    }
+
+    #[test]
+    fn test_recovery_inside_bodies() {
+        let input = "if true; then\n  echo a\n  [[ ]]\n  echo kept\nfi\nwhile false; do\n  for ; do x; done\n  echo also\ndone\ncase x in\n  x) [[ ]] ;;\n  *) echo other ;;\nesac\n";
+        let (commands, errors) = Parser::new(input).parse_with_recovery();
+        let locations: Vec<Option<(usize, usize)>> = errors.iter().map(|e| e.location()).collect();
+        assert_eq!(locations, vec![Some((3, 3)), Some((7, 7)), Some((11, 6))]);
+        assert_eq!(commands.len(), 3);
+        let Command::If(if_stmt, _) = &commands[0] else { panic!("expected if, got {:?}", commands[0]) };
+        let Command::Block(block, _) = &*if_stmt.then_branch else { panic!("expected block, got {:?}", if_stmt.then_branch) };
+        assert!(matches!(&block.commands[..], [Command::Simple(..), Command::Error(text, _), Command::Simple(..)] if text == "[[ ]]"));
+        let Command::While(while_loop, _) = &commands[1] else { panic!("expected while, got {:?}", commands[1]) };
+        assert!(matches!(&while_loop.body.commands[..], [Command::Error(text, _), Command::Simple(..)] if text == "for ; do x; done"));
+        assert!(matches!(commands[2], Command::Case(..)));
+    }
+
+    #[test]
+    fn test_parse_fd_redirects() {
+        let input = "cmd 2>&1 3>&- &>out >|clobber {fd}>log <&4-\n";
+        let commands = Parser::new(input).parse().unwrap();
+        let Command::Redirect(redirect_cmd, _) = &commands[0] else {
+            panic!("expected redirect command, got {:?}", commands[0]);
+        };
+        let summary: Vec<(Option<i32>, Option<&str>, &RedirectOperator, String)> = redirect_cmd.redirects.iter()
+            .map(|r| (r.fd, r.fd_var.as_deref(), &r.operator, r.target.to_string()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some(2), None, &RedirectOperator::DuplicateOutput, "1".to_string()),
+            (Some(3), None, &RedirectOperator::Close, "-".to_string()),
+            (None, None, &RedirectOperator::OutputAll, "out".to_string()),
+            (None, None, &RedirectOperator::OutputClobber, "clobber".to_string()),
+            (None, Some("fd"), &RedirectOperator::Output, "log".to_string()),
+            (None, None, &RedirectOperator::DuplicateInput, "4-".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_exec_redirects() {
+        let commands = Parser::new("exec 3>log.txt\nexec 3>&-\n").parse().unwrap();
+        for command in &commands {
+            let Command::Redirect(redirect_cmd, _) = command else {
+                panic!("expected redirect command, got {:?}", command);
+            };
+            assert!(matches!(&*redirect_cmd.command, Command::BuiltinCommand(cmd, _) if cmd.name == "exec" && cmd.args.is_empty()));
+            assert_eq!(redirect_cmd.redirects[0].fd, Some(3));
+        }
+    }
+
+    #[test]
+    fn test_parse_heredoc_quoting() {
+        let commands = Parser::new("cat <<'EOF'\n$x it's\nEOF\ncat <<-EOF\n\t$x\n\tEOF\n").parse().unwrap();
+        let heredocs: Vec<&Heredoc> = commands.iter().filter_map(|command| match command {
+            Command::Redirect(redirect_cmd, _) => redirect_cmd.redirects[0].heredoc.as_ref(),
+            _ => None,
+        }).collect();
+        assert_eq!(heredocs.len(), 2);
+        assert!(heredocs[0].quoted);
+        assert!(matches!(&heredocs[0].body, Word::Literal(text, _) if text == "$x it's\n"));
+        assert!(!heredocs[1].quoted);
+        assert_eq!(heredocs[1].text, "\t$x\n");
+        let Word::StringInterpolation(interp, _) = &heredocs[1].body else {
+            panic!("expected an expanding body, got {:?}", heredocs[1].body);
+        };
+        assert_eq!(interp.parts, vec![StringPart::Variable("x".to_string()), StringPart::Literal("\n".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_reserved_words_as_arguments() {
+        let commands = Parser::new("grep -e if file\ncase $x in\n  in) echo done ;;\nesac\n").parse().unwrap();
+        let Command::Simple(grep, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        assert_eq!(grep.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(), ["-e", "if", "file"]);
+        let Command::Case(case, _) = &commands[1] else {
+            panic!("expected case statement, got {:?}", commands[1]);
+        };
+        assert_eq!(case.cases.len(), 1);
+        assert_eq!(case.cases[0].patterns[0].to_string(), "in");
+    }
+
+    #[test]
+    fn test_parse_equals_and_status_as_arguments() {
+        let commands = Parser::new("echo a=b x==y =z $?\nrc=$?\n").parse().unwrap();
+        let Command::Simple(echo, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        assert_eq!(echo.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(), ["a=b", "x==y", "=z", "$?"]);
+        let Command::Simple(assignment, _) = &commands[1] else {
+            panic!("expected simple command, got {:?}", commands[1]);
+        };
+        assert_eq!(assignment.env_vars[0].value, Word::Variable("?".to_string(), Span::default()));
+    }
+
+    #[test]
+    fn test_parse_parameter_expansions() {
+        let commands = Parser::new("echo ${#s} ${s: -3:2} ${s/#a/b} ${s-d} ${s:+a} ${s~} ${!pre*} ${s@Q} ${s}\n").parse().unwrap();
+        let Command::Simple(cmd, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        let operators: Vec<_> = cmd.args.iter().map(|arg| match arg {
+            Word::ParameterExpansion(pe, _) => Some(pe.operator.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(operators[..8], [
+            Some(ParameterExpansionOperator::Length),
+            Some(ParameterExpansionOperator::ArraySlice(" -3".to_string(), Some("2".to_string()))),
+            Some(ParameterExpansionOperator::SubstitutePrefix("a".to_string(), "b".to_string())),
+            Some(ParameterExpansionOperator::DefaultIfUnset("d".to_string())),
+            Some(ParameterExpansionOperator::AlternativeValue("a".to_string())),
+            Some(ParameterExpansionOperator::ToggleCaseFirst),
+            Some(ParameterExpansionOperator::PrefixNames('*')),
+            Some(ParameterExpansionOperator::Transform('Q')),
+        ]);
+        assert!(matches!(&cmd.args[8], Word::Variable(name, _) if name == "s"));
+    }
+
+    #[test]
+    fn test_parse_case_glob_patterns() {
+        let commands = Parser::new("case $x in\n  [[:digit:]]*|?) echo n ;;\n  @(a|b)\"c\") echo ab ;;\n  \\?|:) echo opt ;;\nesac\n").parse().unwrap();
+        let Command::Case(case, _) = &commands[0] else {
+            panic!("expected case statement, got {:?}", commands[0]);
+        };
+        let patterns: Vec<Vec<String>> = case.cases.iter()
+            .map(|clause| clause.patterns.iter().map(|pattern| pattern.to_string()).collect())
+            .collect();
+        assert_eq!(patterns, [vec!["[[:digit:]]*", "?"], vec!["@(a|b)\"c\""], vec!["\\?", ":"]]);
+    }
+
+    #[test]
+    fn test_parse_assignment_values() {
+        let commands = Parser::new("IFS= read -r line\nIFS=: read a b <<< \"$x\"\nPATH=/bin:$HOME\n").parse().unwrap();
+        let Command::Simple(read, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        assert_eq!(read.env_vars[0].value, Word::Literal(String::new(), Span::default()));
+        let Command::Redirect(redirect_cmd, _) = &commands[1] else {
+            panic!("expected redirect, got {:?}", commands[1]);
+        };
+        let Command::Simple(read, _) = redirect_cmd.command.as_ref() else {
+            panic!("expected simple command, got {:?}", redirect_cmd.command);
+        };
+        assert_eq!(read.env_vars[0].value.to_string(), ":");
+        let Command::Simple(assignment, _) = &commands[2] else {
+            panic!("expected simple command, got {:?}", commands[2]);
+        };
+        let Word::StringInterpolation(interp, _) = &assignment.env_vars[0].value else {
+            panic!("expected an interpolated value, got {:?}", assignment.env_vars[0].value);
+        };
+        assert_eq!(interp.parts, vec![StringPart::Literal("/bin:".to_string()), StringPart::Variable("HOME".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_adjacent_parts() {
+        let commands = Parser::new("echo x=$x \"$d\"/f ~/bin\n").parse().unwrap();
+        let Command::Simple(echo, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        assert_eq!(echo.args.len(), 3);
+        let Word::Concatenation(parts, _) = &echo.args[0] else {
+            panic!("expected a concatenation, got {:?}", echo.args[0]);
+        };
+        assert!(matches!(parts.as_slice(), [Word::Literal(text, _), Word::Variable(name, _)] if text == "x=" && name == "x"));
+        assert!(matches!(&echo.args[1], Word::Concatenation(parts, _) if parts.len() == 2));
+        assert!(matches!(&echo.args[2], Word::Literal(text, _) if text == "~/bin"));
+    }
+
+    #[test]
+    fn test_parse_printf_arguments() {
+        let commands = Parser::new("printf -- '%x %.2f\\n' 0x1f 3.14159\n").parse().unwrap();
+        let Command::Simple(printf, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        let args: Vec<String> = printf.args.iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(args, vec!["--", "'%x %.2f\\n'", "0x1f", "3.14159"]);
+    }
+
+    #[test]
+    fn test_parse_set_options() {
+        let commands = Parser::new("set +e\nset +o pipefail\n").parse().unwrap();
+        let args = |command: &Command| match command {
+            Command::BuiltinCommand(set, _) => set.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(),
+            _ => panic!("expected builtin command, got {:?}", command),
+        };
+        assert_eq!(args(&commands[0]), vec!["+e"]);
+        assert_eq!(args(&commands[1]), vec!["+o", "pipefail"]);
+    }
+
+    #[test]
+    fn test_parse_heredoc_queue() {
+        let commands = Parser::new("cat <<A; cat <<B | grep x\none\nA\ntwo\nB\necho ok\n").parse().unwrap();
+        let heredoc_text = |command: &Command| match command {
+            Command::Redirect(redirect_cmd, _) => redirect_cmd.redirects[0].heredoc.as_ref().map(|heredoc| heredoc.text.clone()),
+            _ => None,
+        };
+        assert_eq!(heredoc_text(&commands[0]).as_deref(), Some("one\n"));
+        let Command::Pipeline(pipeline, _) = &commands[1] else {
+            panic!("expected pipeline, got {:?}", commands[1]);
+        };
+        assert_eq!(heredoc_text(&pipeline.commands[0]).as_deref(), Some("two\n"));
+        assert!(matches!(&commands[2], Command::Simple(cmd, _) if matches!(&cmd.name, Word::Literal(name, _) if name == "echo")));
+    }
+
+    #[test]
+    fn test_parse_process_substitution() {
+        let commands = Parser::new("diff <(sort a) >(cat)
+").parse().unwrap();
+        let Command::Simple(cmd, _) = &commands[0] else {
+            panic!("expected simple command, got {:?}", commands[0]);
+        };
+        assert_eq!(cmd.args.len(), 2);
+        assert!(matches!(&cmd.args[0], Word::ProcessSubstitution(_, ProcessDirection::Input, _)));
+        assert!(matches!(&cmd.args[1], Word::ProcessSubstitution(_, ProcessDirection::Output, _)));
+    }
+}
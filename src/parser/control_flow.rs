@@ -4,15 +4,16 @@ use crate::parser::errors::ParserError;
 use crate::parser::utilities::ParserUtilities;
 use crate::parser::commands::Parser;
 use crate::parser::words::parse_word;
-use std::collections::HashMap;
+use crate::parser::arithmetic::arithmetic_expression;
+use crate::parser::test_expressions::parse_test_expression;
 
 // Add the missing parse_word_list function
 fn parse_word_list(parser: &mut Parser) -> Result<Vec<Word>, ParserError> {
     let mut words = Vec::new();
     
     loop {
-        // Skip whitespace and comments
-        parser.lexer.skip_whitespace_and_comments();
+        // Skip whitespace and comments; a newline ends the list
+        parser.lexer.skip_inline_whitespace_and_comments();
         
         // Check for end of list
         if parser.lexer.is_eof() || matches!(parser.lexer.peek(), Some(Token::Semicolon | Token::Newline | Token::CarriageReturn | Token::Done | Token::Fi | Token::Then | Token::Else | Token::ParenClose | Token::BraceClose)) {
@@ -22,9 +23,6 @@ fn parse_word_list(parser: &mut Parser) -> Result<Vec<Word>, ParserError> {
         // Parse the next word
         let word = parse_word(&mut parser.lexer)?;
         words.push(word);
-        
-        // Skip whitespace after the word
-        parser.lexer.skip_whitespace_and_comments();
     }
     
     Ok(words)
@@ -38,17 +36,18 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
     
     // Parse condition - check for test expression first, then arithmetic evaluation
     let condition = if let Some(Token::TestBracket) = parser.lexer.peek() {
-        // Handle test expression like: if [ -f "file.txt" ]; then
-        Box::new(parse_test_expression(&mut parser.lexer)?)
+        // Handle test expressions like: if [ -f "file.txt" ] && [ -r "file.txt" ]; then
+        Box::new(parser.parse_command()?)
     } else if let Some(Token::ArithmeticEval) = parser.lexer.peek() {
         // Handle arithmetic evaluation like: if (( a > b )); then
         let arithmetic_word = parse_arithmetic_expression(parser)?;
+        let span = arithmetic_word.span();
         Box::new(Command::Simple(SimpleCommand {
-            name: Word::Literal("test".to_string()),
+            name: Word::Literal("test".to_string(), span),
             args: vec![arithmetic_word],
             redirects: Vec::new(),
-            env_vars: HashMap::new(),
-        }))
+            env_vars: Vec::new(),
+        }, span))
     } else {
         // Parse as a pipeline to handle && and || operators
         Box::new(parse_pipeline(parser)?)
@@ -77,7 +76,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
         match parser.lexer.peek() {
             Some(Token::Else) | Some(Token::Elif) | Some(Token::Fi) | None => break,
             _ => {
-                let cmd = parser.parse_command()?;
+                let cmd = parser.parse_body_command()?;
                 then_cmds.push(cmd);
                 // Skip separators between commands
                 while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::Semicolon | Token::CarriageReturn)) {
@@ -86,7 +85,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
             }
         }
     }
-    let then_branch = Box::new(Command::Block(Block { commands: then_cmds }));
+    let then_branch = Box::new(branch_block(then_cmds));
     
     // Skip whitespace/newlines before checking for separator
     while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline)) {
@@ -115,7 +114,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
             match parser.lexer.peek() {
                 Some(Token::Fi) | None => break,
                 _ => {
-                    let cmd = parser.parse_command()?;
+                    let cmd = parser.parse_body_command()?;
                     else_cmds.push(cmd);
                     while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::Semicolon | Token::CarriageReturn)) {
                         parser.lexer.next();
@@ -123,7 +122,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
                 }
             }
         }
-        Some(Box::new(Command::Block(Block { commands: else_cmds })))
+        Some(Box::new(branch_block(else_cmds)))
     } else if let Some(Token::Elif) = parser.lexer.peek() {
         // Handle multiple elif statements by building a nested if-else structure
         let mut elif_branches = Vec::new();
@@ -138,17 +137,18 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
             
             // Parse the elif condition
             let elif_condition = if let Some(Token::TestBracket) = parser.lexer.peek() {
-                // Handle test expression like: elif [ -f "file.txt" ]; then
-                Box::new(parse_test_expression(&mut parser.lexer)?)
+                // Handle test expressions like: elif [ -f "file.txt" ]; then
+                Box::new(parser.parse_command()?)
             } else if let Some(Token::ArithmeticEval) = parser.lexer.peek() {
                 // Handle arithmetic evaluation like: elif (( a == b )); then
                             let arithmetic_word = parse_arithmetic_expression(parser)?;
+            let span = arithmetic_word.span();
             Box::new(Command::Simple(SimpleCommand {
-                name: Word::Literal("test".to_string()),
+                name: Word::Literal("test".to_string(), span),
                 args: vec![arithmetic_word],
                 redirects: Vec::new(),
-                env_vars: HashMap::new(),
-            }))
+                env_vars: Vec::new(),
+            }, span))
         } else {
             // Parse as a pipeline to handle && and || operators
             Box::new(parse_pipeline(parser)?)
@@ -177,7 +177,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
                 match parser.lexer.peek() {
                     Some(Token::Else) | Some(Token::Elif) | Some(Token::Fi) | None => break,
                     _ => {
-                        let cmd = parser.parse_command()?;
+                        let cmd = parser.parse_body_command()?;
                         elif_then_cmds.push(cmd);
                         // Skip separators between commands
                         while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::Semicolon | Token::CarriageReturn)) {
@@ -186,7 +186,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
                     }
                 }
             }
-            let elif_then_branch = Box::new(Command::Block(Block { commands: elif_then_cmds }));
+            let elif_then_branch = Box::new(branch_block(elif_then_cmds));
             
             elif_branches.push((elif_condition, elif_then_branch));
         }
@@ -203,7 +203,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
                 match parser.lexer.peek() {
                     Some(Token::Fi) | None => break,
                     _ => {
-                        let cmd = parser.parse_command()?;
+                        let cmd = parser.parse_body_command()?;
                         else_cmds.push(cmd);
                         while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::Semicolon | Token::CarriageReturn)) {
                             parser.lexer.next();
@@ -211,7 +211,7 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
                     }
                 }
             }
-            Some(Box::new(Command::Block(Block { commands: else_cmds })))
+            Some(Box::new(branch_block(else_cmds)))
         } else {
             None
         };
@@ -221,11 +221,12 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
         
         // Build from the last elif to the first
         for (condition, then_branch) in elif_branches.into_iter().rev() {
+            let span = condition.span().to(&then_branch.span());
             current_else_branch = Some(Box::new(Command::If(IfStatement {
                 condition,
                 then_branch,
                 else_branch: current_else_branch,
-            })));
+            }, span)));
         }
         
         current_else_branch
@@ -244,7 +245,50 @@ pub fn parse_if_statement(parser: &mut Parser) -> Result<Command, ParserError> {
         condition,
         then_branch,
         else_branch,
-    }))
+    }, Span::default()))
+}
+
+/// Parses one pattern of a case clause. A pattern with a bracket expression,
+/// an extglob group, an escape or a `:` is kept as written, quotes and all,
+/// for the generators to match as a whole; any other pattern is an ordinary
+/// word.
+fn parse_case_pattern(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    let (start, _) = lexer.get_span().ok_or(ParserError::UnexpectedEOF)?;
+    let text = &lexer.input[start..lexer.end];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut glob = false;
+    let mut len = text.len();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                // An escaped character is matched literally, as in `\?)`
+                glob |= quote.is_none();
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[' | '?' | ':') => glob = true,
+            (None, '(') => {
+                depth += 1;
+                glob = true;
+            }
+            (None, ')') if depth > 0 => depth -= 1,
+            (None, '|' | ')' | ' ' | '\t' | '\n' | ';' | '&' | '<' | '>') if depth == 0 => {
+                len = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    if !glob {
+        return parse_word(lexer);
+    }
+    let pattern = text[..len].to_string();
+    lexer.skip_to(start + len);
+    Ok(Word::Literal(pattern, Span::default()))
 }
 
 pub fn parse_case_statement(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -280,13 +324,13 @@ pub fn parse_case_statement(parser: &mut Parser) -> Result<Command, ParserError>
                 let mut patterns = Vec::new();
                 
                 // Parse first pattern
-                patterns.push(parse_word(&mut parser.lexer)?);
+                patterns.push(parse_case_pattern(&mut parser.lexer)?);
                 
                 // Parse additional patterns separated by '|'
                 while matches!(parser.lexer.peek(), Some(Token::Pipe)) {
                     parser.lexer.next(); // consume '|'
                     parser.lexer.skip_whitespace_and_comments();
-                    patterns.push(parse_word(&mut parser.lexer)?);
+                    patterns.push(parse_case_pattern(&mut parser.lexer)?);
                 }
                 
                 // Expect closing parenthesis as part of the case pattern
@@ -307,7 +351,7 @@ pub fn parse_case_statement(parser: &mut Parser) -> Result<Command, ParserError>
                         Some(Token::Esac) => break,
                         None => return Err(ParserError::UnexpectedEOF),
                         _ => {
-                            let cmd = parser.parse_command()?;
+                            let cmd = parser.parse_body_command()?;
                             body.push(cmd);
                             // Skip separators between commands
                             while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::Semicolon | Token::CarriageReturn)) {
@@ -330,21 +374,17 @@ pub fn parse_case_statement(parser: &mut Parser) -> Result<Command, ParserError>
     // Consume 'esac'
     parser.lexer.consume(Token::Esac)?;
     
-    Ok(Command::Case(CaseStatement { word, cases }))
+    Ok(Command::Case(CaseStatement { word, cases }, Span::default()))
 }
 
 pub fn parse_while_loop(parser: &mut Parser) -> Result<Command, ParserError> {
-    parser.lexer.consume(Token::While)?;
+    // `until` loops share the syntax of `while` loops
+    let until = matches!(parser.lexer.peek(), Some(Token::Until));
+    parser.lexer.consume(if until { Token::Until } else { Token::While })?;
     // Skip whitespace after 'while'
     parser.lexer.skip_whitespace_and_comments();
-    // Parse condition - check for test expression first
-    let condition = if let Some(Token::TestBracket) = parser.lexer.peek() {
-        // Handle test expression like: while [ $i -lt 10 ]; do
-        Box::new(parse_test_expression(&mut parser.lexer)?)
-    } else {
-        // Parse as a regular command
-        Box::new(parser.parse_command()?)
-    };
+    // Parse condition
+    let condition = Box::new(parser.parse_command()?);
 
     // Optional separator after condition (semicolon or newline) and skip whitespace
     match parser.lexer.peek() {
@@ -377,7 +417,7 @@ pub fn parse_while_loop(parser: &mut Parser) -> Result<Command, ParserError> {
             _ => {
                 // Parse and add command to body
                 let pre_pos = parser.lexer.current_position();
-                let command = parser.parse_command()?;
+                let command = parser.parse_body_command()?;
                 body_commands.push(command);
                 if parser.lexer.current_position() == pre_pos {
                     if parser.lexer.next().is_none() { break; }
@@ -406,18 +446,24 @@ pub fn parse_while_loop(parser: &mut Parser) -> Result<Command, ParserError> {
     parser.lexer.consume(Token::Done)?;
     
     let body = Block { commands: body_commands };
-    Ok(Command::While(WhileLoop { condition, body }))
+    Ok(Command::While(WhileLoop { condition, body, until }, Span::default()))
 }
 
 pub fn parse_for_loop(parser: &mut Parser) -> Result<Command, ParserError> {
-    parser.lexer.consume(Token::For)?;
+    // `select` menus share the syntax of `for` loops
+    let select = matches!(parser.lexer.peek(), Some(Token::Select));
+    parser.lexer.consume(if select { Token::Select } else { Token::For })?;
     // Allow whitespace/comments after 'for'
     parser.lexer.skip_whitespace_and_comments();
 
+    if !select && matches!(parser.lexer.peek(), Some(Token::ArithmeticEval)) {
+        return parse_arithmetic_for(parser);
+    }
+
     // Variable name
     let variable = match parser.lexer.peek() {
         Some(Token::Identifier) => parser.lexer.get_identifier_text()?,
-        Some(t) => return Err(ParserError::UnexpectedToken { token: t.clone(), line: 1, col: 1 }),
+        Some(_) => return Err(parser.lexer.unexpected_token()),
         None => return Err(ParserError::UnexpectedEOF),
     };
 
@@ -455,6 +501,28 @@ pub fn parse_for_loop(parser: &mut Parser) -> Result<Command, ParserError> {
         Vec::new()
     };
 
+    let body = parse_do_group(parser)?;
+
+    // Skip whitespace after 'done' before checking for pipe
+    parser.lexer.skip_whitespace_and_comments();
+    
+    // Check if there's a pipeline after the for loop
+    let mut final_command = if select {
+        Command::Select(SelectLoop { variable, items, body }, Span::default())
+    } else {
+        Command::For(ForLoop { variable, items, body }, Span::default())
+    };
+    
+    // If there's a pipe after 'done', parse the pipeline
+    if let Some(Token::Pipe) = parser.lexer.peek() {
+        final_command = parse_pipeline_from_command(&mut parser.lexer, final_command)?;
+    }
+    
+    Ok(final_command)
+}
+
+/// The `do ... done` body shared by `for`, `select` and arithmetic `for` loops.
+fn parse_do_group(parser: &mut Parser) -> Result<Block, ParserError> {
     // Allow whitespace/newlines/comments before 'do'
     while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment | Token::Newline | Token::CarriageReturn)) {
         parser.lexer.next();
@@ -493,7 +561,7 @@ pub fn parse_for_loop(parser: &mut Parser) -> Result<Command, ParserError> {
         
         // Parse command in body
         let pre_pos = parser.lexer.current_position();
-        let command = parser.parse_command()?;
+        let command = parser.parse_body_command()?;
         body_commands.push(command);
         if parser.lexer.current_position() == pre_pos {
             if parser.lexer.next().is_none() { break; }
@@ -518,23 +586,32 @@ pub fn parse_for_loop(parser: &mut Parser) -> Result<Command, ParserError> {
     }
 
     parser.lexer.consume(Token::Done)?;
-    
-    // Skip whitespace after 'done' before checking for pipe
-    parser.lexer.skip_whitespace_and_comments();
-    
-    // Check if there's a pipeline after the for loop
-    let mut final_command = Command::For(ForLoop {
-        variable,
-        items,
-        body: Block { commands: body_commands },
-    });
-    
-    // If there's a pipe after 'done', parse the pipeline
-    if let Some(Token::Pipe) = parser.lexer.peek() {
-        final_command = parse_pipeline_from_command(&mut parser.lexer, final_command)?;
+
+    Ok(Block { commands: body_commands })
+}
+
+/// The source text up to the `))` closing an arithmetic expression, which is
+/// consumed. Taking the text verbatim keeps operators like `==` and `++`
+/// intact, as the shell lexer splits them into separate tokens.
+/// `for (( init; condition; step ))`, with the `for` already consumed.
+fn parse_arithmetic_for(parser: &mut Parser) -> Result<Command, ParserError> {
+    parser.lexer.consume(Token::ArithmeticEval)?;
+    let header = parser.lexer.arithmetic_text()?;
+    let parts: Vec<&str> = header.split(';').collect();
+    let [init, condition, step] = parts.as_slice() else {
+        return Err(ParserError::InvalidSyntax(format!("Expected three expressions in for (({}))", header)));
+    };
+    let (init, condition, step) = (arithmetic_expression(init.trim()), arithmetic_expression(condition.trim()), arithmetic_expression(step.trim()));
+
+    // Optional separator before 'do'
+    while matches!(parser.lexer.peek(), Some(Token::Space | Token::Tab | Token::Comment)) {
+        parser.lexer.next();
     }
-    
-    Ok(final_command)
+    if matches!(parser.lexer.peek(), Some(Token::Semicolon)) {
+        parser.lexer.next();
+    }
+    let body = parse_do_group(parser)?;
+    Ok(Command::ArithmeticFor(ArithmeticFor { init, condition, step, body }, Span::default()))
 }
 
 pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -544,10 +621,7 @@ pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
 
     let name = match parser.lexer.peek() {
         Some(Token::Identifier) => parser.lexer.get_identifier_text()?,
-        Some(t) => {
-            let (line, col) = parser.lexer.offset_to_line_col(0);
-            return Err(ParserError::UnexpectedToken { token: t.clone(), line, col });
-        }
+        Some(_) => return Err(parser.lexer.unexpected_token()),
         None => return Err(ParserError::UnexpectedEOF),
     };
 
@@ -582,21 +656,11 @@ pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
                         continue;
                     } else {
                         // Expect comma or closing parenthesis
-                        let (line, col) = parser.lexer.offset_to_line_col(0);
-                        return Err(ParserError::UnexpectedToken { 
-                            token: parser.lexer.peek().unwrap().clone(), 
-                            line, 
-                            col 
-                        });
+                        return Err(parser.lexer.unexpected_token());
                     }
                 }
                 _ => {
-                    let (line, col) = parser.lexer.offset_to_line_col(0);
-                    return Err(ParserError::UnexpectedToken { 
-                        token: parser.lexer.peek().unwrap().clone(), 
-                        line, 
-                        col 
-                    });
+                    return Err(parser.lexer.unexpected_token());
                 }
             }
         }
@@ -620,7 +684,7 @@ pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
         let mut body_commands = Vec::new();
         
         // Parse first command
-        body_commands.push(parser.parse_command()?);
+        body_commands.push(parser.parse_body_command()?);
 
         // Parse additional commands inside the block
         loop {
@@ -632,7 +696,7 @@ pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
                 Some(Token::BraceClose) | None => break,
                 _ => {
                     let pre_pos = parser.lexer.current_position();
-                    let command = parser.parse_command()?;
+                    let command = parser.parse_body_command()?;
                     body_commands.push(command);
                     if parser.lexer.current_position() == pre_pos {
                         if parser.lexer.next().is_none() { break; }
@@ -646,11 +710,11 @@ pub fn parse_function(parser: &mut Parser) -> Result<Command, ParserError> {
         Block { commands: body_commands }
     } else {
         // Fallback: parse next as a single command body
-        let command = parser.parse_command()?;
+        let command = parser.parse_body_command()?;
         Block { commands: vec![command] }
     };
     
-    Ok(Command::Function(Function { name, parameters, body }))
+    Ok(Command::Function(Function { name, parameters, body }, Span::default()))
 }
 
 pub fn parse_posix_function(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -688,21 +752,11 @@ pub fn parse_posix_function(parser: &mut Parser) -> Result<Command, ParserError>
                         continue;
                     } else {
                         // Expect comma or closing parenthesis
-                        let (line, col) = parser.lexer.offset_to_line_col(0);
-                        return Err(ParserError::UnexpectedToken { 
-                            token: parser.lexer.peek().unwrap().clone(), 
-                            line, 
-                            col 
-                        });
+                        return Err(parser.lexer.unexpected_token());
                     }
                 }
                 _ => {
-                    let (line, col) = parser.lexer.offset_to_line_col(0);
-                    return Err(ParserError::UnexpectedToken { 
-                        token: parser.lexer.peek().unwrap().clone(), 
-                        line, 
-                        col 
-                    });
+                    return Err(parser.lexer.unexpected_token());
                 }
             }
         }
@@ -741,7 +795,7 @@ pub fn parse_posix_function(parser: &mut Parser) -> Result<Command, ParserError>
             }
             _ => {
                 // Parse the next command
-                let command = parser.parse_command()?;
+                let command = parser.parse_body_command()?;
                 body_commands.push(command);
             }
         }
@@ -751,7 +805,7 @@ pub fn parse_posix_function(parser: &mut Parser) -> Result<Command, ParserError>
         name, 
         parameters,
         body: Block { commands: body_commands }
-    }))
+    }, Span::default()))
 }
 
 pub fn parse_block(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -785,13 +839,13 @@ pub fn parse_block(parser: &mut Parser) -> Result<Command, ParserError> {
             }
             _ => {
                 // Parse the next command
-                let command = parser.parse_command()?;
+                let command = parser.parse_body_command()?;
                 body_commands.push(command);
             }
         }
     }
     
-    Ok(Command::Block(Block { commands: body_commands }))
+    Ok(Command::Block(Block { commands: body_commands }, Span::default()))
 }
 
 #[allow(dead_code)]
@@ -807,7 +861,7 @@ pub fn parse_break_statement(parser: &mut Parser) -> Result<Command, ParserError
         level = Some(level_text);
     }
     
-    Ok(Command::Break(level))
+    Ok(Command::Break(level, Span::default()))
 }
 
 pub fn parse_continue_statement(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -822,7 +876,7 @@ pub fn parse_continue_statement(parser: &mut Parser) -> Result<Command, ParserEr
         level = Some(level_text);
     }
     
-    Ok(Command::Continue(level))
+    Ok(Command::Continue(level, Span::default()))
 }
 
 pub fn parse_return_statement(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -837,105 +891,41 @@ pub fn parse_return_statement(parser: &mut Parser) -> Result<Command, ParserErro
         return_value = Some(parse_word(&mut parser.lexer)?);
     }
     
-    Ok(Command::Return(return_value))
+    Ok(Command::Return(return_value, Span::default()))
 }
 
 // Placeholder functions - these would need to be implemented based on the actual AST structures
 fn parse_arithmetic_expression(parser: &mut Parser) -> Result<Word, ParserError> {
-    // Handle arithmetic expressions like $((i + 1))
-    // First, consume the opening $(( or $( token
+    // Handle arithmetic conditions like (( i < 10 ))
+    let start = parser.lexer.offset();
     match parser.lexer.peek() {
         Some(Token::Arithmetic) | Some(Token::ArithmeticEval) => {
-            parser.lexer.next(); // consume $(( or $(
+            parser.lexer.next(); // consume $(( or ((
         }
         _ => {
             return Err(ParserError::InvalidSyntax("Expected arithmetic expression start".to_string()));
         }
     }
-    
-    let mut expression_parts = Vec::new();
-    
-    // Simple case: just parse until we find the closing ))
-    loop {
-        match parser.lexer.peek() {
-            Some(Token::ArithmeticEvalClose) => {
-                // Found the closing )), consume it and break
-                parser.lexer.next();
-                break;
-            }
-            Some(Token::Identifier) => {
-                // Variable reference like 'i'
-                let var_name = parser.lexer.get_identifier_text()?;
-                expression_parts.push(var_name);
-                parser.lexer.next(); // consume the identifier token
-            }
-            Some(Token::Number) => {
-                // Number like '1'
-                let num = parser.lexer.get_number_text()?;
-                expression_parts.push(num);
-                parser.lexer.next(); // consume the number token
-            }
-            Some(Token::Plus) => {
-                // Plus operator
-                parser.lexer.next();
-                expression_parts.push("+".to_string());
-            }
-            Some(Token::Minus) => {
-                // Minus operator
-                parser.lexer.next();
-                expression_parts.push("-".to_string());
-            }
-            Some(Token::Star) => {
-                // Multiplication operator
-                parser.lexer.next();
-                expression_parts.push("*".to_string());
-            }
-            Some(Token::Slash) => {
-                // Division operator
-                parser.lexer.next();
-                expression_parts.push("/".to_string());
-            }
-            Some(Token::Space) | Some(Token::Tab) => {
-                // Skip whitespace
-                parser.lexer.next();
-            }
-            Some(Token::Dollar) => {
-                // Handle variable references like $i
-                parser.lexer.next();
-                if let Some(Token::Identifier) = parser.lexer.peek() {
-                    let var_name = parser.lexer.get_identifier_text()?;
-                    expression_parts.push(format!("${}", var_name));
-                } else {
-                    return Err(ParserError::InvalidSyntax("Expected identifier after $ in arithmetic expression".to_string()));
-                }
-            }
-            None => {
-                return Err(ParserError::UnexpectedEOF);
-            }
-            _ => {
-                // For any other token, just consume it and add its text
-                if let Some(text) = parser.lexer.get_current_text() {
-                    expression_parts.push(text);
-                    parser.lexer.next();
-                } else {
-                    break;
-                }
-            }
-        }
-    }
-    
-    let expression = expression_parts.join(" ");
-    
-    Ok(Word::Arithmetic(ArithmeticExpression {
-        expression,
-        tokens: vec![], // TODO: Store actual tokens if needed
-    }))
+    let expression = parser.lexer.arithmetic_text()?;
+    Ok(Word::Arithmetic(arithmetic_expression(expression.trim()), parser.lexer.span_from(start)))
 }
 
 fn parse_pipeline(parser: &mut Parser) -> Result<Command, ParserError> {
     // For control flow constructs, we only need to parse a single command
     // This is used for test conditions in if statements, not for general pipelines
-    parse_simple_command(parser)
+    parser.lexer.skip_whitespace_and_comments();
+    let start = parser.lexer.offset();
+    let command = parse_simple_command(parser)?;
+    Ok(command.with_span(parser.lexer.span_from(start)))
+}
+
+/// The body of an `if` branch, spanning its first to its last command.
+fn branch_block(commands: Vec<Command>) -> Command {
+    let span = match (commands.first(), commands.last()) {
+        (Some(first), Some(last)) => first.span().to(&last.span()),
+        _ => Span::default(),
+    };
+    Command::Block(Block { commands }, span)
 }
 
 pub fn parse_simple_command(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -944,18 +934,19 @@ pub fn parse_simple_command(parser: &mut Parser) -> Result<Command, ParserError>
     
     // Check if this is a test expression first
     if matches!(parser.lexer.peek(), Some(Token::TestBracket)) {
-        return parse_test_expression(&mut parser.lexer);
+        let modifiers = parser.get_current_shopt_state();
+        return parse_test_expression(&mut parser.lexer, modifiers);
     }
     
     let mut args = Vec::new();
     let redirects = Vec::new();
-    let env_vars = HashMap::new();
+    let env_vars = Vec::new();
     
     // Parse the command name
     let name = match parser.lexer.peek() {
         Some(Token::Identifier) => {
             let name_text = parser.lexer.get_identifier_text()?;
-            Word::Literal(name_text)
+            Word::Literal(name_text, Span::default())
         }
         _ => {
             return Err(ParserError::InvalidSyntax("Expected command name".to_string()));
@@ -987,7 +978,7 @@ pub fn parse_simple_command(parser: &mut Parser) -> Result<Command, ParserError>
         args,
         redirects,
         env_vars,
-    }))
+    }, Span::default()))
 }
 
 fn parse_command(parser: &mut Parser) -> Result<Command, ParserError> {
@@ -1000,7 +991,8 @@ fn parse_command(parser: &mut Parser) -> Result<Command, ParserError> {
     
     // Check if this is a test expression
     if matches!(parser.lexer.peek(), Some(Token::TestBracket)) {
-        parse_test_expression(&mut parser.lexer)
+        let modifiers = parser.get_current_shopt_state();
+        parse_test_expression(&mut parser.lexer, modifiers)
     } else if matches!(parser.lexer.peek(), Some(Token::Identifier)) {
         // Check if this is a standalone variable assignment: identifier=value
         let mut pos = 1;
@@ -1017,126 +1009,6 @@ fn parse_command(parser: &mut Parser) -> Result<Command, ParserError> {
     }
 }
 
-fn parse_test_expression(lexer: &mut Lexer) -> Result<Command, ParserError> {
-    use crate::ast::{TestExpression, TestModifiers};
-    
-    // Consume the opening [
-    if !matches!(lexer.peek(), Some(Token::TestBracket)) {
-        return Err(ParserError::InvalidSyntax("Expected '[' for test expression".to_string()));
-    }
-    lexer.next(); // consume '['
-    
-    // Capture the content between [ and ]
-    let mut expression_parts = Vec::new();
-    
-    loop {
-        match lexer.peek() {
-            Some(Token::TestBracketClose) => {
-                lexer.next(); // consume ']'
-                break;
-            }
-            Some(Token::File) => {
-                expression_parts.push("-f".to_string());
-                lexer.next();
-            }
-            Some(Token::Directory) => {
-                expression_parts.push("-d".to_string());
-                lexer.next();
-            }
-            Some(Token::Exists) => {
-                expression_parts.push("-e".to_string());
-                lexer.next();
-            }
-            Some(Token::Readable) => {
-                expression_parts.push("-r".to_string());
-                lexer.next();
-            }
-            Some(Token::Writable) => {
-                expression_parts.push("-w".to_string());
-                lexer.next();
-            }
-            Some(Token::Executable) => {
-                expression_parts.push("-x".to_string());
-                lexer.next();
-            }
-            Some(Token::Size) => {
-                expression_parts.push("-s".to_string());
-                lexer.next();
-            }
-            Some(Token::Symlink) => {
-                expression_parts.push("-L".to_string());
-                lexer.next();
-            }
-            Some(Token::Identifier) => {
-                expression_parts.push(lexer.get_identifier_text()?);
-            }
-            Some(Token::DoubleQuotedString) | Some(Token::SingleQuotedString) => {
-                expression_parts.push(lexer.get_string_text()?);
-            }
-            Some(Token::Dollar) => {
-                // Handle variable references like $i
-                lexer.next(); // consume $
-                if let Some(Token::Identifier) = lexer.peek() {
-                    let var_name = lexer.get_identifier_text()?;
-                    expression_parts.push(format!("${}", var_name));
-                } else {
-                    return Err(ParserError::InvalidSyntax("Expected identifier after $ in test expression".to_string()));
-                }
-            }
-            Some(Token::Number) => {
-                expression_parts.push(lexer.get_number_text()?);
-            }
-            Some(Token::Lt) => {
-                expression_parts.push("-lt".to_string());
-                lexer.next();
-            }
-            Some(Token::Le) => {
-                expression_parts.push("-le".to_string());
-                lexer.next();
-            }
-            Some(Token::Gt) => {
-                expression_parts.push("-gt".to_string());
-                lexer.next();
-            }
-            Some(Token::Ge) => {
-                expression_parts.push("-ge".to_string());
-                lexer.next();
-            }
-            Some(Token::Eq) => {
-                expression_parts.push("-eq".to_string());
-                lexer.next();
-            }
-            Some(Token::Ne) => {
-                expression_parts.push("-ne".to_string());
-                lexer.next();
-            }
-            Some(Token::Space) | Some(Token::Tab) => {
-                lexer.next(); // skip whitespace
-            }
-            None => {
-                return Err(ParserError::InvalidSyntax("Unexpected end of input in test expression".to_string()));
-            }
-            _ => {
-                return Err(ParserError::InvalidSyntax("Unexpected token in test expression".to_string()));
-            }
-        }
-    }
-    
-    let expression = expression_parts.join(" ");
-    
-    Ok(Command::TestExpression(TestExpression {
-        expression,
-        modifiers: TestModifiers {
-            extglob: false,
-            nocasematch: false,
-            globstar: false,
-            nullglob: false,
-            failglob: false,
-            dotglob: false,
-        },
-    }))
-}
-
 fn parse_assignment(parser: &mut Parser) -> Result<Command, ParserError> {
     // Parse a standalone assignment like: var=value or var=$((expr))
     let var_name = parser.lexer.get_identifier_text()?;
@@ -1154,15 +1026,12 @@ fn parse_assignment(parser: &mut Parser) -> Result<Command, ParserError> {
     let value = parse_word(&mut parser.lexer)?;
     
     // Create a simple command that represents the assignment
-    let mut env_vars = HashMap::new();
-    env_vars.insert(var_name.clone(), value);
-    
     Ok(Command::Simple(SimpleCommand {
-        name: Word::Literal("assignment".to_string()), // Placeholder name
+        name: Word::Literal("assignment".to_string(), Span::default()), // Placeholder name
         args: Vec::new(),
         redirects: Vec::new(),
-        env_vars,
-    }))
+        env_vars: vec![Assignment::new(&var_name, value)],
+    }, Span::default()))
 }
 
 fn parse_pipeline_from_command(_lexer: &mut Lexer, _command: Command) -> Result<Command, ParserError> {
@@ -13,5 +13,19 @@ pub enum ParserError {
     UnexpectedEOF,
     #[error("Invalid syntax: {0}")]
     InvalidSyntax(String),
+    #[error("{error} at {line}:{col}")]
+    Located { error: Box<ParserError>, line: usize, col: usize },
+}
+
+impl ParserError {
+    /// The line and column the error was reported at, if it has one.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            ParserError::Lexer(LexerError::UnexpectedChar { line, col, .. })
+            | ParserError::UnexpectedToken { line, col, .. }
+            | ParserError::Located { line, col, .. } => Some((*line, *col)),
+            _ => None,
+        }
+    }
 }
 
@@ -3,6 +3,8 @@
 pub mod commands;
 pub mod control_flow;
 pub mod words;
+pub mod arithmetic;
+pub mod test_expressions;
 pub mod redirects;
 pub mod assignments;
 pub mod utilities;
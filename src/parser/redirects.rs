@@ -2,213 +2,186 @@ use crate::ast::*;
 use crate::lexer::{Lexer, Token};
 use crate::parser::errors::ParserError;
 use crate::parser::utilities::ParserUtilities;
-use crate::parser::words::parse_word;
+use crate::parser::words::{parse_interpolated_text, parse_word};
+
+/// Whether the next tokens start a redirect, counting an `N>` or `{var}>`
+/// descriptor prefix written directly against the operator.
+pub fn at_redirect(lexer: &mut Lexer) -> bool {
+    match lexer.peek() {
+        Some(Token::Number) => is_redirect_operator(lexer.peek_n(1)),
+        Some(Token::BraceOpen) => {
+            matches!(lexer.peek_n(1), Some(Token::Identifier))
+                && matches!(lexer.peek_n(2), Some(Token::BraceClose))
+                && is_redirect_operator(lexer.peek_n(3))
+        }
+        // `<(cmd)` and `>(cmd)` are words, not redirects
+        Some(Token::RedirectIn | Token::RedirectOut) if matches!(lexer.peek_n(1), Some(Token::ParenOpen)) => false,
+        token => is_redirect_operator(token),
+    }
+}
+
+fn is_redirect_operator(token: Option<Token>) -> bool {
+    matches!(token, Some(
+        Token::RedirectIn | Token::RedirectOut | Token::RedirectAppend | Token::RedirectInOut |
+        Token::Heredoc | Token::HeredocTabs | Token::HereString | Token::RedirectOutErr |
+        Token::RedirectInErr | Token::RedirectOutClobber | Token::RedirectAll | Token::RedirectAllAppend
+    ))
+}
 
 pub fn parse_redirect(lexer: &mut Lexer) -> Result<Redirect, ParserError> {
-    let fd = if let Some(Token::Number) = lexer.peek() {
-        let fd_str = lexer.get_number_text()?;
-        Some(fd_str.parse().unwrap_or(0))
-    } else {
-        None
-    };
+    let start = lexer.offset();
+    let mut fd = None;
+    let mut fd_var = None;
+    match lexer.peek() {
+        Some(Token::Number) => {
+            let fd_str = lexer.get_number_text()?;
+            fd = Some(fd_str.parse().unwrap_or(0));
+        }
+        Some(Token::BraceOpen) => {
+            lexer.next();
+            fd_var = Some(lexer.get_identifier_text()?);
+            lexer.next(); // consume }
+        }
+        _ => {}
+    }
     
-    let operator = match lexer.next() {
+    let mut operator = match lexer.next() {
         Some(Token::RedirectIn) => RedirectOperator::Input,
         Some(Token::RedirectOut) => RedirectOperator::Output,
         Some(Token::RedirectAppend) => RedirectOperator::Append,
-        Some(Token::RedirectInOut) => RedirectOperator::Input, // Use Input as fallback
+        Some(Token::RedirectInOut) => RedirectOperator::InputOutput,
         Some(Token::Heredoc) => RedirectOperator::Heredoc,
         Some(Token::HeredocTabs) => RedirectOperator::HeredocTabs,
         Some(Token::HereString) => RedirectOperator::HereString,
-        Some(Token::RedirectOutErr) => RedirectOperator::Output, // Use Output as fallback
-        Some(Token::RedirectInErr) => RedirectOperator::Input, // Use Input as fallback
-        Some(Token::RedirectOutClobber) => RedirectOperator::Output, // Use Output as fallback
-        Some(Token::RedirectAll) => RedirectOperator::Output, // Use Output as fallback
-        Some(Token::RedirectAllAppend) => RedirectOperator::Append, // Use Append as fallback
+        Some(Token::RedirectOutErr) => RedirectOperator::DuplicateOutput,
+        Some(Token::RedirectInErr) => RedirectOperator::DuplicateInput,
+        Some(Token::RedirectOutClobber) => RedirectOperator::OutputClobber,
+        Some(Token::RedirectAll) => RedirectOperator::OutputAll,
+        Some(Token::RedirectAllAppend) => RedirectOperator::AppendAll,
         _ => return Err(ParserError::InvalidSyntax("Invalid redirect operator".to_string())),
     };
+
+    if matches!(operator, RedirectOperator::DuplicateInput | RedirectOperator::DuplicateOutput) {
+        let target = parse_duplicate_target(lexer)?;
+        if target == "-" {
+            // Closing keeps the direction's default descriptor explicit
+            fd = Some(fd.unwrap_or(if matches!(operator, RedirectOperator::DuplicateInput) { 0 } else { 1 }));
+            operator = RedirectOperator::Close;
+        } else if matches!(operator, RedirectOperator::DuplicateOutput) && fd.is_none() && !is_descriptor(&target) {
+            // `>&file` is an old spelling of `&>file`
+            operator = RedirectOperator::OutputAll;
+        }
+        let span = lexer.span_from(start);
+        return Ok(Redirect { fd, fd_var, operator, target, heredoc: None, span });
+    }
     
     // Here-string: '<<< word' often lexes as '<<' '<' then word; accept optional extra '<'
     if matches!(operator, RedirectOperator::Heredoc) {
         if let Some(Token::RedirectIn) = lexer.peek() { 
             lexer.next(); 
+            operator = RedirectOperator::HereString;
         }
     }
     
     // Skip whitespace before target
     lexer.skip_whitespace_and_comments();
 
-    // Process substitution as redirect target, allowing an optional extra '<' before '('
-    // For here-strings, parse the string content as the target
-    let target = if matches!(operator, RedirectOperator::HereString) {
-        // For here-strings, parse the string content that follows
-        parse_word(lexer)?
-    } else if matches!(lexer.peek(), Some(Token::RedirectIn)) && matches!(lexer.peek_n(1), Some(Token::ParenOpen)) {
-        // consume the extra '<' and capture ( ... )
-        lexer.next();
-        Word::Literal(lexer.capture_parenthetical_text()?)
-    } else if matches!(lexer.peek(), Some(Token::ParenOpen)) {
-        Word::Literal(lexer.capture_parenthetical_text()?)
-    } else {
-        parse_word(lexer)?
-    };
+    // For here-strings, the target is the string content; `< <(cmd)` reads
+    // from a process substitution word
+    let target_start = lexer.offset();
+    let target = parse_word(lexer)?;
     
-    // If this is a heredoc, capture lines until the delimiter is found at start of line
-    // If this is a here-string, the target is the string content
-    let heredoc_body = match operator {
+    // A heredoc's lines follow the end of the current line
+    let heredoc = match operator {
         RedirectOperator::Heredoc | RedirectOperator::HeredocTabs => {
-            parse_heredoc(lexer, &target)?
-        }
-        RedirectOperator::HereString => {
-            // For here-strings, the target is the string content
-            // We need to extract the string content from the target
-            match &target {
-                Word::Literal(s) => Some(s.clone()),
-                _ => None,
-            }
+            let delimiter = lexer.span_from(target_start);
+            let delimiter = lexer.get_text(delimiter.start, delimiter.end);
+            Some(parse_heredoc(lexer, &delimiter, matches!(operator, RedirectOperator::HeredocTabs))?)
         }
         _ => None,
     };
 
-    Ok(Redirect { fd, operator, target, heredoc_body })
+    let span = lexer.span_from(start);
+    Ok(Redirect { fd, fd_var, operator, target, heredoc, span })
 }
 
-fn parse_heredoc(lexer: &mut Lexer, target: &Word) -> Result<Option<String>, ParserError> {
-    let delim = match target {
-        Word::Literal(s) => s.clone(),
-        _ => return Err(ParserError::InvalidSyntax("Heredoc delimiter must be a literal string".to_string())),
-    };
-    
-    eprintln!("DEBUG: parse_heredoc called with delimiter: '{}'", delim);
-    let mut body = String::new();
-    let mut found_delim = false;
-    let mut at_line_start = true;
-
-    // Skip to the next newline token
-    while let Some(token) = lexer.peek() {
-        match token {
-            Token::Newline => {
-                eprintln!("DEBUG: Found newline, breaking to start content collection");
-                lexer.next(); // consume the newline
-                break;
-            }
-            _ => {
-                eprintln!("DEBUG: Skipping token: {:?}", token);
-                lexer.next(); // consume other tokens
-            }
+/// The word after `>&` or `<&`: a descriptor number, `-` to close, `N-` to
+/// move, or any other word (a variable holding a descriptor, say).
+fn parse_duplicate_target(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    lexer.skip_inline_whitespace_and_comments();
+    let start = lexer.offset();
+    match lexer.peek() {
+        Some(Token::Minus) => {
+            lexer.next();
+            Ok(Word::Literal("-".to_string(), lexer.span_from(start)))
         }
-    }
-
-    // Collect lines until we find the delimiter at start of line
-    while let Some(token) = lexer.peek() {
-        eprintln!("DEBUG: Processing token: {:?}, at_line_start: {}, pos: {:?}", token, at_line_start, lexer.current_position());
-        match token {
-            Token::Newline => {
-                eprintln!("DEBUG: Found newline in content");
-                lexer.next(); // consume the newline
-                at_line_start = true;
-                body.push('\n');
-            }
-            Token::Identifier => {
-                // This is part of the heredoc content, not a delimiter
-                let word = lexer.get_identifier_text()?;
-                eprintln!("DEBUG: Adding identifier to body: '{}', at_line_start: {}, delimiter: '{}'", word, at_line_start, delim);
-                // Check if this identifier is the delimiter (at start of line)
-                if at_line_start && word == delim {
-                    eprintln!("DEBUG: Found delimiter at start of line, stopping");
-                    found_delim = true;
-                    // Consume the delimiter token to prevent it from being parsed as a separate command
-                    lexer.next();
-                    break;
-                }
-                // Also check if this is the delimiter at the end of content (fallback)
-                if word == delim {
-                    eprintln!("DEBUG: Found delimiter at end of content, stopping");
-                    found_delim = true;
-                    // Consume the delimiter token to prevent it from being parsed as a separate command
-                    lexer.next();
-                    break;
-                }
-                // Add newline before this word if we're not at line start and this is a new word
-                if !at_line_start {
-                    body.push('\n');
-                }
-                body.push_str(&word);
-                at_line_start = false;
-                lexer.next();
-            }
-            Token::Space => {
-                // Add spaces to the body
-                let text = lexer.get_current_text().unwrap_or_default();
-                eprintln!("DEBUG: Adding space token to body: '{}'", text);
-                body.push_str(&text);
-                at_line_start = false;
-                lexer.next();
-            }
-            Token::Tab => {
-                // Add tabs to the body
-                let text = lexer.get_current_text().unwrap_or_default();
-                eprintln!("DEBUG: Adding tab token to body: '{}'", text);
-                body.push_str(&text);
-                at_line_start = false;
-                lexer.next();
-            }
-            _ => {
-                // For any other token, just consume it and add to body
-                let text = lexer.get_current_text().unwrap_or_default();
-                eprintln!("DEBUG: Adding other token to body: '{}'", text);
-                // Only add space before this token if we're not at line start and the previous token was an identifier
-                // This prevents adding spaces between consecutive punctuation tokens
-                if !at_line_start && body.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
-                    body.push(' ');
-                }
-                body.push_str(&text);
-                at_line_start = false;
+        Some(Token::Number) => {
+            let mut text = lexer.get_number_text()?;
+            if matches!(lexer.peek(), Some(Token::Minus)) {
                 lexer.next();
+                text.push('-');
             }
+            Ok(Word::Literal(text, lexer.span_from(start)))
         }
+        _ => parse_word(lexer),
     }
+}
 
-    eprintln!("DEBUG: Final heredoc body: '{}'", body);
-    if found_delim {
-        // Ensure the heredoc body ends with a newline
-        if !body.ends_with('\n') {
-            body.push('\n');
+/// Whether a duplication target names a descriptor rather than a file.
+fn is_descriptor(target: &Word) -> bool {
+    match target {
+        Word::Literal(text, _) => {
+            let digits = text.strip_suffix('-').unwrap_or(text);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
         }
-        // Skip any whitespace after the delimiter
-        lexer.skip_whitespace_and_comments();
-        Ok(Some(body))
-    } else {
-        Ok(Some(String::new()))
+        _ => true,
     }
 }
 
-pub fn parse_process_substitution(lexer: &mut Lexer, is_input: bool) -> Result<Redirect, ParserError> {
-    // Consume the opening < or >
-    lexer.next();
-    
-    // Parse the inner command
-    let inner = lexer.capture_parenthetical_text()?;
-    
-    // Parse the inner command
-    let inner_cmd = parse_command_from_text(lexer, &inner)?;
-    
-    let operator = if is_input {
-        RedirectOperator::ProcessSubstitutionInput(Box::new(inner_cmd))
-    } else {
-        RedirectOperator::ProcessSubstitutionOutput(Box::new(inner_cmd))
+/// Read a heredoc's lines straight from the source, up to the line holding
+/// just the delimiter. They start after the newline that ends the command
+/// line, or after the lines of the heredoc before this one on the same line.
+/// Quoting any part of the delimiter turns expansion off.
+fn parse_heredoc(lexer: &mut Lexer, delimiter: &str, strip_tabs: bool) -> Result<Heredoc, ParserError> {
+    let quoted = delimiter.contains(['\'', '"', '\\']);
+    let delimiter: String = delimiter.chars().filter(|c| !matches!(c, '\'' | '"' | '\\')).collect();
+
+    let newline = lexer.tokens[lexer.current..].iter()
+        .position(|(token, _, _)| matches!(token, Token::Newline))
+        .map(|i| lexer.current + i);
+    let start = match (newline, lexer.heredoc_resume) {
+        (Some(newline), Some((pending, offset))) if pending == newline => offset,
+        (Some(newline), _) => lexer.tokens[newline].2,
+        (None, _) => lexer.end,
     };
-    
-    Ok(Redirect {
-        fd: None,
-        operator,
-        target: Word::Literal("".to_string()), // Not used for process substitution
-        heredoc_body: None,
-    })
-}
 
-// Placeholder function - this would need to be implemented
-fn parse_command_from_text(_lexer: &mut Lexer, _text: &str) -> Result<Command, ParserError> {
-    // TODO: Implement command parsing from text
-    Err(ParserError::InvalidSyntax("Command parsing from text not yet implemented".to_string()))
+    let mut line_start = start;
+    let (end, resume) = loop {
+        if line_start >= lexer.end {
+            break (lexer.end, lexer.end);
+        }
+        let line_end = lexer.input[line_start..lexer.end].find('\n').map_or(lexer.end, |i| line_start + i);
+        let line = &lexer.input[line_start..line_end];
+        let line = if strip_tabs { line.trim_start_matches('\t') } else { line };
+        if line.trim_end_matches('\r') == delimiter {
+            break (line_start, line_end + 1);
+        }
+        line_start = line_end + 1;
+    };
+    let text = lexer.input[start..end].to_string();
+    if let Some(newline) = newline {
+        lexer.skip_heredoc_lines(newline, resume);
+    }
+
+    let lines = if strip_tabs {
+        text.split_inclusive('\n').map(|line| line.trim_start_matches('\t')).collect()
+    } else {
+        text.clone()
+    };
+    let body = if quoted {
+        Word::Literal(lines, Span::default())
+    } else {
+        Word::StringInterpolation(StringInterpolation { parts: parse_interpolated_text(&lines, true) }, Span::default())
+    };
+    Ok(Heredoc { text, quoted, body })
 }
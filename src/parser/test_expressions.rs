@@ -0,0 +1,334 @@
+use crate::ast::*;
+use crate::lexer::{Lexer, Token};
+use crate::parser::errors::ParserError;
+use crate::parser::utilities::ParserUtilities;
+use crate::parser::words::parse_word;
+
+/// Parses `[ ... ]` or `[[ ... ]]`, starting at the opening bracket, into a
+/// `TestExpression` whose condition is a tree of the test's operators.
+pub fn parse_test_expression(lexer: &mut Lexer, modifiers: TestModifiers) -> Result<Command, ParserError> {
+    let start = lexer.offset();
+    let Some((_, _, open_end)) = lexer.tokens.get(lexer.current).cloned() else {
+        return Err(ParserError::UnexpectedEOF);
+    };
+    if !matches!(lexer.peek(), Some(Token::TestBracket)) {
+        return Err(ParserError::InvalidSyntax("Expected '[' for test expression".to_string()));
+    }
+    // `[[` only when the brackets are adjacent: `[ [` is a test of the word `[`
+    let double = matches!(lexer.tokens.get(lexer.current + 1), Some((Token::TestBracket, next, _)) if *next == open_end);
+    let content_start = if double { open_end + 1 } else { open_end };
+
+    let input = lexer.input.clone();
+    let (ranges, close_start, close_end) = split_test_words(&input, content_start, double)?;
+    while !lexer.is_eof() && lexer.offset() < close_end {
+        lexer.next();
+    }
+
+    let words: Vec<TestWord> = ranges.iter()
+        .map(|&(word_start, word_end)| TestWord {
+            text: input[word_start..word_end].to_string(),
+            word: operand_word(lexer, &input, word_start, word_end),
+        })
+        .collect();
+    let condition = TestParser { words: &words, position: 0, double }.parse()?;
+
+    let text = &input[content_start..close_start];
+    let expression = if double { text.to_string() } else { text.trim().to_string() };
+    Ok(Command::TestExpression(TestExpression { expression, condition, modifiers, double_bracket: double }, lexer.span_from(start)))
+}
+
+/// Builds the condition for the arguments of `test` (or of `[`, without the
+/// closing bracket).
+pub fn test_condition_from_words(words: &[Word]) -> Result<TestCondition, ParserError> {
+    let words: Vec<TestWord> = words.iter()
+        .map(|word| TestWord { text: word.to_string(), word: word.clone() })
+        .collect();
+    TestParser { words: &words, position: 0, double: false }.parse()
+}
+
+struct TestWord {
+    /// The word as written, used to recognise operators: a quoted `"-f"` is
+    /// an operand, not an operator.
+    text: String,
+    word: Word,
+}
+
+/// An operand as a `Word`, or as its literal source text if it is more than
+/// the word parser understands as a single word.
+fn operand_word(lexer: &Lexer, input: &str, start: usize, end: usize) -> Word {
+    let mut operand = Lexer::new_range(input, start, end);
+    let (line, col) = lexer.offset_to_line_col(start);
+    let span = Span::new(start, end, line, col);
+    match parse_word(&mut operand) {
+        Ok(word) if operand.is_eof() => word.with_span(span),
+        _ => Word::Literal(input[start..end].to_string(), span),
+    }
+}
+
+/// The byte range of each word of a test, and of its closing bracket.
+type TestWords = (Vec<(usize, usize)>, usize, usize);
+
+/// Splits the text after the opening bracket into words, up to the closing
+/// `]` or `]]`.
+fn split_test_words(input: &str, from: usize, double: bool) -> Result<TestWords, ParserError> {
+    let bytes = input.as_bytes();
+    let close = if double { "]]" } else { "]" };
+    let mut words: Vec<(usize, usize)> = Vec::new();
+    let mut i = from;
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || input[i..].starts_with("\\\n")) {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        if i >= bytes.len() {
+            return Err(ParserError::InvalidSyntax(format!("Expected '{}' to close test expression", close)));
+        }
+        if input[i..].starts_with(close) && bytes.get(i + close.len()).is_none_or(|&b| is_word_end(b)) {
+            return Ok((words, i, i + close.len()));
+        }
+        // The right-hand side of `=~` is a regex, in which `(`, `)` and `|` are not operators
+        let regex = double && words.last().is_some_and(|&(start, end)| &input[start..end] == "=~");
+        let end = if regex {
+            scan_word(bytes, i, true)
+        } else if double && (input[i..].starts_with("&&") || input[i..].starts_with("||")) {
+            i + 2
+        } else {
+            match scan_word(bytes, i, false) {
+                end if end == i => i + 1,
+                end => end,
+            }
+        };
+        words.push((i, end));
+        i = end;
+    }
+}
+
+fn is_word_end(b: u8) -> bool {
+    b.is_ascii_whitespace() || matches!(b, b';' | b'&' | b'|' | b'(' | b')' | b'<' | b'>')
+}
+
+/// The end of the word starting at `start`, skipping over quotes, escapes
+/// and nested expansions.
+fn scan_word(bytes: &[u8], start: usize, regex: bool) -> usize {
+    let mut i = start;
+    let mut depth = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b if b.is_ascii_whitespace() && depth == 0 => break,
+            b'\\' => i += 2,
+            b'\'' => i = skip_quoted(bytes, i, b'\''),
+            b'"' => i = skip_quoted(bytes, i, b'"'),
+            b'`' => i = skip_quoted(bytes, i, b'`'),
+            b'$' if matches!(bytes.get(i + 1), Some(b'(' | b'{')) => i = skip_balanced(bytes, i + 1),
+            // Extended glob groups such as `@(a|b)` are part of the word
+            b'(' if i > start && matches!(bytes[i - 1], b'@' | b'*' | b'+' | b'?' | b'!') => i = skip_balanced(bytes, i),
+            b'(' if regex => {
+                depth += 1;
+                i += 1;
+            }
+            b')' if regex && depth > 0 => {
+                depth -= 1;
+                i += 1;
+            }
+            b'|' if regex => i += 1,
+            b';' | b'&' | b'|' | b'(' | b')' | b'<' | b'>' => break,
+            _ => i += 1,
+        }
+    }
+    i.min(bytes.len())
+}
+
+/// The offset just past the quote that closes the one at `start`.
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' && quote != b'\'' {
+            i += 1;
+        }
+        i += 1;
+    }
+    i + 1
+}
+
+/// The offset just past the bracket that closes the `(` or `{` at `start`.
+fn skip_balanced(bytes: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' | b'{' => depth += 1,
+            b')' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            b'\\' => i += 1,
+            b'\'' | b'"' | b'`' => {
+                i = skip_quoted(bytes, i, bytes[i]);
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+const BINARY_OPERATORS: &[&str] = &[
+    "=", "==", "!=", "<", ">", "=~", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+const UNARY_OPERATORS: &[&str] = &[
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-p", "-r", "-s", "-t", "-u", "-w", "-x",
+    "-G", "-L", "-N", "-O", "-S", "-z", "-n",
+];
+
+/// Recursive descent over the words of a test, lowest precedence first:
+/// `||`, then `&&`, then `!`, then primaries.
+struct TestParser<'a> {
+    words: &'a [TestWord],
+    position: usize,
+    double: bool,
+}
+
+impl TestParser<'_> {
+    fn parse(mut self) -> Result<TestCondition, ParserError> {
+        if self.words.is_empty() {
+            if self.double {
+                return Err(ParserError::InvalidSyntax("Empty [[ ]] test".to_string()));
+            }
+            // `[ ]` is simply false
+            return Ok(TestCondition::Word(Word::Literal(String::new(), Span::default())));
+        }
+        let condition = self.or()?;
+        match self.words.get(self.position) {
+            None => Ok(condition),
+            Some(word) => Err(ParserError::InvalidSyntax(format!("Unexpected '{}' in test expression", word.text))),
+        }
+    }
+
+    /// The word at `offset` from the current position, as an operator. In
+    /// `[ ]`, operators that the shell would otherwise take are escaped.
+    fn operator(&self, offset: usize) -> Option<&str> {
+        let text = self.words.get(self.position + offset)?.text.as_str();
+        if !self.double && matches!(text, "\\(" | "\\)" | "\\<" | "\\>" | "\\!") {
+            Some(&text[1..])
+        } else {
+            Some(text)
+        }
+    }
+
+    fn is_binary(&self, offset: usize) -> bool {
+        self.operator(offset).is_some_and(|op| BINARY_OPERATORS.contains(&op) && (self.double || op != "=~"))
+            && self.position + offset + 1 < self.words.len()
+    }
+
+    fn take(&mut self) -> Result<Word, ParserError> {
+        let word = self.words.get(self.position).ok_or(ParserError::UnexpectedEOF)?;
+        self.position += 1;
+        Ok(word.word.clone())
+    }
+
+    fn or(&mut self) -> Result<TestCondition, ParserError> {
+        let mut left = self.and()?;
+        while self.operator(0) == Some(if self.double { "||" } else { "-o" }) {
+            self.position += 1;
+            let right = self.and()?;
+            left = TestCondition::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<TestCondition, ParserError> {
+        let mut left = self.not()?;
+        while self.operator(0) == Some(if self.double { "&&" } else { "-a" }) {
+            self.position += 1;
+            let right = self.not()?;
+            left = TestCondition::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<TestCondition, ParserError> {
+        // `[ ! = x ]` compares the string `!`
+        if self.operator(0) == Some("!") && !self.is_binary(1) && self.words.len() > self.position + 1 {
+            self.position += 1;
+            return Ok(TestCondition::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<TestCondition, ParserError> {
+        if self.operator(0) == Some("(") && !self.is_binary(1) {
+            self.position += 1;
+            let condition = self.or()?;
+            if self.operator(0) != Some(")") {
+                return Err(ParserError::InvalidSyntax("Expected ')' in test expression".to_string()));
+            }
+            self.position += 1;
+            return Ok(condition);
+        }
+        if self.is_binary(1) {
+            let left = self.take()?;
+            let op = self.operator(0).unwrap_or_default().to_string();
+            self.position += 1;
+            let right = self.take()?;
+            return Ok(TestCondition::Binary(left, op, right));
+        }
+        if let Some(op) = self.operator(0).filter(|op| UNARY_OPERATORS.contains(op)) {
+            if self.position + 1 < self.words.len() {
+                let op = op.to_string();
+                self.position += 1;
+                return Ok(TestCondition::Unary(op, self.take()?));
+            }
+        }
+        Ok(TestCondition::Word(self.take()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(input: &str) -> TestCondition {
+        let mut lexer = Lexer::new(input);
+        match parse_test_expression(&mut lexer, TestModifiers::default()).unwrap() {
+            Command::TestExpression(test_expr, _) => test_expr.condition,
+            other => panic!("expected a test expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_double_bracket_grouping() {
+        let TestCondition::And(left, right) = condition("[[ -f $a && ( $b == x* || -z \"$c\" ) ]]") else {
+            panic!("expected &&");
+        };
+        assert!(matches!(*left, TestCondition::Unary(ref op, Word::Variable(ref name, _)) if op == "-f" && name == "a"));
+        let TestCondition::Or(match_test, empty_test) = *right else { panic!("expected ||") };
+        assert!(matches!(*match_test, TestCondition::Binary(_, ref op, Word::Literal(ref pattern, _)) if op == "==" && pattern == "x*"));
+        assert!(matches!(*empty_test, TestCondition::Unary(ref op, Word::StringInterpolation(..)) if op == "-z"));
+    }
+
+    #[test]
+    fn test_single_bracket_operators() {
+        // A quoted ` = ` is an operand, and `-a` / `-o` are logical operators
+        let TestCondition::Or(left, right) = condition("[ \"a = b\" = x -o ! -d /tmp ]") else {
+            panic!("expected -o");
+        };
+        assert!(matches!(*left, TestCondition::Binary(Word::StringInterpolation(..), ref op, _) if op == "="));
+        assert!(matches!(*right, TestCondition::Not(_)));
+        assert!(matches!(condition("[ -e x -a -n y ]"), TestCondition::And(..)));
+    }
+
+    #[test]
+    fn test_bracket_kind() {
+        let double_bracket = |input: &str| match parse_test_expression(&mut Lexer::new(input), TestModifiers::default()).unwrap() {
+            Command::TestExpression(test_expr, _) => test_expr.double_bracket,
+            other => panic!("expected a test expression, got {:?}", other),
+        };
+        assert!(double_bracket("[[ -n x ]]"));
+        assert!(double_bracket("[[-n x ]]"));
+        assert!(!double_bracket("[ -n x ]"));
+        assert!(!double_bracket("[  -n x ]"));
+    }
+}
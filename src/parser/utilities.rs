@@ -1,3 +1,4 @@
+use crate::ast::Span;
 use crate::lexer::{Lexer, Token};
 use crate::parser::errors::ParserError;
 
@@ -5,8 +6,7 @@ pub trait ParserUtilities {
     fn skip_whitespace_and_comments(&mut self);
     fn skip_inline_whitespace_and_comments(&mut self);
     fn capture_parenthetical_text(&mut self) -> Result<String, ParserError>;
-    fn capture_double_bracket_expression(&mut self) -> Result<String, ParserError>;
-    fn capture_single_bracket_expression(&mut self) -> Result<String, ParserError>;
+    fn arithmetic_text(&mut self) -> Result<String, ParserError>;
     fn get_identifier_text(&mut self) -> Result<String, ParserError>;
     fn get_number_text(&mut self) -> Result<String, ParserError>;
     fn get_raw_token_text(&mut self) -> Result<String, ParserError>;
@@ -16,6 +16,9 @@ pub trait ParserUtilities {
     fn get_span(&mut self) -> Option<(usize, usize)>;
     fn current_position(&mut self) -> usize;
     fn offset_to_line_col(&mut self, offset: usize) -> (usize, usize);
+    fn offset(&mut self) -> usize;
+    fn span_from(&mut self, start: usize) -> Span;
+    fn unexpected_token(&mut self) -> ParserError;
     fn peek(&mut self) -> Option<Token>;
     fn peek_n(&mut self, n: usize) -> Option<Token>;
     fn next(&mut self) -> Option<Token>;
@@ -88,73 +91,35 @@ impl ParserUtilities for Lexer {
         Ok(content)
     }
 
-    fn capture_double_bracket_expression(&mut self) -> Result<String, ParserError> {
-        let mut content = String::new();
-        let mut depth = 2; // Start with depth 2 for [[
-        
-        // Consume the first two [
-        self.next(); // consume first [
-        self.next(); // consume second [
-        
-        while depth > 0 {
-            match self.peek() {
-                Some(Token::TestBracket) => {
-                    depth += 1;
-                    content.push('[');
-                    self.next();
-                }
-                Some(Token::TestBracketClose) => {
-                    depth -= 1;
-                    if depth > 0 {
-                        content.push(']');
-                    }
-                    self.next();
-                }
-                Some(_) => {
-                    if let Some(text) = self.get_current_text() {
-                        content.push_str(&text);
-                    }
-                    self.next();
+    /// The source of an arithmetic expression, read after its opening `((`
+    /// or `$((` up to the matching `))`, which is consumed too. The text is
+    /// sliced from the input, since tokens such as `c--` or `--` don't lex
+    /// the way arithmetic reads them.
+    fn arithmetic_text(&mut self) -> Result<String, ParserError> {
+        // Start right after the opening token: the lexer can drop what
+        // follows it, as with `--c`
+        let start = match self.current.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some((_, _, end)) => *end,
+            None => return Err(ParserError::InvalidSyntax("Expected arithmetic expression start".to_string())),
+        };
+        let bytes = &self.input.as_bytes()[..self.end];
+        let mut depth = 0;
+        let mut i = start;
+        let end = loop {
+            match bytes.get(i) {
+                Some(b'(') => depth += 1,
+                Some(b')') if depth == 0 && bytes.get(i + 1) == Some(&b')') => break i,
+                Some(b')') if depth == 0 => {
+                    return Err(ParserError::InvalidSyntax("Unbalanced ')' in arithmetic expression".to_string()));
                 }
+                Some(b')') => depth -= 1,
+                Some(_) => {}
                 None => return Err(ParserError::UnexpectedEOF),
             }
-        }
-        
-        Ok(content)
-    }
-
-    fn capture_single_bracket_expression(&mut self) -> Result<String, ParserError> {
-        let mut content = String::new();
-        let mut depth = 1; // Start with depth 1 for [
-        
-        // Consume the opening [
-        self.next();
-        
-        while depth > 0 {
-            match self.peek() {
-                Some(Token::TestBracket) => {
-                    depth += 1;
-                    content.push('[');
-                    self.next();
-                }
-                Some(Token::TestBracketClose) => {
-                    depth -= 1;
-                    if depth > 0 {
-                        content.push(']');
-                    }
-                    self.next();
-                }
-                Some(_) => {
-                    if let Some(text) = self.get_current_text() {
-                        content.push_str(&text);
-                    }
-                    self.next();
-                }
-                None => return Err(ParserError::UnexpectedEOF),
-            }
-        }
-        
-        Ok(content)
+            i += 1;
+        };
+        self.skip_to(end + 2);
+        Ok(self.get_text(start, end))
     }
 
     fn get_identifier_text(&mut self) -> Result<String, ParserError> {
@@ -221,6 +186,43 @@ impl ParserUtilities for Lexer {
         Lexer::offset_to_line_col(self, offset)
     }
 
+    /// Byte offset of the next token, or the end of the input.
+    fn offset(&mut self) -> usize {
+        match self.tokens.get(self.current) {
+            Some((_, start, _)) => *start,
+            None => self.tokens.last().map(|(_, _, end)| *end).unwrap_or(0),
+        }
+    }
+
+    /// The span from `start` to the end of the last token consumed, not
+    /// counting any whitespace or comments skipped after it.
+    fn span_from(&mut self, start: usize) -> Span {
+        let mut end = start;
+        for (token, token_start, token_end) in self.tokens[..self.current].iter().rev() {
+            if *token_start < start {
+                break;
+            }
+            if !matches!(token, Token::Space | Token::Tab | Token::Comment | Token::Newline) {
+                end = *token_end;
+                break;
+            }
+        }
+        let (line, col) = Lexer::offset_to_line_col(self, start);
+        Span::new(start, end, line, col)
+    }
+
+    /// An error for the next token, located at that token.
+    fn unexpected_token(&mut self) -> ParserError {
+        match ParserUtilities::peek(self) {
+            Some(token) => {
+                let offset = ParserUtilities::offset(self);
+                let (line, col) = Lexer::offset_to_line_col(self, offset);
+                ParserError::UnexpectedToken { token, line, col }
+            }
+            None => ParserError::UnexpectedEOF,
+        }
+    }
+
     fn peek(&mut self) -> Option<Token> {
         self.tokens.get(self.current).map(|(token, _, _)| token.clone())
     }
@@ -249,16 +251,7 @@ impl ParserUtilities for Lexer {
                 self.next();
                 Ok(())
             } else {
-                // Get the current token's position for accurate error reporting
-                if let Some((_, start, _)) = self.tokens.get(self.current) {
-                    let (line, col) = self.offset_to_line_col(*start);
-                    Err(ParserError::UnexpectedToken { token, line, col })
-                } else {
-                    // Fallback to current position if we can't get the span
-                    let current_pos = self.current_position();
-                    let (line, col) = self.offset_to_line_col(current_pos);
-                    Err(ParserError::UnexpectedToken { token, line, col })
-                }
+                Err(self.unexpected_token())
             }
         } else {
             Err(ParserError::UnexpectedEOF)
@@ -2,16 +2,79 @@ use crate::ast::*;
 use crate::lexer::{Lexer, Token};
 use crate::parser::errors::ParserError;
 use crate::parser::utilities::ParserUtilities;
-use std::collections::HashMap;
+use crate::parser::arithmetic::arithmetic_expression;
 
 pub fn parse_word(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    let word = located(lexer, parse_word_inner)?;
+    lexer.skip_inline_whitespace_and_comments();
+    Ok(word)
+}
+
+/// Parse a word without skipping newlines at the end.
+/// This is used specifically for argument parsing where we want to preserve newlines.
+pub fn parse_word_no_newline_skip(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    located(lexer, parse_word_no_newline_skip_inner)
+}
+
+/// Runs a word parser over every part written with no space between them,
+/// as in `x=$y` or `"$dir"/file`, and stamps the result with the source it
+/// consumed. More than one part gives a `Word::Concatenation`.
+fn located(lexer: &mut Lexer, parse: fn(&mut Lexer) -> Result<Word, ParserError>) -> Result<Word, ParserError> {
+    let start = lexer.offset();
+    let mut parts: Vec<Word> = Vec::new();
+    loop {
+        let part_start = lexer.offset();
+        let part = parse(lexer)?.with_span(lexer.span_from(part_start));
+        match (parts.last_mut(), &part) {
+            // Unquoted text split over several tokens, as in `~/bin`
+            (Some(Word::Literal(text, span)), Word::Literal(next, next_span))
+                if !is_quoted(text) && !is_quoted(next) =>
+            {
+                text.push_str(next);
+                span.end = next_span.end;
+            }
+            _ => parts.push(part),
+        }
+        if matches!(parts.last(), Some(Word::BraceExpansion(..))) || !continues_word(lexer.peek()) {
+            break;
+        }
+    }
+    let span = lexer.span_from(start);
+    if parts.len() == 1 {
+        return Ok(parts.remove(0).with_span(span));
+    }
+    Ok(Word::Concatenation(parts, span))
+}
+
+fn is_quoted(text: &str) -> bool {
+    text.starts_with('\'') || text.starts_with('"')
+}
+
+/// Whether a token carries on the word before it rather than starting one.
+fn continues_word(token: Option<Token>) -> bool {
+    matches!(
+        token,
+        Some(Token::Identifier | Token::Number | Token::OctalNumber | Token::HexNumber | Token::Float | Token::BaseNumber
+            | Token::Slash | Token::Dot | Token::Assign | Token::Colon | Token::Range | Token::Star | Token::Tilde
+            | Token::Minus | Token::Plus | Token::LongOption | Token::CasePattern
+            | Token::DoubleQuotedString | Token::SingleQuotedString | Token::BacktickString
+            | Token::DollarSingleQuotedString | Token::DollarDoubleQuotedString
+            | Token::Dollar | Token::DollarBrace | Token::DollarParen | Token::DollarHashSimple | Token::DollarAtSimple
+            | Token::DollarStarSimple | Token::DollarBraceHash | Token::DollarBraceBang | Token::DollarBraceStar
+            | Token::DollarBraceAt | Token::DollarBraceHashStar | Token::DollarBraceHashAt | Token::DollarBraceBangStar
+            | Token::DollarBraceBangAt | Token::Arithmetic)
+    )
+}
+
+fn parse_word_inner(lexer: &mut Lexer) -> Result<Word, ParserError> {
     // Combine contiguous bare-word tokens (identifiers, numbers, slashes, dots) into a single literal
     // This handles filenames like "file.txt" by combining Identifier + Dot + Identifier
-    if matches!(lexer.peek(), Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::Slash) | Some(Token::Dot)) {
+    // `=` is word text, as in `x=a=b`
+    if matches!(lexer.peek(), Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::HexNumber) | Some(Token::Float) | Some(Token::BaseNumber) | Some(Token::Slash) | Some(Token::Dot) | Some(Token::Assign)) {
         let mut combined = String::new();
         loop {
             match lexer.peek() {
-                Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::Slash) | Some(Token::Dot) => {
+                Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::HexNumber) | Some(Token::Float) | Some(Token::BaseNumber) | Some(Token::Slash) | Some(Token::Dot) | Some(Token::Assign) => {
                     // Append raw token text and consume
                     if let Some(text) = lexer.get_current_text() {
                         combined.push_str(&text);
@@ -23,128 +86,124 @@ pub fn parse_word(lexer: &mut Lexer) -> Result<Word, ParserError> {
                 _ => break,
             }
         }
-        // Skip inline whitespace after consuming the word
-        lexer.skip_inline_whitespace_and_comments();
-        return Ok(Word::Literal(combined));
+        return Ok(Word::Literal(combined, Span::default()));
     }
 
     let result = match lexer.peek() {
-        Some(Token::Identifier) => Ok(Word::Literal(lexer.get_identifier_text()?)),
-        Some(Token::Number) => Ok(Word::Literal(lexer.get_number_text()?)),
-        Some(Token::OctalNumber) => Ok(Word::Literal(lexer.get_raw_token_text()?)),
+        Some(Token::Identifier) => Ok(Word::Literal(lexer.get_identifier_text()?, Span::default())),
+        Some(Token::Number) => Ok(Word::Literal(lexer.get_number_text()?, Span::default())),
+        Some(Token::OctalNumber) => Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default())),
         Some(Token::DoubleQuotedString) => {
             // Always parse as string interpolation for double-quoted strings
             // This handles both strings and strings with variables
             Ok(parse_string_interpolation(lexer)?)
         },
-        Some(Token::SingleQuotedString) => Ok(Word::Literal(lexer.get_string_text()?)),
+        Some(Token::SingleQuotedString) => Ok(Word::Literal(lexer.get_string_text()?, Span::default())),
         Some(Token::BacktickString) => parse_backtick_command_substitution(lexer),
         Some(Token::DollarSingleQuotedString) => Ok(parse_ansic_quoted_string(lexer)?),
         Some(Token::DollarDoubleQuotedString) => Ok(parse_string_interpolation(lexer)?),
         Some(Token::BraceOpen) => Ok(parse_brace_expansion(lexer)?),
-        Some(Token::Source) => {
-            // Treat standalone 'source' as a normal word (e.g., `source file.sh`)
-            lexer.next();
-            Ok(Word::Literal("source".to_string()))
-        }
-        Some(Token::Set) => {
-            // Treat standalone 'set' as a normal word (e.g., `set -euo pipefail`)
-            lexer.next();
-            Ok(Word::Literal("set".to_string()))
+        Some(Token::RedirectIn | Token::RedirectOut) if matches!(lexer.peek_n(1), Some(Token::ParenOpen)) => {
+            parse_process_substitution(lexer)
         }
-        Some(Token::Declare) => {
-            // Treat standalone 'declare' as a normal word (e.g., `declare -a arr`)
+        Some(Token::Colon) => {
+            // The `:` builtin
             lexer.next();
-            Ok(Word::Literal("declare".to_string()))
+            Ok(Word::Literal(":".to_string(), Span::default()))
         }
         Some(Token::Range) => {
             // Treat standalone '..' as a literal (e.g., `cd ..`)
             lexer.next();
-            Ok(Word::Literal("..".to_string()))
+            Ok(Word::Literal("..".to_string(), Span::default()))
         }
         Some(Token::Star) => {
             // Treat standalone '*' as a literal (e.g., `ls *`)
             lexer.next();
-            Ok(Word::Literal("*".to_string()))
+            Ok(Word::Literal("*".to_string(), Span::default()))
         }
         Some(Token::Dot) => {
             // Treat standalone '.' as a literal (e.g., `ls .`)
             lexer.next();
-            Ok(Word::Literal(".".to_string()))
+            Ok(Word::Literal(".".to_string(), Span::default()))
         }
         Some(Token::CasePattern) => {
             // Treat case statement patterns like *.txt as literals.
             // get_raw_token_text() consumes the current token, so do not call next() here.
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Slash) => {
             // Treat standalone '/' as a literal (e.g., `cd /`)
             lexer.next();
-            Ok(Word::Literal("/".to_string()))
+            Ok(Word::Literal("/".to_string(), Span::default()))
         }
         // Test operators
         Some(Token::File) => {
             lexer.next();
-            Ok(Word::Literal("-f".to_string()))
+            Ok(Word::Literal("-f".to_string(), Span::default()))
         }
         Some(Token::Directory) => {
             lexer.next();
-            Ok(Word::Literal("-d".to_string()))
+            Ok(Word::Literal("-d".to_string(), Span::default()))
         }
         Some(Token::Exists) => {
             lexer.next();
-            Ok(Word::Literal("-e".to_string()))
+            Ok(Word::Literal("-e".to_string(), Span::default()))
         }
         Some(Token::Readable) => {
             lexer.next();
-            Ok(Word::Literal("-r".to_string()))
+            Ok(Word::Literal("-r".to_string(), Span::default()))
         }
         Some(Token::Writable) => {
             lexer.next();
-            Ok(Word::Literal("-w".to_string()))
+            Ok(Word::Literal("-w".to_string(), Span::default()))
         }
         Some(Token::Executable) => {
             lexer.next();
-            Ok(Word::Literal("-x".to_string()))
+            Ok(Word::Literal("-x".to_string(), Span::default()))
         }
         Some(Token::Size) => {
             lexer.next();
-            Ok(Word::Literal("-s".to_string()))
+            Ok(Word::Literal("-s".to_string(), Span::default()))
         }
         Some(Token::Symlink) => {
             lexer.next();
-            Ok(Word::Literal("-L".to_string()))
+            Ok(Word::Literal("-L".to_string(), Span::default()))
         }
         Some(Token::TestBracketClose) => {
             lexer.next();
-            Ok(Word::Literal("]".to_string()))
+            Ok(Word::Literal("]".to_string(), Span::default()))
         }
         Some(Token::Tilde) => {
             // Treat standalone '~' as a literal (e.g., `cd ~`)
             lexer.next();
-            Ok(Word::Literal("~".to_string()))
+            Ok(Word::Literal("~".to_string(), Span::default()))
         }
         Some(Token::LongOption) => {
             // Treat long options like --color=always as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::RegexPattern) => {
             // Treat regex patterns as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::RegexMatch) => {
             // Treat regex match operator as literal
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::NameFlag) | Some(Token::MaxDepthFlag) | Some(Token::TypeFlag) => {
             // Treat command-line flags as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Minus) => {
             // Handle minus tokens like -l, -c, etc.
             // Consume the minus and combine with following identifier or number if present
             lexer.next(); // consume the minus
             let mut combined = "-".to_string();
+            // `--`, which ends the options, stays one word
+            while let Some(Token::Minus) = lexer.peek() {
+                lexer.next();
+                combined.push('-');
+            }
             
             // Look ahead to see if there's an identifier or number following
             if let Some(Token::Identifier) = lexer.peek() {
@@ -155,12 +214,21 @@ pub fn parse_word(lexer: &mut Lexer) -> Result<Word, ParserError> {
                 combined.push_str(&number);
             }
             
-            Ok(Word::Literal(combined))
+            Ok(Word::Literal(combined, Span::default()))
+        }
+        Some(Token::Plus) => {
+            // `+e` and `+o` turn shell options off
+            lexer.next();
+            let mut combined = "+".to_string();
+            if let Some(Token::Identifier) = lexer.peek() {
+                combined.push_str(&lexer.get_identifier_text()?);
+            }
+            Ok(Word::Literal(combined, Span::default()))
         }
         Some(Token::Character) | Some(Token::NonZero) | Some(Token::SymlinkH) | Some(Token::PipeFile) | Some(Token::Socket) | Some(Token::Block) | Some(Token::SetGid) | Some(Token::Sticky) | Some(Token::SetUid) | Some(Token::Owned) | Some(Token::GroupOwned) | Some(Token::Modified) | Some(Token::Eq) | Some(Token::Ne) | Some(Token::Lt) | Some(Token::Le) | Some(Token::Gt) | Some(Token::Ge) | Some(Token::Zero) => {
             // Handle test operator tokens like -e, -f, -d, etc.
             // These are already complete flags, just get their text
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Dollar) => Ok(parse_variable_expansion(lexer)?),
         Some(Token::DollarBrace) | Some(Token::DollarParen) | Some(Token::DollarHashSimple) | Some(Token::DollarAtSimple) | Some(Token::DollarStarSimple)
@@ -169,28 +237,22 @@ pub fn parse_word(lexer: &mut Lexer) -> Result<Word, ParserError> {
             => Ok(parse_variable_expansion(lexer)?),
         Some(Token::Arithmetic) | Some(Token::ArithmeticEval) => Ok(parse_arithmetic_expression(lexer)?),
         _ => {
-            let (line, col) = lexer.offset_to_line_col(0);
-            let token = lexer.peek().unwrap_or(Token::Identifier).to_owned();
-            Err(ParserError::UnexpectedToken { token, line, col })
+            Err(lexer.unexpected_token())
         }
     };
     
-    // Skip inline whitespace after consuming the word
-    lexer.skip_inline_whitespace_and_comments();
-    
     result
 }
 
-/// Parse a word without skipping newlines at the end.
-/// This is used specifically for argument parsing where we want to preserve newlines.
-pub fn parse_word_no_newline_skip(lexer: &mut Lexer) -> Result<Word, ParserError> {
+fn parse_word_no_newline_skip_inner(lexer: &mut Lexer) -> Result<Word, ParserError> {
     // Combine contiguous bare-word tokens (identifiers, numbers, slashes, dots) into a single literal
     // This handles filenames like "file.txt" by combining Identifier + Dot + Identifier
-    if matches!(lexer.peek(), Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::Slash) | Some(Token::Dot)) {
+    // `=` and `:` are word text, as in `echo a=b` and `read -d :`
+    if matches!(lexer.peek(), Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::HexNumber) | Some(Token::Float) | Some(Token::BaseNumber) | Some(Token::Slash) | Some(Token::Dot) | Some(Token::Assign) | Some(Token::Colon)) {
         let mut combined = String::new();
         loop {
             match lexer.peek() {
-                Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::Slash) | Some(Token::Dot) => {
+                Some(Token::Identifier) | Some(Token::Number) | Some(Token::OctalNumber) | Some(Token::HexNumber) | Some(Token::Float) | Some(Token::BaseNumber) | Some(Token::Slash) | Some(Token::Dot) | Some(Token::Assign) | Some(Token::Colon) => {
                     // Append raw token text and consume
                     if let Some(text) = lexer.get_current_text() {
                         combined.push_str(&text);
@@ -202,128 +264,119 @@ pub fn parse_word_no_newline_skip(lexer: &mut Lexer) -> Result<Word, ParserError
                 _ => break,
             }
         }
-        // Skip inline whitespace after consuming the word, but NOT newlines
-        lexer.skip_inline_whitespace_and_comments();
-        return Ok(Word::Literal(combined));
+        return Ok(Word::Literal(combined, Span::default()));
     }
 
     let result = match lexer.peek() {
-        Some(Token::Identifier) => Ok(Word::Literal(lexer.get_identifier_text()?)),
-        Some(Token::Number) => Ok(Word::Literal(lexer.get_number_text()?)),
-        Some(Token::OctalNumber) => Ok(Word::Literal(lexer.get_raw_token_text()?)),
+        Some(Token::Identifier) => Ok(Word::Literal(lexer.get_identifier_text()?, Span::default())),
+        Some(Token::Number) => Ok(Word::Literal(lexer.get_number_text()?, Span::default())),
+        Some(Token::OctalNumber) => Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default())),
         Some(Token::DoubleQuotedString) => {
             // Always parse as string interpolation for double-quoted strings
             // This handles both simple strings and strings with variables
             Ok(parse_string_interpolation(lexer)?)
         },
-        Some(Token::SingleQuotedString) => Ok(Word::Literal(lexer.get_string_text()?)),
+        Some(Token::SingleQuotedString) => Ok(Word::Literal(lexer.get_string_text()?, Span::default())),
         Some(Token::BacktickString) => parse_backtick_command_substitution(lexer),
         Some(Token::DollarSingleQuotedString) => Ok(parse_ansic_quoted_string(lexer)?),
         Some(Token::DollarDoubleQuotedString) => Ok(parse_string_interpolation(lexer)?),
         Some(Token::BraceOpen) => Ok(parse_brace_expansion(lexer)?),
-        Some(Token::Source) => {
-            // Treat standalone 'source' as a normal word (e.g., `source file.sh`)
-            lexer.next();
-            Ok(Word::Literal("source".to_string()))
-        }
-        Some(Token::Set) => {
-            // Treat standalone 'set' as a normal word (e.g., `set -euo pipefail`)
-            lexer.next();
-            Ok(Word::Literal("set".to_string()))
-        }
-        Some(Token::Declare) => {
-            // Treat standalone 'declare' as a normal word (e.g., `declare -a arr`)
-            lexer.next();
-            Ok(Word::Literal("declare".to_string()))
+        Some(Token::RedirectIn | Token::RedirectOut) if matches!(lexer.peek_n(1), Some(Token::ParenOpen)) => {
+            parse_process_substitution(lexer)
         }
         Some(Token::Range) => {
             // Treat standalone '..' as a literal (e.g., `cd ..`)
             lexer.next();
-            Ok(Word::Literal("..".to_string()))
+            Ok(Word::Literal("..".to_string(), Span::default()))
         }
         Some(Token::Star) => {
             // Treat standalone '*' as a literal (e.g., `ls *`)
             lexer.next();
-            Ok(Word::Literal("*".to_string()))
+            Ok(Word::Literal("*".to_string(), Span::default()))
         }
         Some(Token::Dot) => {
             // Treat standalone '.' as a literal (e.g., `ls .`)
             lexer.next();
-            Ok(Word::Literal(".".to_string()))
+            Ok(Word::Literal(".".to_string(), Span::default()))
         }
         Some(Token::CasePattern) => {
             // Treat case statement patterns like *.txt as literals.
             // get_raw_token_text() consumes the current token, so do not call next() here.
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Slash) => {
             // Treat standalone '/' as a literal (e.g., `cd /`)
             lexer.next();
-            Ok(Word::Literal("/".to_string()))
+            Ok(Word::Literal("/".to_string(), Span::default()))
         }
         // Test operators
         Some(Token::File) => {
             lexer.next();
-            Ok(Word::Literal("-f".to_string()))
+            Ok(Word::Literal("-f".to_string(), Span::default()))
         }
         Some(Token::Directory) => {
             lexer.next();
-            Ok(Word::Literal("-d".to_string()))
+            Ok(Word::Literal("-d".to_string(), Span::default()))
         }
         Some(Token::Exists) => {
             lexer.next();
-            Ok(Word::Literal("-e".to_string()))
+            Ok(Word::Literal("-e".to_string(), Span::default()))
         }
         Some(Token::Readable) => {
             lexer.next();
-            Ok(Word::Literal("-r".to_string()))
+            Ok(Word::Literal("-r".to_string(), Span::default()))
         }
         Some(Token::Writable) => {
             lexer.next();
-            Ok(Word::Literal("-w".to_string()))
+            Ok(Word::Literal("-w".to_string(), Span::default()))
         }
         Some(Token::Executable) => {
             lexer.next();
-            Ok(Word::Literal("-x".to_string()))
+            Ok(Word::Literal("-x".to_string(), Span::default()))
         }
         Some(Token::Size) => {
             lexer.next();
-            Ok(Word::Literal("-s".to_string()))
+            Ok(Word::Literal("-s".to_string(), Span::default()))
         }
         Some(Token::Symlink) => {
             lexer.next();
-            Ok(Word::Literal("-L".to_string()))
+            Ok(Word::Literal("-L".to_string(), Span::default()))
         }
         Some(Token::TestBracketClose) => {
             lexer.next();
-            Ok(Word::Literal("]".to_string()))
+            Ok(Word::Literal("]".to_string(), Span::default()))
         }
         Some(Token::Tilde) => {
             // Treat standalone '~' as a literal (e.g., `cd ~`)
             lexer.next();
-            Ok(Word::Literal("~".to_string()))
+            Ok(Word::Literal("~".to_string(), Span::default()))
         }
         Some(Token::LongOption) => {
             // Treat long options like --color=always as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::RegexPattern) => {
             // Treat regex patterns as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::RegexMatch) => {
             // Treat regex match operator as literal
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::NameFlag) | Some(Token::MaxDepthFlag) | Some(Token::TypeFlag) => {
             // Treat command-line flags as literals
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Minus) => {
             // Handle minus tokens like -l, -c, etc.
             // Consume the minus and combine with following identifier or number if present
             lexer.next(); // consume the minus
             let mut combined = "-".to_string();
+            // `--`, which ends the options, stays one word
+            while let Some(Token::Minus) = lexer.peek() {
+                lexer.next();
+                combined.push('-');
+            }
             
             // Look ahead to see if there's an identifier or number following
             if let Some(Token::Identifier) = lexer.peek() {
@@ -334,12 +387,21 @@ pub fn parse_word_no_newline_skip(lexer: &mut Lexer) -> Result<Word, ParserError
                 combined.push_str(&number);
             }
             
-            Ok(Word::Literal(combined))
+            Ok(Word::Literal(combined, Span::default()))
+        }
+        Some(Token::Plus) => {
+            // `+e` and `+o` turn shell options off
+            lexer.next();
+            let mut combined = "+".to_string();
+            if let Some(Token::Identifier) = lexer.peek() {
+                combined.push_str(&lexer.get_identifier_text()?);
+            }
+            Ok(Word::Literal(combined, Span::default()))
         }
         Some(Token::Character) | Some(Token::NonZero) | Some(Token::SymlinkH) | Some(Token::PipeFile) | Some(Token::Socket) | Some(Token::Block) | Some(Token::SetGid) | Some(Token::Sticky) | Some(Token::SetUid) | Some(Token::Owned) | Some(Token::GroupOwned) | Some(Token::Modified) | Some(Token::Eq) | Some(Token::Ne) | Some(Token::Lt) | Some(Token::Le) | Some(Token::Gt) | Some(Token::Ge) | Some(Token::Zero) => {
             // Handle test operator tokens like -e, -f, -d, etc.
             // These are already complete flags, just get their text
-            Ok(Word::Literal(lexer.get_raw_token_text()?))
+            Ok(Word::Literal(lexer.get_raw_token_text()?, Span::default()))
         }
         Some(Token::Dollar) => Ok(parse_variable_expansion(lexer)?),
         Some(Token::DollarBrace) | Some(Token::DollarParen) | Some(Token::DollarHashSimple) | Some(Token::DollarAtSimple) | Some(Token::DollarStarSimple)
@@ -348,9 +410,7 @@ pub fn parse_word_no_newline_skip(lexer: &mut Lexer) -> Result<Word, ParserError
             => Ok(parse_variable_expansion(lexer)?),
         Some(Token::Arithmetic) | Some(Token::ArithmeticEval) => Ok(parse_arithmetic_expression(lexer)?),
         _ => {
-            let (line, col) = lexer.offset_to_line_col(0);
-            let token = lexer.peek().unwrap_or(Token::Identifier).to_owned();
-            Err(ParserError::UnexpectedToken { token, line, col })
+            Err(lexer.unexpected_token())
         }
     };
     
@@ -366,324 +426,253 @@ pub fn parse_variable_expansion(lexer: &mut Lexer) -> Result<Word, ParserError>
             lexer.next();
             if let Some(Token::Identifier) = lexer.peek() {
                 let var_name = lexer.get_identifier_text()?;
-                Ok(Word::Variable(var_name))
+                Ok(Word::Variable(var_name, Span::default()))
+            } else if let Some(Token::Question) = lexer.peek() {
+                // `$?`, the status of the last command
+                lexer.next();
+                Ok(Word::Variable("?".to_string(), Span::default()))
             } else {
                 Err(ParserError::InvalidSyntax("Expected identifier after $".to_string()))
             }
         }
         Some(Token::DollarHashSimple) => { 
             lexer.next(); 
-            Ok(Word::Variable("#".to_string()))
+            Ok(Word::Variable("#".to_string(), Span::default()))
         }
         Some(Token::DollarAtSimple) => { 
             lexer.next(); 
-            Ok(Word::Variable("@".to_string()))
+            Ok(Word::Variable("@".to_string(), Span::default()))
         }
         Some(Token::DollarStarSimple) => { 
             lexer.next(); 
-            Ok(Word::Variable("*".to_string()))
+            Ok(Word::Variable("*".to_string(), Span::default()))
         }
-        Some(Token::DollarBrace) => {
-            // Parse ${...} expansions
-            lexer.next(); // consume the token
-            
-            // Check if this is an array access pattern like ${matrix[$i,$j]}
-            if let Some(Token::Identifier) = lexer.peek() {
-                let array_name = lexer.get_identifier_text()?;
-                
-                // Look ahead to see if this is followed by [
-                if let Some(Token::TestBracket) = lexer.peek_n(1) {
-                    // This is an array access, parse it properly
-                    lexer.next(); // consume the identifier
-                    lexer.next(); // consume the [
-                    
-                    // Parse the array index content until we find the closing ]
-                    let mut index_content = String::new();
-                    let mut bracket_depth = 1;
-                    
-                    while bracket_depth > 0 {
-                        if let Some((start, end)) = lexer.get_span() {
-                            let token = lexer.peek();
-                            
-                            match token {
-                                Some(Token::TestBracket) => {
-                                    bracket_depth += 1;
-                                    let text = lexer.get_text(start, end);
-                                    index_content.push_str(&text);
-                                    lexer.next();
-                                }
-                                Some(Token::TestBracketClose) => {
-                                    bracket_depth -= 1;
-                                    if bracket_depth == 0 {
-                                        // Don't consume the closing ] yet, let parse_braced_variable_name handle it
-                                        break;
-                                    } else {
-                                        let text = lexer.get_text(start, end);
-                                        index_content.push_str(&text);
-                                        lexer.next();
-                                    }
-                                }
-                                _ => {
-                                    let text = lexer.get_text(start, end);
-                                    index_content.push_str(&text);
-                                    lexer.next();
-                                }
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    // Now parse the rest of the braced expression to get to the closing }
-                    let rest_content = parse_braced_variable_name(lexer)?;
-                    
-                    // Combine the array name, index, and rest content
-                    let full_content = format!("{}[{}]{}", array_name, index_content, rest_content);
-                    
-                    // Check if this is array syntax first
-                    if full_content.starts_with('#') && full_content.contains('[') && full_content.contains(']') {
-                        // This is ${#arr[@]} - array length
-                        if let Some(bracket_start) = full_content.find('[') {
-                            if let Some(_bracket_end) = full_content.rfind(']') {
-                                let array_name = &full_content[1..bracket_start]; // Remove # prefix
-                                return Ok(Word::MapLength(array_name.to_string()));
-                            }
-                        }
-                    } else if full_content.starts_with('!') && full_content.contains('[') && full_content.contains(']') {
-                        // This is ${!map[@]} - get keys of associative array
-                        if let Some(bracket_start) = full_content.find('[') {
-                            if let Some(_bracket_end) = full_content.rfind(']') {
-                                let map_name = &full_content[1..bracket_start]; // Remove ! prefix
-                                return Ok(Word::MapKeys(map_name.to_string()));
-                            }
-                        }
-                    } else if full_content.contains('[') && full_content.contains(']') {
-                        // This is a map/array access like ${map[foo]} or ${arr[1]}
-                        if let Some(bracket_start) = full_content.find('[') {
-                            if let Some(bracket_end) = full_content.rfind(']') {
-                                let map_name = &full_content[..bracket_start];
-                                let key = &full_content[bracket_start + 1..bracket_end];
-                                
-                                // Special case: if key is "@", this is array iteration
-                                if key == "@" {
-                                    // Check if there's array slicing after the closing brace
-                                    // Look ahead for :offset:length syntax
-                                    if let Some(Token::Colon) = lexer.peek() {
-                                        // This is array slicing like ${arr[@]:start:length}
-                                        return parse_array_slicing(lexer, map_name.to_string());
-                                    }
-                                    return Ok(Word::MapAccess(map_name.to_string(), "@".to_string()));
-                                }
-                                
-                                return Ok(Word::MapAccess(map_name.to_string(), key.to_string()));
-                            }
-                        }
-                    }
-                    
-                    // Check for parameter expansion operators
-                    if full_content.contains(":") {
-                        // Handle array slicing syntax like ${var:offset} or ${var:start:length}
-                        if let Some(colon_pos) = full_content.find(':') {
-                            let var_name = &full_content[..colon_pos];
-                            let slice_part = &full_content[colon_pos + 1..];
-                            
-                            if let Some(second_colon) = slice_part.find(':') {
-                                // This is ${var:start:length} syntax
-                                let offset = &slice_part[..second_colon];
-                                let length = &slice_part[second_colon + 1..];
-                                return Ok(Word::ParameterExpansion(ParameterExpansion {
-                                    variable: var_name.to_string(),
-                                    operator: ParameterExpansionOperator::ArraySlice(offset.to_string(), Some(length.to_string())),
-                                }));
-                            } else {
-                                // This is ${var:offset} syntax
-                                return Ok(Word::ParameterExpansion(ParameterExpansion {
-                                    variable: var_name.to_string(),
-                                    operator: ParameterExpansionOperator::ArraySlice(slice_part.to_string(), None),
-                                }));
-                            }
-                        }
-                    }
-                    
-                    return Ok(Word::Variable(full_content));
-                }
+        Some(Token::DollarBrace) | Some(Token::DollarBraceHash) | Some(Token::DollarBraceBang) | Some(Token::DollarBraceStar)
+        | Some(Token::DollarBraceAt) | Some(Token::DollarBraceHashStar) | Some(Token::DollarBraceHashAt)
+        | Some(Token::DollarBraceBangStar) | Some(Token::DollarBraceBangAt) => parse_braced_parameter(lexer),
+        Some(Token::DollarParen) => {
+            // The `$(` token already holds the opening parenthesis
+            Ok(Word::CommandSubstitution(Box::new(parse_parenthesized_commands(lexer)?), Span::default()))
+        }
+        _ => Err(lexer.unexpected_token()),
+    }
+}
+
+/// `${...}`: the text up to the matching `}` is cut out of the source, as
+/// the tokens inside were lexed without knowing they were in an expansion.
+fn parse_braced_parameter(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    let (start, _) = lexer.get_span().ok_or(ParserError::UnexpectedEOF)?;
+    let end = closing_offset(&lexer.input[start + 1..lexer.end], '{', '}')
+        .map(|len| start + 1 + len)
+        .ok_or_else(|| ParserError::InvalidSyntax("Unterminated ${".to_string()))?;
+    lexer.skip_to(end);
+    Ok(parameter_expansion_word(&lexer.input[start + 2..end - 1]))
+}
+
+/// The length of the parameter name `text` starts with: a variable name,
+/// a positional parameter or a special parameter such as `@`.
+fn parameter_name_len(text: &str) -> usize {
+    match text.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            text.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(text.len())
+        }
+        Some(c) if c.is_ascii_digit() => text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()),
+        Some(c) if "@*#?$!-".contains(c) => 1,
+        _ => 0,
+    }
+}
+
+/// The length of the parameter `text` starts with, including an array
+/// subscript such as `[i]` after a variable name.
+fn parameter_len(text: &str) -> usize {
+    let name = parameter_name_len(text);
+    let starts_with_letter = text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+    if starts_with_letter && text[name..].starts_with('[') {
+        if let Some(len) = closing_offset(&text[name..], '[', ']') {
+            return name + len;
+        }
+    }
+    name
+}
+
+/// Split `name[key]` into the name and the key.
+fn split_subscript(parameter: &str) -> Option<(&str, &str)> {
+    let open = parameter.find('[')?;
+    Some((&parameter[..open], parameter[open + 1..].strip_suffix(']')?))
+}
+
+/// The word for the inside of `${...}`. Anything not understood is kept as
+/// a variable named by the whole text.
+pub fn parameter_expansion_word(inner: &str) -> Word {
+    let expansion = |variable: &str, operator| {
+        Word::ParameterExpansion(ParameterExpansion { variable: variable.to_string(), operator }, Span::default())
+    };
+    let whole = |text: &str| !text.is_empty() && parameter_len(text) == text.len();
+
+    // ${#name}, ${#arr[@]} and ${#@}; `${#}` alone is `$#`
+    if let Some(rest) = inner.strip_prefix('#').filter(|rest| whole(rest)) {
+        return match split_subscript(rest) {
+            Some((name, "@" | "*")) => Word::MapLength(name.to_string(), Span::default()),
+            _ if rest == "@" || rest == "*" => Word::Variable("#".to_string(), Span::default()),
+            _ => expansion(rest, ParameterExpansionOperator::Length),
+        };
+    }
+
+    // ${!map[@]} and ${!prefix*}
+    if let Some(rest) = inner.strip_prefix('!') {
+        if let Some((name, "@" | "*")) = split_subscript(rest).filter(|_| whole(rest)) {
+            return Word::MapKeys(name.to_string(), Span::default());
+        }
+        if let Some(marker) = rest.chars().last().filter(|c| *c == '*' || *c == '@') {
+            let prefix = &rest[..rest.len() - 1];
+            if whole(prefix) && parameter_name_len(prefix) == prefix.len() && !prefix.starts_with(|c: char| c.is_ascii_digit()) {
+                return expansion(prefix, ParameterExpansionOperator::PrefixNames(marker));
             }
-            
-            // Try to parse as a parameter expansion first
-            if let Ok(pe) = parse_parameter_expansion(lexer) {
-                Ok(pe)
-            } else {
-                // Fall back to the old method
-                let var_name = parse_braced_variable_name(lexer)?;
-                
-                // Check if this is array syntax first
-                if var_name.starts_with('#') && var_name.contains('[') && var_name.contains(']') {
-                    // This is ${#arr[@]} - array length
-                    if let Some(bracket_start) = var_name.find('[') {
-                        if let Some(_bracket_end) = var_name.rfind(']') {
-                            let array_name = &var_name[1..bracket_start]; // Remove # prefix
-                            return Ok(Word::MapLength(array_name.to_string()));
-                        }
-                    }
-                } else if var_name.starts_with('!') && var_name.contains('[') && var_name.contains(']') {
-                    // This is ${!map[@]} - get keys of associative array
-                    if let Some(bracket_start) = var_name.find('[') {
-                        if let Some(_bracket_end) = var_name.rfind(']') {
-                            let map_name = &var_name[1..bracket_start]; // Remove ! prefix
-                            return Ok(Word::MapKeys(map_name.to_string()));
-                        }
+        }
+        return Word::Variable(inner.to_string(), Span::default());
+    }
+
+    let len = parameter_len(inner);
+    if len == 0 {
+        return Word::Variable(inner.to_string(), Span::default());
+    }
+    let (parameter, rest) = inner.split_at(len);
+    let subscript = split_subscript(parameter).filter(|_| parameter.ends_with(']'));
+
+    if rest.is_empty() {
+        return match subscript {
+            Some((name, key)) => Word::MapAccess(name.to_string(), key.to_string(), Span::default()),
+            None => Word::Variable(parameter.to_string(), Span::default()),
+        };
+    }
+
+    let operator = match rest {
+        "^^" => Some(ParameterExpansionOperator::UppercaseAll),
+        ",," => Some(ParameterExpansionOperator::LowercaseAll),
+        "^" => Some(ParameterExpansionOperator::UppercaseFirst),
+        "," => Some(ParameterExpansionOperator::LowercaseFirst),
+        "~~" => Some(ParameterExpansionOperator::ToggleCaseAll),
+        "~" => Some(ParameterExpansionOperator::ToggleCaseFirst),
+        "##*/" => Some(ParameterExpansionOperator::Basename),
+        "%/*" => Some(ParameterExpansionOperator::Dirname),
+        _ if rest.len() == 2 && rest.starts_with('@') => {
+            rest.chars().nth(1).map(ParameterExpansionOperator::Transform)
+        }
+        _ => None,
+    };
+    let operator = operator.or_else(|| {
+        let text = |prefix: &str| rest[prefix.len()..].to_string();
+        let substitution = |prefix: &str| {
+            let body = &rest[prefix.len()..];
+            let mut chars = body.char_indices();
+            let mut slash = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
                     }
-                } else if var_name.contains('[') && var_name.contains(']') {
-                    // This is a map/array access like ${map[foo]} or ${arr[1]}
-                    if let Some(bracket_start) = var_name.find('[') {
-                        if let Some(bracket_end) = var_name.rfind(']') {
-                            let map_name = &var_name[..bracket_start];
-                            let key = &var_name[bracket_start + 1..bracket_end];
-                            
-                            // Special case: if key is "@", this is array iteration
-                            if key == "@" {
-                                // Check if there's array slicing after the closing brace
-                                // Look ahead for :offset:length syntax
-                                if let Some(Token::Colon) = lexer.peek() {
-                                    // This is array slicing like ${arr[@]:start:length}
-                                    return parse_array_slicing(lexer, map_name.to_string());
-                                }
-                                return Ok(Word::MapAccess(map_name.to_string(), "@".to_string()));
-                            }
-                            
-                            return Ok(Word::MapAccess(map_name.to_string(), key.to_string()));
-                        }
+                    '/' => {
+                        slash = Some(i);
+                        break;
                     }
+                    _ => {}
                 }
-                
-                // Check if this is a parameter expansion with operators
-                // Check longer patterns first to avoid partial matches
-                if var_name.ends_with("^^") {
-                    let base_var = var_name.trim_end_matches("^^");
-                    Ok(Word::ParameterExpansion(ParameterExpansion {
-                        variable: base_var.to_string(),
-                        operator: ParameterExpansionOperator::UppercaseAll,
-                    }))
-                } else if var_name.ends_with(",,") {
-                    let base_var = var_name.trim_end_matches(",,");
-                    Ok(Word::ParameterExpansion(ParameterExpansion {
-                        variable: base_var.to_string(),
-                        operator: ParameterExpansionOperator::LowercaseAll,
-                    }))
-                } else if var_name.ends_with("^") && !var_name.ends_with("^^") {
-                    let base_var = var_name.trim_end_matches("^");
-                    Ok(Word::ParameterExpansion(ParameterExpansion {
-                        variable: base_var.to_string(),
-                        operator: ParameterExpansionOperator::UppercaseFirst,
-                    }))
-                } else if var_name.ends_with("##*/") {
-                    let base_var = var_name.trim_end_matches("##*/");
-                    Ok(Word::ParameterExpansion(ParameterExpansion {
-                        variable: base_var.to_string(),
-                        operator: ParameterExpansionOperator::Basename,
-                    }))
-                } else if var_name.ends_with("%/*") {
-                    let base_var = var_name.trim_end_matches("%/*");
-                    Ok(Word::ParameterExpansion(ParameterExpansion {
-                        variable: base_var.to_string(),
-                        operator: ParameterExpansionOperator::Dirname,
-                    }))
-                } else if var_name.contains("##") && !var_name.ends_with("##*/") {
-                    let parts: Vec<&str> = var_name.split("##").collect();
-                    if parts.len() == 2 {
-                        Ok(Word::ParameterExpansion(ParameterExpansion {
-                            variable: parts[0].to_string(),
-                            operator: ParameterExpansionOperator::RemoveLongestPrefix(parts[1].to_string()),
-                        }))
-                    } else {
-                        Ok(Word::Variable(var_name))
-                    }
-                } else if var_name.contains("%%") && !var_name.ends_with("%/*") {
-                    let parts: Vec<&str> = var_name.split("%%").collect();
-                    if parts.len() == 2 {
-                        Ok(Word::ParameterExpansion(ParameterExpansion {
-                            variable: parts[0].to_string(),
-                            operator: ParameterExpansionOperator::RemoveLongestSuffix(parts[1].to_string()),
-                        }))
-                    } else {
-                        Ok(Word::Variable(var_name))
-                    }
-                } else if var_name.contains("//") {
-                    let parts: Vec<&str> = var_name.split("//").collect();
-                    if parts.len() == 3 {
-                        Ok(Word::ParameterExpansion(ParameterExpansion {
-                            variable: parts[0].to_string(),
-                            operator: ParameterExpansionOperator::SubstituteAll(parts[1].to_string(), parts[2].to_string()),
-                        }))
-                    } else {
-                        // Check if this is a map access pattern like map[foo]
-                        if var_name.contains('[') && var_name.contains(']') {
-                            if let Some(bracket_start) = var_name.find('[') {
-                                if let Some(bracket_end) = var_name.rfind(']') {
-                                    let map_name = &var_name[..bracket_start];
-                                    let key = &var_name[bracket_start + 1..bracket_end];
-                                    return Ok(Word::MapAccess(map_name.to_string(), key.to_string()));
-                                }
-                            }
-                        }
-                        Ok(Word::Variable(var_name))
-                    }
-                } else if var_name.contains("/") && !var_name.contains("//") {
-                    let parts: Vec<&str> = var_name.split("/").collect();
-                    if parts.len() == 3 {
-                        Ok(Word::ParameterExpansion(ParameterExpansion {
-                            variable: parts[0].to_string(),
-                            operator: ParameterExpansionOperator::SubstituteAll(parts[1].to_string(), parts[2].to_string()),
-                        }))
-                    } else {
-                        // Check if this is a map access pattern like map[foo]
-                        if var_name.contains('[') && var_name.contains(']') {
-                            if let Some(bracket_start) = var_name.find('[') {
-                                if let Some(bracket_end) = var_name.rfind(']') {
-                                    let map_name = &var_name[..bracket_start];
-                                    let key = &var_name[bracket_start + 1..bracket_end];
-                                    return Ok(Word::MapAccess(map_name.to_string(), key.to_string()));
-                                }
-                            }
-                        }
-                        Ok(Word::Variable(var_name))
-                    }
-                } else {
-                    // Check if this is a map access pattern like map[foo]
-                    if var_name.contains('[') && var_name.contains(']') {
-                        if let Some(bracket_start) = var_name.find('[') {
-                            if let Some(bracket_end) = var_name.rfind(']') {
-                                let map_name = &var_name[..bracket_start];
-                                let key = &var_name[bracket_start + 1..bracket_end];
-                                return Ok(Word::MapAccess(map_name.to_string(), key.to_string()));
-                            }
-                        }
+            }
+            match slash {
+                Some(i) => (body[..i].to_string(), body[i + 1..].to_string()),
+                None => (body.to_string(), String::new()),
+            }
+        };
+        Some(match rest {
+            _ if rest.starts_with(":-") => ParameterExpansionOperator::DefaultValue(text(":-")),
+            _ if rest.starts_with(":=") => ParameterExpansionOperator::AssignDefault(text(":=")),
+            _ if rest.starts_with(":?") => ParameterExpansionOperator::ErrorIfUnset(text(":?")),
+            _ if rest.starts_with(":+") => ParameterExpansionOperator::AlternativeValue(text(":+")),
+            _ if rest.starts_with(':') => {
+                // The offset and length are arithmetic, which may hold a `:` of its own
+                let body = &rest[1..];
+                let mut depth = 0;
+                let split = body.char_indices().find(|&(_, c)| {
+                    match c {
+                        '(' | '[' => depth += 1,
+                        ')' | ']' => depth -= 1,
+                        '?' => depth += 1,
+                        ':' if depth > 0 => depth -= 1,
+                        ':' => return true,
+                        _ => {}
                     }
-                    Ok(Word::Variable(var_name))
+                    false
+                });
+                match split {
+                    Some((i, _)) => ParameterExpansionOperator::ArraySlice(body[..i].to_string(), Some(body[i + 1..].to_string())),
+                    None => ParameterExpansionOperator::ArraySlice(body.to_string(), None),
                 }
             }
+            _ if rest.starts_with('-') => ParameterExpansionOperator::DefaultIfUnset(text("-")),
+            _ if rest.starts_with('=') => ParameterExpansionOperator::AssignIfUnset(text("=")),
+            _ if rest.starts_with('?') => ParameterExpansionOperator::ErrorIfNotSet(text("?")),
+            _ if rest.starts_with('+') => ParameterExpansionOperator::AlternativeIfSet(text("+")),
+            _ if rest.starts_with("##") => ParameterExpansionOperator::RemoveLongestPrefix(text("##")),
+            _ if rest.starts_with('#') => ParameterExpansionOperator::RemoveShortestPrefix(text("#")),
+            _ if rest.starts_with("%%") => ParameterExpansionOperator::RemoveLongestSuffix(text("%%")),
+            _ if rest.starts_with('%') => ParameterExpansionOperator::RemoveShortestSuffix(text("%")),
+            _ if rest.starts_with("//") => {
+                let (pattern, replacement) = substitution("//");
+                ParameterExpansionOperator::SubstituteAll(pattern, replacement)
+            }
+            _ if rest.starts_with("/#") => {
+                let (pattern, replacement) = substitution("/#");
+                ParameterExpansionOperator::SubstitutePrefix(pattern, replacement)
+            }
+            _ if rest.starts_with("/%") => {
+                let (pattern, replacement) = substitution("/%");
+                ParameterExpansionOperator::SubstituteSuffix(pattern, replacement)
+            }
+            _ if rest.starts_with('/') => {
+                let (pattern, replacement) = substitution("/");
+                ParameterExpansionOperator::SubstituteFirst(pattern, replacement)
+            }
+            _ => return None,
+        })
+    });
+
+    match (operator, subscript) {
+        // ${arr[@]:offset:length} slices the array
+        (Some(ParameterExpansionOperator::ArraySlice(offset, length)), Some((name, "@" | "*"))) => {
+            Word::ArraySlice(name.to_string(), offset, length, Span::default())
         }
-        Some(Token::DollarParen) => {
-            // Parse $(...) command substitution
-            lexer.next();
-            let command_text = lexer.capture_parenthetical_text()?;
-            // For now, create a simple command as a placeholder
-            // TODO: Parse the command_text into an actual Command
-            let placeholder_cmd = Command::Simple(SimpleCommand {
-                name: Word::Literal("echo".to_string()),
-                args: vec![Word::Literal(command_text)],
-                redirects: Vec::new(),
-                env_vars: HashMap::new(),
-            });
-            Ok(Word::CommandSubstitution(Box::new(placeholder_cmd)))
-        }
-        _ => {
-            let (line, col) = lexer.offset_to_line_col(0);
-            Err(ParserError::UnexpectedToken { token: Token::Identifier, line, col })
-        }
+        (Some(operator), _) => expansion(parameter, operator),
+        (None, _) => Word::Variable(inner.to_string(), Span::default()),
     }
 }
 
+/// `<(cmd)` or `>(cmd)`: the command runs alongside, and the word becomes a
+/// path that reads its output or feeds its input.
+fn parse_process_substitution(lexer: &mut Lexer) -> Result<Word, ParserError> {
+    let direction = match lexer.next() {
+        Some(Token::RedirectIn) => ProcessDirection::Input,
+        _ => ProcessDirection::Output,
+    };
+    let command = parse_parenthesized_commands(lexer)?;
+    Ok(Word::ProcessSubstitution(Box::new(command), direction, Span::default()))
+}
+
+/// Parse the commands between the current token, which ends with `(`, and
+/// the matching `)` in place, so that spans inside point into the original
+/// source.
+fn parse_parenthesized_commands(lexer: &mut Lexer) -> Result<Command, ParserError> {
+    let start = lexer.get_span().map(|(_, end)| end).unwrap_or(0);
+    lexer.capture_parenthetical_text()?;
+    let end = lexer.tokens[lexer.current - 1].1; // start of the closing `)`
+    let inner = Lexer::new_range(&lexer.input, start, end);
+    let mut commands = crate::parser::commands::Parser::new_with_lexer(inner).parse()?;
+    Ok(if commands.len() == 1 {
+        commands.remove(0)
+    } else {
+        Command::Block(Block { commands }, Span::default())
+    })
+}
+
 // Placeholder functions - these would need to be implemented based on the actual AST structures
 fn parse_string_interpolation(lexer: &mut Lexer) -> Result<Word, ParserError> {
     use crate::ast::{StringInterpolation, StringPart};
@@ -698,71 +687,125 @@ fn parse_string_interpolation(lexer: &mut Lexer) -> Result<Word, ParserError> {
         &string_content
     };
     
-    // Parse the string content to extract literal parts and variable references
+    let mut parts = parse_interpolated_text(content, false);
+    if parts.is_empty() {
+        parts.push(StringPart::Literal(String::new()));
+    }
+    
+    Ok(Word::StringInterpolation(StringInterpolation { parts }, Span::default()))
+}
+
+/// Split expanding text, the inside of double quotes or an unquoted heredoc
+/// body, into literal runs and the expansions between them. Literal runs
+/// keep their backslashes as a double-quoted string would write them.
+pub fn parse_interpolated_text(text: &str, heredoc: bool) -> Vec<StringPart> {
     let mut parts = Vec::new();
-    let mut current_literal = String::new();
+    let mut literal = String::new();
     let mut i = 0;
-    
-    while i < content.len() {
-        if content[i..].starts_with("$") && i + 1 < content.len() {
-            // We found a variable reference
-            // First, add any accumulated literal text
-            if !current_literal.is_empty() {
-                parts.push(StringPart::Literal(current_literal.clone()));
-                current_literal.clear();
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        if c == '\\' {
+            let next = rest[1..].chars().next();
+            if heredoc && next == Some('"') {
+                // `\"` is not an escape in a heredoc
+                literal.push_str("\\\\\"");
+            } else {
+                literal.push('\\');
+                literal.extend(next);
             }
-            
-            // Parse the variable name
-            i += 1; // skip the $
-            if i < content.len() {
-                let var_start = i;
-                
-                // Handle special shell variables like $#, $@, $*
-                if i < content.len() {
-                    let next_char = content[i..].chars().next().unwrap();
-                    if next_char == '#' || next_char == '@' || next_char == '*' {
-                        // Special shell variable
-                        parts.push(StringPart::Variable(next_char.to_string()));
-                        i += 1;
-                    } else if next_char.is_alphanumeric() || next_char == '_' {
-                        // Regular variable name
-                        while i < content.len() {
-                            let next_char = content[i..].chars().next();
-                            if let Some(c) = next_char {
-                                if c.is_alphanumeric() || c == '_' {
-                                    i += 1;
-                                } else {
-                                    break;
-                                }
-                            } else {
-                                break;
-                            }
-                        }
-                        let var_name = &content[var_start..i];
-                        if !var_name.is_empty() {
-                            parts.push(StringPart::Variable(var_name.to_string()));
-                        }
-                    }
+            i += 1 + next.map_or(0, char::len_utf8);
+            continue;
+        }
+        let expansion = match c {
+            '$' => expansion_length(rest),
+            '`' => rest[1..].find('`').map(|end| end + 2),
+            _ => None,
+        }
+        .and_then(|len| Some((len, expansion_part(&rest[..len])?)));
+        match expansion {
+            Some((len, part)) => {
+                if !literal.is_empty() {
+                    parts.push(StringPart::Literal(std::mem::take(&mut literal)));
                 }
+                parts.push(part);
+                i += len;
+            }
+            None => {
+                literal.push(c);
+                i += c.len_utf8();
             }
-        } else {
-            // Add to current literal
-            current_literal.push(content[i..].chars().next().unwrap());
-            i += 1;
         }
     }
-    
-    // Add any remaining literal text
-    if !current_literal.is_empty() {
-        parts.push(StringPart::Literal(current_literal));
+    if !literal.is_empty() {
+        parts.push(StringPart::Literal(literal));
     }
-    
-    // If we have no parts, this shouldn't happen, but handle it gracefully
-    if parts.is_empty() {
-        parts.push(StringPart::Literal(content.to_string()));
+    parts
+}
+
+/// The length of the `$` expansion `text` starts with, if it is one.
+fn expansion_length(text: &str) -> Option<usize> {
+    let mut chars = text[1..].chars();
+    match chars.next()? {
+        '(' => closing_offset(&text[1..], '(', ')').map(|end| end + 1),
+        '{' => closing_offset(&text[1..], '{', '}').map(|end| end + 1),
+        c if c.is_ascii_alphabetic() || c == '_' => {
+            Some(1 + text[1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(text.len() - 1))
+        }
+        c if c.is_ascii_digit() || "#@*?$!-".contains(c) => Some(2),
+        _ => None,
+    }
+}
+
+/// The offset just past the bracket closing the one `text` starts with,
+/// skipping over quoted text and escaped characters.
+fn closing_offset(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, c) if c == open => depth += 1,
+            (None, c) if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse one expansion cut out of expanding text; `None` leaves it literal.
+fn expansion_part(text: &str) -> Option<StringPart> {
+    let name = &text[1..];
+    if text.starts_with('$') && !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || "#@*?$!-".contains(c)) {
+        return Some(StringPart::Variable(name.to_string()));
+    }
+    let mut lexer = Lexer::new(text);
+    let word = parse_word(&mut lexer).ok()?;
+    if !lexer.is_eof() {
+        return None;
+    }
+    match word {
+        Word::Variable(var, _) => Some(StringPart::Variable(var)),
+        Word::ParameterExpansion(pe, _) => Some(StringPart::ParameterExpansion(pe)),
+        Word::MapAccess(map_name, key, _) => Some(StringPart::MapAccess(map_name, key)),
+        Word::MapKeys(map_name, _) => Some(StringPart::MapKeys(map_name)),
+        Word::MapLength(map_name, _) => Some(StringPart::MapLength(map_name)),
+        Word::ArraySlice(name, offset, length, _) => Some(StringPart::ArraySlice(name, offset, length)),
+        Word::Arithmetic(expr, _) => Some(StringPart::Arithmetic(expr)),
+        Word::CommandSubstitution(cmd, _) => Some(StringPart::CommandSubstitution(cmd)),
+        _ => None,
     }
-    
-    Ok(Word::StringInterpolation(StringInterpolation { parts }))
 }
 
 fn parse_ansic_quoted_string(_lexer: &mut Lexer) -> Result<Word, ParserError> {
@@ -829,113 +872,21 @@ fn parse_brace_expansion(lexer: &mut Lexer) -> Result<Word, ParserError> {
         prefix: None,
         items,
         suffix: None,
-    }))
+    }, Span::default()))
 }
 
 fn parse_arithmetic_expression(lexer: &mut Lexer) -> Result<Word, ParserError> {
     // Parse arithmetic expressions like $((i + 1))
-    // First, consume the opening $(( or $(
     match lexer.peek() {
         Some(Token::Arithmetic) | Some(Token::ArithmeticEval) => {
-            lexer.next(); // consume $(( or $(
+            lexer.next(); // consume $(( or ((
         }
         _ => {
             return Err(ParserError::InvalidSyntax("Expected arithmetic expression start".to_string()));
         }
     }
-    
-    // Capture the content until we find the closing ))
-    let mut expression_parts = Vec::new();
-    let mut paren_depth = 1; // We're already inside one level of parentheses
-    
-    loop {
-        match lexer.peek() {
-            Some(Token::ArithmeticEvalClose) => {
-                // This is the closing )) for $((...))
-                lexer.next();
-                paren_depth -= 1;
-                if paren_depth == 0 {
-                    break;
-                }
-            }
-            Some(Token::Arithmetic) => {
-                // This is another opening $((...))
-                lexer.next();
-                paren_depth += 1;
-            }
-            Some(Token::Identifier) => {
-                expression_parts.push(lexer.get_identifier_text()?);
-            }
-            Some(Token::Number) => {
-                expression_parts.push(lexer.get_number_text()?);
-            }
-            Some(Token::Plus) => {
-                expression_parts.push("+".to_string());
-                lexer.next();
-            }
-            Some(Token::Minus) => {
-                expression_parts.push("-".to_string());
-                lexer.next();
-            }
-            Some(Token::Star) => {
-                expression_parts.push("*".to_string());
-                lexer.next();
-            }
-            Some(Token::Slash) => {
-                expression_parts.push("/".to_string());
-                lexer.next();
-            }
-            Some(Token::Space) | Some(Token::Tab) => {
-                expression_parts.push(" ".to_string());
-                lexer.next();
-            }
-            Some(Token::Dollar) => {
-                // Handle variable references like $i
-                lexer.next();
-                if let Some(Token::Identifier) = lexer.peek() {
-                    let var_name = lexer.get_identifier_text()?;
-                    expression_parts.push(format!("${}", var_name));
-                } else {
-                    return Err(ParserError::InvalidSyntax("Expected identifier after $ in arithmetic expression".to_string()));
-                }
-            }
-            None => {
-                return Err(ParserError::InvalidSyntax("Unexpected end of input in arithmetic expression".to_string()));
-            }
-            _ => {
-                // For any other token, just consume it and add its text
-                if let Some(text) = lexer.get_current_text() {
-                    expression_parts.push(text);
-                    lexer.next();
-                } else {
-                    break;
-                }
-            }
-        }
-    }
-    
-    let expression = expression_parts.join("");
-    
-    // Return as an Arithmetic Word variant
-    Ok(Word::Arithmetic(ArithmeticExpression {
-        expression,
-        tokens: Vec::new(), // We don't need to store individual tokens for now
-    }))
-}
-
-fn parse_braced_variable_name(_lexer: &mut Lexer) -> Result<String, ParserError> {
-    // TODO: Implement braced variable name parsing
-    Err(ParserError::InvalidSyntax("Braced variable names not yet implemented".to_string()))
-}
-
-fn parse_parameter_expansion(_lexer: &mut Lexer) -> Result<Word, ParserError> {
-    // TODO: Implement parameter expansion parsing
-    Err(ParserError::InvalidSyntax("Parameter expansion not yet implemented".to_string()))
-}
-
-fn parse_array_slicing(_lexer: &mut Lexer, _array_name: String) -> Result<Word, ParserError> {
-    // TODO: Implement array slicing parsing
-    Err(ParserError::InvalidSyntax("Array slicing not yet implemented".to_string()))
+    let expression = lexer.arithmetic_text()?;
+    Ok(Word::Arithmetic(arithmetic_expression(&expression), Span::default()))
 }
 
 fn parse_backtick_command_substitution(lexer: &mut Lexer) -> Result<Word, ParserError> {
@@ -961,25 +912,25 @@ fn parse_backtick_command_substitution(lexer: &mut Lexer) -> Result<Word, Parser
             if !trimmed_part.is_empty() {
                 let cmd_parts: Vec<&str> = trimmed_part.split_whitespace().collect();
                 if !cmd_parts.is_empty() {
-                    let name = Word::Literal(cmd_parts[0].to_string());
-                    let args: Vec<Word> = cmd_parts[1..].iter().map(|&s| Word::Literal(s.to_string())).collect();
+                    let name = Word::Literal(cmd_parts[0].to_string(), Span::default());
+                    let args: Vec<Word> = cmd_parts[1..].iter().map(|&s| Word::Literal(s.to_string(), Span::default())).collect();
                     
                     let cmd = Command::Simple(SimpleCommand {
                         name,
                         args,
                         redirects: vec![],
-                        env_vars: HashMap::new(),
-                    });
+                        env_vars: Vec::new(),
+                    }, Span::default());
                     commands.push(cmd);
                 }
             }
         }
         
         if commands.len() == 1 {
-            Ok(Word::CommandSubstitution(Box::new(commands.remove(0))))
+            Ok(Word::CommandSubstitution(Box::new(commands.remove(0)), Span::default()))
         } else {
-            let pipeline = Command::Pipeline(Pipeline { commands, operators });
-            Ok(Word::CommandSubstitution(Box::new(pipeline)))
+            let pipeline = Command::Pipeline(Pipeline { commands, operators }, Span::default());
+            Ok(Word::CommandSubstitution(Box::new(pipeline), Span::default()))
         }
     } else {
         // Parse as a simple command (original logic)
@@ -988,16 +939,16 @@ fn parse_backtick_command_substitution(lexer: &mut Lexer) -> Result<Word, Parser
             return Err(ParserError::InvalidSyntax("Empty command in backticks".to_string()));
         }
         
-        let name = Word::Literal(parts[0].to_string());
-        let args: Vec<Word> = parts[1..].iter().map(|&s| Word::Literal(s.to_string())).collect();
+        let name = Word::Literal(parts[0].to_string(), Span::default());
+        let args: Vec<Word> = parts[1..].iter().map(|&s| Word::Literal(s.to_string(), Span::default())).collect();
         
         let cmd = Command::Simple(SimpleCommand {
             name,
             args,
             redirects: vec![],
-            env_vars: HashMap::new(),
-        });
+            env_vars: Vec::new(),
+        }, Span::default());
         
-        Ok(Word::CommandSubstitution(Box::new(cmd)))
+        Ok(Word::CommandSubstitution(Box::new(cmd), Span::default()))
     }
 }
\ No newline at end of file
@@ -0,0 +1,591 @@
+use crate::ast::*;
+
+/// Renders an AST back into canonically formatted bash.
+///
+/// The output is meant to parse back into the same AST, so that
+/// `parse(print(parse(s))) == parse(s)` holds for any script the parser
+/// accepts. `debashc fmt` uses it both as a formatter and as a round-trip
+/// check on the parser.
+pub struct ShellPrinter {
+    pub indent_level: usize,
+    /// Heredoc bodies (with their terminators) waiting for the end of the
+    /// current line.
+    pending_heredocs: Vec<String>,
+}
+
+impl Default for ShellPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellPrinter {
+    pub fn new() -> Self {
+        Self {
+            indent_level: 0,
+            pending_heredocs: Vec::new(),
+        }
+    }
+
+    /// Print a whole script, one top-level command per line.
+    pub fn print(&mut self, commands: &[Command]) -> String {
+        self.indent_level = 0;
+        let mut output = self.print_sequence(commands);
+        output.push_str(&self.flush_heredocs());
+        output
+    }
+
+    /// Print a single command. Continuation lines are indented relative to
+    /// `indent_level`; the first line is not.
+    pub fn print_command(&mut self, command: &Command) -> String {
+        match command {
+            Command::Simple(cmd, _) => self.print_simple_command(cmd),
+            Command::BuiltinCommand(cmd, _) => self.print_builtin_command(cmd),
+            Command::ShoptCommand(cmd, _) => {
+                format!("shopt {} {}", if cmd.enable { "-s" } else { "-u" }, cmd.option)
+            }
+            Command::TestExpression(test_expr, _) => self.print_test_expression(test_expr),
+            Command::Pipeline(pipeline, _) => self.print_pipeline(pipeline),
+            Command::If(if_stmt, _) => self.print_if_statement(if_stmt),
+            Command::Case(case_stmt, _) => self.print_case_statement(case_stmt),
+            Command::While(while_loop, _) => self.print_while_loop(while_loop),
+            Command::For(for_loop, _) => self.print_for_loop(for_loop),
+            Command::Select(select_loop, _) => self.print_select_loop(select_loop),
+            Command::ArithmeticFor(arith_for, _) => self.print_arithmetic_for(arith_for),
+            Command::Function(func, _) => self.print_function(func),
+            Command::Subshell(inner, _) => self.print_subshell(inner),
+            Command::Background(inner, _) => format!("{} &", self.print_command(inner)),
+            Command::Block(block, _) => {
+                let body = self.print_body(&block.commands);
+                format!("{{{}{}{}}}", self.line_end(), body, self.indent())
+            }
+            Command::Redirect(redirect_cmd, _) => self.print_redirect_command(redirect_cmd),
+            Command::Break(level, _) => keyword_with_argument("break", level.as_deref()),
+            Command::Continue(level, _) => keyword_with_argument("continue", level.as_deref()),
+            Command::Return(value, _) => match value {
+                Some(value) => format!("return {}", self.print_word(value)),
+                None => "return".to_string(),
+            },
+            Command::BlankLine(_) => String::new(),
+            // Unparsed source is kept exactly as written
+            Command::Error(text, _) => text.clone(),
+        }
+    }
+
+    fn print_sequence(&mut self, commands: &[Command]) -> String {
+        let mut output = String::new();
+        for command in commands {
+            if let Command::BlankLine(_) = command {
+                output.push('\n');
+                continue;
+            }
+            output.push_str(&self.indent());
+            let text = self.print_command(command);
+            output.push_str(&text);
+            output.push_str(&self.line_end());
+        }
+        output
+    }
+
+    /// Print the commands of a compound command's body, one level deeper.
+    fn print_body(&mut self, commands: &[Command]) -> String {
+        self.indent_level += 1;
+        let output = self.print_sequence(commands);
+        self.indent_level -= 1;
+        output
+    }
+
+    fn print_branch(&mut self, branch: &Command) -> String {
+        match branch {
+            Command::Block(block, _) => self.print_body(&block.commands),
+            other => self.print_body(std::slice::from_ref(other)),
+        }
+    }
+
+    fn print_simple_command(&mut self, cmd: &SimpleCommand) -> String {
+        // `((expr))` parses as a command whose name is the arithmetic word
+        if let Word::Arithmetic(expr, _) = &cmd.name {
+            if cmd.args.is_empty() && cmd.env_vars.is_empty() && cmd.redirects.is_empty() {
+                return format!("(({}))", expr.expression);
+            }
+        }
+
+        let mut parts = self.print_assignments(&cmd.env_vars);
+        // A bare assignment is stored as an assignment prefix on `true`
+        let bare_assignment = !parts.is_empty() && cmd.args.is_empty() && cmd.name.as_literal() == Some("true");
+        let mut words: Vec<&Word> = cmd.args.iter().collect();
+        if !bare_assignment && cmd.name.as_literal() != Some("") {
+            words.insert(0, &cmd.name);
+        }
+        parts.extend(self.print_words(&words));
+        for redirect in &cmd.redirects {
+            parts.push(self.print_redirect(redirect));
+        }
+        parts.join(" ")
+    }
+
+    fn print_builtin_command(&mut self, cmd: &BuiltinCommand) -> String {
+        let mut parts = self.print_assignments(&cmd.env_vars);
+        parts.push(cmd.name.clone());
+        let words: Vec<&Word> = cmd.args.iter().collect();
+        parts.extend(self.print_words(&words));
+        for redirect in &cmd.redirects {
+            parts.push(self.print_redirect(redirect));
+        }
+        parts.join(" ")
+    }
+
+    fn print_assignments(&mut self, env_vars: &[Assignment]) -> Vec<String> {
+        env_vars.iter()
+            .map(|assignment| {
+                let value = match &assignment.value {
+                    Word::Literal(s, _) if s.is_empty() => String::new(),
+                    value => self.print_word(value),
+                };
+                format!("{}={}", assignment.target(), value)
+            })
+            .collect()
+    }
+
+    fn print_test_expression(&mut self, test_expr: &TestExpression) -> String {
+        // `[[ ... ]]` keeps the raw text between the brackets, spacing and
+        // all, whereas `[ ... ]` joins its operands with single spaces
+        if test_expr.double_bracket {
+            format!("[[{}]]", test_expr.expression)
+        } else {
+            format!("[ {} ]", test_expr.expression)
+        }
+    }
+
+    fn print_pipeline(&mut self, pipeline: &Pipeline) -> String {
+        let mut output = String::new();
+        for (i, command) in pipeline.commands.iter().enumerate() {
+            if i > 0 {
+                let operator = match pipeline.operators.get(i - 1) {
+                    Some(PipeOperator::And) => "&&",
+                    Some(PipeOperator::Or) => "||",
+                    _ => "|",
+                };
+                output.push_str(&format!(" {} ", operator));
+            }
+            output.push_str(&self.print_command(command));
+        }
+        output
+    }
+
+    fn print_condition(&mut self, condition: &Command) -> String {
+        // `if (( expr ))` is stored as `test` applied to the arithmetic word
+        if let Command::Simple(cmd, _) = condition {
+            if let (Word::Literal(name, _), [Word::Arithmetic(expr, _)]) = (&cmd.name, cmd.args.as_slice()) {
+                if name == "test" && cmd.env_vars.is_empty() && cmd.redirects.is_empty() {
+                    return format!("(( {} ))", expr.expression);
+                }
+            }
+        }
+        self.print_command(condition)
+    }
+
+    fn print_if_statement(&mut self, if_stmt: &IfStatement) -> String {
+        let condition = self.print_condition(&if_stmt.condition);
+        let mut output = format!("if {}; then{}", condition, self.line_end());
+        output.push_str(&self.print_branch(&if_stmt.then_branch));
+
+        let mut else_branch = if_stmt.else_branch.as_deref();
+        while let Some(branch) = else_branch {
+            match branch {
+                Command::If(elif, _) => {
+                    let condition = self.print_condition(&elif.condition);
+                    output.push_str(&format!("{}elif {}; then{}", self.indent(), condition, self.line_end()));
+                    output.push_str(&self.print_branch(&elif.then_branch));
+                    else_branch = elif.else_branch.as_deref();
+                }
+                other => {
+                    output.push_str(&format!("{}else{}", self.indent(), self.line_end()));
+                    output.push_str(&self.print_branch(other));
+                    else_branch = None;
+                }
+            }
+        }
+        output.push_str(&format!("{}fi", self.indent()));
+        output
+    }
+
+    fn print_case_statement(&mut self, case_stmt: &CaseStatement) -> String {
+        let word = self.print_word(&case_stmt.word);
+        let mut output = format!("case {} in{}", word, self.line_end());
+        self.indent_level += 1;
+        for clause in &case_stmt.cases {
+            let patterns: Vec<String> = clause.patterns.iter().map(|p| self.print_word(p)).collect();
+            output.push_str(&format!("{}{}){}", self.indent(), patterns.join(" | "), self.line_end()));
+            output.push_str(&self.print_body(&clause.body));
+            output.push_str(&format!("{}    ;;{}", self.indent(), self.line_end()));
+        }
+        self.indent_level -= 1;
+        output.push_str(&format!("{}esac", self.indent()));
+        output
+    }
+
+    fn print_while_loop(&mut self, while_loop: &WhileLoop) -> String {
+        let condition = self.print_condition(&while_loop.condition);
+        let keyword = if while_loop.until { "until" } else { "while" };
+        let mut output = format!("{} {}; do{}", keyword, condition, self.line_end());
+        output.push_str(&self.print_body(&while_loop.body.commands));
+        output.push_str(&format!("{}done", self.indent()));
+        output
+    }
+
+    fn print_for_loop(&mut self, for_loop: &ForLoop) -> String {
+        self.print_word_loop("for", &for_loop.variable, &for_loop.items, &for_loop.body)
+    }
+
+    fn print_arithmetic_for(&mut self, arith_for: &ArithmeticFor) -> String {
+        let mut output = format!("for (({}; {}; {})); do{}", arith_for.init.expression,
+                                 arith_for.condition.expression, arith_for.step.expression, self.line_end());
+        output.push_str(&self.print_body(&arith_for.body.commands));
+        output.push_str(&format!("{}done", self.indent()));
+        output
+    }
+
+    fn print_select_loop(&mut self, select_loop: &SelectLoop) -> String {
+        self.print_word_loop("select", &select_loop.variable, &select_loop.items, &select_loop.body)
+    }
+
+    /// `for` and `select` differ only in their keyword.
+    fn print_word_loop(&mut self, keyword: &str, variable: &str, items: &[Word], body: &Block) -> String {
+        let mut header = format!("{} {}", keyword, variable);
+        if !items.is_empty() {
+            let items: Vec<&Word> = items.iter().collect();
+            let items = self.print_words(&items);
+            header.push_str(&format!(" in {}", items.join(" ")));
+        }
+        let mut output = format!("{}; do{}", header, self.line_end());
+        output.push_str(&self.print_body(&body.commands));
+        output.push_str(&format!("{}done", self.indent()));
+        output
+    }
+
+    fn print_function(&mut self, func: &Function) -> String {
+        let header = if func.parameters.is_empty() {
+            format!("{}()", func.name)
+        } else {
+            // Named parameters are an extension only the `function` form accepts
+            format!("function {}({})", func.name, func.parameters.join(", "))
+        };
+        let mut output = format!("{} {{{}", header, self.line_end());
+        output.push_str(&self.print_body(&func.body.commands));
+        output.push_str(&format!("{}}}", self.indent()));
+        output
+    }
+
+    fn print_subshell(&mut self, inner: &Command) -> String {
+        match inner {
+            Command::Block(block, _) if block.commands.len() > 1 => {
+                let body = self.print_body(&block.commands);
+                format!("({}{}{})", self.line_end(), body, self.indent())
+            }
+            // Keep a lone brace group apart from the parentheses
+            Command::Block(..) => format!("( {} )", self.print_command(inner)),
+            other => format!("({})", self.print_command(other)),
+        }
+    }
+
+    fn print_redirect_command(&mut self, redirect_cmd: &RedirectCommand) -> String {
+        let mut output = self.print_command(&redirect_cmd.command);
+        for redirect in &redirect_cmd.redirects {
+            output.push(' ');
+            output.push_str(&self.print_redirect(redirect));
+        }
+        output
+    }
+
+    pub fn print_redirect(&mut self, redirect: &Redirect) -> String {
+        let fd = match (&redirect.fd_var, redirect.fd) {
+            (Some(name), _) => format!("{{{}}}", name),
+            (None, Some(fd)) => fd.to_string(),
+            (None, None) => String::new(),
+        };
+        let operator = match &redirect.operator {
+            RedirectOperator::Input => "<",
+            RedirectOperator::Output => ">",
+            RedirectOperator::Append => ">>",
+            RedirectOperator::InputOutput => "<>",
+            RedirectOperator::OutputClobber => ">|",
+            RedirectOperator::OutputAll => "&>",
+            RedirectOperator::AppendAll => "&>>",
+            RedirectOperator::DuplicateInput => "<&",
+            RedirectOperator::DuplicateOutput => ">&",
+            RedirectOperator::Close => return format!("{}>&-", fd),
+            RedirectOperator::Heredoc => "<<",
+            RedirectOperator::HeredocTabs => "<<-",
+            RedirectOperator::HereString => "<<<",
+        };
+
+        match (&redirect.operator, &redirect.target) {
+            // `< <(cmd)` needs the blank, or it would read as a heredoc
+            (_, target @ Word::ProcessSubstitution(..)) => {
+                format!("{}{} {}", fd, operator, self.print_word(target))
+            }
+            (RedirectOperator::Heredoc | RedirectOperator::HeredocTabs, target) => {
+                let delimiter = self.print_word(target);
+                let mut body = redirect.heredoc.as_ref().map(|heredoc| heredoc.text.clone()).unwrap_or_default();
+                if !body.is_empty() && !body.ends_with('\n') {
+                    body.push('\n');
+                }
+                body.push_str(&heredoc_terminator(&delimiter));
+                body.push('\n');
+                self.pending_heredocs.push(body);
+                format!("{}{}{}", fd, operator, delimiter)
+            }
+            (_, target) => format!("{}{}{}", fd, operator, self.print_word(target)),
+        }
+    }
+
+    /// Words separated by spaces, except where the source wrote them next to
+    /// each other, as in `{a,b}{1,2}`, which stay joined.
+    fn print_words(&mut self, words: &[&Word]) -> Vec<String> {
+        let mut printed: Vec<String> = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            let text = self.print_word(word);
+            let touching = i > 0 && {
+                let (before, span) = (words[i - 1].span(), word.span());
+                before.end > before.start && before.end == span.start
+            };
+            match printed.last_mut() {
+                Some(last) if touching => last.push_str(&text),
+                _ => printed.push(text),
+            }
+        }
+        printed
+    }
+
+    pub fn print_word(&mut self, word: &Word) -> String {
+        match word {
+            Word::Literal(s, _) if s.is_empty() => "''".to_string(),
+            Word::Literal(s, _) => s.clone(),
+            Word::Variable(var, _) => variable_reference(var, false),
+            Word::ParameterExpansion(pe, _) => parameter_expansion(pe),
+            Word::Array(_, elements, _) => format!("({})", elements.join(" ")),
+            Word::MapAccess(map_name, key, _) => format!("${{{}[{}]}}", map_name, key),
+            Word::MapKeys(map_name, _) => format!("${{!{}[@]}}", map_name),
+            Word::MapLength(map_name, _) => format!("${{#{}[@]}}", map_name),
+            Word::ArraySlice(name, offset, length, _) => array_slice(name, offset, length.as_deref()),
+            Word::Arithmetic(expr, _) => format!("$(({}))", expr.expression),
+            Word::BraceExpansion(expansion, _) => brace_expansion(expansion),
+            Word::CommandSubstitution(cmd, _) => format!("$({})", self.print_inline(cmd)),
+            Word::ProcessSubstitution(cmd, ProcessDirection::Input, _) => format!("<({})", self.print_inline(cmd)),
+            Word::ProcessSubstitution(cmd, ProcessDirection::Output, _) => format!(">({})", self.print_inline(cmd)),
+            Word::StringInterpolation(interp, _) => self.print_string_interpolation(interp),
+            Word::Concatenation(words, _) => {
+                let printed: Vec<String> = words.iter().map(|word| self.print_word(word)).collect();
+                let mut output = String::new();
+                for (i, word) in words.iter().enumerate() {
+                    match word {
+                        // `$name` followed by a word character would read as a longer name
+                        Word::Variable(var, _) => {
+                            let joined = printed.get(i + 1)
+                                .is_some_and(|next| next.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'));
+                            output.push_str(&variable_reference(var, joined));
+                        }
+                        _ => output.push_str(&printed[i]),
+                    }
+                }
+                output
+            }
+        }
+    }
+
+    fn print_string_interpolation(&mut self, interp: &StringInterpolation) -> String {
+        let mut output = String::from("\"");
+        for (i, part) in interp.parts.iter().enumerate() {
+            let text = match part {
+                StringPart::Literal(s) => s.clone(),
+                StringPart::Variable(var) => {
+                    // `$name` followed by a word character would read as a longer name
+                    let joined = matches!(interp.parts.get(i + 1), Some(StringPart::Literal(next))
+                        if next.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'));
+                    variable_reference(var, joined)
+                }
+                StringPart::ParameterExpansion(pe) => parameter_expansion(pe),
+                StringPart::MapAccess(map_name, key) => format!("${{{}[{}]}}", map_name, key),
+                StringPart::MapKeys(map_name) => format!("${{!{}[@]}}", map_name),
+                StringPart::MapLength(map_name) => format!("${{#{}[@]}}", map_name),
+                StringPart::ArraySlice(name, offset, length) => array_slice(name, offset, length.as_deref()),
+                StringPart::Arithmetic(expr) => format!("$(({}))", expr.expression),
+                StringPart::CommandSubstitution(cmd) => format!("$({})", self.print_inline(cmd)),
+            };
+            output.push_str(&text);
+        }
+        output.push('"');
+        output
+    }
+
+    /// Print a command nested inside a word, such as `$(...)`. Any heredoc
+    /// it opens has to be closed before the word ends.
+    fn print_inline(&mut self, command: &Command) -> String {
+        let outer = std::mem::take(&mut self.pending_heredocs);
+        let mut output = self.print_command(command);
+        if !self.pending_heredocs.is_empty() {
+            output.push_str(&self.line_end());
+        }
+        self.pending_heredocs = outer;
+        output
+    }
+
+    /// End the current line, emitting any heredoc bodies it opened.
+    fn line_end(&mut self) -> String {
+        let mut output = String::from("\n");
+        output.push_str(&self.flush_heredocs());
+        output
+    }
+
+    fn flush_heredocs(&mut self) -> String {
+        self.pending_heredocs.drain(..).collect()
+    }
+
+    fn indent(&self) -> String {
+        "    ".repeat(self.indent_level)
+    }
+}
+
+fn keyword_with_argument(keyword: &str, argument: Option<&str>) -> String {
+    match argument {
+        Some(argument) => format!("{} {}", keyword, argument),
+        None => keyword.to_string(),
+    }
+}
+
+fn variable_reference(var: &str, braced: bool) -> String {
+    let is_name = var.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let is_special = var.len() == 1 && var.chars().all(|c| "#@*?$!-0123456789".contains(c));
+    if (is_name && !braced) || is_special {
+        format!("${}", var)
+    } else {
+        format!("${{{}}}", var)
+    }
+}
+
+pub fn parameter_expansion(pe: &ParameterExpansion) -> String {
+    let var = &pe.variable;
+    match &pe.operator {
+        ParameterExpansionOperator::None => format!("${{{}}}", var),
+        ParameterExpansionOperator::Length => format!("${{#{}}}", var),
+        ParameterExpansionOperator::UppercaseAll => format!("${{{}^^}}", var),
+        ParameterExpansionOperator::LowercaseAll => format!("${{{},,}}", var),
+        ParameterExpansionOperator::UppercaseFirst => format!("${{{}^}}", var),
+        ParameterExpansionOperator::LowercaseFirst => format!("${{{},}}", var),
+        ParameterExpansionOperator::ToggleCaseAll => format!("${{{}~~}}", var),
+        ParameterExpansionOperator::ToggleCaseFirst => format!("${{{}~}}", var),
+        ParameterExpansionOperator::RemoveLongestPrefix(pattern) => format!("${{{}##{}}}", var, pattern),
+        ParameterExpansionOperator::RemoveShortestPrefix(pattern) => format!("${{{}#{}}}", var, pattern),
+        ParameterExpansionOperator::RemoveLongestSuffix(pattern) => format!("${{{}%%{}}}", var, pattern),
+        ParameterExpansionOperator::RemoveShortestSuffix(pattern) => format!("${{{}%{}}}", var, pattern),
+        ParameterExpansionOperator::SubstituteAll(pattern, replacement) => {
+            format!("${{{}//{}/{}}}", var, pattern, replacement)
+        }
+        ParameterExpansionOperator::SubstituteFirst(pattern, replacement) => {
+            format!("${{{}/{}/{}}}", var, pattern, replacement)
+        }
+        ParameterExpansionOperator::SubstitutePrefix(pattern, replacement) => {
+            format!("${{{}/#{}/{}}}", var, pattern, replacement)
+        }
+        ParameterExpansionOperator::SubstituteSuffix(pattern, replacement) => {
+            format!("${{{}/%{}/{}}}", var, pattern, replacement)
+        }
+        ParameterExpansionOperator::DefaultValue(default) => format!("${{{}:-{}}}", var, default),
+        ParameterExpansionOperator::AssignDefault(default) => format!("${{{}:={}}}", var, default),
+        ParameterExpansionOperator::ErrorIfUnset(error) => format!("${{{}:?{}}}", var, error),
+        ParameterExpansionOperator::AlternativeValue(alternative) => format!("${{{}:+{}}}", var, alternative),
+        ParameterExpansionOperator::DefaultIfUnset(default) => format!("${{{}-{}}}", var, default),
+        ParameterExpansionOperator::AssignIfUnset(default) => format!("${{{}={}}}", var, default),
+        ParameterExpansionOperator::ErrorIfNotSet(error) => format!("${{{}?{}}}", var, error),
+        ParameterExpansionOperator::AlternativeIfSet(alternative) => format!("${{{}+{}}}", var, alternative),
+        ParameterExpansionOperator::Basename => format!("${{{}##*/}}", var),
+        ParameterExpansionOperator::Dirname => format!("${{{}%/*}}", var),
+        ParameterExpansionOperator::ArraySlice(offset, length) => match length {
+            Some(length) => format!("${{{}:{}:{}}}", var, offset, length),
+            None => format!("${{{}:{}}}", var, offset),
+        },
+        ParameterExpansionOperator::PrefixNames(marker) => format!("${{!{}{}}}", var, marker),
+        ParameterExpansionOperator::Transform(op) => format!("${{{}@{}}}", var, op),
+    }
+}
+
+fn array_slice(name: &str, offset: &str, length: Option<&str>) -> String {
+    match length {
+        Some(length) => format!("${{{}[@]:{}:{}}}", name, offset, length),
+        None => format!("${{{}[@]:{}}}", name, offset),
+    }
+}
+
+fn brace_expansion(expansion: &BraceExpansion) -> String {
+    let items: Vec<String> = expansion.items.iter()
+        .map(|item| match item {
+            BraceItem::Literal(s) => s.clone(),
+            BraceItem::Range(range) => match &range.step {
+                Some(step) => format!("{}..{}..{}", range.start, range.end, step),
+                None => format!("{}..{}", range.start, range.end),
+            },
+            BraceItem::Sequence(seq) => seq.join(","),
+        })
+        .collect();
+    format!("{}{{{}}}{}",
+            expansion.prefix.as_deref().unwrap_or(""),
+            items.join(","),
+            expansion.suffix.as_deref().unwrap_or(""))
+}
+
+/// The line that closes a heredoc is the delimiter with its quoting removed.
+fn heredoc_terminator(delimiter: &str) -> String {
+    delimiter.chars().filter(|c| !matches!(c, '\'' | '"' | '\\')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::commands::Parser;
+
+    #[test]
+    fn test_print_round_trips() {
+        let scripts = [
+            "echo hello world",
+            "x=1\necho \"$x ${x} done\"",
+            "arr=(one two three)\necho $(echo 'a b' | tr a b)",
+            "if [ -f \"file.txt\" ]; then\n  echo yes\nelse\n  echo no\nfi",
+            "for i in 1 2 3; do echo $i; done",
+            "for ((i = 0; i < 3; i++)); do echo $i; done",
+            "while [ -f \"lock\" ]; do sleep 1; break; done",
+            "until [ -f \"lock\" ]; do sleep 1; done",
+            "select opt in start stop; do echo $opt; break; done",
+            "greet() { echo \"hi $1\" > out.txt 2>&1; }",
+            "case $x in\n  a|b) echo ab\n    ;;\n  *) echo other\n    ;;\nesac",
+            "(cd /tmp && ls)\necho finished >&2",
+        ];
+        for script in scripts {
+            let ast = Parser::new(script).parse().unwrap();
+            let printed = ShellPrinter::new().print(&ast);
+            let reparsed = Parser::new(&printed).parse()
+                .unwrap_or_else(|e| panic!("{:?} printed as {:?} does not parse: {}", script, printed, e));
+            assert_eq!(ast, reparsed, "{:?} printed as {:?}", script, printed);
+        }
+    }
+
+    #[test]
+    fn test_print_keeps_adjacent_words_joined() {
+        let bash = |script: &str| {
+            let output = std::process::Command::new("bash").arg("-c").arg(script).env("HOME", "/home/u").output().unwrap();
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        };
+        let scripts = [
+            "dir=/tmp file=f base=b\necho cp $dir/$file ${base}.bak \"$HOME\"/x",
+            "x=2\necho pre$x \"$x\"post $x'q r'\"s\" a'b'c ${x}_y $x$x",
+            "x=2\necho x=$x --opt=$x --o=\"a b\" ~/bin",
+            "x=2\ny=$x:$x\nexport Z=\"$y\"$x\necho $y $Z",
+            "echo {a,b}{1,2} x{1,2} {a,b}y\nfor i in {1,2}{a,b}; do echo $i; done",
+        ];
+        for script in scripts {
+            let printed = ShellPrinter::new().print(&Parser::new(script).parse().unwrap());
+            assert_eq!(bash(&printed), bash(script), "{:?} printed as {:?}", script, printed);
+        }
+    }
+}
@@ -6,10 +6,11 @@ use std::thread;
 
 use crate::cache::CommandCache;
 use crate::execution::{run_shell_script, create_exit_status};
-use crate::utils::{check_generator_available, cleanup_tmp, generate_unified_diff, 
+use crate::utils::{check_generator_available, cleanup_tmp, compile_rust, generate_unified_diff, 
                    check_perl_must_not_contain, check_ast_must_not_contain, check_ast_must_contain};
 use crate::shared_utils;
 use debashl::{Lexer, Parser, Generator, lexer::Token};
+use crate::commands::backend_for;
 
 #[derive(Debug)]
 pub struct TestResult {
@@ -141,11 +142,12 @@ pub fn test_file_equivalence(lang: &str, filename: &str) -> Result<(), String> {
         Err(e) => { return Err(format!("Failed to parse {}: {:?}", filename, e)); }
     };
 
+    if !matches!(lang, "perl" | "python" | "rust") {
+        return Err(format!("Unsupported language for --test-file: {}", lang));
+    }
+    let code = backend_for(lang).generate(&commands);
     let (tmp_file, run_cmd) = match lang {
         "perl" => {
-            let mut gen = Generator::new();
-            let code = gen.generate(&commands);
-            
             // Check PERL_MUST_NOT_CONTAIN constraints for Perl code
             if let Err(violation_msg) = check_perl_must_not_contain(&shell_content, &code) {
                 return Err(format!("PERL_MUST_NOT_CONTAIN constraint violation in {}:\n{}", filename, violation_msg));
@@ -155,7 +157,17 @@ pub fn test_file_equivalence(lang: &str, filename: &str) -> Result<(), String> {
             if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Perl temp file: {}", e)); }
             (tmp.to_string(), vec!["perl", "__tmp_test_output.pl"])
         }
-        _ => { return Err(format!("Unsupported language for --test-file: {}", lang)); }
+        "python" => {
+            let tmp = "examples/__tmp_test_output.py";
+            if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Python temp file: {}", e)); }
+            (tmp.to_string(), vec!["python3", "__tmp_test_output.py"])
+        }
+        _ => {
+            let tmp = "__tmp_test_output.rs";
+            if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Rust temp file: {}", e)); }
+            if let Err(e) = compile_rust(tmp) { cleanup_tmp(lang, tmp); return Err(e); }
+            (tmp.to_string(), vec!["__tmp_test_bin"])
+        }
     };
 
     // Run shell script using cross-platform shell execution
@@ -167,7 +179,8 @@ pub fn test_file_equivalence(lang: &str, filename: &str) -> Result<(), String> {
             // Run compiled binary directly (first arg of run_cmd)
             let bin = "__tmp_test_bin";
             let abs_bin = std::env::current_dir().unwrap_or_default().join(bin);
-            let mut child = match Command::new(&abs_bin).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            // Like the shell script, the binary runs from examples/
+            let mut child = match Command::new(&abs_bin).current_dir("examples").stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
                 Ok(c) => c,
                 Err(e) => { cleanup_tmp(lang, &tmp_file); return Err(format!("Failed to run compiled Rust: {} ({})", e, abs_bin.display())); }
             };
@@ -186,8 +199,8 @@ pub fn test_file_equivalence(lang: &str, filename: &str) -> Result<(), String> {
         } else {
             let mut cmd = Command::new(run_cmd[0]);
             
-            // For Perl scripts, handle the file path replacement
-            if lang == "perl" {
+            // Perl and Python scripts run from examples/, so handle the file path replacement
+            if lang == "perl" || lang == "python" {
                 cmd.current_dir("examples");
                 // Replace TEMP_FILE placeholder with actual file path
                 for a in &run_cmd[1..] {
@@ -381,15 +394,27 @@ pub fn test_file_equivalence_detailed(lang: &str, filename: &str, ast_options: O
         let ast_options = ast_options.unwrap_or_default();
         ast = ast_options.format_ast_with_options(commands.as_slice());
 
-        let (tmp, run_cmd_vec, code) = match lang {
+        if !matches!(lang, "perl" | "python" | "rust") {
+            return Err(format!("Unsupported language for --test-file: {}", lang));
+        }
+        let code = backend_for(lang).generate(&commands);
+        let (tmp, run_cmd_vec) = match lang {
             "perl" => {
-                let mut gen = Generator::new();
-                let code = gen.generate(&commands);
                 let tmp = "examples/__tmp_test_output.pl";
                 if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Perl temp file: {}", e)); }
-                (tmp.to_string(), vec!["perl", "__tmp_test_output.pl"], code)
+                (tmp.to_string(), vec!["perl", "__tmp_test_output.pl"])
+            }
+            "python" => {
+                let tmp = "examples/__tmp_test_output.py";
+                if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Python temp file: {}", e)); }
+                (tmp.to_string(), vec!["python3", "__tmp_test_output.py"])
+            }
+            _ => {
+                let tmp = "__tmp_test_output.rs";
+                if let Err(e) = shared_utils::SharedUtils::write_utf8_file(tmp, &code) { return Err(format!("Failed to write Rust temp file: {}", e)); }
+                if let Err(e) = compile_rust(tmp) { cleanup_tmp(lang, tmp); return Err(e); }
+                (tmp.to_string(), vec!["__tmp_test_bin"])
             }
-            _ => { return Err(format!("Unsupported language for --test-file: {}", lang)); }
         };
         
         // Assign to the declared variables
@@ -432,7 +457,8 @@ pub fn test_file_equivalence_detailed(lang: &str, filename: &str, ast_options: O
             // Run compiled binary directly (first arg of run_cmd)
             let bin = "__tmp_test_bin";
             let abs_bin = std::env::current_dir().unwrap_or_default().join(bin);
-            let mut child = match Command::new(&abs_bin).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            // Like the shell script, the binary runs from examples/
+            let mut child = match Command::new(&abs_bin).current_dir("examples").stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
                 Ok(c) => c,
                 Err(e) => { cleanup_tmp(lang, &tmp_file); return Err(format!("Failed to run compiled Rust: {} ({})", e, abs_bin.display())); }
             };
@@ -451,8 +477,8 @@ pub fn test_file_equivalence_detailed(lang: &str, filename: &str, ast_options: O
         } else {
             let mut cmd = Command::new(run_cmd[0]);
             
-            // For Perl scripts, handle the file path replacement
-            if lang == "perl" {
+            // Perl and Python scripts run from examples/, so handle the file path replacement
+            if lang == "perl" || lang == "python" {
                 cmd.current_dir("examples");
                 // Replace TEMP_FILE placeholder with actual file path
                 for a in &run_cmd[1..] {
@@ -538,7 +564,7 @@ pub fn test_file_equivalence_detailed(lang: &str, filename: &str, ast_options: O
 }
 
 pub fn test_all_examples() {
-    let all_generators = vec!["perl"];
+    let all_generators = vec!["perl", "python", "rust"];
     
     // Filter to only available generators
     let generators: Vec<_> = all_generators.into_iter()
@@ -552,7 +578,7 @@ pub fn test_all_examples() {
         .collect();
     
     if generators.is_empty() {
-        println!("No supported generators found. Please install perl");
+        println!("No supported generators found. Please install perl, python3 or rustc");
         std::process::exit(1);
     }
     
@@ -702,7 +728,7 @@ pub fn test_all_examples_next_fail(generators: &[String], test_number: Option<us
         .collect();
     
     if generators.is_empty() {
-        println!("No supported generators found. Please install perl");
+        println!("No supported generators found. Please install perl, python3 or rustc");
         std::process::exit(1);
     }
     
@@ -40,10 +40,25 @@ pub fn caret_snippet(input: &str, line: usize, col: usize) -> Option<String> {
 pub fn check_generator_available(generator: &str) -> bool {
     match generator {
         "perl" => Command::new("perl").arg("--version").output().is_ok(),
+        "python" => Command::new("python3").arg("--version").output().is_ok(),
+        "rust" => Command::new("rustc").arg("--version").output().is_ok(),
         _ => false
     }
 }
 
+/// Compile a generated Rust program into `__tmp_test_bin` with plain rustc
+pub fn compile_rust(source: &str) -> Result<(), String> {
+    let output = Command::new("rustc")
+        .args(["--edition", "2021", "-A", "warnings", "-o", "__tmp_test_bin", source])
+        .output()
+        .map_err(|e| format!("Failed to run rustc: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to compile generated Rust:\n{}", String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
 /// Clean up temporary files
 pub fn cleanup_tmp(lang: &str, tmp_file: &str) {
     let _ = std::fs::remove_file(tmp_file);
@@ -160,8 +175,17 @@ pub fn check_perl_must_not_contain(shell_content: &str, perl_code: &str) -> Resu
     }
 }
 
+/// The Debug form of an AST with its source positions removed, so that a
+/// pattern like `Literal("-1")` matches wherever the node appears.
+fn strip_spans(ast_string: &str) -> String {
+    let span = regex::Regex::new(r",\s*(span: )?\d+:\d+").unwrap();
+    span.replace_all(ast_string, "").into_owned()
+}
+
 /// Check if the AST string representation contains forbidden patterns specified in AST_MUST_NOT_CONTAIN comments
 pub fn check_ast_must_not_contain(shell_content: &str, ast_string: &str) -> Result<(), String> {
+    let ast_string = strip_spans(ast_string);
+    let ast_string = ast_string.as_str();
     let lines: Vec<&str> = shell_content.lines().collect();
     let mut violations = Vec::new();
     
@@ -195,6 +219,8 @@ pub fn check_ast_must_not_contain(shell_content: &str, ast_string: &str) -> Resu
 
 /// Check if the AST string representation contains required patterns specified in AST_MUST_CONTAIN comments
 pub fn check_ast_must_contain(shell_content: &str, ast_string: &str) -> Result<(), String> {
+    let ast_string = strip_spans(ast_string);
+    let ast_string = ast_string.as_str();
     let lines: Vec<&str> = shell_content.lines().collect();
     let mut violations = Vec::new();
     
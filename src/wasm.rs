@@ -1,5 +1,5 @@
 use wasm_bindgen::prelude::*;
-use crate::{Lexer, parser::commands::Parser, Generator};
+use crate::{Lexer, parser::commands::Parser, Generator, commands_to_json};
 
 #[wasm_bindgen]
 pub struct Debashc;
@@ -60,6 +60,16 @@ impl Debashc {
         }
     }
 
+    /// Parse a shell script to the tagged JSON form of the AST
+    pub fn parse_json(&mut self, input: &str) -> Result<String, JsValue> {
+        let mut parser = Parser::new(input);
+        match parser.parse() {
+            Ok(commands) => commands_to_json(&commands)
+                .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e))),
+            Err(e) => Err(JsValue::from_str(&format!("Parse error: {}", e))),
+        }
+    }
+
     /// Convert shell script to Perl
     pub fn to_perl(&mut self, input: &str) -> Result<String, JsValue> {
         let mut parser = Parser::new(input);